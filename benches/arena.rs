@@ -0,0 +1,115 @@
+//! Compares allocating per event with recycling buffers through an `EventArena`.
+//!
+//! Run with `cargo bench --bench arena`.
+//! The `fid` benchmark needs `CAP_SYS_ADMIN` and `FAN_REPORT_FID` (Linux 5.1+), and is skipped otherwise.
+
+use std::convert::TryInto;
+use std::fs;
+use std::hint::black_box;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::IntoRawFd;
+use std::time::Duration;
+use std::time::Instant;
+
+use fanotify::event::arena::EventArena;
+use fanotify::fanotify::buffered_fanotify::IntoBufferedFanotify;
+use fanotify::fd::FD;
+use fanotify::init::Init;
+use fanotify::mark;
+use fanotify::mark::Markable;
+use fanotify::mark::Mask;
+use fanotify::mark::OneAction::Add;
+
+type AnyResult<T = ()> = Result<T, Box<dyn std::error::Error>>;
+
+fn report(name: &str, iterations: u32, allocating: Duration, arena: Duration, recycler: &EventArena) {
+    let per = |it: Duration| it / iterations;
+    println!(
+        "{:>5}: {:>9?}/iter allocating, {:>9?}/iter with arena ({:.2}x), hit rate {:.3}",
+        name,
+        per(allocating),
+        per(arena),
+        allocating.as_secs_f64() / arena.as_secs_f64(),
+        recycler.stats().hit_rate(),
+    );
+}
+
+fn path() -> AnyResult {
+    const ITERATIONS: u32 = 100_000;
+    let dir = tempfile::tempdir()?;
+    let file = dir.path().join("a-reasonably-long-file-name-for-resolving");
+    fs::write(&file, "")?;
+    let fd = unsafe { FD::from_raw_fd(fs::File::open(&file)?.into_raw_fd()) };
+    
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(fd.path()?);
+    }
+    let allocating = start.elapsed();
+    
+    let mut arena = EventArena::new();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let path = black_box(arena.path(&fd)?);
+        arena.recycle_path(path);
+    }
+    report("path", ITERATIONS, allocating, start.elapsed(), &arena);
+    Ok(())
+}
+
+fn fid() -> AnyResult {
+    const ROUNDS: u32 = 200;
+    const FILES: usize = 64;
+    let fanotify = match Init::fid_tracking().to_fanotify() {
+        Ok(fanotify) => fanotify,
+        Err(e) => {
+            println!("  fid: skipped ({})", e);
+            return Ok(());
+        }
+    };
+    let dir = tempfile::tempdir()?;
+    let files = (0..FILES)
+        .map(|i| dir.path().join(i.to_string()))
+        .collect::<Vec<_>>();
+    for file in &files {
+        fs::write(file, "")?;
+        fanotify.mark(mark::One {
+            action: Add,
+            what: mark::What::Inode,
+            flags: mark::Flags::empty(),
+            mask: Mask::MODIFY,
+            path: mark::Path::absolute(file),
+        }.try_into()?).map_err(|e| e.error)?;
+    }
+    let mut fanotify = fanotify.buffered_default();
+    let mut arena = EventArena::new();
+    let mut owned = Vec::new();
+    let mut allocating = Duration::default();
+    let mut with_arena = Duration::default();
+    for round in 0..ROUNDS * 2 {
+        for file in &files {
+            fs::write(file, "modified")?;
+        }
+        let events = fanotify.read()?;
+        let start = Instant::now();
+        if round % 2 == 0 {
+            let (events, result) = events.drain_owned();
+            result?;
+            owned.extend(events);
+            owned.clear();
+            allocating += start.elapsed();
+        } else {
+            events.drain_to_in(&mut owned, &mut arena)?;
+            arena.recycle_all(owned.drain(..));
+            with_arena += start.elapsed();
+        }
+    }
+    report("fid", ROUNDS, allocating, with_arena, &arena);
+    Ok(())
+}
+
+fn main() -> AnyResult {
+    path()?;
+    fid()?;
+    Ok(())
+}
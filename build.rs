@@ -0,0 +1,27 @@
+//! With the `bindgen` feature, generate bindings from the installed kernel headers
+//! (`<linux/fanotify.h>`) so that the hand-written ones in `src/libc` can be checked against them.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "bindgen")]
+    bindings::generate();
+}
+
+#[cfg(feature = "bindgen")]
+mod bindings {
+    use std::env;
+    use std::path::PathBuf;
+    
+    pub fn generate() {
+        let out = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("fanotify.rs");
+        bindgen::Builder::default()
+            .header_contents("wrapper.h", "#include <linux/fanotify.h>\n")
+            .allowlist_type("fanotify_.*")
+            .allowlist_var("FAN_.*|FANOTIFY_.*")
+            .layout_tests(false)
+            .generate()
+            .expect("couldn't generate bindings from <linux/fanotify.h>")
+            .write_to_file(out)
+            .expect("couldn't write the generated bindings");
+    }
+}
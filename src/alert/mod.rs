@@ -0,0 +1,278 @@
+//! Alerting on high-severity [`Event`]s, like modifications of sensitive files,
+//! so integrity monitors don't need their own notification plumbing.
+//!
+//! An [`AlertSink`] checks each [`Event`] against its [`Rule`]s,
+//! and sends an [`Incident`] for each match at or above its minimum [`Severity`] to all of its [`Alert`]s:
+//! * [`UnixSocketAlert`] writes them as JSON lines to a unix socket, e.g. for a desktop notifier.
+//! * `WebhookAlert` (with the `http` feature) POSTs them as JSON to a URL.
+//!
+//! An [`Incident`]'s JSON is `{"rule":"...","severity":"high","record":{...}}`,
+//! where `record` is the [`json`] encoding of its [`EventRecord`].
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::event::event::Event;
+use crate::event::sink::Sink;
+use crate::event::sink::SinkError;
+use crate::export::EventRecord;
+use crate::export::json;
+use crate::fanotify::pipeline::Layer;
+use crate::mark::Mask;
+
+#[cfg(feature = "http")]
+pub use webhook::WebhookAlert;
+
+#[cfg(feature = "http")]
+mod webhook;
+
+/// How severe an [`Incident`] is.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// An [`Event`] that matched a [`Rule`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Incident {
+    /// The name of the [`Rule`] that matched.
+    pub rule: String,
+    pub severity: Severity,
+    /// The [`Event`], with its path.
+    pub record: EventRecord,
+}
+
+impl Incident {
+    /// Encode as a JSON object.  See the [module docs](self).
+    pub fn to_json(&self) -> String {
+        let mut out = String::with_capacity(192);
+        out.push_str(r#"{"rule":"#);
+        json::escape_into(&self.rule, &mut out);
+        out.push_str(r#","severity":""#);
+        out.push_str(self.severity.name());
+        out.push_str(r#"","record":"#);
+        out.push_str(&json::encode(&self.record));
+        out.push('}');
+        out
+    }
+}
+
+/// Somewhere to send [`Incident`]s.
+///
+/// Any `FnMut(&Incident) -> io::Result<()>` is an [`Alert`].
+pub trait Alert {
+    fn alert(&mut self, incident: &Incident) -> io::Result<()>;
+}
+
+impl<F: FnMut(&Incident) -> io::Result<()>> Alert for F {
+    fn alert(&mut self, incident: &Incident) -> io::Result<()> {
+        self(incident)
+    }
+}
+
+/// A named filter for [`Event`]s that should raise an [`Incident`] of a given [`Severity`].
+pub struct Rule {
+    name: String,
+    severity: Severity,
+    filter: Box<dyn Layer + Send>,
+}
+
+impl Rule {
+    /// Raise an [`Incident`] for every [`Event`] passed on by `filter`.
+    pub fn new(name: impl Into<String>, severity: Severity, filter: impl Layer + Send + 'static) -> Self {
+        Self {
+            name: name.into(),
+            severity,
+            filter: Box::new(filter),
+        }
+    }
+    
+    /// The [`Mask`] of [`Event`]s that modify a file (or its metadata), used by [`Rule::modified`].
+    ///
+    /// Only [`MODIFY`](Mask::MODIFY) and [`CLOSE_WRITE`](Mask::CLOSE_WRITE)
+    /// can be marked without [`REPORT_FID`](crate::init::Flags::REPORT_FID).
+    pub fn modifications() -> Mask {
+        Mask::MODIFY
+            | Mask::CLOSE_WRITE
+            | Mask::ATTRIBUTE_CHANGED
+            | Mask::DELETE_SELF
+            | Mask::MOVE_SELF
+    }
+    
+    /// Raise an [`Incident`] when any of `paths` are [modified](Rule::modifications),
+    /// e.g. for `/etc/passwd` and `/etc/shadow`.
+    ///
+    /// This only matches [`Event`]s with a path, so not [`FID`](crate::event::file::File::FID) ones.
+    pub fn modified(
+        name: impl Into<String>,
+        severity: Severity,
+        paths: impl IntoIterator<Item = impl Into<PathBuf>>,
+    ) -> Self {
+        let paths = paths.into_iter().map(Into::into).collect::<HashSet<_>>();
+        Self::new(name, severity, move |event: &Event<'_>| {
+            event.mask().intersects(Self::modifications())
+                && matches!(event.file().path(), Some(Ok(path)) if paths.contains(&path))
+        })
+    }
+    
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+}
+
+/// A [`Sink`] that checks every [`Event`] against its [`Rule`]s
+/// and sends the [`Incident`]s at or above its minimum [`Severity`] to all of its [`Alert`]s.
+pub struct AlertSink {
+    min_severity: Severity,
+    rules: Vec<Rule>,
+    alerts: Vec<Box<dyn Alert + Send>>,
+}
+
+impl AlertSink {
+    /// An [`AlertSink`] with no [`Rule`]s or [`Alert`]s yet,
+    /// ignoring [`Incident`]s below `min_severity`.
+    pub fn new(min_severity: Severity) -> Self {
+        Self {
+            min_severity,
+            rules: Vec::new(),
+            alerts: Vec::new(),
+        }
+    }
+    
+    /// Add another [`Rule`].  An [`Event`] raises an [`Incident`] for each [`Rule`] it matches.
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+    
+    /// Add another [`Alert`] to send [`Incident`]s to.
+    pub fn alert(mut self, alert: impl Alert + Send + 'static) -> Self {
+        self.alerts.push(Box::new(alert));
+        self
+    }
+    
+    pub fn min_severity(&self) -> Severity {
+        self.min_severity
+    }
+}
+
+/// Every [`Alert`] is sent every [`Incident`], even if an earlier one fails,
+/// and then the first error is returned.
+impl Sink for AlertSink {
+    fn consume(&mut self, event: &Event<'_>) -> Result<(), SinkError> {
+        let mut error = None;
+        for rule in &mut self.rules {
+            if rule.severity < self.min_severity || !rule.filter.handle(event) {
+                continue;
+            }
+            let incident = Incident {
+                rule: rule.name.clone(),
+                severity: rule.severity,
+                record: EventRecord::new(event, true),
+            };
+            for alert in &mut self.alerts {
+                if let Err(e) = alert.alert(&incident) {
+                    error = error.or(Some(e));
+                }
+            }
+        }
+        match error {
+            None => Ok(()),
+            Some(e) => Err(e.into()),
+        }
+    }
+}
+
+/// An [`Alert`] that writes each [`Incident`] as a line of JSON to a unix stream socket,
+/// e.g. one a desktop notifier listens on.
+///
+/// It connects lazily, and reconnects (once per [`Incident`]) if the connection was lost.
+#[derive(Debug)]
+pub struct UnixSocketAlert {
+    path: PathBuf,
+    timeout: Option<Duration>,
+    stream: Option<UnixStream>,
+}
+
+impl UnixSocketAlert {
+    /// Write to the socket at `path`, with a write timeout of 1 second.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            timeout: Some(Duration::from_secs(1)),
+            stream: None,
+        }
+    }
+    
+    /// Set the write timeout, so that a stuck listener doesn't block event processing.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    
+    fn connect(&mut self) -> io::Result<&mut UnixStream> {
+        if self.stream.is_none() {
+            let stream = UnixStream::connect(&self.path)?;
+            stream.set_write_timeout(self.timeout)?;
+            self.stream = Some(stream);
+        }
+        Ok(self.stream.as_mut().unwrap())
+    }
+    
+    fn send(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let result = self.connect()?.write_all(bytes);
+        if result.is_err() {
+            self.stream = None;
+        }
+        result
+    }
+}
+
+impl Alert for UnixSocketAlert {
+    fn alert(&mut self, incident: &Incident) -> io::Result<()> {
+        let mut line = incident.to_json();
+        line.push('\n');
+        let was_connected = self.stream.is_some();
+        match self.send(line.as_bytes()) {
+            // the listener restarted, so try again with a new connection
+            Err(e) if was_connected && e.kind() == io::ErrorKind::BrokenPipe => self.send(line.as_bytes()),
+            result => result,
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::io;
+use std::time::Duration;
+
+use ureq::Agent;
+use ureq::AgentBuilder;
+
+use super::Alert;
+use super::Incident;
+
+fn invalid_url(url: &str, why: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("invalid webhook URL {:?}: {}", url, why))
+}
+
+/// An [`Alert`] that POSTs each [`Incident`] as JSON to a URL.
+///
+/// Requires the `http` feature, which uses [`ureq`] as the HTTP client.
+///
+/// Both `http://` and `https://` URLs are supported.
+/// Any response other than a `2xx` is an error.
+#[derive(Debug, Clone)]
+pub struct WebhookAlert {
+    url: String,
+    agent: Agent,
+}
+
+impl WebhookAlert {
+    /// POST to `url`, like `http://localhost:8080/alerts`, with a timeout of 5 seconds.
+    pub fn new(url: impl Into<String>) -> io::Result<Self> {
+        let url = url.into();
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(invalid_url(&url, "expected an http:// or https:// URL"));
+        }
+        let alert = Self {
+            agent: Self::agent(Duration::from_secs(5)),
+            url,
+        };
+        alert.agent
+            .post(&alert.url)
+            .request_url()
+            .map_err(|e| invalid_url(&alert.url, &e.to_string()))?;
+        Ok(alert)
+    }
+    
+    fn agent(timeout: Duration) -> Agent {
+        AgentBuilder::new()
+            .timeout_connect(timeout)
+            .timeout_read(timeout)
+            .timeout_write(timeout)
+            .build()
+    }
+    
+    /// Set the timeout for connecting, and for each read and write.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.agent = Self::agent(timeout);
+        self
+    }
+    
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl Alert for WebhookAlert {
+    fn alert(&mut self, incident: &Incident) -> io::Result<()> {
+        let response = self.agent
+            .post(&self.url)
+            .set("Content-Type", "application/json")
+            .send_string(&incident.to_json());
+        let status = match response {
+            Ok(response) => response.status(),
+            Err(ureq::Error::Status(status, _)) => status,
+            Err(ureq::Error::Transport(e)) => return Err(io::Error::other(e)),
+        };
+        if (200..300).contains(&status) {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("webhook {} responded with {}", self.url, status)))
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! A pluggable source of time, so that the time-dependent parts of this crate
+//! (the [`CgroupCache`](crate::event::cgroup::CgroupCache),
+//! [`CredentialsCache`](crate::event::credentials::CredentialsCache),
+//! and [`DecisionCache`](crate::event::file::decision_cache::DecisionCache) TTLs,
+//! the timestamps and rotation of an [`ArchiveWriter`](crate::export::archive::ArchiveWriter),
+//! and a [`Fanotify`](crate::fanotify::Fanotify) group's permission latencies and response flush delays)
+//! can be tested deterministically with a [`MockClock`].
+//!
+//! See [`ProcFs`](crate::proc::ProcFs) for the same for `/proc`.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+/// A source of the current time, both monotonic and wall-clock.
+pub trait Clock: Debug + Send + Sync {
+    /// The current monotonic time, like [`Instant::now`].
+    fn now(&self) -> Instant;
+    
+    /// The current wall-clock time, like [`SystemTime::now`].
+    fn system_now(&self) -> SystemTime;
+}
+
+/// The real [`Clock`], i.e. [`Instant::now`] and [`SystemTime::now`].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+    
+    fn system_now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// The default [`Clock`] to share, the [`SystemClock`].
+pub fn system() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// A [`Clock`] that only moves when [`advance`](MockClock::advance)d.
+///
+/// Clones share the same time, so one can be passed to what's being tested
+/// while another is kept to control it.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    time: Arc<Mutex<(Instant, SystemTime)>>,
+}
+
+impl MockClock {
+    /// A [`MockClock`] starting at the current time.
+    pub fn new() -> Self {
+        Self::starting_at(SystemTime::now())
+    }
+    
+    /// A [`MockClock`] whose wall-clock time starts at `system_now`.
+    pub fn starting_at(system_now: SystemTime) -> Self {
+        Self {
+            time: Arc::new(Mutex::new((Instant::now(), system_now))),
+        }
+    }
+    
+    /// Move both the monotonic and wall-clock time forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let mut time = self.time.lock().unwrap_or_else(|e| e.into_inner());
+        time.0 += duration;
+        time.1 += duration;
+    }
+    
+    /// This [`MockClock`] as a shared [`Clock`], still controlled by `self`.
+    pub fn shared(&self) -> Arc<dyn Clock> {
+        Arc::new(self.clone())
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.time.lock().unwrap_or_else(|e| e.into_inner()).0
+    }
+    
+    fn system_now(&self) -> SystemTime {
+        self.time.lock().unwrap_or_else(|e| e.into_inner()).1
+    }
+}
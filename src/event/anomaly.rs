@@ -0,0 +1,286 @@
+//! Detecting processes that change files at anomalous rates,
+//! like ransomware encrypting, renaming, or deleting a tree in bulk.
+//!
+//! An [`AnomalyDetector`] counts each process's [`ChangeKind`]s over a sliding window per [`Threshold`],
+//! and raises an [`AnomalyAlert`] whenever one is exceeded.
+//! It can be used standalone, as a [`Layer`] in a [`Pipeline`](crate::fanotify::pipeline::Pipeline),
+//! or to [`decide`](AnomalyDetector::decide) permission events,
+//! denying them for processes that recently raised an alert.
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use nix::unistd::Pid;
+
+use crate::clock;
+use crate::clock::Clock;
+use crate::fanotify::pipeline::Layer;
+use crate::mark::Mask;
+
+use super::event::Event;
+use super::file::permission::PermissionDecision;
+
+/// A kind of change to files whose rate an [`AnomalyDetector`] tracks.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum ChangeKind {
+    Modify,
+    Rename,
+    Delete,
+}
+
+impl ChangeKind {
+    pub const ALL: [Self; 3] = [Self::Modify, Self::Rename, Self::Delete];
+    
+    /// The [`Mask`] of events of this [`ChangeKind`].
+    pub fn mask(&self) -> Mask {
+        match self {
+            Self::Modify => Mask::MODIFY | Mask::CLOSE_WRITE,
+            Self::Rename => Mask::moved() | Mask::MOVE_SELF,
+            Self::Delete => Mask::DELETE | Mask::DELETE_SELF,
+        }
+    }
+    
+    /// The [`ChangeKind`]s of an event's [`Mask`], of which there may be multiple for merged events.
+    pub fn of(mask: Mask) -> impl Iterator<Item = Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .filter(move |it| mask.intersects(it.mask()))
+    }
+    
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Modify => "modify",
+            Self::Rename => "rename",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+/// Alert when a process makes more than [`Threshold::count`] changes of a [`ChangeKind`]
+/// within [`Threshold::window`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Threshold {
+    pub kind: ChangeKind,
+    pub count: usize,
+    pub window: Duration,
+}
+
+/// A process exceeded a [`Threshold`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct AnomalyAlert {
+    pub pid: Pid,
+    pub threshold: Threshold,
+    /// The number of changes in the [`Threshold::window`], i.e. more than [`Threshold::count`].
+    pub count: usize,
+}
+
+/// Tracks per-process rates of [`ChangeKind`]s and alerts when a [`Threshold`] is exceeded.
+///
+/// After an alert, that process's window for that [`Threshold`] starts over,
+/// so a sustained burst raises an alert per [`Threshold::count`] changes, not one per change.
+/// A process that raised an alert is flagged for [`AnomalyDetector::with_flag_duration`]
+/// (forever by default, until [`AnomalyDetector::unflag`]ged),
+/// during which [`AnomalyDetector::decide`] denies it.
+///
+/// Events are attributed to their [`pid`](crate::event::id::EventId::pid) (or tid),
+/// and events without one, or generated by this process, are ignored.
+#[derive(Debug)]
+pub struct AnomalyDetector {
+    thresholds: Vec<Threshold>,
+    changes: HashMap<(Pid, usize), VecDeque<Instant>>,
+    flagged: HashMap<Pid, Instant>,
+    flag_duration: Option<Duration>,
+    alerts: Vec<AnomalyAlert>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for AnomalyDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnomalyDetector {
+    /// An [`AnomalyDetector`] with no [`Threshold`]s, which never alerts.
+    pub fn new() -> Self {
+        Self {
+            thresholds: Vec::new(),
+            changes: HashMap::new(),
+            flagged: HashMap::new(),
+            flag_duration: None,
+            alerts: Vec::new(),
+            clock: clock::system(),
+        }
+    }
+    
+    /// Add a [`Threshold`].
+    pub fn threshold(mut self, kind: ChangeKind, count: usize, window: Duration) -> Self {
+        self.thresholds.push(Threshold {
+            kind,
+            count,
+            window,
+        });
+        self
+    }
+    
+    /// Only flag processes for `duration` after their last alert instead of forever.
+    pub fn with_flag_duration(mut self, duration: Duration) -> Self {
+        self.flag_duration = Some(duration);
+        self
+    }
+    
+    /// Use `clock` for the windows instead of the [system](clock::SystemClock) one.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    pub fn thresholds(&self) -> &[Threshold] {
+        &self.thresholds
+    }
+    
+    /// Record that `pid` made a change of `kind`, returning any [`AnomalyAlert`]s it raised.
+    pub fn record(&mut self, pid: Pid, kind: ChangeKind) -> Vec<AnomalyAlert> {
+        let now = self.clock.now();
+        let mut alerts = Vec::new();
+        for (i, threshold) in self.thresholds.iter().enumerate() {
+            if threshold.kind != kind {
+                continue;
+            }
+            let changes = self.changes.entry((pid, i)).or_default();
+            while changes.front().is_some_and(|&it| now.duration_since(it) >= threshold.window) {
+                changes.pop_front();
+            }
+            changes.push_back(now);
+            if changes.len() > threshold.count {
+                alerts.push(AnomalyAlert {
+                    pid,
+                    threshold: *threshold,
+                    count: changes.len(),
+                });
+                changes.clear();
+            }
+        }
+        if !alerts.is_empty() {
+            self.flagged.insert(pid, now);
+        }
+        alerts
+    }
+    
+    /// [`Record`](AnomalyDetector::record) every [`ChangeKind`] of `event`,
+    /// returning any [`AnomalyAlert`]s it raised.
+    pub fn observe(&mut self, event: &Event<'_>) -> Vec<AnomalyAlert> {
+        let id = event.id();
+        let pid = match id.pid().or_else(|| id.tid()) {
+            Some(pid) if !id.is_generated_by_self() => pid,
+            _ => return Vec::new(),
+        };
+        ChangeKind::of(event.mask())
+            .flat_map(|kind| self.record(pid, kind))
+            .collect()
+    }
+    
+    /// If `pid` raised an [`AnomalyAlert`] recently enough to still be flagged.
+    pub fn is_flagged(&self, pid: Pid) -> bool {
+        match (self.flagged.get(&pid), self.flag_duration) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(&at), Some(duration)) => self.clock.now().duration_since(at) < duration,
+        }
+    }
+    
+    pub fn unflag(&mut self, pid: Pid) {
+        self.flagged.remove(&pid);
+    }
+    
+    /// Deny `event` if the process that caused it [`is_flagged`](AnomalyDetector::is_flagged).
+    pub fn decide(&self, event: &Event<'_>) -> PermissionDecision {
+        let id = event.id();
+        match id.pid().or_else(|| id.tid()) {
+            Some(pid) if self.is_flagged(pid) => PermissionDecision::Deny,
+            _ => PermissionDecision::Allow,
+        }
+    }
+    
+    /// Forget the windows of processes with no changes in any of them,
+    /// and expired flags, so that this doesn't grow with every process ever seen.
+    pub fn prune(&mut self) {
+        let now = self.clock.now();
+        let thresholds = &self.thresholds;
+        self.changes.retain(|&(_, i), changes| {
+            changes.back().is_some_and(|&it| now.duration_since(it) < thresholds[i].window)
+        });
+        if let Some(duration) = self.flag_duration {
+            self.flagged.retain(|_, &mut at| now.duration_since(at) < duration);
+        }
+    }
+    
+    /// Take the [`AnomalyAlert`]s raised while used as a [`Layer`].
+    pub fn take_alerts(&mut self) -> Vec<AnomalyAlert> {
+        std::mem::take(&mut self.alerts)
+    }
+}
+
+/// [`Observe`](AnomalyDetector::observe) every [`Event`] and pass it on,
+/// keeping the alerts for [`AnomalyDetector::take_alerts`].
+impl Layer for AnomalyDetector {
+    fn handle(&mut self, event: &Event<'_>) -> bool {
+        let alerts = self.observe(event);
+        self.alerts.extend(alerts);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    
+    use nix::unistd::Pid;
+    
+    use crate::clock::MockClock;
+    use crate::mark::Mask;
+    
+    use super::AnomalyDetector;
+    use super::ChangeKind;
+    
+    #[test]
+    fn change_kinds() {
+        let kinds = ChangeKind::of(Mask::MODIFY | Mask::MOVED_TO | Mask::OPEN).collect::<Vec<_>>();
+        assert_eq!(kinds, vec![ChangeKind::Modify, ChangeKind::Rename]);
+    }
+    
+    #[test]
+    fn sliding_window() {
+        let clock = MockClock::new();
+        let mut detector = AnomalyDetector::new()
+            .threshold(ChangeKind::Rename, 2, Duration::from_secs(10))
+            .with_flag_duration(Duration::from_secs(60))
+            .with_clock(clock.shared());
+        let pid = Pid::from_raw(1000);
+        let other = Pid::from_raw(1001);
+        assert!(detector.record(pid, ChangeKind::Rename).is_empty());
+        assert!(detector.record(pid, ChangeKind::Delete).is_empty());
+        clock.advance(Duration::from_secs(10));
+        // the first rename is out of the window
+        assert!(detector.record(pid, ChangeKind::Rename).is_empty());
+        assert!(detector.record(other, ChangeKind::Rename).is_empty());
+        assert!(detector.record(pid, ChangeKind::Rename).is_empty());
+        assert!(!detector.is_flagged(pid));
+        let alerts = detector.record(pid, ChangeKind::Rename);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!((alerts[0].pid, alerts[0].count), (pid, 3));
+        assert!(detector.is_flagged(pid));
+        assert!(!detector.is_flagged(other));
+        // the window starts over after an alert
+        assert!(detector.record(pid, ChangeKind::Rename).is_empty());
+        clock.advance(Duration::from_secs(60));
+        assert!(!detector.is_flagged(pid));
+        detector.prune();
+        assert!(detector.changes.is_empty());
+        assert!(detector.flagged.is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+use std::ffi::OsString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+
+use crate::fd::FD;
+use crate::proc;
+
+use super::file::fid::OwnedFileHandle;
+use super::owned::OwnedEvent;
+use super::owned::OwnedEventResult;
+use super::owned::OwnedFile;
+
+/// Counts of how an [`EventArena`] has been used, to check how well it's working.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct ArenaStats {
+    /// The number of buffers taken from the pool.
+    pub reused: u64,
+    /// The number of buffers newly allocated because the pool was empty.
+    pub allocated: u64,
+    /// The number of buffers given back to the pool.
+    pub recycled: u64,
+}
+
+impl ArenaStats {
+    /// The fraction of buffers that were [`reused`](Self::reused) instead of [`allocated`](Self::allocated).
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.reused + self.allocated;
+        if total == 0 {
+            0.0
+        } else {
+            self.reused as f64 / total as f64
+        }
+    }
+}
+
+/// A pool of buffers that are recycled across batches of [`Event`](super::event::Event)s,
+/// so that converting them to [`OwnedEvent`]s and resolving their paths doesn't allocate per event.
+///
+/// Buffers are handed out by [`FileFID::to_owned_in`](super::file::fid::FileFID::to_owned_in),
+/// [`Events::drain_to_in`](super::events::Events::drain_to_in), and [`EventArena::path`],
+/// and given back with [`EventArena::recycle`] (or [`recycle_all`](Self::recycle_all))
+/// and [`EventArena::recycle_path`] once the previous batch has been processed.
+///
+/// Anything not given back is simply dropped as usual,
+/// so an [`EventArena`] never changes what's returned, only where its memory comes from.
+#[derive(Debug)]
+pub struct EventArena {
+    handles: Vec<Vec<u8>>,
+    paths: Vec<Vec<u8>>,
+    link: Vec<u8>,
+    capacity: usize,
+    stats: ArenaStats,
+}
+
+impl Default for EventArena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The initial size of a path buffer, enough for almost all paths.
+const PATH_BUFFER_SIZE: usize = 256;
+
+impl EventArena {
+    /// The default maximum number of each kind of buffer kept in the pool.
+    pub const DEFAULT_CAPACITY: usize = 1024;
+    
+    /// An empty [`EventArena`] keeping up to [`EventArena::DEFAULT_CAPACITY`] of each kind of buffer.
+    pub fn new() -> Self {
+        Self::with_capacity(Self::DEFAULT_CAPACITY)
+    }
+    
+    /// An empty [`EventArena`] keeping up to `capacity` of each kind of buffer.
+    ///
+    /// Buffers recycled past that are dropped, so that one huge batch doesn't hold onto memory forever.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            handles: Vec::new(),
+            paths: Vec::new(),
+            link: Vec::new(),
+            capacity,
+            stats: ArenaStats::default(),
+        }
+    }
+    
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    
+    pub fn stats(&self) -> ArenaStats {
+        self.stats
+    }
+    
+    /// The number of buffers currently in the pool, ready to be reused.
+    pub fn pooled(&self) -> usize {
+        self.handles.len() + self.paths.len()
+    }
+    
+    /// Drop all of the pooled buffers, freeing their memory.
+    pub fn clear(&mut self) {
+        self.handles.clear();
+        self.paths.clear();
+    }
+    
+    fn take(pool: &mut Vec<Vec<u8>>, stats: &mut ArenaStats) -> Vec<u8> {
+        match pool.pop() {
+            Some(buffer) => {
+                stats.reused += 1;
+                buffer
+            }
+            None => {
+                stats.allocated += 1;
+                Vec::new()
+            }
+        }
+    }
+    
+    fn give(pool: &mut Vec<Vec<u8>>, stats: &mut ArenaStats, capacity: usize, mut buffer: Vec<u8>) {
+        // an empty buffer never allocated, so there's nothing to reuse
+        if pool.len() < capacity && buffer.capacity() != 0 {
+            buffer.clear();
+            pool.push(buffer);
+            stats.recycled += 1;
+        }
+    }
+    
+    /// Copy `bytes` into a pooled buffer, for an [`OwnedFileHandle`].
+    pub(crate) fn handle_bytes(&mut self, bytes: &[u8]) -> Vec<u8> {
+        let mut buffer = Self::take(&mut self.handles, &mut self.stats);
+        buffer.extend_from_slice(bytes);
+        buffer
+    }
+    
+    /// Give an [`OwnedFileHandle`]'s buffer back to the pool.
+    pub fn recycle_handle(&mut self, handle: OwnedFileHandle) {
+        Self::give(&mut self.handles, &mut self.stats, self.capacity, handle.into_bytes());
+    }
+    
+    /// Give a path, like one from [`EventArena::path`], back to the pool.
+    pub fn recycle_path(&mut self, path: PathBuf) {
+        Self::give(&mut self.paths, &mut self.stats, self.capacity, path.into_os_string().into_vec());
+    }
+    
+    /// Give the buffers of an [`OwnedEvent`] back to the pool.
+    ///
+    /// Its file descriptor, if any, is closed as usual,
+    /// and a [`PermissionTicket`](super::file::ticket::PermissionTicket) is dropped, which allows it.
+    pub fn recycle(&mut self, event: OwnedEvent) {
+        if let OwnedFile::FID(file) = event.into_file() {
+            self.recycle_handle(file.into_handle());
+        }
+    }
+    
+    /// [Recycle](Self::recycle) a whole batch of [`OwnedEvent`]s, like one from
+    /// [`Events::drain_to_in`](super::events::Events::drain_to_in).
+    pub fn recycle_all(&mut self, events: impl IntoIterator<Item = OwnedEventResult>) {
+        for event in events.into_iter().flatten() {
+            self.recycle(event);
+        }
+    }
+    
+    /// Like [`FD::path`], but reading the link into a pooled buffer.
+    pub fn path(&mut self, fd: &FD) -> io::Result<PathBuf> {
+        let link = proc::self_fd(fd.as_raw_fd())?;
+        self.link.clear();
+        self.link.extend_from_slice(link.as_os_str().as_bytes());
+        self.link.push(0);
+        let mut buffer = Self::take(&mut self.paths, &mut self.stats);
+        buffer.reserve(PATH_BUFFER_SIZE);
+        loop {
+            let capacity = buffer.capacity();
+            let len = unsafe {
+                libc::readlink(
+                    self.link.as_ptr() as *const libc::c_char,
+                    buffer.as_mut_ptr() as *mut libc::c_char,
+                    capacity,
+                )
+            };
+            if len == -1 {
+                let error = io::Error::last_os_error();
+                self.recycle_path(PathBuf::from(OsString::from_vec(buffer)));
+                return Err(error);
+            }
+            let len = len as usize;
+            // a full buffer might've been truncated, so try again with a bigger one
+            if len < capacity {
+                unsafe { buffer.set_len(len) };
+                return Ok(PathBuf::from(OsString::from_vec(buffer)));
+            }
+            buffer.reserve(capacity * 2);
+        }
+    }
+}
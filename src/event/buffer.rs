@@ -1,7 +1,11 @@
 /// A general buffer for [`Fanotify`] [`Events`].
 ///
-/// It contains raw byte buffers for reading (event_buffer) and writing (response_buffer).
+/// It contains raw byte buffers for reading (event_buffer) and writing (response_buffer),
+/// and the partial bytes of an event truncated at the end of the last read (carry),
+/// which are prepended to the next read's events so the event is completed instead of lost.
 /// These are used by an [`Events::read`] and iteration over its [`Event`]s.
+/// Responses are always buffered separately, never in place in the events buffer,
+/// so responding to a permission event can't clobber the events still being parsed after it.
 ///
 /// By storing these in a separate buffer,
 /// I can reuse the buffer memory for each [`Fanotify::read`].
@@ -14,17 +18,23 @@
 pub struct EventBuffer {
     pub events: Vec<u8>,
     pub responses: Vec<u8>,
+    pub carry: Vec<u8>,
 }
 
 impl EventBuffer {
+    /// Clear the events and the carried over partial event,
+    /// but not the responses, since any left over are still pending
+    /// and have to be written for their processes to be unblocked,
+    /// e.g. with `AsyncBufferedFanotify::flush_responses` (with the `async` feature).
     pub fn clear(&mut self) {
         self.events.clear();
-        self.responses.clear();
+        self.carry.clear();
     }
     
     pub fn shrink_to_fit(&mut self) {
         self.events.shrink_to_fit();
         self.responses.shrink_to_fit();
+        self.carry.shrink_to_fit();
     }
     
     pub fn reserve(&mut self, additional: EventBufferSize) {
@@ -58,6 +68,7 @@ impl EventBufferSize {
         EventBuffer {
             events: Vec::with_capacity(self.events),
             responses: Vec::with_capacity(self.responses),
+            carry: Vec::new(),
         }
     }
 }
@@ -0,0 +1,135 @@
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use nix::unistd::Pid;
+
+use crate::clock::Clock;
+use crate::proc::ProcFs;
+use crate::proc::SystemProcFs;
+
+use super::event::Event;
+use super::id::Id;
+use super::pid_cache::PidCache;
+
+/// Parse the cgroup path out of a `/proc/{pid}/cgroup` file.  See [`of`].
+fn parse(cgroups: &str) -> Option<PathBuf> {
+    let path = |hierarchy: &str| {
+        cgroups
+            .lines()
+            .find_map(|line| line.strip_prefix(hierarchy))
+            .map(PathBuf::from)
+    };
+    path("0::").or_else(|| {
+        cgroups
+            .lines()
+            .find_map(|line| line.split_once(":name=systemd:"))
+            .map(|(_, it)| PathBuf::from(it))
+    })
+}
+
+/// Read the cgroup path of the process (or thread) `pid`, like `/system.slice/sshd.service`,
+/// from its `/proc/{pid}/cgroup`.
+///
+/// This is the path in the unified (v2) hierarchy, i.e. the `0::` line,
+/// or on a v1-only system, the path in the `name=systemd` hierarchy,
+/// since that's where systemd puts each service and container.
+pub fn of(pid: Pid) -> io::Result<PathBuf> {
+    of_in(&SystemProcFs, pid)
+}
+
+/// Read the cgroup path of the process (or thread) `pid` from `proc_fs`.  See [`of`].
+pub fn of_in(proc_fs: &dyn ProcFs, pid: Pid) -> io::Result<PathBuf> {
+    let cgroups = proc_fs.read_pid_file(pid.as_raw(), "cgroup")?;
+    parse(&cgroups).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("no v2 or systemd cgroup in /proc/{}/cgroup", pid))
+    })
+}
+
+/// Read the cgroup path of the process (or thread) that caused `event`.  See [`of`].
+pub fn of_event(event: &Event<'_>) -> io::Result<PathBuf> {
+    match event.id().id() {
+        Id::Pid(pid) | Id::Tid(pid) => of(pid),
+    }
+}
+
+/// A cache of the cgroup paths of recent [`Event`]s' processes, keyed by pid (or tid).
+///
+/// Processes rarely move between cgroups, but a pid can be reused,
+/// so entries expire after a `ttl`.
+#[derive(Debug)]
+pub struct CgroupCache {
+    cache: PidCache<PathBuf>,
+}
+
+impl Default for CgroupCache {
+    /// A [`CgroupCache`] with a `ttl` of 1 second and a `capacity` of 1024 processes.
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1), 1024)
+    }
+}
+
+impl CgroupCache {
+    /// Create an empty [`CgroupCache`] holding at most `capacity` processes for at most `ttl`.
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            cache: PidCache::new(ttl, capacity),
+        }
+    }
+    
+    pub fn ttl(&self) -> Duration {
+        self.cache.ttl
+    }
+    
+    pub fn capacity(&self) -> usize {
+        self.cache.capacity
+    }
+    
+    pub fn len(&self) -> usize {
+        self.cache.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.cache.entries.is_empty()
+    }
+    
+    pub fn clear(&mut self) {
+        self.cache.entries.clear();
+    }
+    
+    /// Use `clock` to expire entries instead of the [system](crate::clock::SystemClock) one.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.cache.clock = clock;
+        self
+    }
+    
+    /// Read from `proc_fs` instead of the [system](crate::proc::SystemProcFs) one.
+    pub fn with_proc_fs(mut self, proc_fs: Arc<dyn ProcFs>) -> Self {
+        self.cache.proc_fs = proc_fs;
+        self
+    }
+    
+    /// The cgroup path of the process (or thread) that caused `event`,
+    /// or [`None`] if it couldn't be read, e.g. because it has already exited.
+    pub fn get(&mut self, event: &Event<'_>) -> Option<PathBuf> {
+        self.cache.get(event, |proc_fs, pid| of_in(proc_fs, pid).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    
+    use super::parse;
+    
+    #[test]
+    fn parse_cgroups() {
+        assert_eq!(parse("0::/system.slice/sshd.service\n"), Some(PathBuf::from("/system.slice/sshd.service")));
+        let hybrid = "2:cpu:/\n1:name=systemd:/system.slice/docker-1234.scope\n0::/\n";
+        assert_eq!(parse(hybrid), Some(PathBuf::from("/")));
+        let v1 = "2:cpu:/\n1:name=systemd:/system.slice/docker-1234.scope\n";
+        assert_eq!(parse(v1), Some(PathBuf::from("/system.slice/docker-1234.scope")));
+        assert_eq!(parse("2:cpu:/\n"), None);
+    }
+}
@@ -0,0 +1,179 @@
+//! Merging the duplicate events that an fd-reporting group and a [`REPORT_FID`](crate::init::Flags::REPORT_FID) group
+//! both report for the same operation on the same object, e.g. when running both side by side
+//! (see [`AdaptiveFanotify`](crate::fanotify::adaptive::AdaptiveFanotify)).
+//!
+//! The events are correlated by the object's [`FileSystemId`] and [`FileHandle`](super::file::fid::FileHandle),
+//! which an fd event's [`FD`](crate::fd::FD) is [converted](OwnedFileHandle::of) to,
+//! and by how close together they were [pushed](Correlator::push).
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::clock;
+use crate::clock::Clock;
+use crate::mark::Mask;
+
+use super::file::fid::FileSystemId;
+use super::file::fid::InfoType;
+use super::file::fid::OwnedFileHandle;
+use super::owned::OwnedEvent;
+use super::owned::OwnedFile;
+
+/// A single logical event, from an fd event, a FID event, or both merged.
+#[derive(Debug)]
+pub struct CorrelatedEvent {
+    /// The union of the merged events' [`Mask`]s.
+    pub mask: Mask,
+    pub fd: Option<OwnedEvent>,
+    pub fid: Option<OwnedEvent>,
+}
+
+impl CorrelatedEvent {
+    /// If both an fd and a FID event were merged into this.
+    pub fn is_merged(&self) -> bool {
+        self.fd.is_some() && self.fid.is_some()
+    }
+}
+
+struct Pending {
+    key: (FileSystemId, OwnedFileHandle),
+    at: Instant,
+    event: CorrelatedEvent,
+}
+
+/// Merges fd and FID events for the same object into [`CorrelatedEvent`]s.
+///
+/// Each event [pushed](Correlator::push) is held for up to a `window`,
+/// waiting for its counterpart from the other group, which is an event of the other kind
+/// for the same object with an overlapping [`Mask`].
+/// Unmatched events are released once the window passes ([`Correlator::expire`]) or on [`Correlator::flush`].
+///
+/// Events that can't be correlated are released right away:
+/// permission events (which shouldn't be delayed), already [resolved](OwnedFile::Resolved) events,
+/// directory FID events ([`DFid`](InfoType::DFid) and [`DFidName`](InfoType::DFidName)),
+/// and fd events whose handle can't be encoded.
+pub struct Correlator {
+    window: Duration,
+    clock: Arc<dyn Clock>,
+    pending: VecDeque<Pending>,
+    merged: u64,
+}
+
+impl Correlator {
+    /// The default window, long enough for both groups to have been read in the same loop iteration.
+    pub const DEFAULT_WINDOW: Duration = Duration::from_millis(100);
+    
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            clock: clock::system(),
+            pending: VecDeque::new(),
+            merged: 0,
+        }
+    }
+    
+    /// Use `clock` to time the window instead of the [system](clock::SystemClock) one.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+    
+    /// The number of pairs of events merged so far.
+    pub fn merged(&self) -> u64 {
+        self.merged
+    }
+    
+    /// The number of events waiting for their counterparts.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+    
+    fn key(event: &OwnedEvent) -> Option<(FileSystemId, OwnedFileHandle)> {
+        match event.file() {
+            OwnedFile::FD(file) => Some((FileSystemId::of(&file.fd).ok()?, OwnedFileHandle::of(&file.fd).ok()?)),
+            OwnedFile::FID(file) if file.info_type() == InfoType::Fid => {
+                Some((file.file_system_id(), file.handle().clone()))
+            }
+            _ => None,
+        }
+    }
+    
+    /// Push an event from either group, appending any [`CorrelatedEvent`]s now ready to `ready`:
+    /// the event merged with its counterpart, the event itself if it can't be correlated,
+    /// and any [expired](Correlator::expire) ones.
+    pub fn push(&mut self, event: OwnedEvent, ready: &mut Vec<CorrelatedEvent>) {
+        self.expire(ready);
+        let is_fd = matches!(event.file(), OwnedFile::FD(_));
+        let mask = event.mask();
+        let mut correlated = CorrelatedEvent {
+            mask,
+            fd: None,
+            fid: None,
+        };
+        let key = match Self::key(&event) {
+            Some(key) => key,
+            None => {
+                match is_fd {
+                    true => correlated.fd = Some(event),
+                    false => correlated.fid = Some(event),
+                }
+                ready.push(correlated);
+                return;
+            }
+        };
+        let modifiers = Mask::ON_DIR | Mask::EVENT_ON_CHILD;
+        let counterpart = self.pending.iter().position(|it| {
+            it.key == key
+                && (it.event.fd.is_some() != is_fd)
+                && it.event.mask.intersects(mask - modifiers)
+        });
+        if let Some(i) = counterpart {
+            let mut pending = self.pending.remove(i).expect("position is in bounds").event;
+            pending.mask |= mask;
+            match is_fd {
+                true => pending.fd = Some(event),
+                false => pending.fid = Some(event),
+            }
+            self.merged += 1;
+            ready.push(pending);
+            return;
+        }
+        match is_fd {
+            true => correlated.fd = Some(event),
+            false => correlated.fid = Some(event),
+        }
+        self.pending.push_back(Pending {
+            key,
+            at: self.clock.now(),
+            event: correlated,
+        });
+    }
+    
+    /// Release the events that have waited longer than the window for their counterparts, in order.
+    pub fn expire(&mut self, ready: &mut Vec<CorrelatedEvent>) {
+        let now = self.clock.now();
+        while let Some(pending) = self.pending.front() {
+            if now.duration_since(pending.at) < self.window {
+                break;
+            }
+            ready.extend(self.pending.pop_front().map(|it| it.event));
+        }
+    }
+    
+    /// Release all the pending events, in order.
+    pub fn flush(&mut self, ready: &mut Vec<CorrelatedEvent>) {
+        ready.extend(self.pending.drain(..).map(|it| it.event));
+    }
+}
+
+impl Default for Correlator {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_WINDOW)
+    }
+}
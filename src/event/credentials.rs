@@ -0,0 +1,157 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use nix::unistd::Gid;
+use nix::unistd::Pid;
+use nix::unistd::Uid;
+
+use crate::clock::Clock;
+use crate::proc::ProcFs;
+use crate::proc::SystemProcFs;
+
+use super::event::Event;
+use super::id::Id;
+use super::pid_cache::PidCache;
+
+/// The user and group ids of the process (or thread) that caused an [`Event`],
+/// read from its `/proc/{pid}/status`.
+///
+/// These are read after the fact, so they can be missing if the process has already exited,
+/// or changed if it has since changed its ids (e.g. by dropping privileges).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Credentials {
+    /// The real user id, i.e. the user that started the process.
+    pub uid: Uid,
+    /// The effective user id, which is what file access is checked against (e.g. after `sudo`).
+    pub effective_uid: Uid,
+    /// The real group id.
+    pub gid: Gid,
+    /// The effective group id.
+    pub effective_gid: Gid,
+}
+
+impl Credentials {
+    /// Read the [`Credentials`] of the process (or thread) `pid`.
+    pub fn of(pid: Pid) -> io::Result<Self> {
+        Self::of_in(&SystemProcFs, pid)
+    }
+    
+    /// Read the [`Credentials`] of the process (or thread) `pid` from `proc_fs`.
+    pub fn of_in(proc_fs: &dyn ProcFs, pid: Pid) -> io::Result<Self> {
+        let status = proc_fs.read_pid_file(pid.as_raw(), "status")?;
+        Self::parse_status(&status)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("no Uid and Gid in /proc/{}/status", pid)))
+    }
+    
+    /// Read the [`Credentials`] of the process (or thread) that caused `event`.
+    pub fn of_event(event: &Event<'_>) -> io::Result<Self> {
+        match event.id().id() {
+            Id::Pid(pid) | Id::Tid(pid) => Self::of(pid),
+        }
+    }
+    
+    /// Parse the `Uid:` and `Gid:` lines of a `/proc/{pid}/status` file,
+    /// which list the real, effective, saved, and filesystem ids.
+    fn parse_status(status: &str) -> Option<Self> {
+        let ids = |name: &str| -> Option<(u32, u32)> {
+            let line = status.lines().find_map(|it| it.strip_prefix(name))?;
+            let mut ids = line.split_whitespace().map(str::parse);
+            Some((ids.next()?.ok()?, ids.next()?.ok()?))
+        };
+        let (uid, effective_uid) = ids("Uid:")?;
+        let (gid, effective_gid) = ids("Gid:")?;
+        Some(Self {
+            uid: Uid::from_raw(uid),
+            effective_uid: Uid::from_raw(effective_uid),
+            gid: Gid::from_raw(gid),
+            effective_gid: Gid::from_raw(effective_gid),
+        })
+    }
+}
+
+/// A cache of the [`Credentials`] of recent [`Event`]s' processes, keyed by pid (or tid).
+///
+/// Reading `/proc/{pid}/status` for every [`Event`] is relatively expensive,
+/// while a busy process usually causes many [`Event`]s in a row.
+/// Entries expire after a `ttl`, so that a process that changes its ids or a reused pid
+/// is only misattributed for that long.
+#[derive(Debug)]
+pub struct CredentialsCache {
+    cache: PidCache<Credentials>,
+}
+
+impl Default for CredentialsCache {
+    /// A [`CredentialsCache`] with a `ttl` of 1 second and a `capacity` of 1024 processes.
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1), 1024)
+    }
+}
+
+impl CredentialsCache {
+    /// Create an empty [`CredentialsCache`] holding at most `capacity` processes for at most `ttl`.
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            cache: PidCache::new(ttl, capacity),
+        }
+    }
+    
+    pub fn ttl(&self) -> Duration {
+        self.cache.ttl
+    }
+    
+    pub fn capacity(&self) -> usize {
+        self.cache.capacity
+    }
+    
+    pub fn len(&self) -> usize {
+        self.cache.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.cache.entries.is_empty()
+    }
+    
+    pub fn clear(&mut self) {
+        self.cache.entries.clear();
+    }
+    
+    /// Use `clock` to expire entries instead of the [system](crate::clock::SystemClock) one.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.cache.clock = clock;
+        self
+    }
+    
+    /// Read from `proc_fs` instead of the [system](crate::proc::SystemProcFs) one.
+    pub fn with_proc_fs(mut self, proc_fs: Arc<dyn ProcFs>) -> Self {
+        self.cache.proc_fs = proc_fs;
+        self
+    }
+    
+    /// The [`Credentials`] of the process (or thread) that caused `event`,
+    /// or [`None`] if they couldn't be read, e.g. because it has already exited.
+    pub fn get(&mut self, event: &Event<'_>) -> Option<Credentials> {
+        self.cache.get(event, |proc_fs, pid| Credentials::of_in(proc_fs, pid).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::unistd::Gid;
+    use nix::unistd::Uid;
+    
+    use super::Credentials;
+    
+    #[test]
+    fn parse_status() {
+        let status = "Name:\tsh\nUmask:\t0022\nState:\tS (sleeping)\n\
+            Uid:\t1000\t0\t0\t0\nGid:\t100\t100\t100\t100\nGroups:\t100\n";
+        assert_eq!(Credentials::parse_status(status), Some(Credentials {
+            uid: Uid::from_raw(1000),
+            effective_uid: Uid::from_raw(0),
+            gid: Gid::from_raw(100),
+            effective_gid: Gid::from_raw(100),
+        }));
+        assert_eq!(Credentials::parse_status("Name:\tsh\n"), None);
+    }
+}
@@ -0,0 +1,154 @@
+use std::cell::Cell;
+
+use crate::fd::Identity;
+use crate::mark::Mask;
+
+use super::error::EventResult;
+use super::file::File;
+use super::responses::RC;
+use super::sequence::Sequence;
+
+/// The number of duplicate [`Event`](super::event::Event)s a batch's [`Dedup`]s dropped.
+///
+/// Each [`Event`](super::event::Event) is counted once by its [`Sequence`],
+/// so several [`Dedup`]s over the same batch don't count its duplicates more than once.
+///
+/// See [`Events::suppressed_duplicates`](super::events::Events::suppressed_duplicates).
+#[derive(Debug, Default)]
+pub struct DuplicateCount {
+    count: Cell<usize>,
+    last: Cell<Option<Sequence>>,
+}
+
+impl DuplicateCount {
+    pub fn get(&self) -> usize {
+        self.count.get()
+    }
+    
+    fn suppress(&self, sequence: Sequence) {
+        // events are dropped in sequence order, so anything not after the last one was already counted
+        if self.last.get().map_or(true, |last| sequence > last) {
+            self.last.set(Some(sequence));
+            self.count.set(self.count.get() + 1);
+        }
+    }
+}
+
+/// An [`Iterator`] over [`EventResult`]s that drops [`Event`](super::event::Event)s
+/// identical to the one right before them, i.e. with the same [`Mask`] and file [`Identity`].
+///
+/// The kernel merges identical events that are still queued,
+/// but bursts of them can still be delivered once they've been partially read.
+///
+/// Permission events are never dropped, since each one needs its own response,
+/// and neither are events without an [`FD`](crate::fd::FD) or errors.
+///
+/// See [`IntoEvents::dedup`](super::iterator_ext::IntoEvents::dedup).
+pub struct Dedup<I> {
+    events: I,
+    last: Option<(Identity, Mask)>,
+    suppressed: RC<DuplicateCount>,
+}
+
+impl<I> Dedup<I> {
+    pub(super) fn new(events: I, suppressed: RC<DuplicateCount>) -> Self {
+        Self {
+            events,
+            last: None,
+            suppressed,
+        }
+    }
+    
+    /// The number of duplicate [`Event`](super::event::Event)s dropped from the batch so far,
+    /// including by other [`Dedup`]s over it.  See [`DuplicateCount`].
+    pub fn suppressed(&self) -> usize {
+        self.suppressed.get()
+    }
+}
+
+impl<'a, I: Iterator<Item = EventResult<'a>>> Iterator for Dedup<I> {
+    type Item = EventResult<'a>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let event = self.events.next()?;
+            let key = match &event {
+                Ok(event) => match event.file() {
+                    File::FD(file) => file.fd.identity().ok().map(|it| (it, event.mask())),
+                    File::Resolved(file) => file.identity().map(|it| (it, event.mask())),
+                    _ => None,
+                },
+                Err(_) => None,
+            };
+            if key.is_some() && key == self.last {
+                if let Ok(event) = &event {
+                    self.suppressed.suppress(event.sequence());
+                }
+                // dropping a non-permission event does nothing
+                continue;
+            }
+            self.last = key;
+            return Some(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File as StdFile;
+    use std::os::unix::io::FromRawFd;
+    use std::os::unix::io::IntoRawFd;
+    
+    use crate::fd::FD;
+    use crate::mark::Mask;
+    
+    use super::super::event::Event;
+    use super::super::file::fd::FileFD;
+    use super::super::file::File;
+    use super::super::id::EventId;
+    use super::super::id::Id;
+    use super::super::responses::RC;
+    use super::super::sequence::Sequence;
+    use super::Dedup;
+    use super::DuplicateCount;
+    
+    fn event(path: &str, mask: Mask, event: u64) -> Event<'static> {
+        let fd = StdFile::open(path).unwrap().into_raw_fd();
+        Event {
+            mask,
+            id: EventId {
+                id: Id::current(false),
+                is_generated_by_self: true,
+            },
+            file: File::FD(FileFD {
+                fd: unsafe { FD::from_raw_fd(fd) },
+            }),
+            sequence: Sequence { batch: 0, event },
+        }
+    }
+    
+    #[test]
+    fn dedup_consecutive() {
+        let events = || vec![
+            event("/", Mask::OPEN, 0),
+            event("/", Mask::OPEN, 1),
+            event("/", Mask::CLOSE_NO_WRITE, 2),
+            event("/", Mask::OPEN, 3),
+            event("/", Mask::OPEN, 4),
+            event("/", Mask::OPEN, 5),
+        ];
+        let count = RC::new(DuplicateCount::default());
+        let mut dedup = Dedup::new(events().into_iter().map(Ok), count.clone());
+        let masks = dedup
+            .by_ref()
+            .map(|it| it.unwrap().mask())
+            .collect::<Vec<_>>();
+        assert_eq!(masks, vec![Mask::OPEN, Mask::CLOSE_NO_WRITE, Mask::OPEN]);
+        assert_eq!(dedup.suppressed(), 3);
+        
+        // another pass over the same batch doesn't count its duplicates again
+        let again = Dedup::new(events().into_iter().map(Ok), count.clone());
+        assert_eq!(again.count(), 3);
+        assert_eq!(count.get(), 3);
+    }
+}
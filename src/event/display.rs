@@ -1,20 +1,127 @@
 use std::fmt;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use crate::export::json;
+use crate::mark::Mask;
 
 use super::event::Event;
+use super::file::File;
+use super::id::Id;
+
+/// The different ways of formatting [`Event`]s and [`Mask`]s.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Format {
+    /// A single short line, e.g. `fd, Pid(1), OPEN | ACCESS: /etc/passwd`.
+    Compact,
+    /// All the fields, like [`Debug`], but with resolved paths.
+    Verbose,
+    /// A single-line JSON object (or array for [`Mask`]s and lists of [`Event`]s).
+    Json,
+}
+
+impl Format {
+    pub const fn const_default() -> Self {
+        Self::Compact
+    }
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self::const_default()
+    }
+}
+
+/// Write `s` as a JSON string, escaping it as necessary.
+fn write_json_str(f: &mut Formatter<'_>, s: &str) -> fmt::Result {
+    json::write_escaped(f, s)
+}
+
+fn write_json_path(f: &mut Formatter<'_>, path: &Path) -> fmt::Result {
+    write_json_str(f, &path.to_string_lossy())
+}
+
+/// Formats a [`Mask`] using a [`Format`].
+pub struct FormatMask {
+    pub mask: Mask,
+    pub format: Format,
+}
 
+impl Mask {
+    pub fn format(self, format: Format) -> FormatMask {
+        FormatMask {
+            mask: self,
+            format,
+        }
+    }
+    
+    /// The individual (single-bit) flags set in this [`Mask`].
+    pub fn flags(self) -> impl Iterator<Item=Self> {
+        (0..u64::BITS)
+            .map(|i| 1 << i)
+            .filter(move |bit| self.bits() & bit != 0)
+            .filter_map(Self::from_bits)
+    }
+    
+    /// The single flag named `name`, as it's [formatted](Mask::format), like `CLOSE_WRITE`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().flags().find(|flag| format!("{:?}", flag) == name)
+    }
+}
+
+impl Display for FormatMask {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mask = self.mask;
+        match self.format {
+            Format::Compact => write!(f, "{:?}", mask),
+            Format::Verbose => write!(f, "{:?} ({:#x})", mask, mask.bits()),
+            Format::Json => {
+                write!(f, "[")?;
+                for (i, flag) in mask.flags().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write_json_str(f, &format!("{:?}", flag))?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+/// Formats an [`Event`] using a [`Format`].
+pub struct FormatEvent<'a, 'b> {
+    pub event: &'a Event<'b>,
+    pub format: Format,
+}
+
+/// Formats an [`Event`] using [`Format::Compact`].
 pub struct DisplayEvent<'a, 'b>(pub &'a Event<'b>);
 
 impl<'a, 'b> Event<'b> {
     pub fn display(&'a self) -> DisplayEvent<'a, 'b> {
         DisplayEvent(self)
     }
+    
+    pub fn format(&'a self, format: Format) -> FormatEvent<'a, 'b> {
+        FormatEvent {
+            event: self,
+            format,
+        }
+    }
 }
 
 impl Display for DisplayEvent<'_, '_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let event = self.0;
+        write!(f, "{}", self.0.format(Format::Compact))
+    }
+}
+
+impl FormatEvent<'_, '_> {
+    fn fmt_compact(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let event = self.event;
         write!(f, "{}, {:?}, {:?}", event.file().variant_name(), event.id().id(), event.mask())?;
         if let Some(path) = event.file().path() {
             write!(f, ": ")?;
@@ -25,17 +132,114 @@ impl Display for DisplayEvent<'_, '_> {
         }
         Ok(())
     }
+    
+    fn fmt_verbose(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let event = self.event;
+        write!(f, "Event {{ ")?;
+        write!(f, "file: {}, ", event.file().variant_name())?;
+        write!(f, "id: {:?}, ", event.id().id())?;
+        write!(f, "generated_by_self: {}, ", event.id().is_generated_by_self())?;
+        write!(f, "mask: {}", event.mask().format(Format::Verbose))?;
+        match event.file() {
+            File::FID(fid) => {
+                write!(f, ", info_type: {:?}", fid.info_type())?;
+                write!(f, ", file_system_id: {:?}", fid.file_system_id())?;
+                write!(f, ", handle: {:?}", fid.handle())?;
+            }
+            File::FD(_) | File::Permission(_) | File::Resolved(_) => {}
+        }
+        if let Some(fd) = event.file().get_fd() {
+            write!(f, ", fd: {}", fd.as_raw_fd())?;
+        }
+        if let File::Permission(permission) = event.file() {
+            write!(f, ", decision: {:?}", permission.decision)?;
+            write!(f, ", audit: {}", permission.audit)?;
+        }
+        if let Some(path) = event.file().path() {
+            match path {
+                Ok(path) => write!(f, ", path: {}", path.display())?,
+                Err(e) => write!(f, ", path_error: {}", e)?,
+            }
+        }
+        write!(f, " }}")
+    }
+    
+    fn fmt_json(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let event = self.event;
+        write!(f, "{{\"file\":")?;
+        write_json_str(f, event.file().variant_name())?;
+        match event.id().id() {
+            Id::Pid(pid) => write!(f, ",\"pid\":{}", pid)?,
+            Id::Tid(tid) => write!(f, ",\"tid\":{}", tid)?,
+        }
+        write!(f, ",\"generated_by_self\":{}", event.id().is_generated_by_self())?;
+        write!(f, ",\"mask\":{}", event.mask().format(Format::Json))?;
+        if let Some(path) = event.file().path() {
+            match path {
+                Ok(path) => {
+                    write!(f, ",\"path\":")?;
+                    write_json_path(f, &path)?;
+                }
+                Err(e) => {
+                    write!(f, ",\"path_error\":")?;
+                    write_json_str(f, &e.to_string())?;
+                }
+            }
+        }
+        write!(f, "}}")
+    }
+}
+
+impl Display for FormatEvent<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.format {
+            Format::Compact => self.fmt_compact(f),
+            Format::Verbose => self.fmt_verbose(f),
+            Format::Json => self.fmt_json(f),
+        }
+    }
 }
 
-pub struct DisplayEvents<'a, 'b>(pub &'a Vec<Event<'b>>);
+/// Formats a list of [`Event`]s using a [`Format`].
+pub struct FormatEvents<'a, 'b> {
+    pub events: &'a [Event<'b>],
+    pub format: Format,
+}
+
+/// Formats a list of [`Event`]s using [`Format::Compact`], one per line.
+pub struct DisplayEvents<'a, 'b>(pub &'a [Event<'b>]);
 
 impl Display for DisplayEvents<'_, '_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        writeln!(f, "[")?;
-        for event in self.0 {
-            writeln!(f, "    {},", event.display())?;
+        let events = FormatEvents {
+            events: self.0,
+            format: Format::Compact,
+        };
+        write!(f, "{}", events)
+    }
+}
+
+impl Display for FormatEvents<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.format {
+            Format::Compact | Format::Verbose => {
+                writeln!(f, "[")?;
+                for event in self.events {
+                    writeln!(f, "    {},", event.format(self.format))?;
+                }
+                write!(f, "]")?;
+            }
+            Format::Json => {
+                write!(f, "[")?;
+                for (i, event) in self.events.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", event.format(self.format))?;
+                }
+                write!(f, "]")?;
+            }
         }
-        write!(f, "]")?;
         Ok(())
     }
 }
@@ -1,5 +1,12 @@
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
 use crate::fd::FD;
 use crate::init;
+use crate::init::NotificationClass;
+use crate::libc::read::fanotify_event_metadata;
+use crate::mark::Mask;
 
 use super::event::Event;
 
@@ -21,6 +28,46 @@ pub enum TooShortError {
     FidEvent,
 }
 
+/// A copy of the raw [`fanotify_event_metadata`] of a malformed event,
+/// so that an [`EventError`] can show exactly which event it was.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct RawMetadata {
+    pub event_len: u32,
+    pub vers: u8,
+    pub metadata_len: u16,
+    pub mask: u64,
+    pub fd: i32,
+    pub pid: i32,
+}
+
+impl From<&fanotify_event_metadata> for RawMetadata {
+    fn from(this: &fanotify_event_metadata) -> Self {
+        Self {
+            event_len: this.event_len,
+            vers: this.vers,
+            metadata_len: this.metadata_len,
+            mask: this.mask,
+            fd: this.fd,
+            pid: this.pid,
+        }
+    }
+}
+
+impl Display for RawMetadata {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{ event_len: {}, vers: {}, metadata_len: {}, mask: {:?}, fd: {}, pid: {} }}",
+            self.event_len,
+            self.vers,
+            self.metadata_len,
+            Mask::from_bits_truncate(self.mask),
+            self.fd,
+            self.pid,
+        )
+    }
+}
+
 /// An error from reading an [`Event`] from the buffer.
 ///
 /// TODO document each error
@@ -28,26 +75,59 @@ pub enum TooShortError {
 pub enum EventError {
     #[error("the fanotify queue overflowed")]
     QueueOverflowed,
-    #[error("the fanotify event has the wrong version so it can't be handled")]
-    WrongVersion,
+    #[error("the fanotify event {} has the wrong version so it can't be handled", .metadata)]
+    WrongVersion { metadata: RawMetadata },
     #[error("the data read ({} bytes) is too short for a full event ({} bytes), specifically, the {}", .found, .expected, .what)]
     TooShort {
         what: TooShortError,
         found: usize,
         expected: usize,
     },
+    /// The last event was cut off by the end of the bytes being parsed,
+    /// so it can be completed by prepending its `partial` bytes to the next ones.
+    ///
+    /// Events read by [`Fanotify::read`](crate::fanotify::Fanotify::read) never are,
+    /// since a partial event is carried over to the next read instead
+    /// (see [`EventBuffer::carry`](super::buffer::EventBuffer::carry)),
+    /// so this is only returned for [`Events::parse_from_bytes`](super::events::Events::parse_from_bytes).
+    #[error("the last event was truncated at the end of the buffer ({} of {} bytes)", .partial.len(), .expected)]
+    Truncated {
+        partial: Vec<u8>,
+        expected: usize,
+    },
     #[error("the fanotify queue still overflowed even though {:?} was specified", init::Flags::UNLIMITED_QUEUE)]
     UnlimitedQueueButQueueStillOverflowed,
-    #[error("{:?} requested but not received", init::Flags::REPORT_FID)]
-    FidRequestedButNotReceived,
-    #[error("{:?} not requested but received", init::Flags::REPORT_FID)]
-    FidNotRequestedButReceived,
-    #[error("a {:?} fanotify event was received for a permission event, meaning it lacks an fd for writing the permission", init::Flags::REPORT_FID)]
-    FidReturnedForPermissionEvent,
-    #[error("{:?} request but received an invalid or unknown info_type: {}", init::Flags::REPORT_FID, .info_type)]
-    InvalidFidInfoType { info_type: u8 },
-    #[error("received an invalid fd: {}", .fd)]
-    InvalidFd { fd: FD },
+    #[error("{:?} requested but not received in event {}", init::Flags::REPORT_FID, .metadata)]
+    FidRequestedButNotReceived { metadata: RawMetadata },
+    #[error("{:?} not requested but received in event {}", init::Flags::REPORT_FID, .metadata)]
+    FidNotRequestedButReceived { metadata: RawMetadata },
+    #[error(
+        "a {:?} fanotify event was received for a permission event, \
+        meaning it lacks an fd for writing the permission, in event {}",
+        init::Flags::REPORT_FID,
+        .metadata,
+    )]
+    FidReturnedForPermissionEvent { metadata: RawMetadata },
+    /// Permission events should never be received on a [`Notify`](NotificationClass::Notify) group,
+    /// since they can't be responded to (marking them is rejected up front).
+    #[error(
+        "permission event {:?} received on a {:?} group, which can't respond to it; \
+        use {:?} or {:?} instead (without {:?})",
+        .mask,
+        NotificationClass::Notify,
+        NotificationClass::Content,
+        NotificationClass::PreContent,
+        init::Flags::REPORT_FID,
+    )]
+    PermissionEventOnNotifyGroup { mask: Mask },
+    #[error("{:?} request but received an invalid or unknown info_type: {} in event {}", init::Flags::REPORT_FID, .info_type, .metadata)]
+    InvalidFidInfoType { info_type: u8, metadata: RawMetadata },
+    #[error("received an invalid fd: {} in event {}", .fd, .metadata)]
+    InvalidFd { fd: FD, metadata: RawMetadata },
+    /// The event was [parsed from bytes](super::events::Events::parse_from_bytes) read by another process,
+    /// so its fd isn't valid in this one.
+    #[error("the fd in event {} belongs to the process that read it, so it can't be used here", .metadata)]
+    FdFromOtherProcess { metadata: RawMetadata },
 }
 
 pub type EventResult<'a> = Result<Event<'a>, EventError>;
\ No newline at end of file
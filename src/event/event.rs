@@ -5,12 +5,14 @@ use super::file::fid::FileFID;
 use super::file::File;
 use super::file::permission::FilePermission;
 use super::id::EventId;
+use super::sequence::Sequence;
 
 #[derive(Debug)]
 pub struct EventOf<FileT> {
     pub(super) mask: mark::Mask,
     pub(super) id: EventId,
     pub(super) file: FileT,
+    pub(super) sequence: Sequence,
 }
 
 impl<FileT> EventOf<FileT> {
@@ -22,6 +24,11 @@ impl<FileT> EventOf<FileT> {
         &self.id
     }
     
+    /// Where this event falls in its group's stream of events.
+    pub fn sequence(&self) -> Sequence {
+        self.sequence
+    }
+    
     pub fn file(&self) -> &FileT {
         &self.file
     }
@@ -48,11 +55,12 @@ pub type Event<'a> = EventOf<File<'a>>;
 
 impl<'a> Event<'a> {
     fn into_variant<FileT>(self, project: impl Fn(File<'a>) -> Option<FileT>) -> Option<EventOf<FileT>> {
-        let Self { mask, id, file } = self;
+        let Self { mask, id, file, sequence } = self;
         Some(EventOf {
             mask,
             id,
             file: project(file)?,
+            sequence,
         })
     }
     
@@ -1,13 +1,18 @@
 use std::mem::size_of;
 use std::slice;
+use std::time::Duration;
 
 use nix::errno::Errno;
 
 use crate::event::buffer::EventBuffer;
 use crate::fanotify::Fanotify;
 use crate::init;
+use crate::init::RawInit;
+use crate::libc::read::fanotify_event_metadata;
 
+use super::dedup::DuplicateCount;
 use super::id::Id;
+use super::responses::PendingResponse;
 use super::responses::RC;
 use super::responses::Responses;
 
@@ -21,6 +26,10 @@ pub struct Events<'a> {
     id: Id,
     buffer: &'a mut Vec<u8>,
     responses: RC<Responses<'a>>,
+    duplicates: RC<DuplicateCount>,
+    batch: u64,
+    carried_in: usize,
+    carried_over: usize,
 }
 
 impl<'a> Events<'a> {
@@ -32,13 +41,69 @@ impl<'a> Events<'a> {
         self.id
     }
     
+    /// The [number](super::sequence::Sequence::batch) of this batch in its group.
+    pub fn batch(&self) -> u64 {
+        self.batch
+    }
+    
+    /// The number of duplicate [`Event`](super::event::Event)s [deduplicated](super::iterator_ext::IntoEvents::dedup) from this batch so far.
+    ///
+    /// Once these [`Events`] are being iterated over,
+    /// keep their [`IntoEvents::duplicate_count`](super::iterator_ext::IntoEvents::duplicate_count) instead.
+    pub fn suppressed_duplicates(&self) -> usize {
+        self.duplicates.get()
+    }
+    
+    pub(super) fn duplicates(&self) -> RC<DuplicateCount> {
+        self.duplicates.clone()
+    }
+    
     pub(super) fn responses(&self) -> RC<Responses<'a>> {
         self.responses.clone()
     }
     
-    pub(super) fn as_bytes(&self) -> &[u8] {
+    /// See [`Responses::flush_if_due`].
+    pub fn flush_responses_if_due(&self) {
+        self.responses.flush_if_due();
+    }
+    
+    /// See [`Responses::pending_responses`].
+    ///
+    /// Once these [`Events`] are being iterated over,
+    /// use [`EventIterator::pending_responses`](super::iterator::EventIterator::pending_responses) instead.
+    pub fn pending_responses(&self) -> Vec<PendingResponse> {
+        self.responses.pending_responses()
+    }
+    
+    /// The raw bytes of the events, exactly as read from the kernel.
+    ///
+    /// These can be shipped elsewhere, e.g. over a pipe to an unprivileged process,
+    /// and parsed there with [`Events::parse_from_bytes`] without any re-serialization.
+    ///
+    /// Any file descriptors in them belong to this process, though,
+    /// so only the events of a [`REPORT_FID`](init::Flags::REPORT_FID) group,
+    /// which have none, are fully usable elsewhere.
+    /// These [`Events`] still need to be iterated over here to close or respond to any file descriptors.
+    pub fn raw_bytes(&self) -> &[u8] {
         self.buffer.as_slice()
     }
+    
+    /// The number of bytes at the start of [`Events::raw_bytes`]
+    /// that were carried over from an event truncated at the end of the previous read.
+    pub fn carried_in(&self) -> usize {
+        self.carried_in
+    }
+    
+    /// The number of bytes of an event truncated at the end of this read,
+    /// which were moved to the [`EventBuffer::carry`] to be completed by the next read
+    /// instead of being parsed as a [`Truncated`](super::error::EventError::Truncated) event.
+    pub fn carried_over(&self) -> usize {
+        self.carried_over
+    }
+    
+    pub(crate) fn buffer_capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
 }
 
 impl<'a> Events<'a> {
@@ -50,10 +115,22 @@ impl<'a> Events<'a> {
         fanotify: &'a Fanotify,
         buffer: &'a mut EventBuffer,
     ) -> std::result::Result<Self, Errno> {
-        let EventBuffer {
-            events: buffer,
-            responses: response_buffer,
-        } = buffer;
+        Self::read_raw(fanotify, &mut buffer.events)?;
+        Ok(Self::from_buffer(fanotify, buffer))
+    }
+    
+    /// Read raw events from a [`Fanotify`] into the given events buffer,
+    /// replacing whatever was in it before.
+    ///
+    /// On error, the buffer is left empty but otherwise reusable.
+    ///
+    /// This is separate from [`Events::from_buffer`] so that a read can be retried
+    /// (e.g. on [`EAGAIN`](Errno::EAGAIN) in async code)
+    /// without holding onto the buffer for the lifetime of the [`Events`].
+    pub(in super::super) fn read_raw(
+        fanotify: &Fanotify,
+        buffer: &mut Vec<u8>,
+    ) -> std::result::Result<(), Errno> {
         buffer.clear();
         
         // want to use this, but it's unstable
@@ -65,6 +142,65 @@ impl<'a> Events<'a> {
         };
         let bytes_read = fanotify.fd.read(read_buffer)?;
         unsafe { buffer.set_len(bytes_read) };
+        Ok(())
+    }
+    
+    /// Keep reading raw events from a [`Fanotify`] without blocking,
+    /// appending them to the given events buffer until no more are pending
+    /// or the buffer holds at least `max_bytes`.
+    ///
+    /// Each read has at least `chunk` bytes of space,
+    /// growing the buffer as needed, so `chunk` must fit the largest event.
+    pub(in super::super) fn read_pending_raw(
+        fanotify: &Fanotify,
+        buffer: &mut Vec<u8>,
+        chunk: usize,
+        max_bytes: usize,
+    ) -> std::result::Result<(), Errno> {
+        while buffer.len() < max_bytes && fanotify.fd.readable(Some(Duration::from_secs(0)))? {
+            buffer.reserve(chunk);
+            let len = buffer.len();
+            let read_buffer = {
+                let ptr = unsafe { buffer.as_mut_ptr().add(len) };
+                let len = (buffer.capacity() - len) * size_of::<u8>();
+                unsafe { slice::from_raw_parts_mut(ptr, len) }
+            };
+            let bytes_read = match fanotify.fd.read(read_buffer) {
+                Err(Errno::EAGAIN) => break,
+                result => result?,
+            };
+            if bytes_read == 0 {
+                break;
+            }
+            unsafe { buffer.set_len(len + bytes_read) };
+        }
+        Ok(())
+    }
+    
+    /// Construct an [`Events`] from events already read into the buffer by [`Events::read_raw`].
+    ///
+    /// Any partial event carried over from the previous read is prepended to the events,
+    /// and any partial event at the end of them is carried over to the next read.
+    /// The kernel only ever returns whole events, so this is only defensive,
+    /// but it means a truncated event is never surfaced as an error.
+    pub(in super::super) fn from_buffer(
+        fanotify: &'a Fanotify,
+        buffer: &'a mut EventBuffer,
+    ) -> Self {
+        let EventBuffer {
+            events: buffer,
+            responses: response_buffer,
+            carry,
+        } = buffer;
+        
+        let carried_in = carry.len();
+        if carried_in != 0 {
+            buffer.splice(0..0, carry.drain(..)).for_each(drop);
+        }
+        let complete = complete_len(buffer);
+        carry.extend_from_slice(&buffer[complete..]);
+        buffer.truncate(complete);
+        let carried_over = carry.len();
         
         // id is read here for two reason
         // 1. it caches it for this set of events
@@ -73,12 +209,73 @@ impl<'a> Events<'a> {
         let use_tid = fanotify.init.flags().contains(init::Flags::REPORT_TID);
         let id = Id::current(use_tid);
         
-        let this = Self {
+        Self {
             fanotify,
             id,
             buffer,
             responses: RC::new(Responses::new(fanotify, response_buffer)),
-        };
-        Ok(this)
+            duplicates: RC::default(),
+            batch: fanotify.sequence.next_batch(),
+            carried_in,
+            carried_over,
+        }
+    }
+    
+    /// Parse events from [`Events::raw_bytes`] shipped from another [`Fanotify`] group,
+    /// e.g. from a privileged process that only reads events.
+    ///
+    /// `init` must be that group's [`RawInit`] (see [`Fanotify::init`]),
+    /// since it determines how the events are laid out.
+    ///
+    /// Since any file descriptors in `bytes` belong to the other process,
+    /// events with them (including all permission events) are returned as
+    /// [`EventError::FdFromOtherProcess`](super::error::EventError::FdFromOtherProcess)s,
+    /// and their file descriptors are left alone.
+    /// And since the process that read them isn't known,
+    /// no events are [generated by self](super::id::EventId::is_generated_by_self).
+    pub fn parse_from_bytes(bytes: &'a [u8], init: RawInit) -> ParsedEvents<'a> {
+        ParsedEvents { bytes, init }
+    }
+}
+
+/// The length of the whole events at the start of `bytes`,
+/// i.e. where an event truncated at the end of them starts, or the length of `bytes` if there is none.
+///
+/// An event too short for even its own metadata is malformed, not truncated,
+/// so it and everything after it are left for the [`EventIterator`](super::iterator::EventIterator) to report.
+pub(crate) fn complete_len(bytes: &[u8]) -> usize {
+    let mut offset = 0;
+    loop {
+        let remaining = &bytes[offset..];
+        if remaining.len() < size_of::<u32>() {
+            return offset;
+        }
+        // only the leading fanotify_event_metadata::event_len field is guaranteed to be in bounds here
+        let event_len = unsafe { (remaining.as_ptr() as *const u32).read_unaligned() } as usize;
+        if event_len < size_of::<fanotify_event_metadata>() {
+            return bytes.len();
+        }
+        if event_len > remaining.len() {
+            return offset;
+        }
+        offset += event_len;
+    }
+}
+
+/// [`Events`] parsed from [raw bytes](Events::raw_bytes) by [`Events::parse_from_bytes`],
+/// borrowing those bytes instead of an events buffer.
+#[derive(Debug, Copy, Clone)]
+pub struct ParsedEvents<'a> {
+    pub(super) bytes: &'a [u8],
+    pub(super) init: RawInit,
+}
+
+impl<'a> ParsedEvents<'a> {
+    pub fn raw_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+    
+    pub fn init(&self) -> RawInit {
+        self.init
     }
 }
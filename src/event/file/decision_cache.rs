@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::mem::ManuallyDrop;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use nix::errno::Errno;
+
+use crate::clock;
+use crate::clock::Clock;
+use crate::event::event::Event;
+use crate::fanotify::pipeline::Layer;
+use crate::fd::FD;
+use crate::fd::Identity;
+use crate::mark::Mask;
+
+use super::permission::PermissionDecision;
+
+/// The version of a file's content a cached decision was made for:
+/// its modification and status change times (in seconds and nanoseconds) and size.
+///
+/// The status change time can't be set from userspace like the modification time can,
+/// so a file can't be changed and then made to look unchanged.
+type Version = (libc::time_t, libc::c_long, libc::time_t, libc::c_long, libc::off_t);
+
+#[derive(Debug, Copy, Clone)]
+struct Entry {
+    version: Version,
+    decision: PermissionDecision,
+    decided_at: Instant,
+}
+
+/// What [`DecisionCache::prewarm`] did.
+#[derive(Debug, Default)]
+pub struct PrewarmReport {
+    /// The number of files decided and cached.
+    pub decided: usize,
+    /// The files and directories that couldn't be opened or read, and why.
+    pub failed: Vec<(PathBuf, io::Error)>,
+    /// The number of files that changed while being decided, so their decisions weren't cached.
+    pub changed: usize,
+    /// If the cache filled up before every file was decided.
+    pub full: bool,
+}
+
+/// A cache of permission decisions, keyed by the file's `(device, inode)` [`Identity`]
+/// and its modification and status change times and size,
+/// so that e.g. repeated opens of the same binary don't re-run an expensive scan.
+///
+/// A cached decision is used only if the file's times and size are unchanged,
+/// and it is younger than the TTL, if there is one.
+/// Since the times are only as precise as the filesystem's timestamps,
+/// pass modification [`Event`]s to [`DecisionCache::observe`]
+/// (or add the cache as a [`Layer`] to a [`Pipeline`](crate::fanotify::pipeline::Pipeline))
+/// to invalidate decisions as soon as a file is written to.
+#[derive(Debug)]
+pub struct DecisionCache {
+    ttl: Option<Duration>,
+    capacity: usize,
+    entries: HashMap<Identity, Entry>,
+    hits: u64,
+    misses: u64,
+    clock: Arc<dyn Clock>,
+}
+
+impl DecisionCache {
+    /// The [`Event`]s that invalidate a file's cached decision.
+    #[allow(clippy::identity_op)]
+    pub const INVALIDATING: Mask = Mask::from_bits_truncate(0
+        | Mask::MODIFY.bits()
+        | Mask::CLOSE_WRITE.bits()
+    );
+    
+    /// Create an empty [`DecisionCache`] holding at most `capacity` decisions,
+    /// each for at most `ttl`, if given.
+    pub fn new(capacity: usize, ttl: Option<Duration>) -> Self {
+        Self {
+            ttl,
+            capacity,
+            entries: HashMap::new(),
+            hits: 0,
+            misses: 0,
+            clock: clock::system(),
+        }
+    }
+    
+    /// Use `clock` to expire decisions instead of the [system](clock::SystemClock) one.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+    
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+    
+    /// The number of decisions found in the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+    
+    /// The number of decisions that had to be made.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+    
+    /// Remove the cached decision for this file, if there is one.
+    pub fn invalidate(&mut self, identity: Identity) -> Option<PermissionDecision> {
+        self.entries.remove(&identity).map(|it| it.decision)
+    }
+    
+    /// Invalidate the cached decision for the file of an [`Event`] if it's a modification
+    /// (i.e., it intersects [`DecisionCache::INVALIDATING`]).
+    pub fn observe(&mut self, event: &Event<'_>) {
+        if !event.mask().intersects(Self::INVALIDATING) {
+            return;
+        }
+        if let Some(Ok(identity)) = event.file().get_fd().map(|fd| fd.identity()) {
+            self.invalidate(identity);
+        }
+    }
+    
+    fn is_fresh(ttl: Option<Duration>, now: Instant, entry: &Entry) -> bool {
+        match ttl {
+            None => true,
+            Some(ttl) => now.saturating_duration_since(entry.decided_at) < ttl,
+        }
+    }
+    
+    /// Look up the cached decision for `fd`, if there is a fresh one.
+    pub fn get(&self, fd: &FD) -> Result<Option<PermissionDecision>, Errno> {
+        let (identity, version) = Self::key(fd)?;
+        let now = self.clock.now();
+        let decision = self.entries
+            .get(&identity)
+            .filter(|it| it.version == version && Self::is_fresh(self.ttl, now, it))
+            .map(|it| it.decision);
+        Ok(decision)
+    }
+    
+    /// Cache a decision for `fd`.
+    pub fn insert(&mut self, fd: &FD, decision: PermissionDecision) -> Result<(), Errno> {
+        let (identity, version) = Self::key(fd)?;
+        self.insert_version(identity, version, decision);
+        Ok(())
+    }
+    
+    fn insert_version(&mut self, identity: Identity, version: Version, decision: PermissionDecision) {
+        if self.capacity == 0 {
+            return;
+        }
+        let now = self.clock.now();
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&identity) {
+            // drop stale decisions first, and then arbitrary ones if that's not enough
+            let ttl = self.ttl;
+            self.entries.retain(|_, it| Self::is_fresh(ttl, now, it));
+            if self.entries.len() >= self.capacity {
+                let evicted = self.entries.keys().next().copied();
+                if let Some(evicted) = evicted {
+                    self.entries.remove(&evicted);
+                }
+            }
+        }
+        self.entries.insert(identity, Entry {
+            version,
+            decision,
+            decided_at: now,
+        });
+    }
+    
+    /// Return the cached decision for `fd`,
+    /// or else make it with `decide` (e.g. an expensive scan) and cache it.
+    ///
+    /// The decision is only cached if the file is unchanged after `decide`,
+    /// since it may have been made on content that was being changed,
+    /// and if `fd` can't be [`stat`](FD::stat)ed, `decide` is just called without caching.
+    pub fn decide(&mut self, fd: &FD, decide: impl FnOnce() -> PermissionDecision) -> PermissionDecision {
+        if let Ok(Some(decision)) = self.get(fd) {
+            self.hits += 1;
+            return decision;
+        }
+        self.misses += 1;
+        let before = Self::key(fd);
+        let decision = decide();
+        if let (Ok(before), Ok(after)) = (before, Self::key(fd)) {
+            if before == after {
+                self.insert_version(after.0, after.1, decision);
+            }
+        }
+        decision
+    }
+    
+    /// Decide and cache every regular file in `dir` (and its subdirectories if `recursive`)
+    /// ahead of time, e.g. at startup,
+    /// so that the first access of a known file is a cache hit instead of an expensive scan.
+    ///
+    /// `decide` is given each file's path and the file opened for reading.
+    /// Symlinks aren't followed, and this stops once the cache is full.
+    /// Opening the files generates events of their own,
+    /// so this should be done before they're marked, or while events are being responded to.
+    pub fn prewarm(
+        &mut self,
+        dir: impl AsRef<Path>,
+        recursive: bool,
+        mut decide: impl FnMut(&Path, &mut fs::File) -> PermissionDecision,
+    ) -> PrewarmReport {
+        let mut report = PrewarmReport::default();
+        let mut dirs = vec![dir.as_ref().to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    report.failed.push((dir, e));
+                    continue;
+                }
+            };
+            for entry in entries {
+                if self.entries.len() >= self.capacity {
+                    report.full = true;
+                    return report;
+                }
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        report.failed.push((dir.clone(), e));
+                        continue;
+                    }
+                };
+                let path = entry.path();
+                match entry.file_type() {
+                    Ok(file_type) if file_type.is_dir() => {
+                        if recursive {
+                            dirs.push(path);
+                        }
+                        continue;
+                    }
+                    Ok(file_type) if file_type.is_file() => {}
+                    Ok(_) => continue,
+                    Err(e) => {
+                        report.failed.push((path, e));
+                        continue;
+                    }
+                }
+                let mut file = match fs::File::open(&path) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        report.failed.push((path, e));
+                        continue;
+                    }
+                };
+                // borrow the file's fd without closing it
+                let fd = ManuallyDrop::new(unsafe { FD::from_raw_fd(file.as_raw_fd()) });
+                // like in `decide`, only cache it if it didn't change while deciding
+                let before = Self::key(&fd);
+                let decision = decide(&path, &mut file);
+                match before.and_then(|before| Ok((before, Self::key(&fd)?))) {
+                    Ok((before, after)) if before == after => {
+                        self.insert_version(after.0, after.1, decision);
+                        report.decided += 1;
+                    }
+                    Ok(_) => report.changed += 1,
+                    Err(errno) => report.failed.push((path, io::Error::from(errno))),
+                }
+            }
+        }
+        report
+    }
+    
+    fn key(fd: &FD) -> Result<(Identity, Version), Errno> {
+        let stat = fd.stat()?;
+        Ok((
+            (stat.st_dev, stat.st_ino),
+            (stat.st_mtime, stat.st_mtime_nsec, stat.st_ctime, stat.st_ctime_nsec, stat.st_size),
+        ))
+    }
+}
+
+impl Default for DecisionCache {
+    fn default() -> Self {
+        Self::new(4096, None)
+    }
+}
+
+/// [`Observe`](DecisionCache::observe) every [`Event`], passing all of them on.
+impl Layer for DecisionCache {
+    fn handle(&mut self, event: &Event<'_>) -> bool {
+        self.observe(event);
+        true
+    }
+}
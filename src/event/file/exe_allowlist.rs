@@ -0,0 +1,226 @@
+use std::collections::HashSet;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::IntoRawFd;
+use std::path::Path;
+use std::path::PathBuf;
+
+use nix::unistd::Pid;
+
+use crate::event::event::Event;
+use crate::fd::FD;
+use crate::integrity::sha256;
+use crate::integrity::sha256::Digest;
+use crate::proc;
+use crate::proc::LinkState;
+
+use super::decision_cache::DecisionCache;
+use super::decision_cache::PrewarmReport;
+use super::permission::PermissionDecision;
+
+#[derive(thiserror::Error, Debug, Clone, Eq, PartialEq)]
+#[error("line {}: {}", .line, .reason)]
+pub struct AllowlistParseError {
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+/// Why an [`ExeAllowlist`] made the decision it did.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum ExeMatch {
+    /// The executable's path is allowlisted.
+    Path(PathBuf),
+    /// The executable's SHA-256 [`Digest`] is allowlisted.
+    Hash(PathBuf),
+    /// The executable is not allowlisted.
+    NotAllowed(PathBuf),
+    /// The event was generated by this process, which is always allowed.
+    OwnProcess,
+    /// The process's executable couldn't be resolved,
+    /// e.g. because it's a kernel thread or already exited.
+    Unresolved,
+}
+
+/// A permission policy that decides by the executable of the process that caused the event,
+/// i.e. `/proc/<pid>/exe`, allowing it only if its path or its SHA-256 hash is allowlisted.
+///
+/// This is the core of application allowlisting (e.g. marking [`OPEN_EXEC_PERMISSION`](crate::mark::Mask::OPEN_EXEC_PERMISSION)
+/// or [`OPEN_PERMISSION`](crate::mark::Mask::OPEN_PERMISSION)), so a tool only needs to supply the list.
+///
+/// Paths are matched exactly against the literal link target (so a deleted executable never matches by path).
+/// Hashes are computed through `/proc/<pid>/exe`, so they're of the exact file the process is running,
+/// and cached in a [`DecisionCache`] until the file changes.
+/// Note that hashing opens the executable, which generates a permission event of its own if it's marked,
+/// so another thread must be responding to events meanwhile, or only allowlist by path.
+///
+/// Requires the `integrity` feature.
+#[derive(Debug)]
+pub struct ExeAllowlist {
+    paths: HashSet<PathBuf>,
+    hashes: HashSet<Digest>,
+    cache: DecisionCache,
+    unresolved: PermissionDecision,
+}
+
+impl Default for ExeAllowlist {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExeAllowlist {
+    /// An empty [`ExeAllowlist`], which denies everything but this process.
+    pub fn new() -> Self {
+        Self {
+            paths: HashSet::new(),
+            hashes: HashSet::new(),
+            cache: DecisionCache::default(),
+            unresolved: PermissionDecision::Deny,
+        }
+    }
+    
+    /// Parse an allowlist with an entry per line,
+    /// either an absolute path to allow by path,
+    /// or a `sha256sum`-style `<hex digest>  <path>` line to allow by hash (the path is just informational).
+    /// Blank lines and lines starting with `#` are ignored.
+    pub fn parse(text: &str) -> Result<Self, AllowlistParseError> {
+        let mut this = Self::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |reason| AllowlistParseError { line: i + 1, reason };
+            if line.starts_with('/') {
+                this.allow_path(line);
+                continue;
+            }
+            let hex = line.split_whitespace().next().unwrap_or_default();
+            let digest = sha256::from_hex(hex).ok_or_else(|| error("expected an absolute path or a SHA-256 hex digest"))?;
+            this.allow_hash(digest);
+        }
+        Ok(this)
+    }
+    
+    /// Use this [`DecisionCache`] for hashed executables instead of the default one.
+    pub fn with_cache(mut self, cache: DecisionCache) -> Self {
+        self.cache = cache;
+        self
+    }
+    
+    /// The decision when the executable can't be resolved, [`Deny`](PermissionDecision::Deny) by default.
+    pub fn with_unresolved(mut self, decision: PermissionDecision) -> Self {
+        self.unresolved = decision;
+        self
+    }
+    
+    pub fn allow_path(&mut self, path: impl Into<PathBuf>) {
+        self.paths.insert(path.into());
+    }
+    
+    pub fn allow_hash(&mut self, digest: Digest) {
+        self.hashes.insert(digest);
+        self.cache.clear();
+    }
+    
+    /// Allowlist the current contents of the file at `path` by hash.
+    pub fn allow_file(&mut self, path: impl AsRef<Path>) -> io::Result<Digest> {
+        let digest = sha256::hash_reader(fs::File::open(path)?)?;
+        self.allow_hash(digest);
+        Ok(digest)
+    }
+    
+    pub fn paths(&self) -> &HashSet<PathBuf> {
+        &self.paths
+    }
+    
+    pub fn hashes(&self) -> &HashSet<Digest> {
+        &self.hashes
+    }
+    
+    pub fn cache(&self) -> &DecisionCache {
+        &self.cache
+    }
+    
+    /// [Pre-warm](DecisionCache::prewarm) the hash decisions for the executables in `dir`,
+    /// e.g. `/usr/bin`, so that their first execution doesn't have to wait for them to be hashed.
+    pub fn prewarm(&mut self, dir: impl AsRef<Path>, recursive: bool) -> PrewarmReport {
+        let hashes = &self.hashes;
+        self.cache.prewarm(dir, recursive, |_, file| {
+            let allowed = sha256::hash_reader(file).is_ok_and(|digest| hashes.contains(&digest));
+            match allowed {
+                true => PermissionDecision::Allow,
+                false => PermissionDecision::Deny,
+            }
+        })
+    }
+    
+    /// Check the executable of process `pid`.
+    pub fn check_pid(&mut self, pid: Pid) -> ExeMatch {
+        let link = match proc::pid_dir(pid.as_raw()) {
+            Ok(dir) => dir.join("exe"),
+            Err(_) => return ExeMatch::Unresolved,
+        };
+        let target = match fs::read_link(&link) {
+            Ok(target) => target,
+            Err(_) => return ExeMatch::Unresolved,
+        };
+        let (path, state) = proc::split_deleted(&target);
+        let path = path.to_path_buf();
+        if state == LinkState::Present && self.paths.contains(&path) {
+            return ExeMatch::Path(path);
+        }
+        if self.hashes.is_empty() {
+            return ExeMatch::NotAllowed(path);
+        }
+        // O_PATH doesn't generate any events, and is enough to look up the cache
+        let fd = match OpenOptions::new().read(true).custom_flags(libc::O_PATH | libc::O_CLOEXEC).open(&link) {
+            Ok(file) => unsafe { FD::from_raw_fd(file.into_raw_fd()) },
+            Err(_) => return ExeMatch::Unresolved,
+        };
+        let hashes = &self.hashes;
+        let decision = self.cache.decide(&fd, || {
+            let allowed = fs::File::open(&link)
+                .and_then(sha256::hash_reader)
+                .is_ok_and(|digest| hashes.contains(&digest));
+            match allowed {
+                true => PermissionDecision::Allow,
+                false => PermissionDecision::Deny,
+            }
+        });
+        match decision {
+            PermissionDecision::Allow => ExeMatch::Hash(path),
+            PermissionDecision::Deny => ExeMatch::NotAllowed(path),
+        }
+    }
+    
+    /// Check the executable of the process that caused `event`.
+    pub fn check(&mut self, event: &Event<'_>) -> ExeMatch {
+        if event.id().is_generated_by_self() {
+            return ExeMatch::OwnProcess;
+        }
+        let id = event.id();
+        match id.pid().or_else(|| id.tid()) {
+            Some(pid) => self.check_pid(pid),
+            None => ExeMatch::Unresolved,
+        }
+    }
+    
+    /// The decision for an [`ExeMatch`].
+    pub fn decision(&self, exe: &ExeMatch) -> PermissionDecision {
+        match exe {
+            ExeMatch::Path(_) | ExeMatch::Hash(_) | ExeMatch::OwnProcess => PermissionDecision::Allow,
+            ExeMatch::NotAllowed(_) => PermissionDecision::Deny,
+            ExeMatch::Unresolved => self.unresolved,
+        }
+    }
+    
+    /// [`Check`](ExeAllowlist::check) the executable of the process that caused `event` and decide.
+    pub fn decide(&mut self, event: &Event<'_>) -> PermissionDecision {
+        let exe = self.check(event);
+        self.decision(&exe)
+    }
+}
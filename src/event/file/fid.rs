@@ -2,17 +2,41 @@ use std::convert::TryFrom;
 use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Formatter;
+use std::mem::size_of;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::RawFd;
+
+use nix::errno::Errno;
 
 use crate::fd::FD;
+use crate::init::EventFlags;
+use crate::init::ReadWrite;
+use crate::libc::call::libc_call;
 use crate::libc::read::FAN_EVENT_INFO_TYPE_DFID;
 use crate::libc::read::FAN_EVENT_INFO_TYPE_DFID_NAME;
 use crate::libc::read::FAN_EVENT_INFO_TYPE_FID;
-use crate::libc::read::fanotify_event_file_handle;
+use crate::restricted;
+
+use super::super::arena::EventArena;
 
 /// A filesystem id.  It uniquely represents any filesystem object.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub struct FileSystemId {
-    pub(in super::super) fsid: libc::fsid_t,
+    pub(crate) fsid: libc::fsid_t,
+}
+
+impl FileSystemId {
+    /// The [`FileSystemId`] of the filesystem `fd` is on, using `fstatfs`,
+    /// the same as the kernel reports in [`REPORT_FID`](crate::init::Flags::REPORT_FID) events.
+    pub fn of(fd: &FD) -> Result<Self, Errno> {
+        let mut statfs = std::mem::MaybeUninit::<libc::statfs>::uninit();
+        libc_call(|| unsafe { libc::fstatfs(fd.as_raw_fd(), statfs.as_mut_ptr()) })?;
+        let statfs = unsafe { statfs.assume_init() };
+        Ok(Self {
+            fsid: statfs.f_fsid,
+        })
+    }
 }
 
 /// TODO there can be multiple of these per event, so need to handle that
@@ -52,25 +76,154 @@ impl From<InfoType> for u8 {
     }
 }
 
+/// How to open a [`FileHandle`] with [`FileHandle::open`].
+///
+/// The kernel doesn't allow `O_PATH` or `O_DIRECTORY` in the [`EventFlags`] of event fds,
+/// but in [`REPORT_FID`](crate::init::Flags::REPORT_FID) mode,
+/// the consumer opens each [`FileHandle`] itself, so it can choose.
+/// The resulting [`FD`] is always opened with `O_CLOEXEC`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum HandleOpen {
+    /// Open with `O_PATH`, a light fd that can't be read or written.
+    ///
+    /// It's cheap and doesn't generate any fanotify events itself,
+    /// so it's enough to [`stat`](FD::stat) the file, resolve its [`path`](FD::path),
+    /// or use it as the `dirfd` of `*at` syscalls.
+    Path,
+    /// Open with `O_DIRECTORY | O_RDONLY`,
+    /// failing with [`ENOTDIR`](Errno::ENOTDIR) if the handle isn't a directory.
+    ///
+    /// Useful for directory entry events, where the handle is of the parent directory.
+    Directory,
+    /// Open normally, like an event fd is opened.
+    ///
+    /// This generates an [`OPEN`](crate::mark::Mask::OPEN) event of its own if the file is marked,
+    /// so watch out for feedback loops.
+    File {
+        rw: ReadWrite,
+        flags: EventFlags,
+    },
+}
+
+impl HandleOpen {
+    /// The flags passed to `open_by_handle_at`.
+    pub const fn flags(&self) -> libc::c_int {
+        libc::O_CLOEXEC | match self {
+            Self::Path => libc::O_PATH,
+            Self::Directory => libc::O_DIRECTORY | libc::O_RDONLY,
+            Self::File { rw, flags } => (*rw as u32 | flags.bits()) as libc::c_int,
+        }
+    }
+}
+
+/// Open the raw bytes of a `struct file_handle` using `open_by_handle_at`.
+fn open_handle(handle: &[u8], mount: &FD, how: HandleOpen) -> Result<FD, Errno> {
+    restricted::check("open_by_handle_at")?;
+    let fd = libc_call(|| unsafe {
+        libc::syscall(
+            libc::SYS_open_by_handle_at,
+            mount.as_raw_fd(),
+            handle.as_ptr(),
+            how.flags(),
+        )
+    })?;
+    Ok(unsafe { FD::from_raw_fd(fd as RawFd) })
+}
+
 /// An opaque handle to a file.
 /// This is like an absolute [`Path`](std::path::Path), except it is already resolved by the filesystem.
-/// But unlike a [`RawFd`](std::os::unix::io::RawFd), it's not opened yet.
+/// But unlike a [`RawFd`], it's not opened yet.
 /// It can be opened by calling [`Self::open`].
 pub struct FileHandle<'a> {
-    pub(in super::super) handle: &'a fanotify_event_file_handle,
+    /// The whole `struct file_handle`, already checked to be within its event.
+    pub(in super::super) bytes: &'a [u8],
 }
 
 impl Debug for FileHandle<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "FileHandle {{ handle: {:p} }}", self.handle)
+        write!(f, "FileHandle {{ handle: {:p} }}", self.bytes.as_ptr())
     }
 }
 
 impl FileHandle<'_> {
-    /// Open the resolved file handle.
-    /// Not implemented yet.
-    pub fn open(&self) -> FD {
-        todo!("{:p}", self.handle)
+    /// Open the resolved file handle using `open_by_handle_at`.
+    ///
+    /// `mount` can be any [`FD`] on the same filesystem, like the directory that was marked.
+    /// This requires the `CAP_DAC_READ_SEARCH` capability.
+    /// If the file has been deleted since the event, this fails with [`ESTALE`](Errno::ESTALE).
+    /// In [restricted mode](crate::restricted), this fails with [`EOPNOTSUPP`](Errno::EOPNOTSUPP).
+    pub fn open(&self, mount: &FD, how: HandleOpen) -> Result<FD, Errno> {
+        open_handle(self.as_bytes(), mount, how)
+    }
+    
+    /// The raw bytes of the handle, a `struct file_handle`
+    /// (a `u32` `handle_bytes`, an `i32` `handle_type`, and then `handle_bytes` opaque bytes).
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes
+    }
+    
+    /// Copy the handle so that it no longer borrows the events buffer.
+    pub fn to_owned(&self) -> OwnedFileHandle {
+        OwnedFileHandle {
+            bytes: self.as_bytes().to_vec(),
+        }
+    }
+    
+    /// Like [`FileHandle::to_owned`], but copying into a buffer from `arena`.
+    pub fn to_owned_in(&self, arena: &mut EventArena) -> OwnedFileHandle {
+        OwnedFileHandle {
+            bytes: arena.handle_bytes(self.as_bytes()),
+        }
+    }
+}
+
+/// An owned copy of a [`FileHandle`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct OwnedFileHandle {
+    bytes: Vec<u8>,
+}
+
+impl OwnedFileHandle {
+    /// The largest handle the kernel encodes, `MAX_HANDLE_SZ`.
+    const MAX_HANDLE_BYTES: usize = 128;
+    
+    /// The [`FileHandle`] of the file `fd` is open to, using `name_to_handle_at`,
+    /// which is encoded the same as in [`REPORT_FID`](crate::init::Flags::REPORT_FID) events,
+    /// so it can be compared to them.
+    pub fn of(fd: &FD) -> Result<Self, Errno> {
+        let header = size_of::<u32>() + size_of::<i32>();
+        let mut bytes = vec![0u8; header + Self::MAX_HANDLE_BYTES];
+        bytes[..size_of::<u32>()].copy_from_slice(&(Self::MAX_HANDLE_BYTES as u32).to_ne_bytes());
+        let mut mount_id: libc::c_int = 0;
+        libc_call(|| unsafe {
+            libc::syscall(
+                libc::SYS_name_to_handle_at,
+                fd.as_raw_fd(),
+                b"\0".as_ptr(),
+                bytes.as_mut_ptr(),
+                &mut mount_id as *mut libc::c_int,
+                libc::AT_EMPTY_PATH,
+            )
+        })?;
+        let mut handle_bytes = [0u8; size_of::<u32>()];
+        handle_bytes.copy_from_slice(&bytes[..size_of::<u32>()]);
+        bytes.truncate(header + u32::from_ne_bytes(handle_bytes) as usize);
+        Ok(Self { bytes })
+    }
+    
+    /// See [`FileHandle::as_bytes`].
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes.as_slice()
+    }
+    
+    /// Take the handle's buffer, e.g. to [recycle](EventArena::recycle_handle) it.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+    
+    /// See [`FileHandle::open`].
+    pub fn open(&self, mount: &FD, how: HandleOpen) -> Result<FD, Errno> {
+        open_handle(self.as_bytes(), mount, how)
     }
 }
 
@@ -96,4 +249,48 @@ impl<'a> FileFID<'a> {
     pub fn handle(&self) -> &FileHandle<'a> {
         &self.handle
     }
+    
+    /// Copy the [`FileHandle`] so that it no longer borrows the events buffer.
+    pub fn to_owned(&self) -> OwnedFileFID {
+        OwnedFileFID {
+            info_type: self.info_type,
+            file_system_id: self.file_system_id,
+            handle: self.handle.to_owned(),
+        }
+    }
+    
+    /// Like [`FileFID::to_owned`], but copying the [`FileHandle`] into a buffer from `arena`.
+    pub fn to_owned_in(&self, arena: &mut EventArena) -> OwnedFileFID {
+        OwnedFileFID {
+            info_type: self.info_type,
+            file_system_id: self.file_system_id,
+            handle: self.handle.to_owned_in(arena),
+        }
+    }
+}
+
+/// An owned [`FileFID`], with an [`OwnedFileHandle`] instead of a borrowed [`FileHandle`].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct OwnedFileFID {
+    info_type: InfoType,
+    file_system_id: FileSystemId,
+    handle: OwnedFileHandle,
+}
+
+impl OwnedFileFID {
+    pub fn info_type(&self) -> InfoType {
+        self.info_type
+    }
+    
+    pub fn file_system_id(&self) -> FileSystemId {
+        self.file_system_id
+    }
+    
+    pub fn handle(&self) -> &OwnedFileHandle {
+        &self.handle
+    }
+    
+    pub fn into_handle(self) -> OwnedFileHandle {
+        self.handle
+    }
 }
\ No newline at end of file
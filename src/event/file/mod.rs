@@ -2,18 +2,43 @@ use std::io;
 use std::path::PathBuf;
 
 use apply::Apply;
+use nix::errno::Errno;
 
 use crate::event::file::fd::FileFD;
 use crate::event::file::fid::FileFID;
+use crate::event::file::path_cache::PathCache;
 use crate::event::file::permission::FilePermission;
+use crate::event::file::resolved::FileResolved;
 use crate::fd::FD;
+use crate::fd::FileType;
+use crate::fd::Identity;
+use crate::proc::ResolvedPath;
+use crate::proc::Symlinks;
 
 pub mod fd;
 pub mod fid;
 pub mod permission;
+pub mod path_cache;
+pub mod ticket;
+pub mod decision_cache;
+pub mod resolved;
+#[cfg(feature = "integrity")]
+pub mod exe_allowlist;
+#[cfg(feature = "integrity")]
+pub mod protect;
 
 pub trait GetFD {
     fn fd(&self) -> &FD;
+    
+    /// The [`Identity`] of the file.  See [`FD::identity`].
+    fn identity(&self) -> Result<Identity, Errno> {
+        self.fd().identity()
+    }
+    
+    /// The [`FileType`] of the file.  See [`FD::file_type`].
+    fn file_type(&self) -> Result<FileType, Errno> {
+        self.fd().file_type()
+    }
 }
 
 /// An enum of the different kinds of file events.
@@ -22,15 +47,19 @@ pub enum File<'a> {
     FD(FileFD),
     FID(FileFID<'a>),
     Permission(FilePermission<'a>),
+    /// A notification event whose fd was already closed.
+    /// See [`CloseOnParse`](resolved::CloseOnParse).
+    Resolved(FileResolved),
 }
 
 impl<'a> File<'a> {
-    /// Get the name of the current file variant, `fd`, `fid`, or `permission`.
+    /// Get the name of the current file variant, `fd`, `fid`, `permission`, or `resolved`.
     pub fn variant_name(&self) -> &'static str {
         match self {
             Self::FD(_) => "fd",
             Self::FID(_) => "fid",
             Self::Permission(_) => "permission",
+            Self::Resolved(_) => "resolved",
         }
     }
     
@@ -58,14 +87,73 @@ impl<'a> File<'a> {
         }
     }
     
+    /// Return the [`Resolved`](Self::Resolved) variant if it exists.
+    pub fn resolved(self) -> Option<FileResolved> {
+        match self {
+            Self::Resolved(file) => Some(file),
+            _ => None,
+        }
+    }
+    
     /// Try to resolve the path of this file event, if it contains a way to resolve it.
+    ///
+    /// For a [`Resolved`](Self::Resolved) file, this is the path resolved while parsing, if any.
     pub fn path(&self) -> Option<io::Result<PathBuf>> {
-        match self {
-            Self::FD(file) => file.fd(),
-            Self::Permission(file) => file.fd(),
-            _ => return None,
+        if let Self::Resolved(file) = self {
+            return file.path().map(|it| Ok(it.to_path_buf()));
         }
+        self.get_fd()?
             .path()
             .apply(Some)
     }
+    
+    /// Classify the type of this file event's file, if it has an [`FD`].  See [`FD::file_type`].
+    ///
+    /// Many handlers only care about [`Regular`](FileType::Regular) files.
+    pub fn file_type(&self) -> Option<Result<FileType, Errno>> {
+        if let Self::Resolved(file) = self {
+            return file.file_type().map(Ok);
+        }
+        self.get_fd()?
+            .file_type()
+            .apply(Some)
+    }
+    
+    /// If this file event's file is a [`Regular`](FileType::Regular) file.
+    ///
+    /// If it has no [`FD`] or its type can't be determined, it's not considered regular.
+    pub fn is_regular_file(&self) -> bool {
+        self.file_type() == Some(Ok(FileType::Regular))
+    }
+    
+    /// Like [`File::path`], but with control over symlink normalization and deleted files.
+    ///
+    /// See [`FD::resolve`].
+    /// A [`Resolved`](Self::Resolved) file was already resolved while parsing, so `symlinks` is ignored.
+    pub fn resolve(&self, symlinks: Symlinks) -> Option<io::Result<ResolvedPath>> {
+        if let Self::Resolved(file) = self {
+            return file.resolved_path().cloned().map(Ok);
+        }
+        self.get_fd()?
+            .resolve(symlinks)
+            .apply(Some)
+    }
+    
+    /// Like [`File::path`], but using a [`PathCache`].
+    pub fn path_cached(&self, cache: &mut PathCache) -> Option<io::Result<PathBuf>> {
+        if let Self::Resolved(_) = self {
+            return self.path();
+        }
+        cache.path(self.get_fd()?)
+            .apply(Some)
+    }
+    
+    /// The [`FD`] of this file event, if it has one.
+    pub fn get_fd(&self) -> Option<&FD> {
+        match self {
+            Self::FD(file) => Some(file.fd()),
+            Self::Permission(file) => Some(file.fd()),
+            Self::FID(_) | Self::Resolved(_) => None,
+        }
+    }
 }
@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+use crate::fd::FD;
+use crate::fd::Identity;
+
+/// The identity of a file, its `(device, inode)`, as returned by [`FD::identity`].
+pub type FileKey = Identity;
+
+/// An LRU cache of resolved event file paths, keyed by their `(device, inode)`.
+///
+/// Resolving an event [`FD`]'s path with [`FD::path`] does a `readlink` of `/proc/self/fd`,
+/// which is relatively expensive.
+/// A [`PathCache`] does an [`fstat`](FD::stat) instead,
+/// and only resolves the path on a cache miss,
+/// which saves a lot of work for hot files in high-volume monitoring.
+///
+/// Note that a cached path can go stale if the file is renamed,
+/// or if its inode is reused for a different file after it's deleted.
+/// Use [`PathCache::invalidate`] or [`PathCache::clear`] when that matters.
+#[derive(Debug)]
+pub struct PathCache {
+    capacity: usize,
+    paths: HashMap<FileKey, (PathBuf, u64)>,
+    lru: BTreeMap<u64, FileKey>,
+    tick: u64,
+}
+
+impl PathCache {
+    /// Create an empty [`PathCache`] holding at most `capacity` paths.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            paths: HashMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+    
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+    
+    pub fn clear(&mut self) {
+        self.paths.clear();
+        self.lru.clear();
+    }
+    
+    /// Remove the cached path for this file, if there is one.
+    pub fn invalidate(&mut self, key: FileKey) -> Option<PathBuf> {
+        let (path, tick) = self.paths.remove(&key)?;
+        self.lru.remove(&tick);
+        Some(path)
+    }
+    
+    /// Resolve the path of `fd`, using the cached path if there is one.
+    ///
+    /// See [`FD::path`].
+    pub fn path(&mut self, fd: &FD) -> io::Result<PathBuf> {
+        let key = fd.identity()?;
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some((path, last_used)) = self.paths.get_mut(&key) {
+            self.lru.remove(last_used);
+            self.lru.insert(tick, key);
+            *last_used = tick;
+            return Ok(path.clone());
+        }
+        let path = fd.path()?;
+        if self.capacity == 0 {
+            return Ok(path);
+        }
+        if self.paths.len() >= self.capacity {
+            if let Some((&oldest, _)) = self.lru.iter().next() {
+                if let Some(evicted) = self.lru.remove(&oldest) {
+                    self.paths.remove(&evicted);
+                }
+            }
+        }
+        self.paths.insert(key, (path.clone(), tick));
+        self.lru.insert(tick, key);
+        Ok(path)
+    }
+}
+
+impl Default for PathCache {
+    fn default() -> Self {
+        Self::new(4096)
+    }
+}
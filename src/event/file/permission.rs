@@ -1,20 +1,32 @@
 use std::convert::TryFrom;
 use std::convert::TryInto;
+use std::mem;
 use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
+use std::mem::size_of;
 use std::os::unix::io::RawFd;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
 use apply::Apply;
 use nix::errno::Errno;
 use static_assertions::const_assert_eq;
 use to_trait::To;
 
+use crate::event::latency;
 use crate::fd::FD;
 use crate::libc::write::FAN_ALLOW;
 use crate::libc::write::FAN_AUDIT;
 use crate::libc::write::FAN_DENY;
+use crate::libc::write::FAN_INFO;
+use crate::libc::write::FAN_RESPONSE_INFO_AUDIT_RULE;
+use crate::libc::write::fanotify_response_info_audit_rule;
+use crate::libc::write::fanotify_response_info_header;
 use crate::libc::write::fanotify_response;
 
 use super::super::file::GetFD;
+use super::super::file::ticket::PermissionTicket;
 use super::super::responses::RC;
 use super::super::responses::Responses;
 
@@ -63,19 +75,42 @@ impl TryFrom<u32> for PermissionDecision {
 }
 
 #[derive(Debug)]
-pub(in super::super) struct RawFilePermission {
+pub(crate) struct RawFilePermission {
     pub fd: RawFd,
     pub decision: PermissionDecision,
     pub audit: bool,
+    pub audit_rule: Option<u32>,
+}
+
+/// The `subj_trust` and `obj_trust` value for unknown trust.
+const TRUST_UNKNOWN: u32 = 2;
+
+impl RawFilePermission {
+    /// The extra [`fanotify_response_info_audit_rule`] record written after the [`fanotify_response`],
+    /// if there is an [`audit_rule`](Self::audit_rule).
+    pub fn info(&self) -> Option<fanotify_response_info_audit_rule> {
+        let rule_number = self.audit_rule?;
+        Some(fanotify_response_info_audit_rule {
+            hdr: fanotify_response_info_header {
+                type_: FAN_RESPONSE_INFO_AUDIT_RULE,
+                pad: 0,
+                len: size_of::<fanotify_response_info_audit_rule>() as u16,
+            },
+            rule_number,
+            subj_trust: TRUST_UNKNOWN,
+            obj_trust: TRUST_UNKNOWN,
+        })
+    }
 }
 
 impl From<&RawFilePermission> for fanotify_response {
     /// The (more) raw [`fanotify_response`] representation of this partially raw version.
     fn from(this: &RawFilePermission) -> Self {
         let audit = this.audit as u32 * FAN_AUDIT;
+        let info = this.audit_rule.is_some() as u32 * FAN_INFO;
         Self {
             fd: this.fd,
-            response: this.decision.to::<u32>() | audit,
+            response: this.decision.to::<u32>() | audit | info,
         }
     }
 }
@@ -90,6 +125,7 @@ impl TryFrom<&fanotify_response> for RawFilePermission {
             fd: this.fd,
             decision: this.response.try_into()?,
             audit,
+            audit_rule: None,
         }.apply(Ok)
     }
 }
@@ -98,7 +134,10 @@ impl TryFrom<&fanotify_response> for RawFilePermission {
 /// and thus you must make a permission decision.
 ///
 /// Set [`Self::decision`] for the permission decision (it defaults to [`Allow`]).
-/// [`Self::audit`] can also be set to tell the kernel to audit this permission decision.
+/// [`Self::audit`] can also be set to tell the kernel to audit this permission decision,
+/// and [`Self::audit_rule`] to attach the audit rule number that caused the decision
+/// (see [`Fanotify::supports_response_info`](crate::fanotify::Fanotify::supports_response_info)).
+/// Auditing requires the [`ENABLE_AUDIT`](crate::init::Flags::ENABLE_AUDIT) flag.
 /// The decision is written once all [`FilePermission`]s
 /// from this [`Fanotify::read`](crate::fanotify::Fanotify::read) call are dropped.
 #[derive(Debug)]
@@ -106,7 +145,10 @@ pub struct FilePermission<'a> {
     fd: FD,
     pub decision: PermissionDecision,
     pub audit: bool,
+    pub audit_rule: Option<u32>,
     written: bool,
+    read_at: Instant,
+    latency: Option<Duration>,
     responses: RC<Responses<'a>>,
 }
 
@@ -122,7 +164,10 @@ impl<'a> FilePermission<'a> {
             fd,
             decision: PermissionDecision::default(),
             audit: false,
+            audit_rule: None,
             written: false,
+            read_at: responses.now(),
+            latency: None,
             responses,
         }
     }
@@ -135,19 +180,64 @@ impl<'a> FilePermission<'a> {
         self.decision = Deny;
     }
     
+    /// Audit this permission decision with the given audit rule number
+    /// (using `FAN_INFO` and `FAN_RESPONSE_INFO_AUDIT_RULE`, since Linux 6.3).
+    pub fn audit_rule(&mut self, rule_number: u32) {
+        self.audit = true;
+        self.audit_rule = Some(rule_number);
+    }
+    
     pub fn written(&self) -> bool {
         self.written
     }
     
+    /// The time between reading this [`FilePermission`] and writing its response,
+    /// or the time since it was read if it hasn't been written yet.
+    ///
+    /// For [`Self::write_buffered`], this is when it was buffered, not flushed.
+    pub fn latency(&self) -> Duration {
+        self.latency.unwrap_or_else(|| self.responses.now().saturating_duration_since(self.read_at))
+    }
+    
+    /// Mark this [`FilePermission`] as written and record its [`latency`](latency::record).
+    fn mark_written(&mut self) {
+        self.written = true;
+        let latency = self.responses.now().saturating_duration_since(self.read_at);
+        self.latency = Some(latency);
+        latency::record(self.fd.as_raw_fd(), latency);
+    }
+    
     /// The raw [`RawFilePermission`] of this [`FilePermission`].
     fn response(&self) -> RawFilePermission {
         RawFilePermission {
             fd: self.fd.as_raw_fd(),
             decision: self.decision,
             audit: self.audit,
+            audit_rule: self.audit_rule,
         }
     }
     
+    /// Detach this [`FilePermission`] from its [`Events`](crate::event::events::Events)
+    /// into a [`PermissionTicket`] that writes its response to `fanotify_fd`,
+    /// a duplicate of the [`Fanotify`](crate::fanotify::Fanotify) group's file descriptor.
+    pub(in super::super) fn detach(mut self, fanotify_fd: Arc<FD>) -> PermissionTicket {
+        let written = self.written;
+        let latency = self.latency;
+        let response = self.response();
+        // the fd is moved into the ticket, so skip writing the response on drop
+        self.written = true;
+        let fd = mem::replace(&mut self.fd, unsafe { FD::from_raw_fd(-1) });
+        PermissionTicket::new(
+            fd,
+            response,
+            written,
+            self.read_at,
+            latency,
+            fanotify_fd,
+            self.responses.clock(),
+        )
+    }
+    
     /// Write the response immediately to the [`Fanotify`](crate::fanotify::Fanotify).
     ///
     /// Return if the response is written (it can only be written successfully once).
@@ -156,7 +246,7 @@ impl<'a> FilePermission<'a> {
             return Ok(false);
         }
         self.responses.write_immediately(&self.response())?;
-        self.written = true;
+        self.mark_written();
         Ok(true)
     }
     
@@ -168,7 +258,7 @@ impl<'a> FilePermission<'a> {
             return false;
         }
         self.responses.write_buffered(&self.response());
-        self.written = true;
+        self.mark_written();
         true
     }
 }
@@ -0,0 +1,244 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::event::event::Event;
+use crate::mark;
+use crate::mark::directory_marks;
+use crate::mark::Mark;
+use crate::mark::Markable;
+use crate::mark::Mask;
+use crate::mark::OneAction::Add;
+use crate::mark::PathError;
+use crate::mark::What;
+
+use super::File;
+use super::exe_allowlist::ExeAllowlist;
+use super::exe_allowlist::ExeMatch;
+use super::permission::PermissionDecision;
+
+/// The audit record of a decision made by [`ProtectedPaths`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ProtectAudit {
+    /// The protected path that was accessed, or empty if it couldn't be resolved.
+    pub path: PathBuf,
+    /// The process's executable and why it was or wasn't allowed.
+    pub exe: ExeMatch,
+    /// The decision by the [`ExeAllowlist`],
+    /// which is only enforced if [`ProtectAudit::enforced`].
+    pub decision: PermissionDecision,
+    /// If a [`Deny`](PermissionDecision::Deny) was actually enforced,
+    /// which requires [`ProtectedPaths::deny_all_opens`].
+    pub enforced: bool,
+}
+
+/// An error from [`ProtectedPaths::mark`], with the protected path that couldn't be marked.
+#[derive(thiserror::Error, Debug, Eq, PartialEq, Hash)]
+pub enum ProtectError {
+    #[error("couldn't mark protected path {:?}: {}", .path, .error)]
+    Static { path: PathBuf, error: mark::StaticError },
+    #[error("couldn't mark protected path {:?}: {}", .path, .error)]
+    Mark { path: PathBuf, error: mark::RawError },
+}
+
+/// A ready-made permission policy protecting a set of paths from modification,
+/// e.g. against ransomware, by denying opens of them from processes outside an [`ExeAllowlist`].
+///
+/// [`ProtectedPaths::mark`] marks each protected path with [`ProtectedPaths::DEFAULT_MASK`]
+/// (and every directory under it, see [`directory_marks`]),
+/// and [`ProtectedPaths::respond`] decides each permission event.
+/// Events for paths that aren't protected (e.g. from other marks on the same group) are always allowed.
+/// Directories created after marking aren't marked, so they have to be [marked](ProtectedPaths::mark) again.
+///
+/// The kernel doesn't report the access mode of the open being decided,
+/// so this can't tell reads from writes: denying an open from outside the allowlist denies reads, too.
+/// That's only done with [`ProtectedPaths::deny_all_opens`],
+/// and otherwise the opens are allowed and only [audited](ProtectedPaths::on_audit).
+///
+/// Every decision on a protected path is passed to the audit callback (see [`ProtectedPaths::on_audit`]),
+/// and denials can also be audited by the kernel with [`ProtectedPaths::with_audit_rule`].
+///
+/// Requires the `integrity` feature.
+pub struct ProtectedPaths<'h> {
+    paths: Vec<PathBuf>,
+    allowlist: ExeAllowlist,
+    mask: Mask,
+    audit_rule: Option<u32>,
+    deny_all_opens: bool,
+    on_audit: Box<dyn FnMut(&ProtectAudit) + 'h>,
+    allowed: u64,
+    denied: u64,
+}
+
+impl<'h> ProtectedPaths<'h> {
+    /// The permission events [`ProtectedPaths`] marks and decides by default.
+    #[allow(clippy::identity_op)]
+    pub const DEFAULT_MASK: Mask = Mask::from_bits_truncate(0
+        | Mask::OPEN_PERMISSION.bits()
+        | Mask::ACCESS_PERMISSION.bits()
+    );
+    
+    /// Protect `paths`, allowing only processes in `allowlist`.
+    ///
+    /// The paths should be absolute and canonical, since event paths are.
+    pub fn new(paths: impl IntoIterator<Item = impl Into<PathBuf>>, allowlist: ExeAllowlist) -> Self {
+        Self {
+            paths: paths.into_iter().map(Into::into).collect(),
+            allowlist,
+            mask: Self::DEFAULT_MASK,
+            audit_rule: None,
+            deny_all_opens: false,
+            on_audit: Box::new(|_| {}),
+            allowed: 0,
+            denied: 0,
+        }
+    }
+    
+    /// Mark the protected paths with `mask` instead of [`ProtectedPaths::DEFAULT_MASK`],
+    /// e.g. without [`ACCESS_PERMISSION`](Mask::ACCESS_PERMISSION) to only decide opens.
+    pub fn with_mask(mut self, mask: Mask) -> Self {
+        self.mask = mask;
+        self
+    }
+    
+    /// Actually deny the opens and accesses of protected paths from outside the [`ExeAllowlist`].
+    ///
+    /// Since the kernel doesn't report the access mode, this denies every open,
+    /// including read-only ones, so the allowlist has to include every legitimate reader, too.
+    pub fn deny_all_opens(mut self) -> Self {
+        self.deny_all_opens = true;
+        self
+    }
+    
+    /// Call `on_audit` with every decision made on a protected path.
+    pub fn on_audit(mut self, on_audit: impl FnMut(&ProtectAudit) + 'h) -> Self {
+        self.on_audit = Box::new(on_audit);
+        self
+    }
+    
+    /// Also have the kernel audit every denial with `rule_number`.
+    /// See [`FilePermission::audit_rule`](super::permission::FilePermission::audit_rule).
+    pub fn with_audit_rule(mut self, rule_number: u32) -> Self {
+        self.audit_rule = Some(rule_number);
+        self
+    }
+    
+    pub fn mask(&self) -> Mask {
+        self.mask
+    }
+    
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+    
+    pub fn allowlist(&self) -> &ExeAllowlist {
+        &self.allowlist
+    }
+    
+    pub fn allowlist_mut(&mut self) -> &mut ExeAllowlist {
+        &mut self.allowlist
+    }
+    
+    /// The number of opens or accesses of protected paths that were allowed.
+    pub fn allowed(&self) -> u64 {
+        self.allowed
+    }
+    
+    /// The number of opens or accesses of protected paths that were denied,
+    /// not counting the ones only audited without [`ProtectedPaths::deny_all_opens`].
+    pub fn denied(&self) -> u64 {
+        self.denied
+    }
+    
+    pub fn denies_all_opens(&self) -> bool {
+        self.deny_all_opens
+    }
+    
+    /// Mark every protected path with the [`ProtectedPaths::mask`],
+    /// including every directory under it and their children if it's a directory.
+    pub fn mark(&self, markable: &(impl Markable + ?Sized)) -> Result<(), ProtectError> {
+        for path in &self.paths {
+            let error = |error: mark::RawError| ProtectError::Mark { path: path.clone(), error };
+            if path.is_dir() {
+                let marks = directory_marks(path, self.mask).map_err(|e| {
+                    error(PathError::Inaccessible {
+                        path: path.clone(),
+                        kind: e.kind(),
+                    }.into())
+                })?;
+                for mark in &marks {
+                    markable.mark(mark.as_mark()).map_err(|it| error(it.error))?;
+                }
+                continue;
+            }
+            let mark = Mark::one(mark::One {
+                action: Add,
+                what: What::Inode,
+                flags: mark::Flags::empty(),
+                mask: self.mask,
+                path: mark::Path::absolute(path),
+            }).map_err(|error| ProtectError::Static { path: path.clone(), error })?;
+            markable.mark(mark).map_err(|it| error(it.error))?;
+        }
+        Ok(())
+    }
+    
+    /// The protected path containing `path`, if any.
+    pub fn protecting(&self, path: &Path) -> Option<&Path> {
+        self.paths
+            .iter()
+            .find(|it| path.starts_with(it))
+            .map(|it| it.as_path())
+    }
+    
+    /// Decide `event`, auditing the decision if it's for a protected path.
+    ///
+    /// Events whose paths can't be resolved are decided by the [`ExeAllowlist`] alone,
+    /// since they can't be shown to be unprotected.
+    pub fn decide(&mut self, event: &Event<'_>) -> PermissionDecision {
+        let path = match event.file().path() {
+            Some(Ok(path)) => path,
+            _ => PathBuf::new(),
+        };
+        if !path.as_os_str().is_empty() && self.protecting(&path).is_none() {
+            return PermissionDecision::Allow;
+        }
+        let exe = self.allowlist.check(event);
+        let decision = self.allowlist.decision(&exe);
+        let enforced = self.deny_all_opens && decision == PermissionDecision::Deny;
+        match enforced {
+            true => self.denied += 1,
+            false => self.allowed += 1,
+        }
+        (self.on_audit)(&ProtectAudit {
+            path,
+            exe,
+            decision,
+            enforced,
+        });
+        match enforced {
+            true => PermissionDecision::Deny,
+            false => PermissionDecision::Allow,
+        }
+    }
+    
+    /// [`Decide`](ProtectedPaths::decide) a permission `event` and set its [`FilePermission`](super::permission::FilePermission)'s response.
+    ///
+    /// Return the decision, or [`None`] if it isn't a permission event.
+    pub fn respond(&mut self, event: Event<'_>) -> Option<PermissionDecision> {
+        if !matches!(event.file(), File::Permission(_)) {
+            return None;
+        }
+        let decision = self.decide(&event);
+        let mut permission = event.into_file().permission()?;
+        match decision {
+            PermissionDecision::Allow => permission.allow(),
+            PermissionDecision::Deny => {
+                permission.deny();
+                if let Some(rule_number) = self.audit_rule {
+                    permission.audit_rule(rule_number);
+                }
+            }
+        }
+        Some(decision)
+    }
+}
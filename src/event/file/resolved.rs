@@ -0,0 +1,75 @@
+use std::path::Path;
+
+use crate::fd::FD;
+use crate::fd::FileType;
+use crate::fd::Identity;
+use crate::proc::ResolvedPath;
+use crate::proc::Symlinks;
+
+/// What to resolve about a notification event's file before closing its fd right away while parsing,
+/// instead of leaving it open until the [`Event`](crate::event::event::Event) is dropped.
+///
+/// Every notification event opens an fd, which counts against the process's fd limit,
+/// so high-rate monitoring that only needs the paths (or identities) can use this
+/// to only hold one fd at a time.  The events then have [`File::Resolved`](super::File::Resolved)s.
+///
+/// Permission events keep their fds, since they're needed to respond.
+/// Set it with [`Fanotify::set_close_on_parse`](crate::fanotify::Fanotify::set_close_on_parse).
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct CloseOnParse {
+    /// Resolve the path (see [`FD::resolve`]) with these [`Symlinks`], if any.
+    pub path: Option<Symlinks>,
+    /// [`Stat`](FD::stat) the file for its [`Identity`] and [`FileType`].
+    pub identity: bool,
+}
+
+impl CloseOnParse {
+    /// Resolve just the literal path, which is the cheapest.
+    pub const fn path() -> Self {
+        Self {
+            path: Some(Symlinks::Literal),
+            identity: false,
+        }
+    }
+}
+
+/// A notification event whose fd was closed right after parsing,
+/// only keeping what was resolved according to a [`CloseOnParse`].
+///
+/// Whatever couldn't be resolved (e.g. the path of a file in another mount namespace) is [`None`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct FileResolved {
+    resolved_path: Option<ResolvedPath>,
+    identity: Option<Identity>,
+    file_type: Option<FileType>,
+}
+
+impl FileResolved {
+    /// Resolve `fd` according to `policy` and then close it.
+    pub(in super::super) fn resolve(fd: FD, policy: CloseOnParse) -> Self {
+        let resolved_path = policy.path.and_then(|symlinks| fd.resolve(symlinks).ok());
+        let stat = if policy.identity { fd.stat().ok() } else { None };
+        Self {
+            resolved_path,
+            identity: stat.map(|it| (it.st_dev, it.st_ino)),
+            file_type: stat.map(|it| FileType::from_mode(it.st_mode)),
+        }
+    }
+    
+    /// The path of the file, canonicalized if that was requested.
+    pub fn path(&self) -> Option<&Path> {
+        self.resolved_path.as_ref()?.path()
+    }
+    
+    pub fn resolved_path(&self) -> Option<&ResolvedPath> {
+        self.resolved_path.as_ref()
+    }
+    
+    pub fn identity(&self) -> Option<Identity> {
+        self.identity
+    }
+    
+    pub fn file_type(&self) -> Option<FileType> {
+        self.file_type
+    }
+}
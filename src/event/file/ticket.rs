@@ -0,0 +1,126 @@
+use std::os::unix::io::AsRawFd;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use nix::errno::Errno;
+
+use crate::clock::Clock;
+use crate::event::latency;
+use crate::event::responses::write_responses;
+use crate::fd::FD;
+
+use super::GetFD;
+use super::permission::PermissionDecision;
+use super::permission::RawFilePermission;
+
+/// A [`FilePermission`](super::permission::FilePermission) detached from its [`Events`](crate::event::events::Events),
+/// so that it can be kept after the events buffer is reused and be sent to other threads.
+///
+/// Instead of sharing the buffered responses of its [`Events`](crate::event::events::Events),
+/// it holds a duplicate of the [`Fanotify`](crate::fanotify::Fanotify) group's file descriptor
+/// and writes its response directly with [`Self::respond`].
+/// If it's dropped without responding, [`Self::decision`] is still written (it defaults to [`Allow`](PermissionDecision::Allow)),
+/// but any error is ignored.
+#[derive(Debug)]
+pub struct PermissionTicket {
+    fd: FD,
+    pub decision: PermissionDecision,
+    pub audit: bool,
+    pub audit_rule: Option<u32>,
+    written: bool,
+    read_at: Instant,
+    latency: Option<Duration>,
+    fanotify_fd: Arc<FD>,
+    clock: Arc<dyn Clock>,
+}
+
+impl GetFD for PermissionTicket {
+    fn fd(&self) -> &FD {
+        &self.fd
+    }
+}
+
+impl PermissionTicket {
+    /// A [`PermissionTicket`] for `fd`, whose [`RawFilePermission::fd`] should be the same,
+    /// responding with the rest of `response`.
+    pub(super) fn new(
+        fd: FD,
+        response: RawFilePermission,
+        written: bool,
+        read_at: Instant,
+        latency: Option<Duration>,
+        fanotify_fd: Arc<FD>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        let RawFilePermission { fd: _, decision, audit, audit_rule } = response;
+        Self {
+            fd,
+            decision,
+            audit,
+            audit_rule,
+            written,
+            read_at,
+            latency,
+            fanotify_fd,
+            clock,
+        }
+    }
+    
+    pub fn allow(&mut self) {
+        self.decision = PermissionDecision::Allow;
+    }
+    
+    pub fn deny(&mut self) {
+        self.decision = PermissionDecision::Deny;
+    }
+    
+    /// See [`FilePermission::audit_rule`](super::permission::FilePermission::audit_rule).
+    pub fn audit_rule(&mut self, rule_number: u32) {
+        self.audit = true;
+        self.audit_rule = Some(rule_number);
+    }
+    
+    pub fn written(&self) -> bool {
+        self.written
+    }
+    
+    /// See [`FilePermission::latency`](super::permission::FilePermission::latency).
+    pub fn latency(&self) -> Duration {
+        self.latency.unwrap_or_else(|| self.clock.now().saturating_duration_since(self.read_at))
+    }
+    
+    /// Write the response to the [`Fanotify`](crate::fanotify::Fanotify) group.
+    ///
+    /// Return if the response is written (it can only be written successfully once).
+    pub fn respond(&mut self) -> Result<bool, Errno> {
+        if self.written {
+            return Ok(false);
+        }
+        let response = RawFilePermission {
+            fd: self.fd.as_raw_fd(),
+            decision: self.decision,
+            audit: self.audit,
+            audit_rule: self.audit_rule,
+        };
+        let mut bytes = Vec::new();
+        response.write_bytes(&mut bytes);
+        let bytes_written = write_responses(&self.fanotify_fd, &bytes)?;
+        // a write this small should definitely succeed, so only try once
+        if bytes_written != bytes.len() {
+            return Err(Errno::EAGAIN);
+        }
+        self.written = true;
+        let latency = self.clock.now().saturating_duration_since(self.read_at);
+        self.latency = Some(latency);
+        latency::record(self.fd.as_raw_fd(), latency);
+        Ok(true)
+    }
+}
+
+/// Make sure the permission has always been written.
+impl Drop for PermissionTicket {
+    fn drop(&mut self) {
+        let _ = self.respond();
+    }
+}
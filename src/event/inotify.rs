@@ -0,0 +1,160 @@
+//! Translating fanotify events into inotify-style `(wd, mask, cookie, name)` records,
+//! so that code migrating from inotify can keep its downstream logic unchanged.
+//!
+//! Watch descriptors are synthesized from a [`MarkRegistry`],
+//! one per marked path, like `inotify_add_watch` returns one per watched inode.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::mark::Action;
+use crate::mark::Mask;
+use crate::mark::MarkRegistry;
+use crate::mark::What;
+
+use super::event::Event;
+
+/// An inotify-style event, like a `struct inotify_event`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct InotifyEvent {
+    /// The synthetic watch descriptor of the [`InotifyWatch`] the event is for.
+    pub wd: i32,
+    /// The `IN_*` mask bits.
+    pub mask: u32,
+    /// Always 0, since fanotify doesn't pair up renames with cookies.
+    pub cookie: u32,
+    /// The path relative to the watch, which is empty for the watched path itself,
+    /// and a single file name for a child of a watched directory.
+    pub name: PathBuf,
+}
+
+/// A synthetic inotify watch for a marked path.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct InotifyWatch {
+    pub wd: i32,
+    pub path: PathBuf,
+    pub what: What,
+    /// The union of the [`Mask`]s marked on the path.
+    pub mask: Mask,
+}
+
+impl InotifyWatch {
+    /// The name of `path` relative to this watch, if it's covered by it.
+    ///
+    /// An [`Inode`](What::Inode) watch covers its own path,
+    /// and its direct children if it has [`EVENT_ON_CHILD`](Mask::EVENT_ON_CHILD).
+    /// [`MountPoint`](What::MountPoint) and [`FileSystem`](What::FileSystem) watches
+    /// are approximated as covering everything under their path.
+    fn name_of<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        let name = path.strip_prefix(&self.path).ok()?;
+        let covered = match self.what {
+            What::Inode => {
+                name.as_os_str().is_empty()
+                    || (self.mask.contains(Mask::EVENT_ON_CHILD) && name.components().count() == 1)
+            }
+            What::MountPoint | What::FileSystem => true,
+        };
+        covered.then_some(name)
+    }
+}
+
+/// Translates fanotify events into [`InotifyEvent`]s
+/// using the watches [synthesized](InotifyTranslator::new) from a [`MarkRegistry`].
+#[derive(Debug, Clone, Default)]
+pub struct InotifyTranslator {
+    watches: Vec<InotifyWatch>,
+    next_wd: i32,
+}
+
+impl InotifyTranslator {
+    /// Synthesize watches from all the marks recorded in `registry`.
+    ///
+    /// Each newly marked path gets the next watch descriptor, starting at 1 like inotify's.
+    /// [`Remove`](Action::Remove)s subtract from a watch's [`Mask`],
+    /// and a watch is dropped once it's empty (or [`Flush`](Action::Flush)ed),
+    /// but its watch descriptor isn't reused.
+    pub fn new(registry: &MarkRegistry) -> Self {
+        let mut this = Self {
+            watches: Vec::new(),
+            next_wd: 1,
+        };
+        for mark in registry.marks() {
+            let existing = this.watches
+                .iter_mut()
+                .find(|it| it.path == mark.path && it.what == mark.what);
+            match (mark.action, existing) {
+                (Action::Add, Some(watch)) => watch.mask |= mark.mask,
+                (Action::Add, None) => {
+                    this.watches.push(InotifyWatch {
+                        wd: this.next_wd,
+                        path: mark.path.clone(),
+                        what: mark.what,
+                        mask: mark.mask,
+                    });
+                    this.next_wd += 1;
+                }
+                (Action::Remove, Some(watch)) => watch.mask -= mark.mask,
+                (Action::Remove, None) | (Action::Flush, _) => {}
+            }
+        }
+        this.watches.retain(|it| !(it.mask - (Mask::ON_DIR | Mask::EVENT_ON_CHILD)).is_empty());
+        this
+    }
+    
+    pub fn watches(&self) -> &[InotifyWatch] {
+        &self.watches
+    }
+    
+    /// The watch descriptor for `path`, if it's watched.
+    pub fn wd(&self, path: &Path) -> Option<i32> {
+        self.watches.iter().find(|it| it.path == path).map(|it| it.wd)
+    }
+    
+    /// The watch for a watch descriptor.
+    pub fn watch(&self, wd: i32) -> Option<&InotifyWatch> {
+        self.watches.iter().find(|it| it.wd == wd)
+    }
+    
+    /// Translate a fanotify [`Mask`] into an inotify mask.
+    ///
+    /// The event bits have the same values in both,
+    /// except that [`OPEN_EXEC`](Mask::OPEN_EXEC) becomes `IN_OPEN`,
+    /// permission events become their notification equivalents,
+    /// and [`ON_DIR`](Mask::ON_DIR) becomes `IN_ISDIR`.
+    pub fn translate_mask(mask: Mask) -> u32 {
+        let mut translated = (mask.bits() as u32) & libc::IN_ALL_EVENTS;
+        if mask.intersects(Mask::OPEN_EXEC | Mask::OPEN_PERMISSION | Mask::OPEN_EXEC_PERMISSION) {
+            translated |= libc::IN_OPEN;
+        }
+        if mask.contains(Mask::ACCESS_PERMISSION) {
+            translated |= libc::IN_ACCESS;
+        }
+        if mask.contains(Mask::ON_DIR) {
+            translated |= libc::IN_ISDIR;
+        }
+        translated
+    }
+    
+    /// Translate an event with the given [`Mask`] for the file at `path`,
+    /// using the most specific (longest) watch covering it.
+    pub fn translate_path(&self, mask: Mask, path: &Path) -> Option<InotifyEvent> {
+        let (watch, name) = self.watches
+            .iter()
+            .filter_map(|watch| Some((watch, watch.name_of(path)?)))
+            .max_by_key(|(watch, _)| watch.path.as_os_str().len())?;
+        Some(InotifyEvent {
+            wd: watch.wd,
+            mask: Self::translate_mask(mask),
+            cookie: 0,
+            name: name.to_path_buf(),
+        })
+    }
+    
+    /// Translate an [`Event`], if its path can be resolved and it's covered by a watch.
+    ///
+    /// [`FID`](super::file::File::FID) events have no path, so they can't be translated.
+    pub fn translate(&self, event: &Event) -> Option<InotifyEvent> {
+        let path = event.file().path()?.ok()?;
+        self.translate_path(event.mask(), &path)
+    }
+}
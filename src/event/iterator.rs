@@ -1,11 +1,13 @@
 use std::convert::TryInto;
 use std::mem::size_of;
 use std::os::unix::io::FromRawFd;
+use std::slice;
 
 use nix::unistd::Pid;
 
 use crate::fd::FD;
 use crate::init;
+use crate::init::RawInit;
 use crate::libc::mark::mask::FAN_Q_OVERFLOW;
 use crate::libc::read::FAN_NOFD;
 use crate::libc::read::fanotify_event_info_fid;
@@ -14,12 +16,15 @@ use crate::libc::read::fanotify_event_metadata;
 use crate::libc::read::FANOTIFY_METADATA_VERSION;
 use crate::mark;
 
+use super::dedup::DuplicateCount;
 use super::error::EventError;
 use super::error::EventResult;
 use super::error::TooShortError;
 use super::event::Event;
 use super::events::Events;
+use super::events::ParsedEvents;
 use super::file::fd::FileFD;
+use super::file::resolved::FileResolved;
 use super::file::fid::FileFID;
 use super::file::fid::FileHandle;
 use super::file::fid::FileSystemId;
@@ -28,15 +33,55 @@ use super::file::File;
 use super::file::permission::FilePermission;
 use super::id::EventId;
 use super::id::Id;
+use super::sequence::Sequence;
 use super::iterator_ext::IntoEvents;
+use super::responses::PendingResponse;
+use super::responses::RC;
 
-/// A consuming [`Iterator`] over [`Events`].
+/// Where an [`EventIterator`]'s events come from.
+enum Source<'a> {
+    /// Read by this process, so its file descriptors are valid and permission events can be responded to.
+    Read(Events<'a>),
+    /// [Parsed from bytes](Events::parse_from_bytes) read by another process.
+    Parsed(ParsedEvents<'a>),
+}
+
+impl<'a> Source<'a> {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Self::Read(events) => events.raw_bytes(),
+            Self::Parsed(events) => events.raw_bytes(),
+        }
+    }
+    
+    fn init(&self) -> RawInit {
+        match self {
+            Self::Read(events) => events.fanotify().init,
+            Self::Parsed(events) => events.init(),
+        }
+    }
+}
+
+/// A consuming [`Iterator`] over [`Events`] or [`ParsedEvents`].
 pub struct EventIterator<'a> {
-    events: Events<'a>,
+    source: Source<'a>,
     read_index: usize,
+    /// The number of events parsed so far from [`ParsedEvents`], which don't have a group to number them.
+    parsed: u64,
 }
 
 impl<'a> EventIterator<'a> {
+    /// The permission responses of the events iterated over so far that are buffered but not written yet.
+    /// See [`Responses::pending_responses`](super::responses::Responses::pending_responses).
+    ///
+    /// [`ParsedEvents`] can't be responded to, so they never have any.
+    pub fn pending_responses(&self) -> Vec<PendingResponse> {
+        match &self.source {
+            Source::Read(events) => events.pending_responses(),
+            Source::Parsed(_) => Vec::new(),
+        }
+    }
+    
     /// Like [`Self::next`] except it doesn't check if there is still more room in the events buffer
     /// so it returns a plain [`Result`] instead of an [`Option<Result>`].
     ///
@@ -46,7 +91,7 @@ impl<'a> EventIterator<'a> {
         use EventError::*;
         use TooShortError::*;
         
-        let remaining = &self.events.as_bytes()[self.read_index..];
+        let remaining = &self.source.bytes()[self.read_index..];
         
         let too_short = |what: TooShortError, expected: usize| -> std::result::Result<(), EventError> {
             let found = remaining.len();
@@ -61,23 +106,40 @@ impl<'a> EventIterator<'a> {
             }
         };
         
+        // an event cut off by the end of the bytes, rather than a malformed one
+        let truncated = |expected: usize| -> std::result::Result<(), EventError> {
+            if remaining.len() < expected {
+                Err(Truncated {
+                    partial: remaining.to_vec(),
+                    expected,
+                })
+            } else {
+                Ok(())
+            }
+        };
+        
         let event_len_size = size_of::<u32>();
         // in case we error here, we want read_index to be at the end,
         // so None is returned from next() next time
         self.read_index += event_len_size;
-        too_short(EventLenField, event_len_size)?;
+        truncated(event_len_size)?;
         self.read_index -= event_len_size;
         let ptr = remaining.as_ptr() as *const fanotify_event_metadata;
-        let event = unsafe { &*ptr };
+        // FID events are only padded to 4 bytes, so the next one's metadata can be misaligned
+        let event = &unsafe { ptr.read_unaligned() };
         let event_len = event.event_len as usize;
         self.read_index += event_len;
+        if event_len >= size_of::<fanotify_event_metadata>() {
+            truncated(event_len)?;
+        }
         too_short(FullEvent, event_len)?;
         too_short(BaseEvent, size_of::<fanotify_event_metadata>())?;
         if event.vers != FANOTIFY_METADATA_VERSION {
-            return Err(WrongVersion);
+            return Err(WrongVersion { metadata: event.into() });
         }
         
-        let flags = self.events.fanotify().init.flags();
+        let init = self.source.init();
+        let flags = init.flags();
         
         if event.mask & FAN_Q_OVERFLOW != 0 {
             let has_unlimited_queue = flags.contains(init::Flags::UNLIMITED_QUEUE);
@@ -94,18 +156,34 @@ impl<'a> EventIterator<'a> {
         let requested_fid = flags.contains(init::Flags::REPORT_FID);
         let received_fid = event_len > size_of::<fanotify_event_metadata>();
         let is_perm = mask.includes_permission();
+        if is_perm && init.notification_class() == init::NotificationClass::Notify {
+            return Err(PermissionEventOnNotifyGroup { mask });
+        }
         if requested_fid {
             if !received_fid {
-                return Err(FidRequestedButNotReceived);
+                return Err(FidRequestedButNotReceived { metadata: event.into() });
             } else {
                 match (has_no_fd, is_perm) {
-                    (true, true) => return Err(FidReturnedForPermissionEvent),
-                    (false, false) => return Err(FidRequestedButNotReceived),
-                    #[allow(clippy::identity_op)]
-                    (true, false) => too_short(BaseAndFidEvent, 0
-                        + size_of::<fanotify_event_metadata>()
-                        + size_of::<fanotify_event_info_fid>(),
-                    )?,
+                    (true, true) => return Err(FidReturnedForPermissionEvent { metadata: event.into() }),
+                    (false, false) => return Err(FidRequestedButNotReceived { metadata: event.into() }),
+                    (true, false) => {
+                        // fanotify_event_info_fid's file handle is a flexible array member,
+                        // so also count the handle_bytes and handle_type the handle starts with,
+                        // and check against the event's own length, not the rest of the buffer
+                        #[allow(clippy::identity_op)]
+                        let expected = 0
+                            + size_of::<fanotify_event_metadata>()
+                            + size_of::<fanotify_event_info_fid>()
+                            + size_of::<u32>()
+                            + size_of::<i32>();
+                        if event_len < expected {
+                            return Err(TooShort {
+                                what: BaseAndFidEvent,
+                                found: event_len,
+                                expected,
+                            });
+                        }
+                    }
                     (false, true) => {}
                 }
             }
@@ -114,72 +192,132 @@ impl<'a> EventIterator<'a> {
                 return Err(QueueOverflowed);
             }
             if received_fid {
-                return Err(FidNotRequestedButReceived);
+                return Err(FidNotRequestedButReceived { metadata: event.into() });
             }
         }
         
         let raw_id = Pid::from_raw(event.pid);
-        let own_id = self.events.id();
-        let id = match own_id {
-            Id::Pid(_) => Id::Pid(raw_id),
-            Id::Tid(_) => Id::Tid(raw_id),
+        let id = if flags.contains(init::Flags::REPORT_TID) {
+            Id::Tid(raw_id)
+        } else {
+            Id::Pid(raw_id)
+        };
+        let is_generated_by_self = match &self.source {
+            Source::Read(events) => id == events.id(),
+            Source::Parsed(_) => false,
         };
         let id = EventId {
-            is_generated_by_self: id == own_id,
+            is_generated_by_self,
             id,
         };
         
         let get_fd = || -> std::result::Result<FD, EventError> {
+            if let Source::Parsed(_) = self.source {
+                // don't take ownership of (and later close) some unrelated fd of this process
+                return Err(FdFromOtherProcess { metadata: event.into() });
+            }
             let fd = unsafe { FD::from_raw_fd(event.fd) };
             if !fd.check() {
-                return Err(InvalidFd { fd });
+                return Err(InvalidFd { fd, metadata: event.into() });
             }
             Ok(fd)
         };
         
         let file = if is_perm {
-            File::Permission(FilePermission::new(get_fd()?, self.events.responses()))
+            File::Permission(match &self.source {
+                Source::Read(events) => FilePermission::new(get_fd()?, events.responses()),
+                Source::Parsed(_) => return Err(FdFromOtherProcess { metadata: event.into() }),
+            })
         } else if received_fid {
             // already checked that we have enough bytes for this
             let remaining = &remaining[size_of::<fanotify_event_metadata>()..];
+            // these are bytes from anywhere (see Events::parse_from_bytes), so they may be misaligned,
+            // and only the header and fsid are copied out, not the handle (a flexible array member)
             let ptr = remaining.as_ptr() as *const fanotify_event_info_fid;
-            let fid = unsafe { &*ptr };
+            let fid = unsafe { ptr.read_unaligned() };
             let info_type: InfoType = fid.hdr.info_type
                 .try_into()
-                .map_err(|info_type| InvalidFidInfoType { info_type })?;
-            {
-                let found = fid.hdr.len as usize;
-                #[allow(clippy::identity_op)]
-                    let expected = 0
-                    + size_of::<fanotify_event_info_header>()
-                    + size_of::<libc::fsid_t>();
-                if found != expected {
-                    return Err(TooShort {
-                        what: FidEvent,
-                        found,
-                        expected,
-                    });
-                }
+                .map_err(|info_type| InvalidFidInfoType { info_type, metadata: event.into() })?;
+            // the info record's len includes the variable-length file handle,
+            // which starts with a u32 handle_bytes and an i32 handle_type,
+            // and the record has to fit in what's left of the event
+            let found = fid.hdr.len as usize;
+            let available = event_len - size_of::<fanotify_event_metadata>();
+            #[allow(clippy::identity_op)]
+                let expected = 0
+                + size_of::<fanotify_event_info_header>()
+                + size_of::<libc::fsid_t>()
+                + size_of::<u32>()
+                + size_of::<i32>();
+            if found < expected {
+                return Err(TooShort {
+                    what: FidEvent,
+                    found,
+                    expected,
+                });
             }
+            if found > available {
+                return Err(TooShort {
+                    what: FidEvent,
+                    found: available,
+                    expected: found,
+                });
+            }
+            // the file handle's u32 handle_bytes says how long the rest of it is,
+            // so check that all of it is within the record before borrowing it
+            let handle_offset = size_of::<fanotify_event_info_header>() + size_of::<libc::fsid_t>();
+            let handle = &remaining[handle_offset..found];
+            let handle_header_len = size_of::<u32>() + size_of::<i32>();
+            let handle_len = match handle.get(..size_of::<u32>()) {
+                Some(handle_bytes) => handle_header_len + u32::from_ne_bytes(handle_bytes.try_into().unwrap()) as usize,
+                None => handle_header_len,
+            };
+            let handle = handle.get(..handle_len).ok_or(TooShort {
+                what: FidEvent,
+                found: handle.len(),
+                expected: handle_len,
+            })?;
+            // the events buffer is borrowed for 'a and isn't changed while it's iterated over
+            let handle = unsafe { slice::from_raw_parts::<'a, u8>(handle.as_ptr(), handle.len()) };
             File::FID(FileFID {
                 info_type,
                 file_system_id: FileSystemId {
                     fsid: fid.fsid,
                 },
                 handle: FileHandle {
-                    handle: &fid.handle,
+                    bytes: handle,
                 },
             })
         } else {
-            File::FD(FileFD {
-                fd: get_fd()?,
-            })
+            let fd = get_fd()?;
+            let close_on_parse = match &self.source {
+                Source::Read(events) => events.fanotify().close_on_parse(),
+                Source::Parsed(_) => None,
+            };
+            match close_on_parse {
+                None => File::FD(FileFD { fd }),
+                Some(policy) => File::Resolved(FileResolved::resolve(fd, policy)),
+            }
         };
         
+        let sequence = match &self.source {
+            Source::Read(events) => Sequence {
+                batch: events.batch(),
+                event: events.fanotify().sequence.next_event(),
+            },
+            Source::Parsed(_) => {
+                self.parsed += 1;
+                Sequence {
+                    batch: 0,
+                    event: self.parsed - 1,
+                }
+            }
+        };
         let this = Event {
             mask,
             id,
             file,
+            sequence,
         };
         Ok(this)
     }
@@ -189,9 +327,13 @@ impl<'a> Iterator for EventIterator<'a> {
     type Item = EventResult<'a>;
     
     fn next(&mut self) -> Option<Self::Item> {
-        if self.events.as_bytes().len() <= self.read_index {
+        if self.source.bytes().len() <= self.read_index {
             None
         } else {
+            // bound how long earlier responses wait while events are processed
+            if let Source::Read(events) = &self.source {
+                events.flush_responses_if_due();
+            }
             Some(self.next_unchecked())
         }
     }
@@ -202,8 +344,23 @@ impl<'a> IntoIterator for Events<'a> {
     type IntoIter = EventIterator<'a>;
     
     fn into_iter(self) -> Self::IntoIter {
-        EventIterator { events: self, read_index: 0 }
+        EventIterator { source: Source::Read(self), read_index: 0, parsed: 0 }
+    }
+}
+
+impl<'a> IntoEvents<'a> for Events<'a> {
+    fn duplicate_count(&self) -> RC<DuplicateCount> {
+        self.duplicates()
+    }
+}
+
+impl<'a> IntoIterator for ParsedEvents<'a> {
+    type Item = EventResult<'a>;
+    type IntoIter = EventIterator<'a>;
+    
+    fn into_iter(self) -> Self::IntoIter {
+        EventIterator { source: Source::Parsed(self), read_index: 0, parsed: 0 }
     }
 }
 
-impl<'a> IntoEvents<'a> for Events<'a> {}
\ No newline at end of file
+impl<'a> IntoEvents<'a> for ParsedEvents<'a> {}
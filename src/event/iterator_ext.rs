@@ -1,11 +1,17 @@
+use std::iter::Chain;
 use std::iter::FilterMap;
+use std::vec;
 
+use super::dedup::Dedup;
+use super::dedup::DuplicateCount;
 use super::error::EventResult;
 use super::event::Event;
 use super::event::EventOf;
 use super::file::fd::FileFD;
 use super::file::fid::FileFID;
+use super::file::File;
 use super::file::permission::FilePermission;
+use super::responses::RC;
 
 type UnwrapEventResult<'a> = fn(EventResult<'a>) -> Option<Event<'a>>;
 type ProjectEvent<'a, FileT> = fn(Event<'a>) -> Option<EventOf<FileT>>;
@@ -47,4 +53,39 @@ pub trait IntoEvents<'a>: IntoIterator<Item=EventResult<'a>> + Sized {
     fn permissions(self) -> FilterMap<FilterMap<Self::IntoIter, UnwrapEventResult<'a>>, ProjectEvent<'a, FilePermission<'a>>> {
         self.ok().filter_map(|it| it.permission())
     }
+    
+    /// Split all [`EventResult`]s into permission [`Event`]s and everything else
+    /// (non-permission [`Event`]s and errors), each in their original order.
+    ///
+    /// See [`IntoEvents::prioritized`].
+    fn split_permissions(self) -> (Vec<EventResult<'a>>, Vec<EventResult<'a>>) {
+        self.all().partition(|it| matches!(it, Ok(Event { file: File::Permission(_), .. })))
+    }
+    
+    /// An [`Iterator`] over all [`EventResult`]s that yields permission [`Event`]s first.
+    ///
+    /// Processes are blocked until their permission events are responded to,
+    /// so when a group delivers both permission and notification events,
+    /// handling the permission events first unblocks them sooner.
+    /// Otherwise, events are yielded in their original order.
+    ///
+    /// Note that this has to parse the whole batch of events up front.
+    fn prioritized(self) -> Chain<vec::IntoIter<EventResult<'a>>, vec::IntoIter<EventResult<'a>>> {
+        let (permissions, others) = self.split_permissions();
+        permissions.into_iter().chain(others)
+    }
+    
+    /// The [`DuplicateCount`] that [`IntoEvents::dedup`] counts into,
+    /// which is shared by everything iterating over the same [`Events`](super::events::Events) batch,
+    /// and otherwise is a new one.
+    fn duplicate_count(&self) -> RC<DuplicateCount> {
+        RC::default()
+    }
+    
+    /// An [`Iterator`] over all [`EventResult`]s that drops consecutive duplicate [`Event`]s,
+    /// counting them in the batch's [`IntoEvents::duplicate_count`].
+    fn dedup(self) -> Dedup<Self::IntoIter> {
+        let suppressed = self.duplicate_count();
+        Dedup::new(self.into_iter(), suppressed)
+    }
 }
@@ -0,0 +1,125 @@
+use crate::mark::Mask;
+
+use super::event::EventOf;
+
+/// Which way a file was moved in an [`EventKind::Moved`] event.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum MoveKind {
+    /// Moved out of a marked directory ([`Mask::MOVED_FROM`]).
+    From,
+    /// Moved into a marked directory ([`Mask::MOVED_TO`]).
+    To,
+    /// The marked file or directory itself was moved ([`Mask::MOVE_SELF`]).
+    Itself,
+}
+
+/// What permission was requested in an [`EventKind::PermissionRequested`] event.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum PermissionKind {
+    /// [`Mask::ACCESS_PERMISSION`]
+    Access,
+    /// [`Mask::OPEN_PERMISSION`]
+    Open,
+    /// [`Mask::OPEN_EXEC_PERMISSION`]
+    OpenExec,
+}
+
+/// A match-friendly version of a single event flag in a [`Mask`].
+///
+/// A single event's [`Mask`] can contain multiple kinds (the kernel merges events),
+/// so use [`EventOf::kinds`] to get each of them.
+///
+/// [`Mask::ON_DIR`] and [`Mask::EVENT_ON_CHILD`] aren't kinds of events,
+/// but modifiers on them, so they have no [`EventKind`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum EventKind {
+    /// [`Mask::ACCESS`]
+    Accessed,
+    /// [`Mask::OPEN`] or [`Mask::OPEN_EXEC`]
+    Opened { exec: bool },
+    /// [`Mask::MODIFY`]
+    Modified,
+    /// [`Mask::ATTRIBUTE_CHANGED`]
+    AttributeChanged,
+    /// [`Mask::CLOSE_WRITE`] or [`Mask::CLOSE_NO_WRITE`]
+    Closed { write: bool },
+    /// [`Mask::CREATE`]
+    Created,
+    /// [`Mask::DELETE`] or [`Mask::DELETE_SELF`] (if `itself`)
+    Deleted { itself: bool },
+    /// [`Mask::MOVED_FROM`], [`Mask::MOVED_TO`], or [`Mask::MOVE_SELF`]
+    Moved { kind: MoveKind },
+    /// [`Mask::ACCESS_PERMISSION`], [`Mask::OPEN_PERMISSION`], or [`Mask::OPEN_EXEC_PERMISSION`]
+    PermissionRequested { kind: PermissionKind },
+}
+
+impl EventKind {
+    /// Convert a single flag of a [`Mask`] into an [`EventKind`],
+    /// or [`None`] if it's not a single event flag.
+    pub fn from_flag(flag: Mask) -> Option<Self> {
+        use EventKind::*;
+        let this = match flag {
+            Mask::ACCESS => Accessed,
+            Mask::OPEN => Opened { exec: false },
+            Mask::OPEN_EXEC => Opened { exec: true },
+            Mask::MODIFY => Modified,
+            Mask::ATTRIBUTE_CHANGED => AttributeChanged,
+            Mask::CLOSE_WRITE => Closed { write: true },
+            Mask::CLOSE_NO_WRITE => Closed { write: false },
+            Mask::CREATE => Created,
+            Mask::DELETE => Deleted { itself: false },
+            Mask::DELETE_SELF => Deleted { itself: true },
+            Mask::MOVED_FROM => Moved { kind: MoveKind::From },
+            Mask::MOVED_TO => Moved { kind: MoveKind::To },
+            Mask::MOVE_SELF => Moved { kind: MoveKind::Itself },
+            Mask::ACCESS_PERMISSION => PermissionRequested { kind: PermissionKind::Access },
+            Mask::OPEN_PERMISSION => PermissionRequested { kind: PermissionKind::Open },
+            Mask::OPEN_EXEC_PERMISSION => PermissionRequested { kind: PermissionKind::OpenExec },
+            _ => return None,
+        };
+        Some(this)
+    }
+    
+    /// The single [`Mask`] flag for this [`EventKind`].
+    pub const fn mask(&self) -> Mask {
+        use EventKind::*;
+        match self {
+            Accessed => Mask::ACCESS,
+            Opened { exec: false } => Mask::OPEN,
+            Opened { exec: true } => Mask::OPEN_EXEC,
+            Modified => Mask::MODIFY,
+            AttributeChanged => Mask::ATTRIBUTE_CHANGED,
+            Closed { write: true } => Mask::CLOSE_WRITE,
+            Closed { write: false } => Mask::CLOSE_NO_WRITE,
+            Created => Mask::CREATE,
+            Deleted { itself: false } => Mask::DELETE,
+            Deleted { itself: true } => Mask::DELETE_SELF,
+            Moved { kind: MoveKind::From } => Mask::MOVED_FROM,
+            Moved { kind: MoveKind::To } => Mask::MOVED_TO,
+            Moved { kind: MoveKind::Itself } => Mask::MOVE_SELF,
+            PermissionRequested { kind: PermissionKind::Access } => Mask::ACCESS_PERMISSION,
+            PermissionRequested { kind: PermissionKind::Open } => Mask::OPEN_PERMISSION,
+            PermissionRequested { kind: PermissionKind::OpenExec } => Mask::OPEN_EXEC_PERMISSION,
+        }
+    }
+}
+
+impl From<EventKind> for Mask {
+    fn from(kind: EventKind) -> Self {
+        kind.mask()
+    }
+}
+
+impl Mask {
+    /// Each [`EventKind`] set in this [`Mask`], in order of their bits.
+    pub fn kinds(self) -> impl Iterator<Item=EventKind> {
+        self.flags().filter_map(EventKind::from_flag)
+    }
+}
+
+impl<FileT> EventOf<FileT> {
+    /// Each [`EventKind`] of this event.  See [`Mask::kinds`].
+    pub fn kinds(&self) -> impl Iterator<Item=EventKind> {
+        self.mask().kinds()
+    }
+}
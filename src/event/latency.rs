@@ -0,0 +1,191 @@
+//! Tracking of the latency between reading a permission event and writing its response.
+//!
+//! While a permission event is pending, the process that triggered it is blocked,
+//! so slow responses directly slow down all permission-gated IO.
+//! Every [`FilePermission`](super::file::permission::FilePermission) records its own
+//! [`latency`](super::file::permission::FilePermission::latency).
+//! With the `metrics` feature, all the latencies are also collected into a global `Histogram`,
+//! and with the `tracing` feature, a warning is logged for every response slower than the
+//! [`slow_threshold`].
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+const NO_THRESHOLD: u64 = u64::MAX;
+
+static SLOW_THRESHOLD_NANOS: AtomicU64 = AtomicU64::new(NO_THRESHOLD);
+
+fn to_nanos(duration: Duration) -> u64 {
+    duration.as_nanos().min((NO_THRESHOLD - 1) as u128) as u64
+}
+
+/// The global threshold over which a permission response is considered slow, if any.
+///
+/// Defaults to [`None`].
+pub fn slow_threshold() -> Option<Duration> {
+    match SLOW_THRESHOLD_NANOS.load(Ordering::Relaxed) {
+        NO_THRESHOLD => None,
+        nanos => Some(Duration::from_nanos(nanos)),
+    }
+}
+
+/// Set the global [`slow_threshold`], returning the previous one.
+pub fn set_slow_threshold(threshold: Option<Duration>) -> Option<Duration> {
+    let nanos = threshold.map_or(NO_THRESHOLD, to_nanos);
+    match SLOW_THRESHOLD_NANOS.swap(nanos, Ordering::Relaxed) {
+        NO_THRESHOLD => None,
+        nanos => Some(Duration::from_nanos(nanos)),
+    }
+}
+
+/// Whether this latency is over the [`slow_threshold`].
+pub fn is_slow(latency: Duration) -> bool {
+    slow_threshold().is_some_and(|threshold| latency > threshold)
+}
+
+/// Record the latency of a permission response for the given raw fd.
+#[cfg_attr(not(any(feature = "metrics", feature = "tracing")), allow(unused_variables))]
+pub(crate) fn record(fd: i32, latency: Duration) {
+    #[cfg(feature = "metrics")]
+    histogram().record(latency);
+    #[cfg(feature = "tracing")]
+    if is_slow(latency) {
+        tracing::warn!(
+            fd,
+            latency_us = latency.as_micros() as u64,
+            "slow fanotify permission response",
+        );
+    }
+}
+
+/// The number of [`Histogram`] buckets.
+#[cfg(feature = "metrics")]
+pub const NUM_BUCKETS: usize = 32;
+
+/// A lock-free histogram of latencies with power-of-2 microsecond buckets.
+///
+/// Bucket `0` counts latencies under 1 µs,
+/// and bucket `i` counts latencies in `[2^(i - 1), 2^i)` µs,
+/// except the last bucket, which counts everything above that, too.
+#[cfg(feature = "metrics")]
+#[derive(Debug)]
+pub struct Histogram {
+    buckets: [AtomicU64; NUM_BUCKETS],
+    count: AtomicU64,
+    sum_nanos: AtomicU64,
+}
+
+/// A point-in-time copy of a [`Histogram`].
+#[cfg(feature = "metrics")]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct HistogramSnapshot {
+    pub buckets: [u64; NUM_BUCKETS],
+    pub count: u64,
+    pub sum: Duration,
+}
+
+#[cfg(feature = "metrics")]
+impl HistogramSnapshot {
+    /// The mean latency, or [`None`] if nothing has been recorded.
+    pub fn mean(&self) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(self.sum / self.count as u32)
+    }
+    
+    /// The exclusive upper bound of a bucket, or [`None`] for the unbounded last one.
+    pub fn bucket_upper_bound(bucket: usize) -> Option<Duration> {
+        if bucket + 1 >= NUM_BUCKETS {
+            return None;
+        }
+        Some(Duration::from_micros(1 << bucket))
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Histogram {
+    pub const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+        Self {
+            buckets: [ZERO; NUM_BUCKETS],
+            count: ZERO,
+            sum_nanos: ZERO,
+        }
+    }
+    
+    fn bucket(latency: Duration) -> usize {
+        let micros = latency.as_micros();
+        let bits = (u128::BITS - micros.leading_zeros()) as usize;
+        bits.min(NUM_BUCKETS - 1)
+    }
+    
+    pub fn record(&self, latency: Duration) {
+        self.buckets[Self::bucket(latency)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos.fetch_add(to_nanos(latency), Ordering::Relaxed);
+    }
+    
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let mut buckets = [0; NUM_BUCKETS];
+        for (snapshot, bucket) in buckets.iter_mut().zip(self.buckets.iter()) {
+            *snapshot = bucket.load(Ordering::Relaxed);
+        }
+        HistogramSnapshot {
+            buckets,
+            count: self.count.load(Ordering::Relaxed),
+            sum: Duration::from_nanos(self.sum_nanos.load(Ordering::Relaxed)),
+        }
+    }
+    
+    pub fn reset(&self) {
+        for bucket in self.buckets.iter() {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        self.count.store(0, Ordering::Relaxed);
+        self.sum_nanos.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "metrics")]
+static HISTOGRAM: Histogram = Histogram::new();
+
+/// The global [`Histogram`] of all permission response latencies.
+#[cfg(feature = "metrics")]
+pub fn histogram() -> &'static Histogram {
+    &HISTOGRAM
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use std::time::Duration;
+    
+    use super::Histogram;
+    use super::HistogramSnapshot;
+    use super::NUM_BUCKETS;
+    
+    #[test]
+    fn histogram_buckets() {
+        let histogram = Histogram::new();
+        histogram.record(Duration::from_nanos(500));
+        histogram.record(Duration::from_micros(3));
+        histogram.record(Duration::from_secs(1 << 40));
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.count, 3);
+        assert_eq!(snapshot.buckets[0], 1);
+        assert_eq!(snapshot.buckets[2], 1);
+        assert_eq!(snapshot.buckets[NUM_BUCKETS - 1], 1);
+        assert_eq!(HistogramSnapshot::bucket_upper_bound(2), Some(Duration::from_micros(4)));
+        histogram.reset();
+        assert_eq!(histogram.snapshot().mean(), None);
+    }
+}
@@ -1,4 +1,8 @@
 pub mod id;
+pub mod sequence;
+pub mod credentials;
+pub mod cgroup;
+mod pid_cache;
 pub mod file;
 pub mod responses;
 #[allow(clippy::module_inception)]
@@ -9,3 +13,15 @@ pub mod error;
 pub mod buffer;
 pub mod iterator_ext;
 pub mod display;
+pub mod kind;
+pub mod latency;
+pub mod sink;
+pub mod owned;
+pub mod arena;
+pub mod origin;
+pub mod dedup;
+pub mod inotify;
+pub mod correlate;
+pub mod anomaly;
+#[cfg(feature = "rayon")]
+pub mod par;
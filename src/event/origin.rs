@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::fd::Identity;
+use crate::mark::Action;
+use crate::mark::MarkRegistry;
+use crate::mark::Mask;
+use crate::mark::What;
+
+use super::event::Event;
+
+/// Which marked inode an [`Event`] came from, as classified by an [`OriginClassifier`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Origin {
+    /// The [`Event`] is for the marked inode at `path` itself.
+    Itself {
+        path: PathBuf,
+    },
+    /// The [`Event`] is for a child of the marked directory at `watched`,
+    /// which was marked with [`EVENT_ON_CHILD`](Mask::EVENT_ON_CHILD).
+    Child {
+        watched: PathBuf,
+    },
+    /// The [`Event`] couldn't be attributed to any inode mark,
+    /// e.g. because it's from a mount or filesystem mark, it has no fd,
+    /// or it was moved since.
+    Unknown,
+}
+
+/// A marked inode in an [`OriginClassifier`].
+#[derive(Debug)]
+struct Watched {
+    path: PathBuf,
+    on_child: bool,
+}
+
+/// Classifies [`Event`]s from a directory marked with [`EVENT_ON_CHILD`](Mask::EVENT_ON_CHILD)
+/// as being for the directory itself or for one of its children, which the [`Event`]s don't say.
+///
+/// It is built from the inode marks recorded in a [`MarkRegistry`],
+/// and then matches the `(device, inode)` [`Identity`] of an [`Event`]'s file
+/// (and of its parent directory) against them.
+/// This is a best effort, since the file may have been moved since,
+/// so the resulting [`Origin`] is only probable.
+#[derive(Debug, Default)]
+pub struct OriginClassifier {
+    watched: HashMap<Identity, Watched>,
+}
+
+impl OriginClassifier {
+    /// Look up the [`Identity`] of all of the added inode marks in the [`MarkRegistry`].
+    ///
+    /// Marks whose paths no longer exist are skipped.
+    pub fn new(registry: &MarkRegistry) -> Self {
+        let mut this = Self::default();
+        for mark in registry.marks() {
+            if mark.action != Action::Add || mark.what != What::Inode {
+                continue;
+            }
+            let metadata = match fs::metadata(&mark.path) {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            let watched = this.watched
+                .entry((metadata.dev(), metadata.ino()))
+                .or_insert_with(|| Watched {
+                    path: mark.path.clone(),
+                    on_child: false,
+                });
+            watched.on_child |= mark.mask.contains(Mask::EVENT_ON_CHILD);
+        }
+        this
+    }
+    
+    /// The number of marked inodes being matched against.
+    pub fn len(&self) -> usize {
+        self.watched.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.watched.is_empty()
+    }
+    
+    /// Classify the [`Origin`] of an [`Event`].
+    pub fn classify(&self, event: &Event<'_>) -> Origin {
+        let fd = match event.file().get_fd() {
+            Some(fd) => fd,
+            None => return Origin::Unknown,
+        };
+        if let Some(watched) = fd.identity().ok().and_then(|it| self.watched.get(&it)) {
+            return Origin::Itself {
+                path: watched.path.clone(),
+            };
+        }
+        let parent = match fd.path() {
+            Ok(path) => path.parent().map(Path::to_path_buf),
+            Err(_) => None,
+        };
+        let watched = parent
+            .and_then(|parent| fs::metadata(parent).ok())
+            .and_then(|it| self.watched.get(&(it.dev(), it.ino())))
+            .filter(|it| it.on_child);
+        match watched {
+            Some(watched) => Origin::Child {
+                watched: watched.path.clone(),
+            },
+            None => Origin::Unknown,
+        }
+    }
+}
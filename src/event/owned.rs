@@ -0,0 +1,210 @@
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use apply::Apply;
+use nix::errno::Errno;
+use static_assertions::assert_impl_all;
+
+use crate::fd::FD;
+use crate::fd::FileType;
+
+use super::arena::EventArena;
+use super::error::EventError;
+use super::event::Event;
+use super::event::EventOf;
+use super::events::Events;
+use super::file::fd::FileFD;
+use super::file::fid::FileFID;
+use super::file::fid::OwnedFileFID;
+use super::file::resolved::FileResolved;
+use super::file::File;
+use super::file::GetFD;
+use super::file::ticket::PermissionTicket;
+
+/// Like [`File`], but owning all of its data,
+/// so it doesn't borrow the events buffer it was read from.
+#[derive(Debug)]
+pub enum OwnedFile {
+    FD(FileFD),
+    FID(OwnedFileFID),
+    Permission(PermissionTicket),
+    Resolved(FileResolved),
+}
+
+impl OwnedFile {
+    /// See [`File::variant_name`].
+    pub fn variant_name(&self) -> &'static str {
+        match self {
+            Self::FD(_) => "fd",
+            Self::FID(_) => "fid",
+            Self::Permission(_) => "permission",
+            Self::Resolved(_) => "resolved",
+        }
+    }
+    
+    /// Return the [`FD`](Self::FD) variant if it exists.
+    pub fn fd(self) -> Option<FileFD> {
+        match self {
+            Self::FD(file) => Some(file),
+            _ => None,
+        }
+    }
+    
+    /// Return the [`FID`](Self::FID) variant if it exists.
+    pub fn fid(self) -> Option<OwnedFileFID> {
+        match self {
+            Self::FID(file) => Some(file),
+            _ => None,
+        }
+    }
+    
+    /// Return the [`Permission`](Self::Permission) variant if it exists.
+    pub fn permission(self) -> Option<PermissionTicket> {
+        match self {
+            Self::Permission(file) => Some(file),
+            _ => None,
+        }
+    }
+    
+    /// Return the [`Resolved`](Self::Resolved) variant if it exists.
+    pub fn resolved(self) -> Option<FileResolved> {
+        match self {
+            Self::Resolved(file) => Some(file),
+            _ => None,
+        }
+    }
+    
+    /// See [`File::path`].
+    pub fn path(&self) -> Option<io::Result<PathBuf>> {
+        if let Self::Resolved(file) = self {
+            return file.path().map(|it| Ok(it.to_path_buf()));
+        }
+        self.get_fd()?
+            .path()
+            .apply(Some)
+    }
+    
+    /// See [`File::file_type`].
+    pub fn file_type(&self) -> Option<Result<FileType, Errno>> {
+        if let Self::Resolved(file) = self {
+            return file.file_type().map(Ok);
+        }
+        self.get_fd()?
+            .file_type()
+            .apply(Some)
+    }
+    
+    /// The [`FD`] of this file event, if it has one.
+    pub fn get_fd(&self) -> Option<&FD> {
+        match self {
+            Self::FD(file) => Some(file.fd()),
+            Self::Permission(file) => Some(file.fd()),
+            Self::FID(_) | Self::Resolved(_) => None,
+        }
+    }
+}
+
+/// Like [`Event`], but owning all of its data,
+/// so it can outlive its [`Events`] and be sent to other threads.
+///
+/// Permission events become [`PermissionTicket`]s.
+pub type OwnedEvent = EventOf<OwnedFile>;
+
+pub type OwnedEventResult = Result<OwnedEvent, EventError>;
+
+assert_impl_all!(OwnedEvent: Send);
+assert_impl_all!(EventError: Send);
+
+impl OwnedEvent {
+    /// The [`PermissionTicket`] of a permission event, to respond to it without consuming the event.
+    pub fn permission_mut(&mut self) -> Option<&mut PermissionTicket> {
+        match &mut self.file {
+            OwnedFile::Permission(ticket) => Some(ticket),
+            _ => None,
+        }
+    }
+}
+
+impl Event<'_> {
+    /// Convert into an [`OwnedEvent`], copying a [`FileFID`] with `to_owned_fid`
+    /// and detaching a permission event into a [`PermissionTicket`] that responds using `fanotify_fd`.
+    fn into_owned(
+        self,
+        to_owned_fid: impl FnOnce(&FileFID<'_>) -> OwnedFileFID,
+        fanotify_fd: impl FnOnce() -> Result<Arc<FD>, Errno>,
+    ) -> Result<OwnedEvent, Errno> {
+        let Self { mask, id, file, sequence } = self;
+        let file = match file {
+            File::FD(file) => OwnedFile::FD(file),
+            File::FID(file) => OwnedFile::FID(to_owned_fid(&file)),
+            File::Permission(file) => OwnedFile::Permission(file.detach(fanotify_fd()?)),
+            File::Resolved(file) => OwnedFile::Resolved(file),
+        };
+        Ok(EventOf { mask, id, file, sequence })
+    }
+}
+
+impl Events<'_> {
+    /// Parse all of the [`Event`]s into [`OwnedEvent`]s, appending them to `owned` in order.
+    ///
+    /// This frees up the events buffer for the next read right away,
+    /// which is convenient for handing events off to other threads or actors.
+    ///
+    /// The first permission event duplicates the [`Fanotify`](crate::fanotify::Fanotify) group's file descriptor
+    /// (shared by all of the [`PermissionTicket`]s) so they can respond independently.
+    /// If that fails, the permission events are allowed instead,
+    /// and the [`Errno`] is returned after all of the other events are appended.
+    pub fn drain_to(self, owned: &mut Vec<OwnedEventResult>) -> Result<(), Errno> {
+        self.drain_with(owned, |file| file.to_owned())
+    }
+    
+    /// Like [`Events::drain_to`], but copying [`FileFID`]s into buffers from `arena`,
+    /// so that steadily draining batches and [recycling](EventArena::recycle_all) them doesn't allocate per event.
+    pub fn drain_to_in(self, owned: &mut Vec<OwnedEventResult>, arena: &mut EventArena) -> Result<(), Errno> {
+        self.drain_with(owned, |file| file.to_owned_in(arena))
+    }
+    
+    fn drain_with(
+        self,
+        owned: &mut Vec<OwnedEventResult>,
+        mut to_owned_fid: impl FnMut(&FileFID<'_>) -> OwnedFileFID,
+    ) -> Result<(), Errno> {
+        let fanotify = self.fanotify();
+        let mut fanotify_fd = None;
+        let mut error = None;
+        for event in self {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    owned.push(Err(e));
+                    continue;
+                }
+            };
+            let event = event.into_owned(&mut to_owned_fid, || {
+                if fanotify_fd.is_none() {
+                    fanotify_fd = Some(Arc::new(fanotify.fd.try_clone()?));
+                }
+                Ok(fanotify_fd.clone().unwrap())
+            });
+            match event {
+                Ok(event) => owned.push(Ok(event)),
+                // the permission event was dropped, which allows it
+                Err(e) => error = error.or(Some(e)),
+            }
+        }
+        match error {
+            None => Ok(()),
+            Some(e) => Err(e),
+        }
+    }
+    
+    /// Parse all of the [`Event`]s into a [`Vec`] of [`OwnedEvent`]s.  See [`Events::drain_to`].
+    ///
+    /// Like [`Events::drain_to`], the [`Vec`] has all of the other events even if there's an [`Errno`].
+    pub fn drain_owned(self) -> (Vec<OwnedEventResult>, Result<(), Errno>) {
+        let mut owned = Vec::new();
+        let result = self.drain_to(&mut owned);
+        (owned, result)
+    }
+}
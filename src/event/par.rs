@@ -0,0 +1,64 @@
+use rayon::prelude::*;
+
+use super::error::EventError;
+use super::event::Event;
+use super::event::EventOf;
+use super::events::Events;
+use super::file::File;
+use super::owned::OwnedEvent;
+use super::owned::OwnedFile;
+
+/// The result of [`Events::par_process`].
+#[derive(Debug)]
+pub struct ParProcessed<'a, R> {
+    /// The results of processing each non-permission [`Event`], in their original order.
+    pub results: Vec<R>,
+    /// The permission [`Event`]s, in their original order, still to be responded to.
+    pub permissions: Vec<Event<'a>>,
+    /// The [`EventError`]s, in their original order.
+    pub errors: Vec<EventError>,
+}
+
+impl<'a> Events<'a> {
+    /// Convert all of the non-permission [`Event`]s to [`OwnedEvent`]s
+    /// and process them in parallel on the `rayon` thread pool with `f`.
+    ///
+    /// Permission [`Event`]s aren't processed in parallel, so that they can still be responded to
+    /// in order on this thread and their responses are still buffered and written together.
+    /// They're returned in [`ParProcessed::permissions`] instead.
+    /// Note that the permission [`Event`]s are parsed before the others are processed,
+    /// so they're not delayed by them.
+    pub fn par_process<R: Send, F: Fn(OwnedEvent) -> R + Sync + Send>(self, f: F) -> ParProcessed<'a, R> {
+        let mut owned = Vec::new();
+        let mut permissions = Vec::new();
+        let mut errors = Vec::new();
+        for event in self {
+            let Event { mask, id, file, sequence } = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+            let file = match file {
+                File::FD(file) => OwnedFile::FD(file),
+                File::Resolved(file) => OwnedFile::Resolved(file),
+                File::FID(file) => OwnedFile::FID(file.to_owned()),
+                file @ File::Permission(_) => {
+                    permissions.push(Event { mask, id, file, sequence });
+                    continue;
+                }
+            };
+            owned.push(EventOf { mask, id, file, sequence });
+        }
+        let results = owned
+            .into_par_iter()
+            .map(f)
+            .collect();
+        ParProcessed {
+            results,
+            permissions,
+            errors,
+        }
+    }
+}
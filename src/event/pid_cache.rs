@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use nix::unistd::Pid;
+
+use crate::clock;
+use crate::clock::Clock;
+use crate::proc;
+use crate::proc::ProcFs;
+
+use super::event::Event;
+use super::id::Id;
+
+/// A cache of something read from `/proc/{pid}` for recent [`Event`]s' processes, keyed by pid (or tid),
+/// whose entries expire after a `ttl`.
+#[derive(Debug)]
+pub(crate) struct PidCache<T> {
+    pub(crate) ttl: Duration,
+    pub(crate) capacity: usize,
+    /// [`None`] if it couldn't be read, so that's not retried every time either.
+    pub(crate) entries: HashMap<libc::pid_t, (Instant, Option<T>)>,
+    pub(crate) clock: Arc<dyn Clock>,
+    /// Where the values are read from.
+    pub(crate) proc_fs: Arc<dyn ProcFs>,
+}
+
+impl<T: Clone> PidCache<T> {
+    pub(crate) fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            entries: HashMap::new(),
+            clock: clock::system(),
+            proc_fs: proc::system(),
+        }
+    }
+    
+    /// Get the cached value for the process (or thread) that caused `event`,
+    /// or [`read`](FnOnce) it from the [`ProcFs`] and cache it if it's missing or expired.
+    pub(crate) fn get(&mut self, event: &Event<'_>, read: impl FnOnce(&dyn ProcFs, Pid) -> Option<T>) -> Option<T> {
+        let pid = match event.id().id() {
+            Id::Pid(pid) | Id::Tid(pid) => pid,
+        };
+        let now = self.clock.now();
+        if let Some((read_at, value)) = self.entries.get(&pid.as_raw()) {
+            if now.duration_since(*read_at) < self.ttl {
+                return value.clone();
+            }
+        }
+        if self.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            self.entries.retain(|_, (read_at, _)| now.duration_since(*read_at) < ttl);
+            if self.entries.len() >= self.capacity {
+                self.entries.clear();
+            }
+        }
+        let value = read(&*self.proc_fs, pid);
+        if self.capacity > 0 {
+            self.entries.insert(pid.as_raw(), (now, value.clone()));
+        }
+        value
+    }
+}
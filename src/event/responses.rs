@@ -1,17 +1,30 @@
+use std::cell::Cell;
 use std::cell::RefCell;
 use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Formatter;
 use std::mem::size_of;
+use std::os::unix::io::RawFd;
 use std::rc::Rc;
 use std::slice;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
 use nix::errno::Errno;
 use to_trait::To;
 
+use super::file::permission::PermissionDecision;
 use super::file::permission::RawFilePermission;
+use super::super::clock::Clock;
 use super::super::fanotify::Fanotify;
+use super::super::fd::FD;
+use super::super::libc::read::FAN_NOFD;
+use super::super::libc::write::FAN_INFO;
+use super::super::libc::write::FAN_RESPONSE_INFO_AUDIT_RULE;
 use super::super::libc::write::fanotify_response;
+use super::super::libc::write::fanotify_response_info_audit_rule;
+use super::super::libc::write::fanotify_response_info_header;
 
 impl fanotify_response {
     /// Reinterpret as a byte slice for [`writing`](libc::write) to a [`Fanotify`] instance.
@@ -31,6 +44,104 @@ impl fanotify_response {
     }
 }
 
+impl fanotify_response_info_audit_rule {
+    /// Reinterpret as a byte slice for [`writing`](libc::write) to a [`Fanotify`] instance.
+    fn as_bytes(&self) -> &[u8] {
+        // Safe for the same reasons as fanotify_response::as_bytes(),
+        // since this is all integers with no padding.
+        unsafe {
+            slice::from_raw_parts(
+                self as *const Self as *const u8,
+                size_of::<Self>(),
+            )
+        }
+    }
+}
+
+impl RawFilePermission {
+    /// Append the raw bytes of the [`fanotify_response`]
+    /// and its extra info record, if any, to the buffer.
+    ///
+    /// These must be written together in a single [`write`](libc::write).
+    pub(crate) fn write_bytes(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(self.to::<fanotify_response>().as_bytes());
+        if let Some(info) = self.info() {
+            buffer.extend_from_slice(info.as_bytes());
+        }
+    }
+    
+    /// Parse a [`RawFilePermission`] from the front of the buffer, as written by [`Self::write_bytes`].
+    ///
+    /// Return it (or `Err(())` if it's invalid) and its length in bytes,
+    /// or [`None`] if the buffer is too short.
+    pub(crate) fn read_bytes(buffer: &[u8]) -> Option<(Result<Self, ()>, usize)> {
+        let read = |offset: usize, len: usize| buffer.get(offset..offset + len);
+        let response = read(0, size_of::<fanotify_response>())?;
+        let response = unsafe { (response.as_ptr() as *const fanotify_response).read_unaligned() };
+        let mut len = size_of::<fanotify_response>();
+        let mut audit_rule = None;
+        if response.response & FAN_INFO != 0 {
+            let header = read(len, size_of::<fanotify_response_info_header>())?;
+            let header = unsafe { (header.as_ptr() as *const fanotify_response_info_header).read_unaligned() };
+            if header.type_ == FAN_RESPONSE_INFO_AUDIT_RULE {
+                let info = read(len, size_of::<fanotify_response_info_audit_rule>())?;
+                let info = unsafe { (info.as_ptr() as *const fanotify_response_info_audit_rule).read_unaligned() };
+                audit_rule = Some(info.rule_number);
+            }
+            len += (header.len as usize).max(size_of::<fanotify_response_info_header>());
+        }
+        let permission = (&response).try_to::<RawFilePermission>().map(|it| Self {
+            audit_rule,
+            ..it
+        });
+        Some((permission, len))
+    }
+}
+
+/// The length of the whole responses (as written by [`RawFilePermission::write_bytes`])
+/// at the start of `bytes` that fit in its first `len` bytes.
+fn whole_responses_len(bytes: &[u8], len: usize) -> usize {
+    let mut offset = 0;
+    while let Some((_, response_len)) = RawFilePermission::read_bytes(&bytes[offset..]) {
+        if offset + response_len > len {
+            break;
+        }
+        offset += response_len;
+    }
+    offset
+}
+
+/// [`Write`](FD::write) the responses in `bytes` (as written by [`RawFilePermission::write_bytes`]) to `fd`,
+/// never splitting a response across writes.
+///
+/// The kernel only handles whole responses, so if a write ever ends partway through one,
+/// that response wasn't handled, and writing just its tail next would be misparsed
+/// as the start of another response, corrupting the rest of the stream.
+/// Instead, the number of bytes written is rounded down to the whole responses,
+/// so the partially written one is written again in full the next time.
+/// If nothing is written at all, the write is retried right away once,
+/// and then [`EIO`](Errno::EIO) is returned, since retrying forever could spin.
+/// If only part of the first response is written, [`EIO`](Errno::EIO) is returned right away,
+/// since the kernel may have handled it after all, and writing it again could respond twice.
+///
+/// Return the number of bytes of whole responses written.
+pub(crate) fn write_responses(fd: &FD, bytes: &[u8]) -> Result<usize, Errno> {
+    if bytes.is_empty() {
+        return Ok(0);
+    }
+    for _ in 0..2 {
+        let bytes_written = fd.write(bytes)?;
+        let whole = whole_responses_len(bytes, bytes_written);
+        if whole > 0 {
+            return Ok(whole);
+        }
+        if bytes_written > 0 {
+            break;
+        }
+    }
+    Err(Errno::EIO)
+}
+
 /// A buffer of responses to fanotify [`Event`](super::event::Event)s.
 ///
 /// A [`ResponseBuffer`] can be explicitly written to a [`Fanotify`] instance
@@ -40,8 +151,9 @@ struct ResponseBuffer<'a> {
 }
 
 impl<'a> ResponseBuffer<'a> {
+    /// Any responses already in the buffer were left over
+    /// because writing them would've blocked, so they're kept to be written first.
     fn new(buffer: &'a mut Vec<u8>) -> Self {
-        buffer.clear();
         Self {
             buffer,
         }
@@ -55,16 +167,16 @@ impl<'a> ResponseBuffer<'a> {
         !self.is_empty()
     }
     
-    /// Add another raw [`fanotify_response`] to the buffer.
-    fn add(&mut self, response: &fanotify_response) {
-        self.buffer.extend_from_slice(response.as_bytes());
+    /// Add another raw [`fanotify_response`] (and its info) to the buffer.
+    fn add(&mut self, response: &RawFilePermission) {
+        response.write_bytes(self.buffer);
     }
     
     /// Attempt to [`write`](libc::write) the buffer to the [`Fanotify`] instance.
     /// It also removes what has been written from the buffer,
     /// so this method can be called repeatedly until [`ResponseBuffer::is_empty`] is true.
     fn write(&mut self, fanotify: &Fanotify) -> Result<usize, Errno> {
-        let bytes_written = fanotify.fd.write(self.buffer.as_slice())?;
+        let bytes_written = write_responses(&fanotify.fd, self.buffer.as_slice())?;
         // this drain call is O(n) even for small bytes_written, so write_all() is O(n^2)
         // could use a deque instead, but this should be a rare case
         // since the whole buffer should normally be written at once,
@@ -85,27 +197,47 @@ impl<'a> ResponseBuffer<'a> {
         Ok(())
     }
     
-    pub fn responses(&self) -> &[fanotify_response] {
-        // the buffer could slice a fanotify_response in half when writing
-        // but from the back it should be all contiguous
-        // so we just skip the offset at the beginning and cast to &[fanotify_response]
-        let offset = self.buffer.len() % size_of::<fanotify_response>();
-        let len = self.buffer.len() / size_of::<fanotify_response>();
-        unsafe {
-            let ptr = self.buffer.as_ptr().add(offset) as *const fanotify_response;
-            slice::from_raw_parts(ptr, len)
+    /// A [`FlushError`] for `errno` with the responses left in the buffer.
+    fn error(&self, errno: Errno) -> FlushError {
+        let mut pending = 0;
+        let mut fds = Vec::new();
+        for response in self.responses() {
+            pending += 1;
+            if let Ok(response) = response {
+                fds.push(response.fd);
+            }
         }
+        FlushError {
+            errno,
+            pending,
+            fds,
+        }
+    }
+    
+    /// Parse the responses (of varying lengths) in the buffer.
+    ///
+    /// The kernel only ever writes whole responses, so the buffer always starts at one.
+    /// This used to return a `&[fanotify_response]`, but a response with an audit rule
+    /// is followed by its info, so they can't be sliced anymore.
+    /// It's crate-private, like [`ResponseBuffer`], and [`Responses::pending_responses`] is the public view.
+    fn responses(&self) -> impl Iterator<Item = Result<RawFilePermission, ()>> + '_ {
+        let mut offset = 0;
+        std::iter::from_fn(move || {
+            let (response, len) = RawFilePermission::read_bytes(&self.buffer[offset..])?;
+            offset += len;
+            Some(response)
+        })
     }
 }
 
 impl Debug for ResponseBuffer<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "[")?;
-        for (i, response) in self.responses().iter().enumerate() {
+        for (i, response) in self.responses().enumerate() {
             if i != 0 {
                 write!(f, ", ")?;
             }
-            match response.try_to::<RawFilePermission>() {
+            match response {
                 Ok(response) => {
                     write!(f, "{:?}", response)?;
                 }
@@ -119,6 +251,86 @@ impl Debug for ResponseBuffer<'_> {
     }
 }
 
+/// An error from [`Responses::flush`] or [`Responses::flush_all`],
+/// with the responses that are still buffered after it.
+///
+/// Unflushed responses are retried by the next flush (or on [`Drop`]),
+/// but the processes waiting on them stay blocked until then,
+/// so knowing which ones are stuck lets the caller decide to retry,
+/// give up on them, or e.g. allow them some other way.
+#[derive(thiserror::Error, Debug, Clone, Eq, PartialEq)]
+#[error("failed to flush permission responses ({} still pending, for fds {:?}): {}", .pending, .fds, .errno)]
+pub struct FlushError {
+    pub errno: Errno,
+    /// The number of responses still buffered.
+    pub pending: usize,
+    /// The event fds of the responses still buffered, in order.
+    pub fds: Vec<RawFd>,
+}
+
+impl From<FlushError> for Errno {
+    fn from(this: FlushError) -> Self {
+        this.errno
+    }
+}
+
+/// A read-only view of a permission response that's buffered but not written yet.
+///
+/// See [`Responses::pending_responses`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PendingResponse {
+    /// The file descriptor of the permission event this responds to.
+    pub fd: RawFd,
+    pub decision: PermissionDecision,
+    pub audit: bool,
+    pub audit_rule: Option<u32>,
+}
+
+impl From<RawFilePermission> for PendingResponse {
+    fn from(this: RawFilePermission) -> Self {
+        let RawFilePermission { fd, decision, audit, audit_rule } = this;
+        Self {
+            fd,
+            decision,
+            audit,
+            audit_rule,
+        }
+    }
+}
+
+/// When to flush buffered permission responses early,
+/// instead of only when all of the [`FilePermission`](super::file::permission::FilePermission)s
+/// from one read are dropped.
+///
+/// Processes are blocked until their permission events are responded to,
+/// so this bounds how long a buffered decision can wait,
+/// e.g. when a batch of events is processed slowly in async code.
+///
+/// It's checked whenever a response is buffered and before each event is parsed,
+/// so the [`max_delay`](Self::max_delay) is only as precise as that,
+/// and [`Responses::flush_if_due`] can be called to check it in between.
+/// Errors from these early flushes are ignored;
+/// the responses just stay buffered until the next flush.
+///
+/// The default never flushes early.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct FlushPolicy {
+    /// Flush once this many responses are buffered.
+    pub max_pending: Option<usize>,
+    /// Flush once the oldest buffered response has been waiting this long.
+    pub max_delay: Option<Duration>,
+}
+
+impl FlushPolicy {
+    /// Flush every response as soon as it's buffered.
+    pub const fn immediate() -> Self {
+        Self {
+            max_pending: Some(1),
+            max_delay: None,
+        }
+    }
+}
+
 /// A buffer of responses to fanotify [`Event`](super::event::Event)s.
 ///
 /// A [`fanotify_response`] can be written to this [`Responses`] buffer
@@ -136,6 +348,11 @@ impl Debug for ResponseBuffer<'_> {
 pub struct Responses<'a> {
     fanotify: &'a Fanotify,
     responses: RefCell<ResponseBuffer<'a>>,
+    policy: FlushPolicy,
+    /// The number of responses buffered since the buffer was last empty.
+    pending: Cell<usize>,
+    /// When the first response was buffered since the buffer was last empty.
+    oldest: Cell<Option<Instant>>,
 }
 
 impl<'a> Responses<'a> {
@@ -144,6 +361,9 @@ impl<'a> Responses<'a> {
         Self {
             fanotify,
             responses: RefCell::new(ResponseBuffer::new(buffer)),
+            policy: fanotify.flush_policy(),
+            pending: Cell::new(0),
+            oldest: Cell::new(None),
         }
     }
     
@@ -151,30 +371,93 @@ impl<'a> Responses<'a> {
         self.responses.borrow().is_empty()
     }
     
+    /// The current time of the [`Fanotify`] group's [`Clock`](crate::clock::Clock).
+    pub(super) fn now(&self) -> Instant {
+        self.fanotify.clock().now()
+    }
+    
+    pub(super) fn clock(&self) -> Arc<dyn Clock> {
+        self.fanotify.clock().clone()
+    }
+    
     pub fn has_more(&self) -> bool {
         !self.is_empty()
     }
     
+    /// The responses that are buffered but not written yet, in the order they'll be written,
+    /// e.g. to assert what will be written before a flush.
+    pub fn pending_responses(&self) -> Vec<PendingResponse> {
+        self.responses
+            .borrow()
+            .responses()
+            .filter_map(Result::ok)
+            .map(PendingResponse::from)
+            .collect()
+    }
+    
     /// [`Write`](libc::write) a raw [`fanotify_response`] immediately to the [`Fanotify`] instance.
     pub(super) fn write_immediately(&self, response: &RawFilePermission) -> Result<(), Errno> {
-        let bytes_written = self.fanotify.fd.write(response.to::<fanotify_response>().as_bytes())?;
+        let mut bytes = Vec::new();
+        response.write_bytes(&mut bytes);
+        let bytes_written = write_responses(&self.fanotify.fd, &bytes)?;
         // a write this small should definitely succeed, so only try once
-        match bytes_written {
-            0 => Ok(()),
-            _ => Err(Errno::EAGAIN),
+        if bytes_written == bytes.len() {
+            Ok(())
+        } else {
+            Err(Errno::EAGAIN)
         }
     }
     
     /// Write a raw [`fanotify_response`] to the buffer.
     pub(super) fn write_buffered(&self, response: &RawFilePermission) {
-        self.responses.borrow_mut().add(&response.into());
+        self.responses.borrow_mut().add(response);
+        self.pending.set(self.pending.get() + 1);
+        if self.oldest.get().is_none() {
+            self.oldest.set(Some(self.now()));
+        }
+        self.flush_if_due();
+    }
+    
+    /// If the buffered responses should be flushed early according to the [`FlushPolicy`].
+    pub fn is_flush_due(&self) -> bool {
+        let FlushPolicy { max_pending, max_delay } = self.policy;
+        if self.is_empty() {
+            return false;
+        }
+        let too_many = max_pending.is_some_and(|max| self.pending.get() >= max);
+        let too_old = max_delay.is_some_and(|max| {
+            self.oldest.get().is_some_and(|it| self.now().saturating_duration_since(it) >= max)
+        });
+        too_many || too_old
+    }
+    
+    /// [`Flush`](Responses::flush_all) the buffered responses if the [`FlushPolicy`] says they're due,
+    /// ignoring any errors.
+    pub fn flush_if_due(&self) {
+        if self.is_flush_due() {
+            let _ = self.flush_all();
+        }
+    }
+    
+    /// Reset the [`FlushPolicy`] tracking after a flush,
+    /// so that only the responses still buffered count towards the next one.
+    fn flushed(&self) {
+        let pending = self.responses.borrow().responses().count();
+        self.pending.set(pending);
+        if pending == 0 {
+            self.oldest.set(None);
+        }
     }
     
     /// Attempt to [`write`](libc::write) the buffer to the [`Fanotify`] instance.
     /// It also removes what has been written from the buffer,
     /// so this method can be called repeatedly until [`Responses::is_empty`] is true.
-    pub fn flush(&self) -> Result<usize, Errno> {
-        self.responses.borrow_mut().write(self.fanotify)
+    pub fn flush(&self) -> Result<usize, FlushError> {
+        let mut responses = self.responses.borrow_mut();
+        let result = responses.write(self.fanotify).map_err(|e| responses.error(e));
+        drop(responses);
+        self.flushed();
+        result
     }
     
     /// Write the entire buffer to the [`Fanotify`] instance.
@@ -182,8 +465,12 @@ impl<'a> Responses<'a> {
     /// This keeps calling [`Responses::flush`] until either
     /// all of the responses have been written
     /// or one of the writes throws an error, in which case we exit early with the error.
-    pub fn flush_all(&self) -> Result<(), Errno> {
-        self.responses.borrow_mut().write_all(self.fanotify)
+    pub fn flush_all(&self) -> Result<(), FlushError> {
+        let mut responses = self.responses.borrow_mut();
+        let result = responses.write_all(self.fanotify).map_err(|e| responses.error(e));
+        drop(responses);
+        self.flushed();
+        result
     }
 }
 
@@ -195,14 +482,78 @@ impl Drop for Responses<'_> {
     ///
     /// This panics on error.
     /// To handle the error, first call [`Responses::flush_all`] until it returns `Ok(())`.
+    ///
+    /// The exception is [`EAGAIN`](Errno::EAGAIN), which can only happen in non-blocking (async) mode.
+    /// Then the rest of the responses are left in the buffer,
+    /// to be written before the next [`Events`](super::events::Events)' responses
+    /// or by `AsyncBufferedFanotify::flush_responses` (with the `async` feature).
     fn drop(&mut self) {
-        self.flush_all().expect(
-            "Responses::write_all() threw in Responses::drop().  \
-                To handle this, call Responses::write_all() yourself first."
-        );
+        match self.flush_all() {
+            Ok(()) => {}
+            Err(e) if e.errno == Errno::EAGAIN => {}
+            Err(e) => panic!(
+                "Responses::write_all() threw {} in Responses::drop().  \
+                    To handle this, call Responses::write_all() yourself first.",
+                e,
+            ),
+        }
     }
 }
 
 /// Parameterized reference counter here just to simplify things a bit.
 /// [`Arc`](std::sync::Arc) doesn't work for now.
 pub type RC<T> = Rc<T>;
+
+impl Fanotify {
+    /// Check if the kernel supports extra info records in permission responses
+    /// (`FAN_INFO`, since Linux 6.3), like [`FilePermission::audit_rule`](super::file::permission::FilePermission::audit_rule).
+    ///
+    /// The kernel allows writing a response with info but without a file descriptor
+    /// just to check this, so it doesn't affect any events.
+    /// This only works on a content class group that can respond to permission events, though.
+    pub fn supports_response_info(&self) -> bool {
+        let response = RawFilePermission {
+            fd: FAN_NOFD,
+            decision: Default::default(),
+            audit: false,
+            audit_rule: Some(0),
+        };
+        let mut bytes = Vec::new();
+        response.write_bytes(&mut bytes);
+        self.fd.write(&bytes).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::errno::Errno;
+    
+    use super::ResponseBuffer;
+    use super::whole_responses_len;
+    use super::super::file::permission::PermissionDecision::Deny;
+    use super::super::file::permission::RawFilePermission;
+    
+    #[test]
+    fn response_bytes_round_trip() {
+        let responses = [
+            RawFilePermission { fd: 3, decision: Deny, audit: false, audit_rule: None },
+            RawFilePermission { fd: 4, decision: Deny, audit: true, audit_rule: Some(42) },
+        ];
+        let mut bytes = Vec::new();
+        for response in &responses {
+            response.write_bytes(&mut bytes);
+        }
+        assert_eq!(bytes.len(), 8 + 8 + 16);
+        let (first, len) = RawFilePermission::read_bytes(&bytes).unwrap();
+        assert_eq!((first.unwrap().audit_rule, len), (None, 8));
+        let (second, len) = RawFilePermission::read_bytes(&bytes[len..]).unwrap();
+        let second = second.unwrap();
+        assert_eq!((second.fd, second.audit, second.audit_rule, len), (4, true, Some(42), 24));
+        
+        // a partial write is rounded down to the whole responses written
+        assert_eq!([0, 7, 8, 31, 32].map(|len| whole_responses_len(&bytes, len)), [0, 0, 8, 8, 32]);
+        
+        let error = ResponseBuffer::new(&mut bytes).error(Errno::ENOENT);
+        assert_eq!((error.pending, error.fds), (2, vec![3, 4]));
+    }
+}
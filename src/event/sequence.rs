@@ -0,0 +1,153 @@
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use super::error::EventError;
+use super::error::EventResult;
+
+/// Where an [`Event`](super::event::Event) falls in its group's stream of events.
+///
+/// Both numbers increase monotonically (starting at 0) per [`Fanotify`](crate::fanotify::Fanotify) group,
+/// so downstream systems can do at-least-once bookkeeping, e.g. with a [`GapDetector`].
+/// Events [parsed from bytes](super::events::Events::parse_from_bytes) are numbered from 0 in batch 0 instead.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct Sequence {
+    /// The number of the batch (i.e., the [`Events`](super::events::Events) of one read) this event is from.
+    pub batch: u64,
+    /// The number of this event.
+    pub event: u64,
+}
+
+/// The counters a [`Fanotify`](crate::fanotify::Fanotify) group numbers its batches and events with.
+#[derive(Debug, Default)]
+pub(crate) struct SequenceCounter {
+    batches: AtomicU64,
+    events: AtomicU64,
+}
+
+impl SequenceCounter {
+    /// A new counter continuing where `other` left off, e.g. for a recreated group.
+    pub fn continuing(other: &Self) -> Self {
+        Self {
+            batches: AtomicU64::new(other.batches.load(Ordering::Relaxed)),
+            events: AtomicU64::new(other.events.load(Ordering::Relaxed)),
+        }
+    }
+    
+    pub fn next_batch(&self) -> u64 {
+        self.batches.fetch_add(1, Ordering::Relaxed)
+    }
+    
+    pub fn next_event(&self) -> u64 {
+        self.events.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+/// A run of events that a [`GapDetector`] didn't observe.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Gap {
+    /// The last event [number](Sequence::event) observed before the gap, if any.
+    pub after: Option<u64>,
+    /// The first event number observed after the gap.
+    pub before: u64,
+    /// The number of events missing, which is exact for skipped sequence numbers,
+    /// but only a lower bound for a queue overflow, since the kernel doesn't say how many it dropped.
+    pub missing: u64,
+    /// If the kernel's queue overflowed, dropping events before they were numbered.
+    pub overflow: bool,
+}
+
+/// Detects [`Gap`]s in a stream of events by their [`Sequence`] numbers and queue overflows.
+///
+/// Sequence numbers are assigned as events are parsed, so within one process,
+/// gaps only come from queue overflows (or from events filtered out along the way).
+/// Downstream of an export or a network hop, skipped sequence numbers also reveal lost events.
+#[derive(Debug, Default, Clone)]
+pub struct GapDetector {
+    last: Option<u64>,
+    overflowed: bool,
+    gaps: u64,
+    missing: u64,
+}
+
+impl GapDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// The last event number observed.
+    pub fn last(&self) -> Option<u64> {
+        self.last
+    }
+    
+    /// The number of [`Gap`]s detected so far.
+    pub fn gaps(&self) -> u64 {
+        self.gaps
+    }
+    
+    /// The (estimated) total number of events missing from all the [`Gap`]s so far.
+    pub fn missing(&self) -> u64 {
+        self.missing
+    }
+    
+    /// Observe a queue overflow, which is reported as a [`Gap`] once the next event is [observed](Self::observe).
+    pub fn observe_overflow(&mut self) {
+        self.overflowed = true;
+    }
+    
+    /// Observe the next event's [number](Sequence::event), returning the [`Gap`] before it, if any.
+    ///
+    /// Event numbers that aren't after the [last](Self::last) one (duplicates or reordered events) are ignored.
+    pub fn observe(&mut self, event: u64) -> Option<Gap> {
+        if self.last.is_some_and(|last| event <= last) {
+            return None;
+        }
+        let skipped = match self.last {
+            Some(last) => event - last - 1,
+            None => 0,
+        };
+        let overflow = std::mem::take(&mut self.overflowed);
+        let after = self.last.replace(event);
+        if skipped == 0 && !overflow {
+            return None;
+        }
+        let gap = Gap {
+            after,
+            before: event,
+            missing: skipped.max(overflow as u64),
+            overflow,
+        };
+        self.gaps += 1;
+        self.missing += gap.missing;
+        Some(gap)
+    }
+    
+    /// [`Observe`](Self::observe) an event or a queue overflow error, ignoring other errors.
+    pub fn observe_result(&mut self, result: &EventResult<'_>) -> Option<Gap> {
+        match result {
+            Ok(event) => self.observe(event.sequence().event),
+            Err(EventError::QueueOverflowed) | Err(EventError::UnlimitedQueueButQueueStillOverflowed) => {
+                self.observe_overflow();
+                None
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Gap;
+    use super::GapDetector;
+    
+    #[test]
+    fn gap_detector() {
+        let mut detector = GapDetector::new();
+        assert_eq!(detector.observe(0), None);
+        assert_eq!(detector.observe(1), None);
+        assert_eq!(detector.observe(4), Some(Gap { after: Some(1), before: 4, missing: 2, overflow: false }));
+        assert_eq!(detector.observe(3), None);
+        detector.observe_overflow();
+        assert_eq!(detector.observe(5), Some(Gap { after: Some(4), before: 5, missing: 1, overflow: true }));
+        assert_eq!((detector.gaps(), detector.missing(), detector.last()), (2, 3, Some(5)));
+    }
+}
@@ -0,0 +1,107 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use super::event::Event;
+
+/// The error a [`Sink`] can fail with.
+///
+/// It's boxed so that different kinds of [`Sink`]s can be combined in a [`FanOut`].
+pub type SinkError = Box<dyn Error + Send + Sync>;
+
+/// A consumer of [`Event`]s, like a logger, metrics, or a permission policy.
+///
+/// Any `FnMut(&Event) -> Result<(), SinkError>` is a [`Sink`].
+pub trait Sink {
+    fn consume(&mut self, event: &Event<'_>) -> Result<(), SinkError>;
+}
+
+impl<F: FnMut(&Event<'_>) -> Result<(), SinkError>> Sink for F {
+    fn consume(&mut self, event: &Event<'_>) -> Result<(), SinkError> {
+        self(event)
+    }
+}
+
+/// The errors from the [`Sink`]s in a [`FanOut`] that failed to consume an [`Event`].
+#[derive(Debug)]
+pub struct FanOutError {
+    /// The index of each failed [`Sink`] (in the order they were added) and its error.
+    pub errors: Vec<(usize, SinkError)>,
+}
+
+impl Display for FanOutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} sink(s) failed: ", self.errors.len())?;
+        for (i, (index, error)) in self.errors.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "sink {}: {}", index, error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for FanOutError {}
+
+/// A [`Sink`] that feeds every [`Event`] to multiple [`Sink`]s,
+/// so that one read loop can feed multiple consumers
+/// without each of them re-reading the [`Fanotify`](crate::fanotify::Fanotify) group.
+///
+/// Errors are isolated per [`Sink`]:
+/// every [`Sink`] consumes every [`Event`] even if an earlier one failed,
+/// and all the errors are collected into a [`FanOutError`].
+#[derive(Default)]
+pub struct FanOut<'s> {
+    sinks: Vec<Box<dyn Sink + 's>>,
+}
+
+impl<'s> FanOut<'s> {
+    pub fn new() -> Self {
+        Self {
+            sinks: Vec::new(),
+        }
+    }
+    
+    /// Add another [`Sink`], returning its index.
+    pub fn add(&mut self, sink: impl Sink + 's) -> usize {
+        self.sinks.push(Box::new(sink));
+        self.sinks.len() - 1
+    }
+    
+    /// Add another [`Sink`] builder-style.
+    pub fn with(mut self, sink: impl Sink + 's) -> Self {
+        self.add(sink);
+        self
+    }
+    
+    pub fn len(&self) -> usize {
+        self.sinks.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+    
+    /// Feed the [`Event`] to every [`Sink`].
+    pub fn consume_all(&mut self, event: &Event<'_>) -> Result<(), FanOutError> {
+        let errors = self.sinks
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, sink)| sink.consume(event).err().map(|e| (i, e)))
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(FanOutError { errors })
+        }
+    }
+}
+
+impl Sink for FanOut<'_> {
+    fn consume(&mut self, event: &Event<'_>) -> Result<(), SinkError> {
+        self.consume_all(event)?;
+        Ok(())
+    }
+}
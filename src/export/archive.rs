@@ -0,0 +1,436 @@
+use std::fmt;
+use std::fmt::Formatter;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::UNIX_EPOCH;
+
+use crate::clock;
+use crate::clock::Clock;
+use crate::event::event::Event;
+use crate::event::sink::Sink;
+use crate::event::sink::SinkError;
+
+use super::EventRecord;
+use super::compact;
+
+/// The name of the index file in an archive directory.
+pub const INDEX: &str = "index";
+
+const SEGMENT_EXTENSION: &str = "events";
+
+/// When an [`ArchiveWriter`] starts a new segment file.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Rotation {
+    /// Rotate once a segment has at least this many bytes.
+    pub max_bytes: u64,
+    /// Rotate once a segment has been open for this long, if set.
+    ///
+    /// This is only checked when appending, so an idle segment can stay open for longer.
+    pub max_age: Option<Duration>,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self {
+            max_bytes: 64 * 1024 * 1024,
+            max_age: Some(Duration::from_secs(60 * 60)),
+        }
+    }
+}
+
+/// An entry in an archive's index, describing one finished segment file.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SegmentInfo {
+    /// The sequence number of the segment, which determines its file name.
+    pub sequence: u64,
+    /// The number of [`EventRecord`]s in the segment.
+    pub records: u64,
+    /// The size of the segment's records in bytes, before any compression.
+    pub bytes: u64,
+    /// When the segment was started, in seconds since the Unix epoch.
+    pub start: u64,
+    /// When the segment was finished, in seconds since the Unix epoch.
+    pub end: u64,
+    /// If the segment is compressed with zstd (see `ArchiveWriter::with_compression`, with the `zstd` feature).
+    pub compressed: bool,
+}
+
+impl SegmentInfo {
+    /// The file name of segment `sequence`, zero-padded so that they sort in order.
+    pub fn file_name(sequence: u64) -> String {
+        format!("{:020}.{}", sequence, SEGMENT_EXTENSION)
+    }
+    
+    fn to_line(self) -> String {
+        let compressed = if self.compressed { " zstd" } else { "" };
+        format!("{} {} {} {} {}{}\n", self.sequence, self.records, self.bytes, self.start, self.end, compressed)
+    }
+    
+    fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.split(' ');
+        let mut next = || fields.next()?.parse::<u64>().ok();
+        let mut this = Self {
+            sequence: next()?,
+            records: next()?,
+            bytes: next()?,
+            start: next()?,
+            end: next()?,
+            compressed: false,
+        };
+        match fields.next() {
+            None => {}
+            Some("zstd") => this.compressed = true,
+            Some(_) => return None,
+        }
+        Some(this).filter(|_| fields.next().is_none())
+    }
+}
+
+fn unix_time(clock: &dyn Clock) -> u64 {
+    clock.system_now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |it| it.as_secs())
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// The file of the segment an [`ArchiveWriter`] is currently appending to.
+enum SegmentFile {
+    Plain(BufWriter<fs::File>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, BufWriter<fs::File>>),
+}
+
+impl fmt::Debug for SegmentFile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Plain(file) => f.debug_tuple("Plain").field(file).finish(),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(encoder) => f.debug_tuple("Zstd").field(encoder.get_ref()).finish(),
+        }
+    }
+}
+
+impl SegmentFile {
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        match self {
+            Self::Plain(file) => file.write_all(bytes),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(encoder) => encoder.write_all(bytes),
+        }
+    }
+    
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(file) => file.flush(),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(encoder) => encoder.flush(),
+        }
+    }
+    
+    /// Write everything to disk, ending the zstd frame, if any.
+    ///
+    /// Writing more after this starts a new frame, which is still decompressed along with the rest.
+    fn finish(&mut self) -> io::Result<()> {
+        let file = match self {
+            Self::Plain(file) => file,
+            #[cfg(feature = "zstd")]
+            Self::Zstd(encoder) => {
+                encoder.do_finish()?;
+                encoder.get_mut()
+            }
+        };
+        file.flush()?;
+        file.get_ref().sync_data()
+    }
+}
+
+/// The segment an [`ArchiveWriter`] is currently appending to.
+#[derive(Debug)]
+struct Segment {
+    info: SegmentInfo,
+    file: SegmentFile,
+    opened_at: Instant,
+}
+
+/// An archival [`Sink`] for long-term audit retention,
+/// appending [`compact`]-encoded [`EventRecord`]s to a directory of segment files.
+///
+/// Records are written in batches (through a buffer) to the current segment,
+/// which is rotated according to a [`Rotation`].
+/// When a segment is finished, a line describing it is appended to the [`INDEX`] file,
+/// so an [`ArchiveReader`] only ever sees finished segments.
+///
+/// Segments aren't compressed by default, since the [`compact`] encoding is already small,
+/// but with the `zstd` feature, they can be compressed as they're written
+/// (see `ArchiveWriter::with_compression`).
+/// Finished segments can also be shipped elsewhere by a separate job that consults the [`INDEX`].
+#[derive(Debug)]
+pub struct ArchiveWriter {
+    dir: PathBuf,
+    rotation: Rotation,
+    compression: Option<i32>,
+    next_sequence: u64,
+    segment: Option<Segment>,
+    encoded: Vec<u8>,
+    clock: Arc<dyn Clock>,
+}
+
+impl ArchiveWriter {
+    /// Open (or create) the archive directory `dir`,
+    /// continuing after any segments already in it.
+    pub fn open(dir: impl AsRef<Path>, rotation: Rotation) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        // a segment could have been left unfinished (and unindexed) by a crash, so don't reuse it
+        let mut next_sequence = 0;
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|it| it.to_str()) != Some(SEGMENT_EXTENSION) {
+                continue;
+            }
+            if let Some(sequence) = path.file_stem().and_then(|it| it.to_str()?.parse::<u64>().ok()) {
+                next_sequence = next_sequence.max(sequence + 1);
+            }
+        }
+        Ok(Self {
+            dir,
+            rotation,
+            compression: None,
+            next_sequence,
+            segment: None,
+            encoded: Vec::new(),
+            clock: clock::system(),
+        })
+    }
+    
+    /// Use `clock` to timestamp and age segments instead of the [system](clock::SystemClock) one.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    /// Compress new segments with zstd at `level` (where 0 is zstd's default level).
+    ///
+    /// Requires the `zstd` feature.
+    #[cfg(feature = "zstd")]
+    pub fn with_compression(mut self, level: i32) -> Self {
+        self.compression = Some(level);
+        self
+    }
+    
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+    
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+    
+    /// The zstd compression level of new segments, if they're compressed.
+    pub fn compression(&self) -> Option<i32> {
+        self.compression
+    }
+    
+    fn should_rotate(&self, segment: &Segment) -> bool {
+        let too_big = segment.info.bytes >= self.rotation.max_bytes;
+        let too_old = match self.rotation.max_age {
+            None => false,
+            Some(max_age) => self.clock.now().saturating_duration_since(segment.opened_at) >= max_age,
+        };
+        too_big || too_old
+    }
+    
+    fn start_segment(&mut self) -> io::Result<Segment> {
+        let sequence = self.next_sequence;
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(self.dir.join(SegmentInfo::file_name(sequence)))?;
+        self.next_sequence += 1;
+        let file = BufWriter::new(file);
+        let file = match self.compression {
+            None => SegmentFile::Plain(file),
+            #[cfg(feature = "zstd")]
+            Some(level) => SegmentFile::Zstd(zstd::Encoder::new(file, level)?),
+            #[cfg(not(feature = "zstd"))]
+            Some(_) => unreachable!("compression requires the zstd feature"),
+        };
+        Ok(Segment {
+            info: SegmentInfo {
+                sequence,
+                records: 0,
+                bytes: 0,
+                start: unix_time(&*self.clock),
+                end: 0,
+                compressed: self.compression.is_some(),
+            },
+            file,
+            opened_at: self.clock.now(),
+        })
+    }
+    
+    /// Finish the current segment, if there is one, and add it to the index,
+    /// so that the next record starts a new segment.
+    ///
+    /// If this fails, the segment is kept as the current one, so no records are lost,
+    /// and finishing it can be retried.
+    pub fn rotate(&mut self) -> io::Result<Option<SegmentInfo>> {
+        let segment = match &mut self.segment {
+            None => return Ok(None),
+            Some(segment) => segment,
+        };
+        segment.file.finish()?;
+        let info = SegmentInfo {
+            end: unix_time(&*self.clock),
+            ..segment.info
+        };
+        let mut index = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(self.dir.join(INDEX))?;
+        index.write_all(info.to_line().as_bytes())?;
+        index.sync_data()?;
+        self.segment = None;
+        Ok(Some(info))
+    }
+    
+    /// Append an [`EventRecord`], rotating first if needed.
+    pub fn append(&mut self, record: &EventRecord) -> io::Result<()> {
+        if let Some(segment) = &self.segment {
+            if self.should_rotate(segment) {
+                self.rotate()?;
+            }
+        }
+        let segment = match self.segment.take() {
+            Some(segment) => segment,
+            None => self.start_segment()?,
+        };
+        let segment = self.segment.get_or_insert(segment);
+        self.encoded.clear();
+        compact::encode(record, &mut self.encoded);
+        segment.file.write_all(&self.encoded)?;
+        segment.info.records += 1;
+        segment.info.bytes += self.encoded.len() as u64;
+        Ok(())
+    }
+    
+    /// Write the current batch to the current segment.
+    ///
+    /// The segment still isn't visible to an [`ArchiveReader`] until it's [rotated](Self::rotate).
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.segment {
+            None => Ok(()),
+            Some(segment) => segment.file.flush(),
+        }
+    }
+    
+    /// Finish the current segment, reporting any errors, unlike dropping.
+    pub fn close(mut self) -> io::Result<Option<SegmentInfo>> {
+        self.rotate()
+    }
+}
+
+impl Drop for ArchiveWriter {
+    fn drop(&mut self) {
+        let _ = self.rotate();
+    }
+}
+
+/// Records every [`Event`] with its path.
+impl Sink for ArchiveWriter {
+    fn consume(&mut self, event: &Event<'_>) -> Result<(), SinkError> {
+        self.append(&EventRecord::new(event, true))?;
+        Ok(())
+    }
+}
+
+/// Reads the finished segments of an archive written by an [`ArchiveWriter`].
+#[derive(Debug)]
+pub struct ArchiveReader {
+    dir: PathBuf,
+    segments: Vec<SegmentInfo>,
+}
+
+impl ArchiveReader {
+    /// Open the archive directory `dir` and read its index.
+    ///
+    /// An archive without an index (i.e., no finished segments yet) is empty.
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let index = match fs::read_to_string(dir.join(INDEX)) {
+            Ok(index) => index,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let segments = index
+            .lines()
+            .map(|line| SegmentInfo::from_line(line).ok_or_else(|| invalid(format!("invalid index line: {:?}", line))))
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Self { dir, segments })
+    }
+    
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+    
+    /// The finished segments, oldest first.
+    pub fn segments(&self) -> &[SegmentInfo] {
+        &self.segments
+    }
+    
+    /// The segments that overlap the time range `start..=end` (in seconds since the Unix epoch).
+    pub fn segments_between(&self, start: u64, end: u64) -> impl Iterator<Item = &SegmentInfo> {
+        self.segments
+            .iter()
+            .filter(move |it| it.start <= end && it.end >= start)
+    }
+    
+    /// Read all of the [`EventRecord`]s in a segment.
+    ///
+    /// Reading a [compressed](SegmentInfo::compressed) segment requires the `zstd` feature.
+    pub fn read_segment(&self, segment: &SegmentInfo) -> io::Result<Vec<EventRecord>> {
+        let bytes = fs::read(self.dir.join(SegmentInfo::file_name(segment.sequence)))?;
+        let bytes = match segment.compressed {
+            false => bytes,
+            #[cfg(feature = "zstd")]
+            true => zstd::decode_all(bytes.as_slice())?,
+            #[cfg(not(feature = "zstd"))]
+            true => return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("segment {} is compressed, which requires the zstd feature", segment.sequence),
+            )),
+        };
+        let records = compact::decode_all(&bytes).map_err(|e| invalid(e.to_string()))?;
+        if records.len() as u64 != segment.records {
+            return Err(invalid(format!(
+                "segment {} has {} records, but the index says {}",
+                segment.sequence,
+                records.len(),
+                segment.records,
+            )));
+        }
+        Ok(records)
+    }
+    
+    /// Iterate over all of the [`EventRecord`]s in the archive, oldest first,
+    /// reading one segment at a time.
+    pub fn records(&self) -> impl Iterator<Item = io::Result<EventRecord>> + '_ {
+        self.segments
+            .iter()
+            .flat_map(move |segment| match self.read_segment(segment) {
+                Ok(records) => records.into_iter().map(Ok).collect::<Vec<_>>(),
+                Err(e) => vec![Err(e)],
+            })
+    }
+}
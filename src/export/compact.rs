@@ -0,0 +1,176 @@
+//! A compact binary encoding of [`EventRecord`]s,
+//! for exporters where the overhead of JSON (or of a fixed-size [`EventRecord`]) matters.
+//!
+//! Each record is wrapped in a versioned envelope:
+//! the [`MAGIC`] byte, the [`VERSION`] byte, and the varint length of the body.
+//! The body is a sequence of varints (LEB128, with the `pid` zigzag-encoded):
+//! `mask`, `flags`, then `device` and `inode` if [`HAS_IDENTITY`](EventRecord::HAS_IDENTITY),
+//! then `pid`, and then the length and bytes of the path if [`HAS_PATH`](EventRecord::HAS_PATH).
+//! Paths are kept as raw bytes, so unlike JSON, this is lossless.
+//!
+//! A decoder skips any bytes left in a body it doesn't understand,
+//! so fields can be appended to the body without bumping the [`VERSION`].
+//!
+//! A typical record without a path takes about a dozen bytes,
+//! versus 256 for an [`EventRecord`] and about 60 for its JSON.
+
+use std::convert::TryFrom;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use crate::event::event::Event;
+use crate::event::owned::OwnedEvent;
+
+use super::EventRecord;
+
+/// The first byte of every envelope.
+pub const MAGIC: u8 = 0xFA;
+
+/// The version of the encoding, the second byte of every envelope.
+pub const VERSION: u8 = 1;
+
+#[derive(thiserror::Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DecodeError {
+    #[error("compact encoding is truncated")]
+    Truncated,
+    #[error("expected magic byte {:#x}, but found {:#x}", MAGIC, .0)]
+    WrongMagic(u8),
+    #[error("unsupported compact encoding version {} (expected {})", .0, VERSION)]
+    UnsupportedVersion(u8),
+    #[error("varint overflows its field")]
+    Overflow,
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn zigzag(value: i32) -> u64 {
+    ((value << 1) ^ (value >> 31)) as u32 as u64
+}
+
+fn unzigzag(value: u32) -> i32 {
+    (value >> 1) as i32 ^ -((value & 1) as i32)
+}
+
+/// Encode an [`EventRecord`] in an envelope, appending it to `out`.
+pub fn encode(record: &EventRecord, out: &mut Vec<u8>) {
+    let mut body = Vec::with_capacity(32);
+    put_varint(&mut body, record.mask);
+    put_varint(&mut body, record.flags as u64);
+    if let Some((device, inode)) = record.identity() {
+        put_varint(&mut body, device);
+        put_varint(&mut body, inode);
+    }
+    put_varint(&mut body, zigzag(record.pid));
+    if let Some(path) = record.path() {
+        let path = path.as_os_str().as_bytes();
+        put_varint(&mut body, path.len() as u64);
+        body.extend_from_slice(path);
+    }
+    out.push(MAGIC);
+    out.push(VERSION);
+    put_varint(out, body.len() as u64);
+    out.extend_from_slice(&body);
+}
+
+/// [Record](EventRecord::new) and [`encode`] an [`Event`].
+pub fn encode_event(event: &Event<'_>, with_path: bool, out: &mut Vec<u8>) {
+    encode(&EventRecord::new(event, with_path), out)
+}
+
+/// [Record](EventRecord::from_owned) and [`encode`] an [`OwnedEvent`].
+pub fn encode_owned(event: &OwnedEvent, with_path: bool, out: &mut Vec<u8>) {
+    encode(&EventRecord::from_owned(event, with_path), out)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        let (&first, rest) = self.bytes.split_first().ok_or(DecodeError::Truncated)?;
+        self.bytes = rest;
+        Ok(first)
+    }
+    
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if len > self.bytes.len() {
+            return Err(DecodeError::Truncated);
+        }
+        let (first, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(first)
+    }
+    
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            let bits = (byte & 0x7F) as u64;
+            if shift == 63 && bits > 1 {
+                return Err(DecodeError::Overflow);
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::Overflow)
+    }
+    
+    fn varint_as<T: TryFrom<u64>>(&mut self) -> Result<T, DecodeError> {
+        T::try_from(self.varint()?).map_err(|_| DecodeError::Overflow)
+    }
+}
+
+/// Decode the first [`EventRecord`] in `bytes`,
+/// returning it and the number of bytes its envelope took up.
+///
+/// A path longer than [`EventRecord::PATH_CAPACITY`] is truncated.
+pub fn decode(bytes: &[u8]) -> Result<(EventRecord, usize), DecodeError> {
+    let mut envelope = Reader { bytes };
+    match envelope.byte()? {
+        MAGIC => {}
+        magic => return Err(DecodeError::WrongMagic(magic)),
+    }
+    match envelope.byte()? {
+        VERSION => {}
+        version => return Err(DecodeError::UnsupportedVersion(version)),
+    }
+    let len = envelope.varint_as()?;
+    let mut body = Reader { bytes: envelope.bytes(len)? };
+    let consumed = bytes.len() - envelope.bytes.len();
+    let mut record = EventRecord::empty();
+    record.mask = body.varint()?;
+    let flags: u16 = body.varint_as()?;
+    if flags & EventRecord::HAS_IDENTITY != 0 {
+        record.device = body.varint()?;
+        record.inode = body.varint()?;
+    }
+    record.pid = unzigzag(body.varint_as()?);
+    if flags & EventRecord::HAS_PATH != 0 {
+        let len = body.varint_as()?;
+        record.set_path(Path::new(OsStr::from_bytes(body.bytes(len)?)));
+    }
+    // after `set_path`, so that a path truncated by the encoder stays `IS_PATH_TRUNCATED`
+    record.flags |= flags;
+    Ok((record, consumed))
+}
+
+/// Decode all of the [`EventRecord`]s in `bytes`, which must end on an envelope boundary.
+pub fn decode_all(mut bytes: &[u8]) -> Result<Vec<EventRecord>, DecodeError> {
+    let mut records = Vec::new();
+    while !bytes.is_empty() {
+        let (record, consumed) = decode(bytes)?;
+        records.push(record);
+        bytes = &bytes[consumed..];
+    }
+    Ok(records)
+}
@@ -0,0 +1,232 @@
+//! A JSON encoding of [`EventRecord`]s, as used by the `server` protocol and alerts.
+//!
+//! Each [`EventRecord`] is a flat object with the same fields, like
+//! `{"mask":32,"device":2049,"inode":1234,"pid":42,"flags":3,"path":"/etc/passwd"}`,
+//! where `path` is omitted if the record has none.
+//! Paths that aren't valid UTF-8 are converted lossily.
+
+use std::fmt;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::export::EventRecord;
+
+#[derive(thiserror::Error, Debug, Eq, PartialEq)]
+pub enum DecodeError {
+    #[error("unexpected end of JSON input")]
+    UnexpectedEnd,
+    #[error("unexpected {:?} at byte {}", .found, .at)]
+    Unexpected { found: char, at: usize },
+    #[error("invalid JSON value for {:?}", .field)]
+    InvalidValue { field: String },
+    #[error("invalid JSON string escape at byte {}", .at)]
+    InvalidEscape { at: usize },
+    #[error("missing field {:?}", .field)]
+    MissingField { field: &'static str },
+}
+
+/// Write `s` to `out` as a JSON string, escaping it as necessary.
+///
+/// This is shared with the [`Json`](crate::event::display::Format::Json) display format.
+pub(crate) fn write_escaped(out: &mut impl fmt::Write, s: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+/// Append `s` to `out` as a JSON string.
+pub(crate) fn escape_into(s: &str, out: &mut String) {
+    // writing to a String can't fail
+    let _ = write_escaped(out, s);
+}
+
+/// Encode an [`EventRecord`] as a JSON object.
+pub fn encode(record: &EventRecord) -> String {
+    let mut out = String::with_capacity(128);
+    let _ = write!(
+        out,
+        r#"{{"mask":{},"device":{},"inode":{},"pid":{},"flags":{}"#,
+        record.mask,
+        record.device,
+        record.inode,
+        record.pid,
+        record.flags,
+    );
+    if let Some(path) = record.path() {
+        out.push_str(r#","path":"#);
+        escape_into(&path.to_string_lossy(), &mut out);
+    }
+    out.push('}');
+    out
+}
+
+enum Value {
+    Number(String),
+    String(String),
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    at: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.at..].chars().next()
+    }
+    
+    fn next(&mut self) -> Result<char, DecodeError> {
+        let c = self.peek().ok_or(DecodeError::UnexpectedEnd)?;
+        self.at += c.len_utf8();
+        Ok(c)
+    }
+    
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.at += 1;
+        }
+    }
+    
+    fn expect(&mut self, expected: char) -> Result<(), DecodeError> {
+        self.skip_whitespace();
+        let at = self.at;
+        match self.next()? {
+            c if c == expected => Ok(()),
+            found => Err(DecodeError::Unexpected { found, at }),
+        }
+    }
+    
+    fn hex4(&mut self) -> Result<u32, DecodeError> {
+        let at = self.at;
+        let digits = self.input.get(at..at + 4).ok_or(DecodeError::UnexpectedEnd)?;
+        let value = u32::from_str_radix(digits, 16).map_err(|_| DecodeError::InvalidEscape { at })?;
+        self.at += 4;
+        Ok(value)
+    }
+    
+    fn string(&mut self) -> Result<String, DecodeError> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            let at = self.at;
+            match self.next()? {
+                '"' => return Ok(s),
+                '\\' => {
+                    let c = match self.next()? {
+                        '"' => '"',
+                        '\\' => '\\',
+                        '/' => '/',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => {
+                            let mut code = self.hex4()?;
+                            if (0xD800..0xDC00).contains(&code) {
+                                // a surrogate pair
+                                self.expect('\\')?;
+                                self.expect('u')?;
+                                let low = self.hex4()?;
+                                if !(0xDC00..0xE000).contains(&low) {
+                                    return Err(DecodeError::InvalidEscape { at });
+                                }
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                            }
+                            char::from_u32(code).ok_or(DecodeError::InvalidEscape { at })?
+                        }
+                        _ => return Err(DecodeError::InvalidEscape { at }),
+                    };
+                    s.push(c);
+                }
+                c => s.push(c),
+            }
+        }
+    }
+    
+    fn value(&mut self) -> Result<Value, DecodeError> {
+        self.skip_whitespace();
+        match self.peek() {
+            None => Err(DecodeError::UnexpectedEnd),
+            Some('"') => self.string().map(Value::String),
+            Some(_) => {
+                let start = self.at;
+                while let Some('-' | '+' | '.' | 'e' | 'E' | '0'..='9') = self.peek() {
+                    self.at += 1;
+                }
+                if start == self.at {
+                    let found = self.next()?;
+                    return Err(DecodeError::Unexpected { found, at: start });
+                }
+                Ok(Value::Number(self.input[start..self.at].to_owned()))
+            }
+        }
+    }
+}
+
+fn number<T: std::str::FromStr>(field: &str, value: Value) -> Result<T, DecodeError> {
+    let invalid = || DecodeError::InvalidValue { field: field.to_owned() };
+    match value {
+        Value::Number(n) => n.parse().map_err(|_| invalid()),
+        Value::String(_) => Err(invalid()),
+    }
+}
+
+/// Decode an [`EventRecord`] from a JSON object, ignoring unknown fields.
+///
+/// A path longer than [`EventRecord::PATH_CAPACITY`] is truncated.
+pub fn decode(input: &str) -> Result<EventRecord, DecodeError> {
+    let mut parser = Parser { input, at: 0 };
+    let mut record = EventRecord::empty();
+    let mut has_mask = false;
+    let mut flags = 0;
+    parser.expect('{')?;
+    parser.skip_whitespace();
+    if parser.peek() == Some('}') {
+        return Err(DecodeError::MissingField { field: "mask" });
+    }
+    loop {
+        let key = parser.string()?;
+        parser.expect(':')?;
+        let value = parser.value()?;
+        match key.as_str() {
+            "mask" => {
+                record.mask = number(&key, value)?;
+                has_mask = true;
+            }
+            "device" => record.device = number(&key, value)?,
+            "inode" => record.inode = number(&key, value)?,
+            "pid" => record.pid = number(&key, value)?,
+            // `HAS_PATH` depends on if there's a `path`
+            "flags" => flags = number::<u16>(&key, value)? & !EventRecord::HAS_PATH,
+            "path" => match value {
+                Value::String(path) => record.set_path(Path::new(&path)),
+                Value::Number(_) => return Err(DecodeError::InvalidValue { field: key }),
+            },
+            _ => {}
+        }
+        parser.skip_whitespace();
+        let at = parser.at;
+        match parser.next()? {
+            ',' => parser.skip_whitespace(),
+            '}' => break,
+            found => return Err(DecodeError::Unexpected { found, at }),
+        }
+    }
+    if !has_mask {
+        return Err(DecodeError::MissingField { field: "mask" });
+    }
+    // after `set_path`, so that a path truncated by the encoder stays `IS_PATH_TRUNCATED`
+    record.flags |= flags;
+    Ok(record)
+}
@@ -0,0 +1,7 @@
+pub use record::EventRecord;
+
+pub mod archive;
+pub mod compact;
+pub mod json;
+pub mod record;
+pub mod ring;
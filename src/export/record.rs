@@ -0,0 +1,188 @@
+use std::ffi::OsStr;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::mem::size_of;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use static_assertions::const_assert_eq;
+
+use crate::event::event::Event;
+use crate::event::event::EventOf;
+use crate::event::file::File;
+use crate::event::file::resolved::FileResolved;
+use crate::event::owned::OwnedEvent;
+use crate::event::owned::OwnedFile;
+use crate::fd::FD;
+use crate::mark::Mask;
+
+/// A fixed-size, plain-old-data record of an [`Event`],
+/// for exporting [`Event`]s to other processes, like through a [`ring`](super::ring) buffer.
+///
+/// Its layout is `repr(C)` and it contains no pointers, so it can be copied byte for byte.
+#[derive(Copy, Clone, Eq, PartialEq)]
+#[repr(C)]
+pub struct EventRecord {
+    /// The raw bits of the [`Mask`].
+    pub mask: u64,
+    /// The device of the file, if [`EventRecord::HAS_IDENTITY`].
+    pub device: u64,
+    /// The inode of the file, if [`EventRecord::HAS_IDENTITY`].
+    pub inode: u64,
+    /// The process or thread id that caused the [`Event`].
+    pub pid: i32,
+    /// The `HAS_*` and `IS_*` flags.
+    pub flags: u16,
+    /// The number of bytes of [`EventRecord::path`] that are used.
+    pub path_len: u16,
+    /// The path of the file, if it was resolved, truncated to [`EventRecord::PATH_CAPACITY`] bytes.
+    pub path: [u8; EventRecord::PATH_CAPACITY],
+}
+
+const_assert_eq!(size_of::<EventRecord>(), EventRecord::SIZE);
+
+impl EventRecord {
+    /// The size of every [`EventRecord`] in bytes.
+    pub const SIZE: usize = 256;
+    
+    /// The maximum number of bytes of a recorded path.
+    pub const PATH_CAPACITY: usize = Self::SIZE - 32;
+    
+    /// [`EventRecord::device`] and [`EventRecord::inode`] are set.
+    pub const HAS_IDENTITY: u16 = 1 << 0;
+    /// [`EventRecord::path`] is set.
+    pub const HAS_PATH: u16 = 1 << 1;
+    /// [`EventRecord::path`] was truncated.
+    pub const IS_PATH_TRUNCATED: u16 = 1 << 2;
+    /// The [`Event`] was a permission event.
+    pub const IS_PERMISSION: u16 = 1 << 3;
+    /// The [`Event`] was a [`REPORT_FID`](crate::init::Flags::REPORT_FID) event.
+    pub const IS_FID: u16 = 1 << 4;
+    /// The [`Event`] was generated by the process that read it.
+    pub const IS_GENERATED_BY_SELF: u16 = 1 << 5;
+    
+    /// An empty [`EventRecord`].
+    pub const fn empty() -> Self {
+        Self {
+            mask: 0,
+            device: 0,
+            inode: 0,
+            pid: 0,
+            flags: 0,
+            path_len: 0,
+            path: [0; Self::PATH_CAPACITY],
+        }
+    }
+    
+    fn of<FileT>(event: &EventOf<FileT>, variant: u16, fd: Option<&FD>, with_path: bool) -> Self {
+        let mut this = Self::empty();
+        this.mask = event.mask().bits();
+        let id = event.id();
+        this.pid = id.pid().or_else(|| id.tid()).map_or(0, |it| it.as_raw());
+        this.flags |= variant;
+        if id.is_generated_by_self() {
+            this.flags |= Self::IS_GENERATED_BY_SELF;
+        }
+        if let Some(Ok((device, inode))) = fd.map(|fd| fd.identity()) {
+            this.device = device;
+            this.inode = inode;
+            this.flags |= Self::HAS_IDENTITY;
+        }
+        if with_path {
+            if let Some(Ok(path)) = fd.map(|fd| fd.path()) {
+                this.set_path(&path);
+            }
+        }
+        this
+    }
+    
+    /// Record an [`Event`], also resolving its path if `with_path`,
+    /// which costs a `readlink` per [`Event`].
+    pub fn new(event: &Event<'_>, with_path: bool) -> Self {
+        let variant = match event.file() {
+            File::FD(_) => 0,
+            File::FID(_) => Self::IS_FID,
+            File::Permission(_) => Self::IS_PERMISSION,
+            File::Resolved(file) => return Self::of(event, 0, None, false).with_resolved(file, with_path),
+        };
+        Self::of(event, variant, event.file().get_fd(), with_path)
+    }
+    
+    /// Record an [`OwnedEvent`], like [`EventRecord::new`].
+    pub fn from_owned(event: &OwnedEvent, with_path: bool) -> Self {
+        let variant = match event.file() {
+            OwnedFile::FD(_) => 0,
+            OwnedFile::FID(_) => Self::IS_FID,
+            OwnedFile::Permission(_) => Self::IS_PERMISSION,
+            OwnedFile::Resolved(file) => return Self::of(event, 0, None, false).with_resolved(file, with_path),
+        };
+        Self::of(event, variant, event.file().get_fd(), with_path)
+    }
+    
+    /// Fill in what was resolved before a [`FileResolved`]'s fd was closed.
+    fn with_resolved(mut self, file: &FileResolved, with_path: bool) -> Self {
+        if let Some((device, inode)) = file.identity() {
+            self.device = device;
+            self.inode = inode;
+            self.flags |= Self::HAS_IDENTITY;
+        }
+        if with_path {
+            if let Some(path) = file.path() {
+                self.set_path(path);
+            }
+        }
+        self
+    }
+    
+    /// Set [`EventRecord::path`], truncating it if it's too long.
+    pub fn set_path(&mut self, path: &Path) {
+        let bytes = path.as_os_str().as_bytes();
+        let len = bytes.len().min(Self::PATH_CAPACITY);
+        self.path[..len].copy_from_slice(&bytes[..len]);
+        self.path[len..].iter_mut().for_each(|it| *it = 0);
+        self.path_len = len as u16;
+        self.flags |= Self::HAS_PATH;
+        if len < bytes.len() {
+            self.flags |= Self::IS_PATH_TRUNCATED;
+        } else {
+            self.flags &= !Self::IS_PATH_TRUNCATED;
+        }
+    }
+    
+    pub fn mask(&self) -> Mask {
+        Mask::from_bits_truncate(self.mask)
+    }
+    
+    /// The `(device, inode)` of the file, if it was recorded.
+    pub fn identity(&self) -> Option<(u64, u64)> {
+        Some((self.device, self.inode)).filter(|_| self.flags & Self::HAS_IDENTITY != 0)
+    }
+    
+    /// The (possibly truncated) path of the file, if it was recorded.
+    pub fn path(&self) -> Option<&Path> {
+        if self.flags & Self::HAS_PATH == 0 {
+            return None;
+        }
+        let len = (self.path_len as usize).min(Self::PATH_CAPACITY);
+        Some(Path::new(OsStr::from_bytes(&self.path[..len])))
+    }
+}
+
+impl Default for EventRecord {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl Debug for EventRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventRecord")
+            .field("mask", &self.mask())
+            .field("identity", &self.identity())
+            .field("pid", &self.pid)
+            .field("flags", &self.flags)
+            .field("path", &self.path())
+            .finish()
+    }
+}
@@ -0,0 +1,283 @@
+use std::convert::TryFrom;
+use std::ffi::OsString;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::mem::size_of;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::process;
+use std::ptr;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use static_assertions::const_assert_eq;
+
+use crate::event::event::Event;
+
+use super::EventRecord;
+
+/// The header at the start of a ring buffer file.
+///
+/// The producer only writes `head` and the consumer only writes `tail`,
+/// so they're on separate cache lines.
+#[repr(C, align(64))]
+struct Header {
+    magic: AtomicU64,
+    version: u32,
+    record_size: u32,
+    capacity: u64,
+    dropped: AtomicU64,
+    _pad0: [u8; 32],
+    head: AtomicU64,
+    _pad1: [u8; 56],
+    tail: AtomicU64,
+    _pad2: [u8; 56],
+}
+
+const_assert_eq!(size_of::<Header>(), 3 * 64);
+
+const MAGIC: u64 = u64::from_le_bytes(*b"fanoring");
+
+/// The version of the ring buffer file layout.
+pub const VERSION: u32 = 1;
+
+/// A shared memory mapping of a ring buffer file.
+struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+    /// The capacity validated when the file was opened or created,
+    /// since the other process could change the one in the [`Header`].
+    capacity: u64,
+}
+
+// the atomics in the header synchronize access to the records
+unsafe impl Send for Mapping {}
+
+impl Mapping {
+    fn new(file: &fs::File, len: usize) -> io::Result<Self> {
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            ptr: ptr as *mut u8,
+            len,
+            capacity: 0,
+        })
+    }
+    
+    fn header(&self) -> &Header {
+        unsafe { &*(self.ptr as *const Header) }
+    }
+    
+    fn record(&self, index: u64) -> *mut EventRecord {
+        let offset = size_of::<Header>() + (index % self.capacity) as usize * EventRecord::SIZE;
+        unsafe { self.ptr.add(offset) as *mut EventRecord }
+    }
+    
+    /// The number of records between `tail` and `head`,
+    /// or [`None`] if they're inconsistent, which a misbehaving process sharing the file could cause.
+    fn used(&self, head: u64, tail: u64) -> Option<u64> {
+        Some(head.wrapping_sub(tail)).filter(|it| *it <= self.capacity)
+    }
+    
+    fn len_for(capacity: u64) -> Option<usize> {
+        usize::try_from(capacity)
+            .ok()?
+            .checked_mul(EventRecord::SIZE)?
+            .checked_add(size_of::<Header>())
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len) };
+    }
+}
+
+/// The producer side of a single-producer, single-consumer ring buffer of [`EventRecord`]s
+/// in a memory-mapped file, which a separate process can consume with a [`RingReader`].
+///
+/// This decouples reading [`Event`]s from the kernel, which should be fast
+/// (especially for permission events), from heavy downstream processing.
+/// When the consumer falls behind and the ring is full, new records are dropped and counted,
+/// rather than blocking the producer.
+///
+/// The file should be on a memory-backed filesystem like `/dev/shm` to avoid disk writeback.
+pub struct RingWriter {
+    mapping: Mapping,
+}
+
+impl RingWriter {
+    /// Create (or replace) the ring buffer file at `path` with room for `capacity` records.
+    ///
+    /// The new file is initialized under a temporary name and then renamed to `path`,
+    /// so a [`RingReader`] never sees it partially initialized,
+    /// and one that still has a replaced file mapped isn't affected.
+    pub fn create(path: impl AsRef<Path>, capacity: u64) -> io::Result<Self> {
+        let path = path.as_ref();
+        let len = match Mapping::len_for(capacity) {
+            Some(len) if capacity != 0 => len,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid ring buffer capacity")),
+        };
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "ring buffer path has no file name"))?;
+        let mut temp_name = OsString::from(".");
+        temp_name.push(file_name);
+        temp_name.push(format!(".{}.tmp", process::id()));
+        let temp_path = path.with_file_name(temp_name);
+        let mapping = Self::create_at(&temp_path, len, capacity).and_then(|mapping| {
+            fs::rename(&temp_path, path)?;
+            Ok(mapping)
+        });
+        if mapping.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        Ok(Self { mapping: mapping? })
+    }
+    
+    fn create_at(path: &Path, len: usize, capacity: u64) -> io::Result<Mapping> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        file.set_len(len as u64)?;
+        let mut mapping = Mapping::new(&file, len)?;
+        mapping.capacity = capacity;
+        // the file was just zeroed, so the atomics are already 0
+        unsafe {
+            let header = mapping.ptr as *mut Header;
+            (*header).version = VERSION;
+            (*header).record_size = EventRecord::SIZE as u32;
+            (*header).capacity = capacity;
+        }
+        // publish the magic last, so a reader never sees a partially initialized header
+        mapping.header().magic.store(MAGIC, Ordering::Release);
+        Ok(mapping)
+    }
+    
+    pub fn capacity(&self) -> u64 {
+        self.mapping.capacity
+    }
+    
+    /// The number of records not consumed yet.
+    pub fn len(&self) -> u64 {
+        let header = self.mapping.header();
+        let head = header.head.load(Ordering::Relaxed);
+        let tail = header.tail.load(Ordering::Acquire);
+        self.mapping.used(head, tail).unwrap_or(self.mapping.capacity)
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    
+    /// The number of records dropped because the ring was full.
+    pub fn dropped(&self) -> u64 {
+        self.mapping.header().dropped.load(Ordering::Relaxed)
+    }
+    
+    /// Push a record, returning `false` (and counting it as [dropped](Self::dropped)) if the ring is full.
+    ///
+    /// The ring is also treated as full if the consumer corrupted its position.
+    pub fn push(&mut self, record: &EventRecord) -> bool {
+        let header = self.mapping.header();
+        let head = header.head.load(Ordering::Relaxed);
+        let tail = header.tail.load(Ordering::Acquire);
+        if self.mapping.used(head, tail).map_or(true, |it| it >= self.mapping.capacity) {
+            header.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        unsafe { ptr::write_volatile(self.mapping.record(head), *record) };
+        header.head.store(head.wrapping_add(1), Ordering::Release);
+        true
+    }
+    
+    /// [Record](EventRecord::new) and [push](Self::push) an [`Event`].
+    pub fn export(&mut self, event: &Event<'_>, with_path: bool) -> bool {
+        self.push(&EventRecord::new(event, with_path))
+    }
+}
+
+/// The consumer side of a [`RingWriter`]'s ring buffer, possibly in another process.
+pub struct RingReader {
+    mapping: Mapping,
+}
+
+impl RingReader {
+    /// Open the ring buffer file at `path`, which must've been created by [`RingWriter::create`].
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_owned());
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)?;
+        let len = file.metadata()?.len() as usize;
+        if len < size_of::<Header>() {
+            return Err(invalid("ring buffer file is too short"));
+        }
+        let mut mapping = Mapping::new(&file, len)?;
+        let header = mapping.header();
+        if header.magic.load(Ordering::Acquire) != MAGIC {
+            return Err(invalid("not a ring buffer file"));
+        }
+        if header.version != VERSION || header.record_size as usize != EventRecord::SIZE {
+            return Err(invalid("unsupported ring buffer version"));
+        }
+        let capacity = header.capacity;
+        match Mapping::len_for(capacity) {
+            Some(needed) if capacity != 0 && needed <= len => {}
+            _ => return Err(invalid("ring buffer file is too short")),
+        }
+        mapping.capacity = capacity;
+        Ok(Self { mapping })
+    }
+    
+    pub fn capacity(&self) -> u64 {
+        self.mapping.capacity
+    }
+    
+    /// The number of records available to [`RingReader::pop`].
+    pub fn len(&self) -> u64 {
+        let header = self.mapping.header();
+        let head = header.head.load(Ordering::Acquire);
+        let tail = header.tail.load(Ordering::Relaxed);
+        self.mapping.used(head, tail).unwrap_or(0)
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    
+    /// See [`RingWriter::dropped`].
+    pub fn dropped(&self) -> u64 {
+        self.mapping.header().dropped.load(Ordering::Relaxed)
+    }
+    
+    /// Pop the oldest record, if there is one.
+    ///
+    /// There is none if the producer corrupted its position.
+    pub fn pop(&mut self) -> Option<EventRecord> {
+        let header = self.mapping.header();
+        let tail = header.tail.load(Ordering::Relaxed);
+        let head = header.head.load(Ordering::Acquire);
+        if self.mapping.used(head, tail).map_or(true, |it| it == 0) {
+            return None;
+        }
+        let record = unsafe { ptr::read_volatile(self.mapping.record(tail)) };
+        header.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Some(record)
+    }
+}
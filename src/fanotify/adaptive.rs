@@ -0,0 +1,201 @@
+use std::io;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::event::events::Events;
+use crate::event::sequence::SequenceCounter;
+use crate::init;
+use crate::init::Flags;
+use crate::mark;
+use crate::mark::Mark;
+use crate::mark::MarkRegistry;
+use crate::mark::ReapplyReport;
+
+use super::buffered_fanotify::BufferedFanotify;
+use super::fd_limit::FdUsage;
+use super::Fanotify;
+
+/// A record of an [`AdaptiveFanotify`] switching to FID mode.
+#[derive(Debug)]
+pub struct FidSwitch {
+    /// The [`FdUsage`] that triggered the switch.
+    pub usage: FdUsage,
+    /// How re-applying the marks to the FID group went.
+    pub report: ReapplyReport,
+}
+
+/// A fanotify group that starts out reporting fds,
+/// but switches to a [`REPORT_FID`](Flags::REPORT_FID) group
+/// once this process's fd usage crosses a threshold.
+///
+/// Every fd-reporting event holds an fd until it's dropped,
+/// so a long-running monitor under load can exhaust its fd limit
+/// (see [`fd_limit`](super::fd_limit)), while FID events hold none.
+/// Marks are made through [`AdaptiveFanotify::mark`] so they're recorded in a [`MarkRegistry`]
+/// and can be re-applied to the FID group, which is fully set up before it replaces the fd group.
+///
+/// The switch is one-way, and events still queued in the fd group when it's replaced are lost.
+/// If the FID group can't be created (e.g. the kernel doesn't support FID,
+/// or the group is a permission group), the [`fid_error`](AdaptiveFanotify::fid_error) is kept,
+/// and it stays in fd mode without retrying.
+///
+/// Counting the fds in use means listing `/proc/self/fd`,
+/// so [`AdaptiveFanotify::read`] only does it once per [`AdaptiveFanotify::with_check_interval`].
+pub struct AdaptiveFanotify {
+    fanotify: BufferedFanotify,
+    registry: MarkRegistry,
+    threshold: f64,
+    check_interval: Duration,
+    last_check: Option<Instant>,
+    fid_flags: Flags,
+    switch: Option<FidSwitch>,
+    fid_error: Option<init::Error>,
+}
+
+impl AdaptiveFanotify {
+    /// The default fraction of the fd soft limit in use at which to switch to FID mode.
+    pub const DEFAULT_THRESHOLD: f64 = 0.8;
+    
+    /// The default minimum time between the fd usage checks done by [`AdaptiveFanotify::read`].
+    pub const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+    
+    pub fn new(fanotify: BufferedFanotify) -> Self {
+        Self {
+            fanotify,
+            registry: MarkRegistry::new(),
+            threshold: Self::DEFAULT_THRESHOLD,
+            check_interval: Self::DEFAULT_CHECK_INTERVAL,
+            last_check: None,
+            fid_flags: Flags::REPORT_FID,
+            switch: None,
+            fid_error: None,
+        }
+    }
+    
+    /// Set the [`FdUsage::utilization`] at which to switch to FID mode.
+    pub fn with_threshold(self, threshold: f64) -> Self {
+        Self {
+            threshold,
+            ..self
+        }
+    }
+    
+    /// Set the minimum time between the fd usage checks done by [`AdaptiveFanotify::read`],
+    /// [`AdaptiveFanotify::DEFAULT_CHECK_INTERVAL`] by default.
+    pub fn with_check_interval(self, check_interval: Duration) -> Self {
+        Self {
+            check_interval,
+            ..self
+        }
+    }
+    
+    /// Set the [`Flags`] added to the FID group's, [`REPORT_FID`](Flags::REPORT_FID) by default.
+    pub fn with_fid_flags(self, fid_flags: Flags) -> Self {
+        Self {
+            fid_flags,
+            ..self
+        }
+    }
+    
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+    
+    pub fn check_interval(&self) -> Duration {
+        self.check_interval
+    }
+    
+    pub fn fanotify(&self) -> &BufferedFanotify {
+        &self.fanotify
+    }
+    
+    pub fn into_fanotify(self) -> BufferedFanotify {
+        self.fanotify
+    }
+    
+    pub fn registry(&self) -> &MarkRegistry {
+        &self.registry
+    }
+    
+    /// If this has switched to FID mode.
+    pub fn is_fid(&self) -> bool {
+        self.switch.is_some()
+    }
+    
+    /// The switch to FID mode, if it's happened.
+    pub fn switch(&self) -> Option<&FidSwitch> {
+        self.switch.as_ref()
+    }
+    
+    /// Why switching to FID mode failed, if it did.
+    pub fn fid_error(&self) -> Option<&init::Error> {
+        self.fid_error.as_ref()
+    }
+    
+    /// Add a [`Mark`] to the current group and record it for the FID group.
+    pub fn mark<'a>(&mut self, mark: Mark<'a>) -> Result<(), mark::Error<'a>> {
+        self.registry.mark(&self.fanotify, mark)
+    }
+    
+    /// Switch to FID mode now, regardless of the fd usage,
+    /// unless it already has (or already failed to).
+    pub fn switch_to_fid(&mut self, usage: FdUsage) -> Result<Option<&FidSwitch>, &init::Error> {
+        if self.switch.is_none() && self.fid_error.is_none() {
+            match self.create_fid_group() {
+                Ok(fanotify) => {
+                    let report = self.registry.reapply(&fanotify);
+                    self.fanotify.fanotify = fanotify;
+                    self.switch = Some(FidSwitch {
+                        usage,
+                        report,
+                    });
+                }
+                Err(e) => self.fid_error = Some(e),
+            }
+        }
+        match &self.fid_error {
+            Some(e) => Err(e),
+            None => Ok(self.switch.as_ref()),
+        }
+    }
+    
+    fn create_fid_group(&self) -> Result<Fanotify, init::Error> {
+        let current = &self.fanotify.fanotify;
+        let mut init = current.init.undo_raw();
+        init.flags |= self.fid_flags;
+        let mut fanotify = init.to_fanotify()?;
+        fanotify.name = current.name.clone();
+        fanotify.flush_policy = current.flush_policy;
+        fanotify.precheck = current.precheck;
+        fanotify.sequence = SequenceCounter::continuing(&current.sequence);
+        Ok(fanotify)
+    }
+    
+    /// Check the current [`FdUsage`] and switch to FID mode if it's crossed the threshold.
+    pub fn check(&mut self) -> io::Result<Option<&FidSwitch>> {
+        if self.switch.is_some() || self.fid_error.is_some() {
+            return Ok(self.switch.as_ref());
+        }
+        let usage = FdUsage::current()?;
+        if usage.utilization() < self.threshold {
+            return Ok(None);
+        }
+        Ok(self.switch_to_fid(usage).ok().flatten())
+    }
+    
+    /// [`Check`](AdaptiveFanotify::check) the fd usage if it hasn't been for the [check interval](AdaptiveFanotify::with_check_interval),
+    /// and then [read](BufferedFanotify::read) events.
+    ///
+    /// If the fd usage can't be determined, it just reads.
+    pub fn read(&mut self) -> io::Result<Events<'_>> {
+        let now = Instant::now();
+        let due = self
+            .last_check
+            .map_or(true, |it| now.duration_since(it) >= self.check_interval);
+        if due {
+            self.last_check = Some(now);
+            let _ = self.check();
+        }
+        self.fanotify.read()
+    }
+}
@@ -1,23 +1,33 @@
+use std::future::Future;
+use std::future::poll_fn;
 use std::io;
+use std::pin::pin;
+use std::task::Poll;
 
 use async_io::Async;
 
 use crate::event::buffer::EventBuffer;
+use crate::event::buffer::EventBufferSize;
 use crate::event::events::Events;
+use crate::event::responses::write_responses;
+use crate::fanotify::async_fd::AsyncFdWrapper;
 use crate::fanotify::Fanotify;
 use crate::mark;
 use crate::mark::Mark;
 use crate::mark::Markable;
 
 /// An async version of [`Fanotify`].
-pub struct AsyncFanotify {
-    inner: Async<Fanotify>,
+///
+/// It is generic over the [`AsyncFdWrapper`] `W` used to wait for events,
+/// which defaults to `async-io`'s [`Async`].
+pub struct AsyncFanotify<W: AsyncFdWrapper = Async<Fanotify>> {
+    inner: W,
 }
 
-impl AsyncFanotify {
+impl<W: AsyncFdWrapper> AsyncFanotify<W> {
     pub fn new(fanotify: Fanotify) -> io::Result<Self> {
         let this = Self {
-            inner: Async::new(fanotify)?,
+            inner: W::new(fanotify)?,
         };
         Ok(this)
     }
@@ -27,9 +37,14 @@ impl Fanotify {
     pub fn into_async(self) -> io::Result<AsyncFanotify> {
         AsyncFanotify::new(self)
     }
+    
+    /// Like [`Fanotify::into_async`], but using a specific [`AsyncFdWrapper`].
+    pub fn into_async_with<W: AsyncFdWrapper>(self) -> io::Result<AsyncFanotify<W>> {
+        AsyncFanotify::new(self)
+    }
 }
 
-impl AsyncFanotify {
+impl<W: AsyncFdWrapper> AsyncFanotify<W> {
     pub fn fanotify(&self) -> &Fanotify {
         self.inner.get_ref()
     }
@@ -51,13 +66,17 @@ impl AsyncFanotify {
     }
 }
 
-impl Markable for AsyncFanotify {
+impl<W: AsyncFdWrapper> Markable for AsyncFanotify<W> {
     fn mark<'a>(&self, mark: Mark<'a>) -> Result<(), mark::Error<'a>> {
         self.fanotify().mark(mark)
     }
+    
+    fn check<'a>(&self, mark: Mark<'a>) -> Result<Mark<'a>, mark::Error<'a>> {
+        self.fanotify().check(mark)
+    }
 }
 
-impl AsyncFanotify {
+impl<W: AsyncFdWrapper> AsyncFanotify<W> {
     /// Read file events from the wrapped [`Fanotify`] group into the given buffer.
     ///
     /// Return an [`Events`] iterator over the individual events.
@@ -73,9 +92,88 @@ impl AsyncFanotify {
     /// or else [`Errno::EAGAIN`](nix::errno::Errno::EAGAIN) will be thrown.
     /// This likely won't happen though,
     /// since writing permission responses to a fanotify file descriptor shouldn't normally block.
+    ///
+    /// Buffered responses are normally only written once the whole batch of events is dropped,
+    /// which can take a while if processing them awaits in between.
+    /// Set a [`FlushPolicy`](crate::event::responses::FlushPolicy) to write them sooner.
     pub async fn read<'a>(&'a self, buffer: &'a mut EventBuffer) -> io::Result<Events<'a>> {
-        self.inner.readable().await?;
-        let events = self.fanotify().read(buffer)?;
-        Ok(events)
+        let events = &mut buffer.events;
+        poll_fn(|cx| {
+            self.inner.poll_read_with(cx, &mut |fanotify| {
+                Events::read_raw(fanotify, events).map_err(io::Error::from)
+            })
+        }).await?;
+        Ok(Events::from_buffer(self.fanotify(), buffer))
+    }
+    
+    /// Like [`AsyncFanotify::read`], but after the first read,
+    /// keep reading without blocking until no more events are pending.
+    /// See [`Fanotify::read_all_pending`].
+    pub async fn read_all_pending<'a>(&'a self, buffer: &'a mut EventBuffer, max_bytes: usize) -> io::Result<Events<'a>> {
+        let chunk = buffer.events.capacity().max(EventBufferSize::default().events);
+        let events = &mut buffer.events;
+        poll_fn(|cx| {
+            self.inner.poll_read_with(cx, &mut |fanotify| {
+                Events::read_raw(fanotify, events).map_err(io::Error::from)
+            })
+        }).await?;
+        Events::read_pending_raw(self.fanotify(), events, chunk, max_bytes)?;
+        Ok(Events::from_buffer(self.fanotify(), buffer))
     }
-}
\ No newline at end of file
+    
+    /// Like [`AsyncFanotify::read`], but races the read against the `cancel` future,
+    /// e.g. a `CancellationToken::cancelled()` future or a timeout.
+    ///
+    /// Return [`None`] if `cancel` completed before any events were read.
+    /// In that case, nothing was read from the [`Fanotify`],
+    /// so no events are lost and the `buffer` is left empty and reusable.
+    ///
+    /// The read itself happens all at once inside a single poll,
+    /// so it is never interrupted halfway through.
+    /// If both are ready at the same time, `cancel` wins.
+    pub async fn read_cancellable<'a, C: Future>(
+        &'a self,
+        buffer: &'a mut EventBuffer,
+        cancel: C,
+    ) -> io::Result<Option<Events<'a>>> {
+        let mut cancel = pin!(cancel);
+        let events = &mut buffer.events;
+        events.clear();
+        let read = poll_fn(|cx| {
+            if cancel.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Ok(false));
+            }
+            self.inner.poll_read_with(cx, &mut |fanotify| {
+                Events::read_raw(fanotify, events).map_err(io::Error::from)
+            }).map_ok(|()| true)
+        }).await?;
+        if !read {
+            return Ok(None);
+        }
+        Ok(Some(Events::from_buffer(self.fanotify(), buffer)))
+    }
+    
+    /// Write raw bytes (e.g. permission responses) to the wrapped [`Fanotify`] group,
+    /// waiting until it's writable if the write would block.
+    ///
+    /// Return the number of bytes written.
+    pub async fn write(&self, bytes: &[u8]) -> io::Result<usize> {
+        poll_fn(|cx| {
+            self.inner.poll_write_with(cx, &mut |fanotify| {
+                fanotify.fd.write(bytes).map_err(io::Error::from)
+            })
+        }).await
+    }
+    
+    /// Like [`write`](Self::write), but for permission responses,
+    /// never splitting a response across writes.
+    ///
+    /// Return the number of bytes of whole responses written.
+    pub(crate) async fn write_responses(&self, bytes: &[u8]) -> io::Result<usize> {
+        poll_fn(|cx| {
+            self.inner.poll_write_with(cx, &mut |fanotify| {
+                write_responses(&fanotify.fd, bytes).map_err(io::Error::from)
+            })
+        }).await
+    }
+}
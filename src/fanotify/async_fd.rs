@@ -0,0 +1,162 @@
+use std::io;
+use std::task::Context;
+use std::task::Poll;
+
+use async_io::Async;
+
+use crate::fanotify::Fanotify;
+
+/// A wrapper around a [`Fanotify`] that registers it with an async runtime's reactor,
+/// so that [`AsyncFanotify`](super::async_fanotify::AsyncFanotify) can wait for it to be readable (or writable).
+///
+/// This is the only thing [`AsyncFanotify`](super::async_fanotify::AsyncFanotify)
+/// and the layers on top of it need from a runtime, so they are executor-agnostic.
+///
+/// Implementations:
+/// * [`Async<Fanotify>`] from `async-io`, the default.
+///   This is also the one to use with `async-std` and `smol`,
+///   since they run on `async-io`'s reactor.
+/// * `tokio::io::unix::AsyncFd<Fanotify>` with the `tokio` feature.
+pub trait AsyncFdWrapper: Sized {
+    /// Register the [`Fanotify`] with the reactor,
+    /// putting its file descriptor in non-blocking mode.
+    fn new(fanotify: Fanotify) -> io::Result<Self>;
+    
+    fn get_ref(&self) -> &Fanotify;
+    
+    fn get_mut(&mut self) -> &mut Fanotify;
+    
+    /// Deregister the [`Fanotify`] from the reactor.
+    fn into_inner(self) -> io::Result<Fanotify>;
+    
+    /// Try to `read` from the [`Fanotify`], and if it would block,
+    /// wait until the [`Fanotify`] is readable and try again.
+    ///
+    /// `read` must return an [`io::ErrorKind::WouldBlock`] error if it would block.
+    fn poll_read_with<R>(
+        &self,
+        cx: &mut Context<'_>,
+        read: &mut dyn FnMut(&Fanotify) -> io::Result<R>,
+    ) -> Poll<io::Result<R>>;
+    
+    /// Try to `write` to the [`Fanotify`], and if it would block,
+    /// wait until the [`Fanotify`] is writable and try again.
+    ///
+    /// `write` must return an [`io::ErrorKind::WouldBlock`] error if it would block.
+    fn poll_write_with<R>(
+        &self,
+        cx: &mut Context<'_>,
+        write: &mut dyn FnMut(&Fanotify) -> io::Result<R>,
+    ) -> Poll<io::Result<R>>;
+}
+
+impl AsyncFdWrapper for Async<Fanotify> {
+    fn new(fanotify: Fanotify) -> io::Result<Self> {
+        Async::new(fanotify)
+    }
+    
+    fn get_ref(&self) -> &Fanotify {
+        self.get_ref()
+    }
+    
+    fn get_mut(&mut self) -> &mut Fanotify {
+        self.get_mut()
+    }
+    
+    fn into_inner(self) -> io::Result<Fanotify> {
+        self.into_inner()
+    }
+    
+    fn poll_read_with<R>(
+        &self,
+        cx: &mut Context<'_>,
+        read: &mut dyn FnMut(&Fanotify) -> io::Result<R>,
+    ) -> Poll<io::Result<R>> {
+        loop {
+            match read(self.get_ref()) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                result => return Poll::Ready(result),
+            }
+            match self.poll_readable(cx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+    
+    fn poll_write_with<R>(
+        &self,
+        cx: &mut Context<'_>,
+        write: &mut dyn FnMut(&Fanotify) -> io::Result<R>,
+    ) -> Poll<io::Result<R>> {
+        loop {
+            match write(self.get_ref()) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                result => return Poll::Ready(result),
+            }
+            match self.poll_writable(cx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncFdWrapper for tokio::io::unix::AsyncFd<Fanotify> {
+    fn new(fanotify: Fanotify) -> io::Result<Self> {
+        // unlike async-io, tokio leaves this up to us
+        fanotify.fd.set_non_blocking()?;
+        Self::new(fanotify)
+    }
+    
+    fn get_ref(&self) -> &Fanotify {
+        self.get_ref()
+    }
+    
+    fn get_mut(&mut self) -> &mut Fanotify {
+        self.get_mut()
+    }
+    
+    fn into_inner(self) -> io::Result<Fanotify> {
+        Ok(self.into_inner())
+    }
+    
+    fn poll_read_with<R>(
+        &self,
+        cx: &mut Context<'_>,
+        read: &mut dyn FnMut(&Fanotify) -> io::Result<R>,
+    ) -> Poll<io::Result<R>> {
+        loop {
+            let mut guard = match self.poll_read_ready(cx) {
+                Poll::Ready(Ok(guard)) => guard,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            };
+            // try_io() clears the readiness if read() would block
+            if let Ok(result) = guard.try_io(|fd| read(fd.get_ref())) {
+                return Poll::Ready(result);
+            }
+        }
+    }
+    
+    fn poll_write_with<R>(
+        &self,
+        cx: &mut Context<'_>,
+        write: &mut dyn FnMut(&Fanotify) -> io::Result<R>,
+    ) -> Poll<io::Result<R>> {
+        loop {
+            let mut guard = match self.poll_write_ready(cx) {
+                Poll::Ready(Ok(guard)) => guard,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            };
+            // try_io() clears the readiness if write() would block
+            if let Ok(result) = guard.try_io(|fd| write(fd.get_ref())) {
+                return Poll::Ready(result);
+            }
+        }
+    }
+}
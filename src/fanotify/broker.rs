@@ -0,0 +1,459 @@
+//! A multi-process architecture where a privileged broker owns a [`Fanotify`] group
+//! and (e.g. sandboxed) worker processes make its permission decisions.
+//!
+//! A [`Broker`] reads raw event batches from the group and sends each one,
+//! along with its events' file descriptors (with `SCM_RIGHTS`), to the next of its [`Worker`]s in turn,
+//! over a `SOCK_SEQPACKET` unix socket from [`socket_pair`].
+//! A [`Worker`] [receives](Worker::receive) them as [`BrokeredEvent`]s, each with its own file descriptor,
+//! so it can read and scan the file, and [responds](Worker::respond) to the permission events,
+//! which the [`Broker`] then writes to the group.
+//!
+//! A [`Broker`] only accepts responses to the permission events it sent to that same [`Worker`],
+//! so a compromised [`Worker`] can't decide for other [`Worker`]s or for arbitrary file descriptors.
+//! If a [`Worker`] disconnects (e.g. it crashed), or the [`Broker`] is dropped,
+//! the permission events it hasn't responded to are allowed, like an unwritten
+//! [`FilePermission`](crate::event::file::permission::FilePermission), so they don't block forever.
+//!
+//! Only groups without [`REPORT_FID`](init::Flags::REPORT_FID) are supported,
+//! since a [`BrokeredEvent`] doesn't carry file handles.
+
+use std::collections::HashMap;
+use std::io;
+use std::mem::size_of;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::OwnedFd;
+use std::os::unix::io::RawFd;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use nix::errno::Errno;
+use nix::sys::socket::recv;
+use nix::sys::socket::recvmsg;
+use nix::sys::socket::send;
+use nix::sys::socket::sendmsg;
+use nix::sys::socket::socketpair;
+use nix::sys::socket::AddressFamily;
+use nix::sys::socket::ControlMessage;
+use nix::sys::socket::ControlMessageOwned;
+use nix::sys::socket::MsgFlags;
+use nix::sys::socket::SockFlag;
+use nix::sys::socket::SockType;
+use nix::sys::uio::IoVec;
+use nix::unistd::Pid;
+
+use crate::event::events::Events;
+use crate::event::file::permission::PermissionDecision;
+use crate::event::file::permission::RawFilePermission;
+use crate::event::id::Id;
+use crate::event::responses::write_responses;
+use crate::fd::FD;
+use crate::init;
+use crate::init::RawInit;
+use crate::libc::call::libc_call;
+use crate::libc::read::fanotify_event_metadata;
+use crate::libc::read::FAN_NOFD;
+use crate::mark::Mask;
+
+use super::fd_passing::nix_to_io;
+use super::Fanotify;
+
+/// The size of the batches a [`Broker`] reads and sends.
+///
+/// It holds at most 170 events, which is less than the kernel's limit
+/// of 253 file descriptors in one `SCM_RIGHTS` message.
+const BATCH_SIZE: usize = 4096;
+
+/// The most file descriptors in one batch, one per event.
+const MAX_BATCH_FDS: usize = BATCH_SIZE / size_of::<fanotify_event_metadata>();
+
+/// The size of a [`RawInit`] as it's sent to a [`Worker`] when it's added to a [`Broker`].
+const INIT_LEN: usize = 2 * size_of::<u32>();
+
+fn errno_to_io(errno: Errno) -> io::Error {
+    io::Error::from_raw_os_error(errno as i32)
+}
+
+/// Read the [`fanotify_event_metadata`] of each event in a raw batch.
+fn metadata(bytes: &[u8]) -> impl Iterator<Item = fanotify_event_metadata> + '_ {
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        let remaining = bytes.get(offset..)?;
+        if remaining.len() < size_of::<fanotify_event_metadata>() {
+            return None;
+        }
+        let metadata = unsafe { (remaining.as_ptr() as *const fanotify_event_metadata).read_unaligned() };
+        // a zero length would loop forever
+        offset += (metadata.event_len as usize).max(size_of::<fanotify_event_metadata>());
+        Some(metadata)
+    })
+}
+
+/// Create a connected pair of `SOCK_SEQPACKET` unix sockets for a [`Broker`] and a [`Worker`],
+/// the first to be [added](Broker::add_worker) to the [`Broker`] and the second for the [`Worker`].
+///
+/// Both are close-on-exec, so the [`Worker`]'s end must be made inheritable
+/// (e.g. with `dup2` in a `pre_exec` hook) to pass it to a new program.
+pub fn socket_pair() -> io::Result<(OwnedFd, OwnedFd)> {
+    let (broker, worker) = socketpair(AddressFamily::Unix, SockType::SeqPacket, None, SockFlag::SOCK_CLOEXEC)
+        .map_err(nix_to_io)?;
+    Ok(unsafe { (OwnedFd::from_raw_fd(broker), OwnedFd::from_raw_fd(worker)) })
+}
+
+/// A [`Worker`] connected to a [`Broker`].
+struct Connection {
+    socket: OwnedFd,
+    /// The file descriptors of the permission events sent to this [`Worker`] that it hasn't responded to yet,
+    /// keyed by their number, which is also what the [`Worker`] responds with.
+    pending: HashMap<RawFd, FD>,
+}
+
+/// Owns a [`Fanotify`] group and forwards its events to [`Worker`]s.  See the [module docs](self).
+pub struct Broker {
+    fanotify: Fanotify,
+    workers: Vec<Connection>,
+    next: usize,
+    buffer: Vec<u8>,
+    rejected_responses: u64,
+}
+
+impl Broker {
+    /// Create a [`Broker`] for `fanotify`, which must not use [`REPORT_FID`](init::Flags::REPORT_FID).
+    pub fn new(fanotify: Fanotify) -> io::Result<Self> {
+        if fanotify.init.flags().contains(init::Flags::REPORT_FID) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "a Broker can't forward REPORT_FID events"));
+        }
+        Ok(Self {
+            fanotify,
+            workers: Vec::new(),
+            next: 0,
+            buffer: Vec::with_capacity(BATCH_SIZE),
+            rejected_responses: 0,
+        })
+    }
+    
+    pub fn fanotify(&self) -> &Fanotify {
+        &self.fanotify
+    }
+    
+    /// The number of connected [`Worker`]s.
+    pub fn workers(&self) -> usize {
+        self.workers.len()
+    }
+    
+    /// The number of permission events sent to [`Worker`]s that they haven't responded to yet.
+    pub fn pending(&self) -> usize {
+        self.workers.iter().map(|worker| worker.pending.len()).sum()
+    }
+    
+    /// The number of responses that were ignored because they were malformed
+    /// or weren't for a permission event pending on that [`Worker`].
+    pub fn rejected_responses(&self) -> u64 {
+        self.rejected_responses
+    }
+    
+    /// Add a [`Worker`] connected to `socket`, the first of a [`socket_pair`],
+    /// sending it the group's [`RawInit`].
+    pub fn add_worker(&mut self, socket: OwnedFd) -> io::Result<()> {
+        let init = self.fanotify.init;
+        let mut data = [0; INIT_LEN];
+        data[..4].copy_from_slice(&init.flags.to_ne_bytes());
+        data[4..].copy_from_slice(&init.event_flags.to_ne_bytes());
+        send(socket.as_raw_fd(), &data, MsgFlags::empty()).map_err(nix_to_io)?;
+        self.workers.push(Connection {
+            socket,
+            pending: HashMap::new(),
+        });
+        Ok(())
+    }
+    
+    /// Write a response for the permission event `fd` to the group.
+    fn respond(&self, fd: &FD, decision: PermissionDecision) -> io::Result<()> {
+        let response = RawFilePermission {
+            fd: fd.as_raw_fd(),
+            decision,
+            audit: false,
+            audit_rule: None,
+        };
+        let mut bytes = Vec::new();
+        response.write_bytes(&mut bytes);
+        write_responses(&self.fanotify.fd, &bytes).map_err(errno_to_io)?;
+        Ok(())
+    }
+    
+    /// Disconnect the `i`th [`Worker`], allowing all its pending permission events.
+    fn disconnect(&mut self, i: usize) -> io::Result<()> {
+        let worker = self.workers.remove(i);
+        if self.next > i {
+            self.next -= 1;
+        }
+        let mut result = Ok(());
+        for fd in worker.pending.values() {
+            // keep allowing the rest even if one fails
+            result = result.and(self.respond(fd, PermissionDecision::Allow));
+        }
+        result
+    }
+    
+    /// Receive and write all the responses the `i`th [`Worker`] has sent so far.
+    ///
+    /// Return `false` if it has disconnected.
+    fn receive_responses(&mut self, i: usize) -> io::Result<bool> {
+        let mut buffer = [0; 64];
+        loop {
+            let len = match recv(self.workers[i].socket.as_raw_fd(), &mut buffer, MsgFlags::MSG_DONTWAIT) {
+                Err(nix::Error::Sys(Errno::EAGAIN)) => return Ok(true),
+                Err(_) | Ok(0) => return Ok(false),
+                Ok(len) => len,
+            };
+            let response = match RawFilePermission::read_bytes(&buffer[..len]) {
+                Some((Ok(response), _)) => response,
+                _ => {
+                    self.rejected_responses += 1;
+                    continue;
+                }
+            };
+            match self.workers[i].pending.remove(&response.fd) {
+                None => self.rejected_responses += 1,
+                Some(fd) => self.respond(&fd, response.decision)?,
+            }
+        }
+    }
+    
+    /// Read a batch of events and send it to the next [`Worker`].
+    ///
+    /// If that fails, the [`Worker`] is disconnected and the batch's permission events are allowed.
+    fn forward(&mut self) -> io::Result<()> {
+        match Events::read_raw(&self.fanotify, &mut self.buffer) {
+            Err(Errno::EAGAIN) => return Ok(()),
+            result => result.map_err(errno_to_io)?,
+        }
+        // take ownership of every fd right away, so they're closed even on error
+        let fds = metadata(&self.buffer)
+            .filter(|metadata| metadata.fd != FAN_NOFD)
+            .map(|metadata| {
+                let is_permission = Mask::from_bits_truncate(metadata.mask).includes_permission();
+                (unsafe { FD::from_raw_fd(metadata.fd) }, is_permission)
+            })
+            .collect::<Vec<_>>();
+        let i = self.next % self.workers.len();
+        self.next = i + 1;
+        let raw_fds = fds.iter().map(|(fd, _)| fd.as_raw_fd()).collect::<Vec<_>>();
+        let sent = sendmsg(
+            self.workers[i].socket.as_raw_fd(),
+            &[IoVec::from_slice(&self.buffer)],
+            &[ControlMessage::ScmRights(&raw_fds)],
+            MsgFlags::empty(),
+            None,
+        );
+        match sent {
+            Ok(_) => {
+                // the worker has its own copies of the non-permission fds, so these are closed
+                let pending = fds.into_iter().filter(|(_, is_permission)| *is_permission);
+                let worker = &mut self.workers[i];
+                for (fd, _) in pending {
+                    worker.pending.insert(fd.as_raw_fd(), fd);
+                }
+                Ok(())
+            }
+            Err(_) => {
+                let mut result = self.disconnect(i);
+                for (fd, _) in fds.iter().filter(|(_, is_permission)| *is_permission) {
+                    result = result.and(self.respond(fd, PermissionDecision::Allow));
+                }
+                result
+            }
+        }
+    }
+    
+    /// Wait up to `timeout` (or forever if [`None`]) for events or responses, and handle them.
+    ///
+    /// Events are only read while there are [`Worker`]s to forward them to,
+    /// so without any, they stay queued (and permission events block).
+    pub fn run_once(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        let mut poll_fds = Vec::with_capacity(1 + self.workers.len());
+        poll_fds.push(libc::pollfd {
+            fd: self.fanotify.as_raw_fd(),
+            events: if self.workers.is_empty() { 0 } else { libc::POLLIN },
+            revents: 0,
+        });
+        poll_fds.extend(self.workers.iter().map(|worker| libc::pollfd {
+            fd: worker.socket.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        }));
+        let timeout = match timeout {
+            None => -1,
+            Some(timeout) => timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int,
+        };
+        libc_call(|| unsafe { libc::poll(poll_fds.as_mut_ptr(), poll_fds.len() as libc::nfds_t, timeout) })
+            .map_err(errno_to_io)?;
+        // handle responses first, and in reverse so disconnecting doesn't shift the rest
+        for (i, poll_fd) in poll_fds.iter().enumerate().skip(1).rev() {
+            if poll_fd.revents != 0 && !self.receive_responses(i - 1)? {
+                self.disconnect(i - 1)?;
+            }
+        }
+        if poll_fds[0].revents & libc::POLLIN != 0 && !self.workers.is_empty() {
+            self.forward()?;
+        }
+        Ok(())
+    }
+    
+    /// [`Broker::run_once`] until there are no [`Worker`]s left.
+    pub fn run(&mut self) -> io::Result<()> {
+        while !self.workers.is_empty() {
+            self.run_once(None)?;
+        }
+        Ok(())
+    }
+}
+
+/// Allow any pending permission events, so they don't block forever.
+impl Drop for Broker {
+    fn drop(&mut self) {
+        while !self.workers.is_empty() {
+            let _ = self.disconnect(self.workers.len() - 1);
+        }
+    }
+}
+
+/// An event forwarded by a [`Broker`] to a [`Worker`].
+#[derive(Debug)]
+pub struct BrokeredEvent {
+    mask: Mask,
+    id: Id,
+    fd: Option<FD>,
+    broker_fd: RawFd,
+}
+
+impl BrokeredEvent {
+    pub fn mask(&self) -> Mask {
+        self.mask
+    }
+    
+    /// The process (or thread, with [`REPORT_TID`](init::Flags::REPORT_TID)) that caused this event.
+    pub fn id(&self) -> Id {
+        self.id
+    }
+    
+    /// This [`Worker`]'s own file descriptor for the event's file,
+    /// or [`None`] for an event without one, like a queue overflow.
+    pub fn fd(&self) -> Option<&FD> {
+        self.fd.as_ref()
+    }
+    
+    /// See [`File::path`](crate::event::file::File::path).
+    pub fn path(&self) -> Option<io::Result<PathBuf>> {
+        self.fd.as_ref().map(FD::path)
+    }
+    
+    /// If this is a permission event, which must be [responded](Worker::respond) to.
+    pub fn is_permission(&self) -> bool {
+        self.mask.includes_permission()
+    }
+}
+
+/// Receives events from a [`Broker`] and responds to them.  See the [module docs](self).
+#[derive(Debug)]
+pub struct Worker {
+    socket: OwnedFd,
+    init: RawInit,
+}
+
+impl Worker {
+    /// Connect to a [`Broker`] over `socket`, the second of a [`socket_pair`],
+    /// receiving its group's [`RawInit`].
+    pub fn new(socket: OwnedFd) -> io::Result<Self> {
+        let mut data = [0; INIT_LEN];
+        let len = recv(socket.as_raw_fd(), &mut data, MsgFlags::empty()).map_err(nix_to_io)?;
+        if len != INIT_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "expected the broker's init flags"));
+        }
+        let init = RawInit {
+            flags: u32::from_ne_bytes([data[0], data[1], data[2], data[3]]),
+            event_flags: u32::from_ne_bytes([data[4], data[5], data[6], data[7]]),
+        };
+        Ok(Self { socket, init })
+    }
+    
+    /// The [`RawInit`] of the [`Broker`]'s group.
+    pub fn init(&self) -> RawInit {
+        self.init
+    }
+    
+    /// Wait for and receive the next batch of events,
+    /// or [`None`] if the [`Broker`] has disconnected.
+    pub fn receive(&self) -> io::Result<Option<Vec<BrokeredEvent>>> {
+        let mut bytes = [0; BATCH_SIZE];
+        let mut cmsgs = nix::cmsg_space!([RawFd; MAX_BATCH_FDS]);
+        let message = recvmsg(
+            self.socket.as_raw_fd(),
+            &[IoVec::from_mut_slice(&mut bytes)],
+            Some(&mut cmsgs),
+            MsgFlags::MSG_CMSG_CLOEXEC,
+        )
+        .map_err(nix_to_io)?;
+        // take ownership of every received fd right away, so they're closed even on error
+        let fds = message
+            .cmsgs()
+            .flat_map(|cmsg| match cmsg {
+                ControlMessageOwned::ScmRights(fds) => fds,
+                _ => Vec::new(),
+            })
+            .map(|fd| unsafe { FD::from_raw_fd(fd) })
+            .collect::<Vec<_>>();
+        let len = message.bytes;
+        if len == 0 && fds.is_empty() {
+            return Ok(None);
+        }
+        if message.flags.intersects(MsgFlags::MSG_TRUNC | MsgFlags::MSG_CTRUNC) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "batch from broker was truncated"));
+        }
+        let use_tid = self.init.flags().contains(init::Flags::REPORT_TID);
+        let mut fds = fds.into_iter();
+        let mut events = Vec::new();
+        for metadata in metadata(&bytes[..len]) {
+            let fd = if metadata.fd == FAN_NOFD {
+                None
+            } else {
+                let fd = fds.next()
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "batch from broker is missing fds"))?;
+                Some(fd)
+            };
+            let pid = Pid::from_raw(metadata.pid);
+            events.push(BrokeredEvent {
+                mask: Mask::from_bits_truncate(metadata.mask),
+                id: if use_tid { Id::Tid(pid) } else { Id::Pid(pid) },
+                fd,
+                broker_fd: metadata.fd,
+            });
+        }
+        Ok(Some(events))
+    }
+    
+    /// Send the [`PermissionDecision`] for a permission `event` to the [`Broker`].
+    ///
+    /// Other events don't need a response, so this does nothing for them.
+    pub fn respond(&self, event: &BrokeredEvent, decision: PermissionDecision) -> io::Result<()> {
+        if !event.is_permission() {
+            return Ok(());
+        }
+        let response = RawFilePermission {
+            fd: event.broker_fd,
+            decision,
+            audit: false,
+            audit_rule: None,
+        };
+        let mut bytes = Vec::new();
+        response.write_bytes(&mut bytes);
+        send(self.socket.as_raw_fd(), &bytes, MsgFlags::empty()).map_err(nix_to_io)?;
+        Ok(())
+    }
+}
+
+impl AsRawFd for Worker {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
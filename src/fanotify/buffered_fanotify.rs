@@ -1,12 +1,31 @@
+#[cfg(feature = "async")]
+use std::future::Future;
 use std::io;
+#[cfg(feature = "async")]
+use std::mem::ManuallyDrop;
+#[cfg(feature = "async")]
+use std::ptr;
+#[cfg(feature = "async")]
+use std::thread;
+use std::time::Duration;
 
+#[cfg(feature = "async")]
 use apply::Apply;
 
+#[cfg(feature = "async")]
+use async_io::Async;
+
+#[cfg(feature = "async")]
 use crate::fanotify::async_fanotify::AsyncFanotify;
+#[cfg(feature = "async")]
+use crate::fanotify::async_fd::AsyncFdWrapper;
 use crate::event::buffer::EventBuffer;
 use crate::event::buffer::EventBufferSize;
+use crate::event::error::EventResult;
 use crate::event::events::Events;
 use crate::fanotify::Fanotify;
+use crate::fanotify::stats::LagWarning;
+use crate::fanotify::stats::Stats;
 use crate::mark;
 use crate::mark::Mark;
 use crate::mark::Markable;
@@ -14,36 +33,185 @@ use crate::mark::Markable;
 pub struct BufferedFanotify {
     pub fanotify: Fanotify,
     pub buffer: EventBuffer,
+    pub(super) stats: Stats,
+    pub(super) lag_warning: Option<LagWarning>,
 }
 
 impl Markable for BufferedFanotify {
     fn mark<'a>(&self, mark: Mark<'a>) -> Result<(), mark::Error<'a>> {
         self.fanotify.mark(mark)
     }
+    
+    fn check<'a>(&self, mark: Mark<'a>) -> Result<Mark<'a>, mark::Error<'a>> {
+        self.fanotify.check(mark)
+    }
 }
 
 impl BufferedFanotify {
     /// See [`Fanotify::read`].
     pub fn read(&mut self) -> io::Result<Events> {
-        self.fanotify.read(&mut self.buffer)
+        let events = self.fanotify.read(&mut self.buffer);
+        self.stats.record_and_check(&events, &mut self.lag_warning);
+        events
+    }
+    
+    /// See [`Fanotify::read_all_pending`].
+    pub fn read_all_pending(&mut self, max_bytes: usize) -> io::Result<Events> {
+        let events = self.fanotify.read_all_pending(&mut self.buffer, max_bytes);
+        self.stats.record_and_check(&events, &mut self.lag_warning);
+        events
+    }
+    
+    /// The [`Stats`] of all the reads so far.
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+    
+    /// Call `hook` after every read whose [`Stats::lag`] exceeds `threshold`,
+    /// with the lag and the [`Stats`], e.g. to warn that the reads are falling behind
+    /// before the queue overflows.
+    pub fn on_lag(&mut self, threshold: Duration, hook: impl FnMut(Duration, &Stats) + Send + 'static) {
+        self.lag_warning = Some(LagWarning::new(threshold, hook));
+    }
+    
+    /// Wait until no events have arrived for `duration`, i.e., until the filesystem settles,
+    /// reading and discarding any events that arrive in the meantime.
+    ///
+    /// Return the number of events discarded (including errors).
+    /// Any permission events among them are [allowed](crate::event::file::permission::PermissionDecision::Allow).
+    /// To handle them instead, use [`BufferedFanotify::wait_idle_with`].
+    pub fn wait_idle(&mut self, duration: Duration) -> io::Result<usize> {
+        self.wait_idle_with(duration, |_| {})
+    }
+    
+    /// Like [`BufferedFanotify::wait_idle`], but passing each event that arrives in the meantime to `handle`.
+    ///
+    /// Each batch of events restarts the wait, so this can wait forever if events keep arriving.
+    pub fn wait_idle_with(&mut self, duration: Duration, mut handle: impl FnMut(EventResult<'_>)) -> io::Result<usize> {
+        let mut count = 0;
+        loop {
+            let readable = self.fanotify
+                .readable(Some(duration))
+                .map_err(|errno| io::Error::from_raw_os_error(errno as i32))?;
+            if !readable {
+                return Ok(count);
+            }
+            for event in self.read()? {
+                count += 1;
+                handle(event);
+            }
+        }
     }
 }
 
-pub struct AsyncBufferedFanotify {
-    pub fanotify: AsyncFanotify,
+#[cfg(feature = "async")]
+pub struct AsyncBufferedFanotify<W: AsyncFdWrapper = Async<Fanotify>> {
+    pub fanotify: AsyncFanotify<W>,
     pub buffer: EventBuffer,
+    stats: Stats,
+    lag_warning: Option<LagWarning>,
 }
 
-impl Markable for AsyncBufferedFanotify {
+#[cfg(feature = "async")]
+impl<W: AsyncFdWrapper> Markable for AsyncBufferedFanotify<W> {
     fn mark<'a>(&self, mark: Mark<'a>) -> Result<(), mark::Error<'a>> {
         self.fanotify.mark(mark)
     }
+    
+    fn check<'a>(&self, mark: Mark<'a>) -> Result<Mark<'a>, mark::Error<'a>> {
+        self.fanotify.check(mark)
+    }
 }
 
-impl AsyncBufferedFanotify {
+#[cfg(feature = "async")]
+impl<W: AsyncFdWrapper> AsyncBufferedFanotify<W> {
     /// See [`Fanotify::read`].
+    ///
+    /// Any pending responses are [flushed](AsyncBufferedFanotify::flush_responses) first,
+    /// so they're never left behind in the buffer across reads.
     pub async fn read(&mut self) -> io::Result<Events<'_>> {
-        self.fanotify.read(&mut self.buffer).await
+        self.flush_responses().await?;
+        let events = self.fanotify.read(&mut self.buffer).await;
+        self.stats.record_and_check(&events, &mut self.lag_warning);
+        events
+    }
+    
+    /// See [`Fanotify::read_all_pending`].
+    ///
+    /// Like [`AsyncBufferedFanotify::read`], any pending responses are flushed first.
+    pub async fn read_all_pending(&mut self, max_bytes: usize) -> io::Result<Events<'_>> {
+        self.flush_responses().await?;
+        let events = self.fanotify.read_all_pending(&mut self.buffer, max_bytes).await;
+        self.stats.record_and_check(&events, &mut self.lag_warning);
+        events
+    }
+    
+    /// See [`AsyncFanotify::read_cancellable`].
+    ///
+    /// Like [`AsyncBufferedFanotify::read`], any pending responses are flushed first,
+    /// which isn't raced against `cancel`.
+    pub async fn read_cancellable<C: Future>(&mut self, cancel: C) -> io::Result<Option<Events<'_>>> {
+        self.flush_responses().await?;
+        let events = match self.fanotify.read_cancellable(&mut self.buffer, cancel).await {
+            Ok(None) => return Ok(None),
+            Ok(Some(events)) => Ok(events),
+            Err(e) => Err(e),
+        };
+        self.stats.record_and_check(&events, &mut self.lag_warning);
+        events.map(Some)
+    }
+    
+    /// The [`Stats`] of all the reads so far.
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+    
+    /// Call `hook` after every read whose [`Stats::lag`] exceeds `threshold`,
+    /// with the lag and the [`Stats`], e.g. to warn that the reads are falling behind
+    /// before the queue overflows.
+    pub fn on_lag(&mut self, threshold: Duration, hook: impl FnMut(Duration, &Stats) + Send + 'static) {
+        self.lag_warning = Some(LagWarning::new(threshold, hook));
+    }
+    
+    /// If there are permission responses that couldn't be written yet without blocking.
+    ///
+    /// See [`AsyncBufferedFanotify::flush_responses`].
+    pub fn has_pending_responses(&self) -> bool {
+        !self.buffer.responses.is_empty()
+    }
+    
+    /// Write any permission responses that couldn't be written without blocking when their [`Events`] were dropped,
+    /// waiting until the [`Fanotify`] group is writable.
+    ///
+    /// Otherwise, they are only written along with the responses of the next [`Events`].
+    pub async fn flush_responses(&mut self) -> io::Result<()> {
+        while self.has_pending_responses() {
+            let bytes_written = self.fanotify.write_responses(&self.buffer.responses).await?;
+            self.buffer.responses.drain(0..bytes_written);
+        }
+        Ok(())
+    }
+    
+    /// Flush all outstanding permission responses with [`AsyncBufferedFanotify::flush_responses`]
+    /// and then close the [`Fanotify`] group.
+    ///
+    /// Since there is no async [`Drop`], this is the correct way to tear down an [`AsyncBufferedFanotify`].
+    /// Otherwise, outstanding responses are lost when it's dropped
+    /// (and a debug assertion fails),
+    /// so the kernel just allows them when the group is closed.
+    pub async fn shutdown(mut self) -> io::Result<()> {
+        self.flush_responses().await
+    }
+}
+
+#[cfg(feature = "async")]
+impl<W: AsyncFdWrapper> Drop for AsyncBufferedFanotify<W> {
+    fn drop(&mut self) {
+        debug_assert!(
+            !self.has_pending_responses() || thread::panicking(),
+            "AsyncBufferedFanotify dropped with pending permission responses; \
+                call AsyncBufferedFanotify::shutdown() first",
+        );
     }
 }
 
@@ -67,37 +235,63 @@ impl IntoBufferedFanotify for Fanotify {
         Self::Buffered {
             fanotify: self,
             buffer,
+            stats: Stats::default(),
+            lag_warning: None,
         }
     }
 }
 
-impl IntoBufferedFanotify for AsyncFanotify {
-    type Buffered = AsyncBufferedFanotify;
+#[cfg(feature = "async")]
+impl<W: AsyncFdWrapper> IntoBufferedFanotify for AsyncFanotify<W> {
+    type Buffered = AsyncBufferedFanotify<W>;
     
     fn buffered(self, buffer: EventBuffer) -> Self::Buffered {
         Self::Buffered {
             fanotify: self,
             buffer,
+            stats: Stats::default(),
+            lag_warning: None,
         }
     }
 }
 
+#[cfg(feature = "async")]
 impl BufferedFanotify {
     pub fn into_async(self) -> io::Result<AsyncBufferedFanotify> {
-        let Self { fanotify, buffer } = self;
+        let Self { fanotify, buffer, stats, lag_warning } = self;
         AsyncBufferedFanotify {
             fanotify: fanotify.into_async()?,
             buffer,
+            stats,
+            lag_warning,
+        }.apply(Ok)
+    }
+    
+    /// Like [`BufferedFanotify::into_async`], but using a specific [`AsyncFdWrapper`].
+    pub fn into_async_with<W: AsyncFdWrapper>(self) -> io::Result<AsyncBufferedFanotify<W>> {
+        let Self { fanotify, buffer, stats, lag_warning } = self;
+        AsyncBufferedFanotify {
+            fanotify: fanotify.into_async_with()?,
+            buffer,
+            stats,
+            lag_warning,
         }.apply(Ok)
     }
 }
 
-impl AsyncBufferedFanotify {
+#[cfg(feature = "async")]
+impl<W: AsyncFdWrapper> AsyncBufferedFanotify<W> {
     pub fn into_sync(self) -> io::Result<BufferedFanotify> {
-        let Self {fanotify, buffer} = self;
+        // can't destructure b/c of Drop, but any pending responses are kept in the buffer
+        let this = ManuallyDrop::new(self);
+        let (fanotify, buffer, stats, lag_warning) = unsafe {
+            (ptr::read(&this.fanotify), ptr::read(&this.buffer), this.stats, ptr::read(&this.lag_warning))
+        };
         BufferedFanotify {
             fanotify: fanotify.into_sync()?,
             buffer,
+            stats,
+            lag_warning,
         }.apply(Ok)
     }
 }
@@ -0,0 +1,161 @@
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+
+use nix::errno::Errno;
+
+use crate::event::file::File;
+use crate::event::file::GetFD;
+use crate::event::file::permission::PermissionDecision;
+use crate::event::file::permission::RawFilePermission;
+use crate::event::id::Id;
+use crate::event::responses::write_responses;
+use crate::mark::Mask;
+
+use super::buffered_fanotify::BufferedFanotify;
+
+fn errno_to_io(errno: Errno) -> io::Error {
+    io::Error::from_raw_os_error(errno as i32)
+}
+
+/// A permission event that [`BufferedFanotify::drain_pending`] gave the default decision.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AutoDecision {
+    pub mask: Mask,
+    pub id: Id,
+    /// The path of the file, if it could be resolved.
+    pub path: Option<PathBuf>,
+    pub decision: PermissionDecision,
+}
+
+/// What [`BufferedFanotify::drain_pending`] did.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct DrainReport {
+    /// The permission events that were given the default decision, in order.
+    pub decided: Vec<AutoDecision>,
+    /// The number of other events that were read and discarded.
+    pub discarded: usize,
+    /// The number of events that couldn't be parsed (e.g. queue overflows).
+    pub errors: usize,
+    /// The number of leftover buffered responses that were flushed first.
+    pub flushed: usize,
+    /// The number of responses (leftover or decided) that failed to be written, and were given up on.
+    pub failed: usize,
+    /// If the timeout expired before everything outstanding was drained,
+    /// so some permission events may still be unanswered.
+    pub timed_out: bool,
+    pub elapsed: Duration,
+}
+
+impl DrainReport {
+    /// If everything outstanding was answered in time.
+    pub fn is_complete(&self) -> bool {
+        !self.timed_out
+    }
+}
+
+impl BufferedFanotify {
+    /// Answer everything outstanding, e.g. on shutdown, since processes waiting on
+    /// unanswered permission events hang until the group is closed.
+    ///
+    /// This first flushes any leftover buffered responses,
+    /// and then reads all the pending events without blocking,
+    /// giving each permission event `default_decision` and discarding the rest,
+    /// until no more events are pending or `timeout` expires,
+    /// and then flushes those responses, too.
+    /// A response that fails to be written is counted in [`DrainReport::failed`]
+    /// and given up on, so that the rest can still be written.
+    ///
+    /// Events keep arriving while the group still has marks,
+    /// so this stops as soon as the queue is empty, not when `timeout` expires.
+    pub fn drain_pending(&mut self, timeout: Duration, default_decision: PermissionDecision) -> io::Result<DrainReport> {
+        let start = Instant::now();
+        let deadline = start + timeout;
+        let remaining = || deadline.saturating_duration_since(Instant::now());
+        let mut report = DrainReport::default();
+        
+        let leftover = {
+            let mut count = 0;
+            let mut offset = 0;
+            while let Some((_, len)) = RawFilePermission::read_bytes(&self.buffer.responses[offset..]) {
+                count += 1;
+                offset += len;
+            }
+            count
+        };
+        if !self.flush_until(deadline, &mut report)? {
+            report.timed_out = true;
+            report.elapsed = start.elapsed();
+            return Ok(report);
+        }
+        report.flushed = leftover;
+        
+        loop {
+            if !self.fanotify.readable(Some(Duration::from_secs(0))).map_err(errno_to_io)? {
+                break;
+            }
+            if remaining() == Duration::from_secs(0) {
+                report.timed_out = true;
+                break;
+            }
+            for event in self.read()? {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(_) => {
+                        report.errors += 1;
+                        continue;
+                    }
+                };
+                let mask = event.mask();
+                let id = event.id().id();
+                match event.into_file() {
+                    File::Permission(mut permission) => {
+                        permission.decision = default_decision;
+                        permission.write_buffered();
+                        report.decided.push(AutoDecision {
+                            mask,
+                            id,
+                            path: permission.fd().path().ok(),
+                            decision: default_decision,
+                        });
+                    }
+                    _ => report.discarded += 1,
+                }
+            }
+        }
+        // the responses are only buffered when their events are dropped
+        if !self.flush_until(deadline, &mut report)? {
+            report.timed_out = true;
+        }
+        report.elapsed = start.elapsed();
+        Ok(report)
+    }
+    
+    /// Write the buffered responses until they're all written or `deadline` passes,
+    /// returning if they were all written.
+    fn flush_until(&mut self, deadline: Instant, report: &mut DrainReport) -> io::Result<bool> {
+        while !self.buffer.responses.is_empty() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::from_secs(0) {
+                return Ok(false);
+            }
+            match write_responses(&self.fanotify.fd, &self.buffer.responses) {
+                Ok(bytes_written) => {
+                    self.buffer.responses.drain(0..bytes_written);
+                }
+                Err(Errno::EAGAIN) => {
+                    self.fanotify.fd.writable(Some(remaining)).map_err(errno_to_io)?;
+                }
+                Err(_) => {
+                    // give up on the first response, so the rest can still be written
+                    let len = RawFilePermission::read_bytes(&self.buffer.responses)
+                        .map_or(self.buffer.responses.len(), |(_, len)| len);
+                    self.buffer.responses.drain(0..len);
+                    report.failed += 1;
+                }
+            }
+        }
+        Ok(true)
+    }
+}
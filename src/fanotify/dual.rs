@@ -0,0 +1,185 @@
+use std::io;
+use std::iter::Chain;
+use std::iter::Flatten;
+use std::option;
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+use nix::errno::Errno;
+
+use crate::event::error::EventResult;
+use crate::event::events::Events;
+use crate::event::iterator_ext::IntoEvents;
+use crate::init;
+use crate::init::Flags;
+use crate::init::Init;
+use crate::init::NotificationClass;
+use crate::libc::call::libc_call;
+use crate::mark;
+use crate::mark::Action;
+use crate::mark::Mark;
+use crate::mark::Markable;
+use crate::mark::Mask;
+
+use super::buffered_fanotify::BufferedFanotify;
+use super::buffered_fanotify::IntoBufferedFanotify;
+use super::Fanotify;
+
+/// The [`Mask`] bits that modify the others instead of selecting events, so they go to both groups.
+const MODIFIERS: Mask = Mask::from_bits_truncate(Mask::ON_DIR.bits() | Mask::EVENT_ON_CHILD.bits());
+
+/// A [`Notify`](NotificationClass::Notify) group and a [`Content`](NotificationClass::Content) group
+/// behind one facade.
+///
+/// Permission events block the processes that caused them until they're responded to,
+/// so they shouldn't wait behind a flood of notification events in the same group's queue.
+/// A [`DualGroup`] [marks](Markable::mark) the permission part of each [`Mark`]'s [`Mask`]
+/// on its [`content`](DualGroup::content) group and the rest on its [`notify`](DualGroup::notify) group,
+/// and [reads](DualGroup::read) from both, yielding permission events first.
+pub struct DualGroup {
+    notify: BufferedFanotify,
+    content: BufferedFanotify,
+}
+
+impl DualGroup {
+    /// Create both groups from `init`, with its [`NotificationClass`] replaced.
+    ///
+    /// The [`Content`](NotificationClass::Content) group can't report file handles,
+    /// so [`REPORT_FID`](Flags::REPORT_FID) and related flags only apply to the [`Notify`](NotificationClass::Notify) group.
+    pub fn new(init: Init) -> Result<Self, init::Error> {
+        let notify = Init {
+            notification_class: NotificationClass::Notify,
+            ..init
+        };
+        let content = Init {
+            notification_class: NotificationClass::Content,
+            flags: init.flags - (Flags::REPORT_FID | Flags::REPORT_DIR_FID | Flags::REPORT_NAME),
+            ..init
+        };
+        Ok(Self::from_groups(notify.to_fanotify()?, content.to_fanotify()?))
+    }
+    
+    /// Combine an existing `notify` group and `content` group, which should have those [`NotificationClass`]es.
+    pub fn from_groups(notify: Fanotify, content: Fanotify) -> Self {
+        Self {
+            notify: notify.buffered_default(),
+            content: content.buffered_default(),
+        }
+    }
+    
+    /// The group for notification events.
+    pub fn notify(&self) -> &BufferedFanotify {
+        &self.notify
+    }
+    
+    /// The group for permission events.
+    pub fn content(&self) -> &BufferedFanotify {
+        &self.content
+    }
+    
+    pub fn notify_mut(&mut self) -> &mut BufferedFanotify {
+        &mut self.notify
+    }
+    
+    pub fn content_mut(&mut self) -> &mut BufferedFanotify {
+        &mut self.content
+    }
+    
+    /// Split `mark` into the [`Mark`]s for the [`notify`](Self::notify) and [`content`](Self::content) groups,
+    /// if either gets any part of it.
+    ///
+    /// A flush goes to both.
+    fn split<'a>(mark: &Mark<'a>) -> (Option<Mark<'a>>, Option<Mark<'a>>) {
+        let mask = mark.mask();
+        if mark.action() == Action::Flush {
+            return (mark.with_mask(mask).ok(), mark.with_mask(mask).ok());
+        }
+        let permissions = mask & Mask::all_permissions();
+        let notifications = mask - Mask::all_permissions() - MODIFIERS;
+        let modifiers = mask & MODIFIERS;
+        let content = if permissions.is_empty() {
+            None
+        } else {
+            mark.with_mask(permissions | modifiers).ok()
+        };
+        // a mask of only modifiers still goes to one group, to be rejected or removed there
+        let notify = if notifications.is_empty() && !permissions.is_empty() {
+            None
+        } else {
+            mark.with_mask(notifications | modifiers).ok()
+        };
+        (notify, content)
+    }
+    
+    /// Wait up to `timeout` (or forever if [`None`]) until either group has events,
+    /// and read from each one that does.
+    pub fn read(&mut self, timeout: Option<Duration>) -> io::Result<DualEvents<'_>> {
+        let mut poll_fds = [&self.content, &self.notify].map(|group| libc::pollfd {
+            fd: group.fanotify.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        });
+        let timeout = match timeout {
+            None => -1,
+            Some(timeout) => timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int,
+        };
+        libc_call(|| unsafe { libc::poll(poll_fds.as_mut_ptr(), poll_fds.len() as libc::nfds_t, timeout) })
+            .map_err(|errno: Errno| io::Error::from_raw_os_error(errno as i32))?;
+        let [content_ready, notify_ready] = poll_fds.map(|it| it.revents & libc::POLLIN != 0);
+        // read the permission events first, so they're responded to sooner
+        let content = if content_ready { Some(self.content.read()?) } else { None };
+        let notify = if notify_ready { Some(self.notify.read()?) } else { None };
+        Ok(DualEvents { content, notify })
+    }
+}
+
+/// Each half of the [`Mark`] is added to its group, the [`content`](DualGroup::content) group first.
+impl Markable for DualGroup {
+    fn mark<'a>(&self, mark: Mark<'a>) -> Result<(), mark::Error<'a>> {
+        let (notify, content) = Self::split(&mark);
+        if let Some(content) = content {
+            self.content.mark(content)?;
+        }
+        if let Some(notify) = notify {
+            self.notify.mark(notify)?;
+        }
+        Ok(())
+    }
+    
+    fn check<'a>(&self, mark: Mark<'a>) -> Result<Mark<'a>, mark::Error<'a>> {
+        let (notify, content) = Self::split(&mark);
+        if let Some(content) = content {
+            self.content.check(content)?;
+        }
+        if let Some(notify) = notify {
+            self.notify.check(notify)?;
+        }
+        Ok(mark)
+    }
+}
+
+/// The [`Events`] from one [`DualGroup::read`], from each group that had any.
+///
+/// As an [`IntoIterator`], the [`content`](Self::content) group's (permission) events come first.
+pub struct DualEvents<'a> {
+    pub content: Option<Events<'a>>,
+    pub notify: Option<Events<'a>>,
+}
+
+impl DualEvents<'_> {
+    /// If neither group had events, i.e., the [`DualGroup::read`] timed out.
+    pub fn is_empty(&self) -> bool {
+        self.content.is_none() && self.notify.is_none()
+    }
+}
+
+impl<'a> IntoIterator for DualEvents<'a> {
+    type Item = EventResult<'a>;
+    type IntoIter = Chain<Flatten<option::IntoIter<Events<'a>>>, Flatten<option::IntoIter<Events<'a>>>>;
+    
+    fn into_iter(self) -> Self::IntoIter {
+        self.content.into_iter().flatten().chain(self.notify.into_iter().flatten())
+    }
+}
+
+impl<'a> IntoEvents<'a> for DualEvents<'a> {}
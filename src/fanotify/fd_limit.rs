@@ -0,0 +1,278 @@
+//! Detecting and handling running out of fds while reading events.
+//!
+//! The kernel opens a new fd for every notification event it reads (without [`FID`](crate::init::Flags::REPORT_FID)),
+//! so a process at its `RLIMIT_NOFILE` (or a system at its `fs.file-max`) can't read events.
+//! The read fails with `EMFILE` (or `ENFILE`), and the event it couldn't open an fd for is dropped,
+//! which otherwise looks like any other transient read error.
+//!
+//! [`BufferedFanotify::read_monitored`] classifies these into an [`FdLimitError`]
+//! and asks an [`FdLimitStrategy`] what to do about it,
+//! e.g. back off until fds are closed, or switch to [`CloseOnParse`] so fewer fds are held at once.
+
+use std::fs;
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use nix::errno::Errno;
+
+use crate::event::events::Events;
+use crate::event::file::resolved::CloseOnParse;
+use crate::proc;
+
+use super::buffered_fanotify::BufferedFanotify;
+
+/// Which fd limit was hit.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Limit {
+    /// This process's `RLIMIT_NOFILE`, i.e. `EMFILE`.
+    Process,
+    /// The system-wide `fs.file-max`, i.e. `ENFILE`.
+    System,
+}
+
+impl Limit {
+    /// The [`Limit`] an [`Errno`] means was hit, if any.
+    pub fn of(errno: Errno) -> Option<Self> {
+        match errno {
+            Errno::EMFILE => Some(Self::Process),
+            Errno::ENFILE => Some(Self::System),
+            _ => None,
+        }
+    }
+    
+    /// The [`Limit`] an [`io::Error`] means was hit, if any.
+    pub fn of_io(error: &io::Error) -> Option<Self> {
+        Self::of(Errno::from_i32(error.raw_os_error()?))
+    }
+}
+
+/// How many fds this process has open, and its `RLIMIT_NOFILE`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct FdUsage {
+    pub open: u64,
+    pub soft_limit: u64,
+    pub hard_limit: u64,
+}
+
+impl FdUsage {
+    /// The current [`FdUsage`], counting the fds in `/proc/self/fd`.
+    pub fn current() -> io::Result<Self> {
+        let (soft_limit, hard_limit) = nofile_limit()?;
+        let dir = fs::read_dir(proc::self_fd_dir()?)?;
+        // the directory being read is itself an open fd
+        let open = dir.count().saturating_sub(1) as u64;
+        Ok(Self {
+            open,
+            soft_limit,
+            hard_limit,
+        })
+    }
+    
+    /// How many more fds can be opened before hitting the soft limit.
+    ///
+    /// This is only approximate, since the kernel actually checks the number of the lowest free fd
+    /// against the limit, so fds numbered above the limit (e.g. opened before it was lowered)
+    /// make this an underestimate.
+    pub fn headroom(&self) -> u64 {
+        self.soft_limit.saturating_sub(self.open)
+    }
+    
+    /// The fraction of the soft limit in use.
+    pub fn utilization(&self) -> f64 {
+        if self.soft_limit == 0 {
+            return 1.0;
+        }
+        self.open as f64 / self.soft_limit as f64
+    }
+}
+
+/// The soft and hard `RLIMIT_NOFILE`.
+fn nofile_limit() -> io::Result<(u64, u64)> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((limit.rlim_cur, limit.rlim_max))
+}
+
+/// A read that failed because an fd [`Limit`] was hit.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct FdExhausted {
+    pub limit: Limit,
+    /// The [`FdUsage`] when the read failed, if it could be determined.
+    pub usage: Option<FdUsage>,
+    /// How many reads in a row have failed this way, starting at 1.
+    pub attempt: u32,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum FdLimitError {
+    #[error("out of fds ({:?} limit, {:?}) after {} reads", .0.limit, .0.usage, .0.attempt)]
+    Exhausted(FdExhausted),
+    #[error("{}", .0)]
+    Io(#[from] io::Error),
+}
+
+impl From<FdLimitError> for io::Error {
+    fn from(e: FdLimitError) -> Self {
+        match e {
+            FdLimitError::Exhausted(exhausted) => Self::from_raw_os_error(match exhausted.limit {
+                Limit::Process => libc::EMFILE,
+                Limit::System => libc::ENFILE,
+            }),
+            FdLimitError::Io(e) => e,
+        }
+    }
+}
+
+/// What to do after a read failed with [`FdExhausted`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum FdAction {
+    /// Sleep and then read again, hoping fds have been closed in the meantime.
+    Backoff(Duration),
+    /// Switch to [`CloseOnParse`] (see [`Fanotify::set_close_on_parse`](crate::fanotify::Fanotify::set_close_on_parse))
+    /// so that events hold fewer fds, and read again.
+    CloseOnParse(CloseOnParse),
+    /// Give up and return the [`FdLimitError::Exhausted`].
+    Fail,
+}
+
+/// Decides what to do when reading events runs out of fds.
+pub trait FdLimitStrategy {
+    fn on_exhausted(&mut self, exhausted: &FdExhausted) -> FdAction;
+}
+
+impl<F: FnMut(&FdExhausted) -> FdAction> FdLimitStrategy for F {
+    fn on_exhausted(&mut self, exhausted: &FdExhausted) -> FdAction {
+        self(exhausted)
+    }
+}
+
+/// The default [`FdLimitStrategy`]: exponential backoff,
+/// switching to [`CloseOnParse`] after a few attempts, and eventually failing.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    /// Fail on this attempt.
+    pub max_attempts: u32,
+    /// Switch to [`CloseOnParse::path`] on this attempt (once), if any.
+    pub close_on_parse_at: Option<u32>,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(1),
+            max: Duration::from_secs(1),
+            max_attempts: 16,
+            close_on_parse_at: Some(4),
+        }
+    }
+}
+
+impl FdLimitStrategy for Backoff {
+    fn on_exhausted(&mut self, exhausted: &FdExhausted) -> FdAction {
+        let attempt = exhausted.attempt;
+        if attempt >= self.max_attempts {
+            return FdAction::Fail;
+        }
+        if self.close_on_parse_at == Some(attempt) {
+            return FdAction::CloseOnParse(CloseOnParse::path());
+        }
+        let backoff = self.initial
+            .checked_mul(1 << (attempt - 1).min(31))
+            .unwrap_or(self.max)
+            .min(self.max);
+        FdAction::Backoff(backoff)
+    }
+}
+
+/// Tracks reads that ran out of fds and handles them with an [`FdLimitStrategy`].
+#[derive(Debug, Default)]
+pub struct FdLimitMonitor<S = Backoff> {
+    strategy: S,
+    exhausted_reads: u64,
+    backoff: Duration,
+    last: Option<FdExhausted>,
+}
+
+impl<S: FdLimitStrategy> FdLimitMonitor<S> {
+    pub fn new(strategy: S) -> Self {
+        Self {
+            strategy,
+            exhausted_reads: 0,
+            backoff: Duration::ZERO,
+            last: None,
+        }
+    }
+    
+    pub fn strategy(&self) -> &S {
+        &self.strategy
+    }
+    
+    pub fn strategy_mut(&mut self) -> &mut S {
+        &mut self.strategy
+    }
+    
+    /// How many reads failed from running out of fds,
+    /// each of which means a notification event was dropped by the kernel.
+    pub fn exhausted_reads(&self) -> u64 {
+        self.exhausted_reads
+    }
+    
+    /// The total time spent backing off.
+    pub fn backoff(&self) -> Duration {
+        self.backoff
+    }
+    
+    /// The last read that ran out of fds.
+    pub fn last(&self) -> Option<FdExhausted> {
+        self.last
+    }
+}
+
+impl BufferedFanotify {
+    /// Like [`BufferedFanotify::read`], but if the read runs out of fds (`EMFILE` or `ENFILE`),
+    /// let the `monitor`'s [`FdLimitStrategy`] decide what to do instead of just returning the error.
+    pub fn read_monitored<S: FdLimitStrategy>(
+        &mut self,
+        monitor: &mut FdLimitMonitor<S>,
+    ) -> Result<Events<'_>, FdLimitError> {
+        let mut attempt = 0;
+        loop {
+            let errno = match Events::read_raw(&self.fanotify, &mut self.buffer.events) {
+                Ok(()) => break,
+                Err(errno) => errno,
+            };
+            self.stats.record_and_check(&Err(io::Error::from(errno)), &mut self.lag_warning);
+            let limit = match Limit::of(errno) {
+                Some(limit) => limit,
+                None => return Err(io::Error::from(errno).into()),
+            };
+            attempt += 1;
+            monitor.exhausted_reads += 1;
+            let exhausted = FdExhausted {
+                limit,
+                usage: FdUsage::current().ok(),
+                attempt,
+            };
+            monitor.last = Some(exhausted);
+            match monitor.strategy.on_exhausted(&exhausted) {
+                FdAction::Backoff(duration) => {
+                    monitor.backoff += duration;
+                    thread::sleep(duration);
+                }
+                FdAction::CloseOnParse(policy) => self.fanotify.set_close_on_parse(Some(policy)),
+                FdAction::Fail => return Err(FdLimitError::Exhausted(exhausted)),
+            }
+        }
+        let events = Ok(Events::from_buffer(&self.fanotify, &mut self.buffer));
+        self.stats.record_and_check(&events, &mut self.lag_warning);
+        events.map_err(FdLimitError::from)
+    }
+}
@@ -0,0 +1,131 @@
+//! Passing a [`Fanotify`] group's fd to another process,
+//! so that a privileged process can create the group and add its marks,
+//! and an unprivileged one can read and respond to its events.
+//!
+//! [`Fanotify::send_to`] sends the fd and its [`RawInit`] over a unix socket with `SCM_RIGHTS`,
+//! and [`Fanotify::receive_from`] receives and [verifies](Fanotify::verify_init) them on the other end.
+//! For an fd received some other way, like from systemd's socket activation or fd store
+//! (see `systemd::listen_fds` with the `systemd` feature), use [`Fanotify::from_received_fd`].
+
+use std::convert::TryInto;
+use std::io;
+use std::mem;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::OwnedFd;
+use std::os::unix::io::RawFd;
+
+use nix::sys::socket::recvmsg;
+use nix::sys::socket::sendmsg;
+use nix::sys::socket::ControlMessage;
+use nix::sys::socket::ControlMessageOwned;
+use nix::sys::socket::MsgFlags;
+use nix::sys::uio::IoVec;
+
+use crate::init::RawInit;
+
+use super::verify::InitVerifyError;
+use super::Fanotify;
+
+/// The size of a [`RawInit`] as it's sent: its flags and then its event flags, in native byte order.
+const INIT_LEN: usize = 2 * mem::size_of::<u32>();
+
+pub(crate) fn nix_to_io(error: nix::Error) -> io::Error {
+    match error.as_errno() {
+        Some(errno) => io::Error::from_raw_os_error(errno as i32),
+        None => io::Error::other(error.to_string()),
+    }
+}
+
+/// Send `data` and `fd` (with `SCM_RIGHTS`) in one message over the unix `socket`.
+pub(crate) fn send_fd(socket: RawFd, data: &[u8], fd: RawFd) -> io::Result<()> {
+    let fds = [fd];
+    let sent = sendmsg(
+        socket,
+        &[IoVec::from_slice(data)],
+        &[ControlMessage::ScmRights(&fds)],
+        MsgFlags::empty(),
+        None,
+    )
+    .map_err(nix_to_io)?;
+    // it's one message, so it's either fully sent or not at all for datagrams,
+    // and even a stream socket doesn't split such a small one
+    if sent != data.len() {
+        return Err(io::Error::new(io::ErrorKind::WriteZero, "fd message was only partially sent"));
+    }
+    Ok(())
+}
+
+/// An error from [`Fanotify::receive_from`].
+#[derive(thiserror::Error, Debug)]
+pub enum ReceiveError {
+    #[error("{}", .0)]
+    Io(#[from] io::Error),
+    #[error("expected a fanotify fd and its init flags, but received {} bytes and {} fds", .bytes, .fds)]
+    Malformed { bytes: usize, fds: usize },
+    /// More data or fds were sent than fit, so some of them were discarded by the kernel.
+    #[error("the message with the fanotify fd was truncated")]
+    Truncated,
+    #[error("{}", .0)]
+    Verify(#[from] InitVerifyError),
+}
+
+impl Fanotify {
+    /// Create a [`Fanotify`] from an fd received from another process, after [verifying](Fanotify::verify_init)
+    /// that `init` matches it, since a mismatched [`RawInit`] would mis-parse its events.
+    ///
+    /// This is just [`Fanotify::try_from_owned`].
+    pub fn from_received_fd(fd: OwnedFd, init: RawInit) -> Result<Self, InitVerifyError> {
+        Self::try_from_owned(fd, init)
+    }
+    
+    /// Send this group's fd and [`RawInit`] over a connected unix `socket` (stream or datagram)
+    /// to be received by [`Fanotify::receive_from`].
+    ///
+    /// The fd is duplicated, so this group can still be used (or dropped) afterwards,
+    /// though both processes then share the same group and its queue of events.
+    pub fn send_to(&self, socket: &impl AsRawFd) -> io::Result<()> {
+        let mut data = [0; INIT_LEN];
+        data[..4].copy_from_slice(&self.init.flags.to_ne_bytes());
+        data[4..].copy_from_slice(&self.init.event_flags.to_ne_bytes());
+        send_fd(socket.as_raw_fd(), &data, self.as_raw_fd())
+    }
+    
+    /// Receive a group sent by [`Fanotify::send_to`] over a unix `socket`,
+    /// and [verify](Fanotify::verify_init) it.
+    ///
+    /// The received fd is close-on-exec.
+    pub fn receive_from(socket: &impl AsRawFd) -> Result<Self, ReceiveError> {
+        let mut data = [0; INIT_LEN];
+        let mut cmsgs = nix::cmsg_space!([RawFd; 1]);
+        let message = recvmsg(
+            socket.as_raw_fd(),
+            &[IoVec::from_mut_slice(&mut data)],
+            Some(&mut cmsgs),
+            MsgFlags::MSG_CMSG_CLOEXEC,
+        )
+        .map_err(nix_to_io)?;
+        // take ownership of every received fd right away, so any extra ones are closed
+        let fds = message
+            .cmsgs()
+            .flat_map(|cmsg| match cmsg {
+                ControlMessageOwned::ScmRights(fds) => fds,
+                _ => Vec::new(),
+            })
+            .map(|fd| unsafe { OwnedFd::from_raw_fd(fd) })
+            .collect::<Vec<_>>();
+        if message.flags.intersects(MsgFlags::MSG_TRUNC | MsgFlags::MSG_CTRUNC) {
+            return Err(ReceiveError::Truncated);
+        }
+        let bytes = message.bytes;
+        if bytes != INIT_LEN || fds.len() != 1 {
+            return Err(ReceiveError::Malformed { bytes, fds: fds.len() });
+        }
+        let init = RawInit {
+            flags: u32::from_ne_bytes(data[..4].try_into().unwrap()),
+            event_flags: u32::from_ne_bytes(data[4..].try_into().unwrap()),
+        };
+        let fd = fds.into_iter().next().unwrap();
+        Ok(Self::from_received_fd(fd, init)?)
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::io;
+use std::mem;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+
+use async_io::Timer;
+
+use crate::event::events::Events;
+use crate::mark::Mask;
+
+use super::async_fd::AsyncFdWrapper;
+use super::buffered_fanotify::AsyncBufferedFanotify;
+
+/// All of the [`Event`](crate::event::event::Event)s for one path during one window of a [`GroupByPath`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PathGroup {
+    pub path: PathBuf,
+    /// The union of the [`Mask`]s of the events.
+    pub mask: Mask,
+    /// The number of events.
+    pub count: usize,
+}
+
+/// The [`PathGroup`]s of the current window, in the order each path was first seen.
+#[derive(Debug, Default)]
+struct Groups {
+    groups: Vec<PathGroup>,
+    indices: HashMap<PathBuf, usize>,
+    skipped: usize,
+}
+
+impl Groups {
+    fn add(&mut self, events: Events<'_>) {
+        for event in events {
+            let (mask, path) = match event {
+                Ok(event) => match event.file().path() {
+                    Some(Ok(path)) => (event.mask(), path),
+                    _ => {
+                        self.skipped += 1;
+                        continue;
+                    }
+                },
+                Err(_) => {
+                    self.skipped += 1;
+                    continue;
+                }
+            };
+            match self.indices.get(&path) {
+                Some(&i) => {
+                    let group = &mut self.groups[i];
+                    group.mask |= mask;
+                    group.count += 1;
+                }
+                None => {
+                    self.indices.insert(path.clone(), self.groups.len());
+                    self.groups.push(PathGroup { path, mask, count: 1 });
+                }
+            }
+        }
+    }
+    
+    fn take(&mut self) -> Vec<PathGroup> {
+        self.indices.clear();
+        mem::take(&mut self.groups)
+    }
+}
+
+/// An async stream of [`PathGroup`]s, one per path per window,
+/// for consumers like build watchers that only need to know which files changed and how.
+///
+/// A window starts with the first read after the previous one,
+/// and then events are read until `window` has passed.
+///
+/// Events without a path (like [`FID`](crate::event::file::File::FID) events)
+/// and errors are skipped and only [counted](GroupByPath::skipped).
+/// Permission events are grouped like any other, and then allowed when dropped.
+///
+/// Created by [`AsyncBufferedFanotify::group_by_path`].
+pub struct GroupByPath<'f, W: AsyncFdWrapper> {
+    fanotify: &'f mut AsyncBufferedFanotify<W>,
+    window: Duration,
+    groups: Groups,
+}
+
+impl<W: AsyncFdWrapper> GroupByPath<'_, W> {
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+    
+    /// The number of events skipped so far, because they had no path or were errors.
+    pub fn skipped(&self) -> usize {
+        self.groups.skipped
+    }
+    
+    /// Wait for the next window with any paths and return its [`PathGroup`]s,
+    /// in the order each path was first seen.
+    pub async fn next(&mut self) -> io::Result<Vec<PathGroup>> {
+        loop {
+            let events = self.fanotify.read().await?;
+            let deadline = Instant::now() + self.window;
+            self.groups.add(events);
+            while let Some(events) = self.fanotify.read_cancellable(Timer::at(deadline)).await? {
+                self.groups.add(events);
+            }
+            let groups = self.groups.take();
+            if !groups.is_empty() {
+                return Ok(groups);
+            }
+        }
+    }
+}
+
+impl<W: AsyncFdWrapper> AsyncBufferedFanotify<W> {
+    /// Group the events read in each `window` by path.  See [`GroupByPath`].
+    pub fn group_by_path(&mut self, window: Duration) -> GroupByPath<'_, W> {
+        GroupByPath {
+            fanotify: self,
+            window,
+            groups: Groups::default(),
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::io;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+use crate::event::owned::OwnedEvent;
+use crate::event::owned::OwnedEventResult;
+use crate::mark::Mask;
+
+use super::buffered_fanotify::BufferedFanotify;
+use super::pause::Pause;
+use super::pause::PauseMode;
+
+/// An event delivered by a [`SubscriptionHub`], shared by all the subscribers it matched.
+pub type SharedEvent = Arc<OwnedEventResult>;
+
+/// What a subscriber of a [`SubscriptionHub`] wants to receive.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Interest {
+    /// Only events intersecting this [`Mask`].
+    pub mask: Mask,
+    /// Only events on paths under one of these (absolute and canonical) prefixes, or on any path if empty.
+    pub paths: Vec<PathBuf>,
+}
+
+impl Interest {
+    /// An [`Interest`] in events intersecting `mask` on any path.
+    pub fn new(mask: Mask) -> Self {
+        Self {
+            mask,
+            paths: Vec::new(),
+        }
+    }
+    
+    /// Also only events on paths under `prefix`, in addition to any other prefixes.
+    pub fn path(mut self, prefix: impl Into<PathBuf>) -> Self {
+        self.paths.push(prefix.into());
+        self
+    }
+}
+
+struct Subscriber {
+    interest: Interest,
+    sender: Sender<SharedEvent>,
+}
+
+/// Shares one [`BufferedFanotify`] group among many in-process subscribers,
+/// so that every component doesn't have to open its own group.
+///
+/// Each subscriber registers an [`Interest`] and gets its own channel,
+/// over which it receives only the matching events, as [`SharedEvent`]s,
+/// while the hub does the single kernel read loop (see [`SubscriptionHub::run`]).
+/// The group still has to be marked for the union of the [`Interest`]s' [`Mask`]s,
+/// i.e. [`SubscriptionHub::mask`].
+///
+/// Errors, like a queue overflow, are delivered to every subscriber,
+/// and events whose paths can't be resolved, like [`FID`](crate::event::owned::OwnedFile::FID) events,
+/// are delivered to every subscriber with a matching [`Mask`], regardless of its [`Interest::paths`].
+///
+/// Subscribers can't respond to permission events, since they're shared,
+/// so each one is allowed as soon as it's read, before it's delivered,
+/// so that a slow subscriber can't leave the process that triggered it blocked.
+/// The channels are unbounded, so a slow subscriber doesn't block the others, either.
+///
+/// Reading can be [paused](SubscriptionHub::pause) without losing any marks or subscribers.
+pub struct SubscriptionHub {
+    fanotify: BufferedFanotify,
+    subscribers: Vec<Subscriber>,
+    delivered: u64,
+    pause: Pause,
+}
+
+impl SubscriptionHub {
+    pub fn new(fanotify: BufferedFanotify) -> Self {
+        Self {
+            fanotify,
+            subscribers: Vec::new(),
+            delivered: 0,
+            pause: Pause::new(),
+        }
+    }
+    
+    pub fn fanotify(&self) -> &BufferedFanotify {
+        &self.fanotify
+    }
+    
+    pub fn into_fanotify(self) -> BufferedFanotify {
+        self.fanotify
+    }
+    
+    /// Subscribe to the events matching `interest`,
+    /// which are received over the returned channel.
+    ///
+    /// Drop the [`Receiver`] to unsubscribe.
+    pub fn subscribe(&mut self, interest: Interest) -> Receiver<SharedEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(Subscriber { interest, sender });
+        receiver
+    }
+    
+    /// The number of subscribers, including ones that have unsubscribed
+    /// but haven't been sent an event since.
+    pub fn subscribers(&self) -> usize {
+        self.subscribers.len()
+    }
+    
+    /// The union of all the subscribers' [`Mask`]s, which the group should be marked for.
+    pub fn mask(&self) -> Mask {
+        self.subscribers
+            .iter()
+            .fold(Mask::empty(), |mask, it| mask | it.interest.mask)
+    }
+    
+    /// The total number of events sent to subscribers, counting an event once per subscriber.
+    pub fn delivered(&self) -> u64 {
+        self.delivered
+    }
+    
+    /// Pause reading in `mode`.  See [`Pause::pause`].
+    pub fn pause(&self, mode: PauseMode) {
+        self.pause.pause(mode);
+    }
+    
+    /// Resume reading.  See [`Pause::resume`].
+    pub fn resume(&self) {
+        self.pause.resume();
+    }
+    
+    /// A [`Pause`] handle to pause and resume this hub from another thread,
+    /// e.g. while it's [running](SubscriptionHub::run).
+    pub fn pause_handle(&self) -> Pause {
+        self.pause.clone()
+    }
+    
+    /// Read a batch of events and deliver each one to the matching subscribers,
+    /// returning the number of events read.
+    ///
+    /// Permission events are allowed before they're delivered,
+    /// and if any response couldn't be written, its error is returned after delivering all of them.
+    ///
+    /// Subscribers that have unsubscribed are removed when an event for them is delivered.
+    /// This blocks, including while [paused](SubscriptionHub::pause)
+    /// (see [`BufferedFanotify::read_or_pause`]).
+    pub fn read(&mut self) -> io::Result<usize> {
+        let mut owned = Vec::new();
+        let mut result = self.fanotify.read_or_pause(&self.pause)?.drain_to(&mut owned);
+        let len = owned.len();
+        for mut event in owned {
+            if let Some(ticket) = event.as_mut().ok().and_then(OwnedEvent::permission_mut) {
+                ticket.allow();
+                if let Err(errno) = ticket.respond() {
+                    result = result.and(Err(errno));
+                }
+            }
+            self.deliver(Arc::new(event));
+        }
+        result.map_err(|errno| io::Error::from_raw_os_error(errno as i32))?;
+        Ok(len)
+    }
+    
+    fn deliver(&mut self, event: SharedEvent) {
+        let mut path = None;
+        let mut delivered = 0;
+        self.subscribers.retain(|subscriber| {
+            let interest = &subscriber.interest;
+            let matches = match &*event {
+                Err(_) => true,
+                Ok(event) if !event.mask().intersects(interest.mask) => false,
+                Ok(_) if interest.paths.is_empty() => true,
+                Ok(event) => {
+                    // resolve the path only once, and only if some subscriber filters by it
+                    let path = path.get_or_insert_with(|| event.file().path().and_then(Result::ok));
+                    match path {
+                        None => true,
+                        Some(path) => interest.paths.iter().any(|it| path.starts_with(it)),
+                    }
+                }
+            };
+            if !matches {
+                return true;
+            }
+            let sent = subscriber.sender.send(event.clone()).is_ok();
+            if sent {
+                delivered += 1;
+            }
+            sent
+        });
+        self.delivered += delivered;
+    }
+    
+    /// [`SubscriptionHub::read`] until every subscriber has unsubscribed.
+    ///
+    /// Unsubscribing is only noticed after the next event for that subscriber,
+    /// so this may keep blocking in a read for a while after the last one unsubscribes.
+    pub fn run(&mut self) -> io::Result<()> {
+        while !self.subscribers.is_empty() {
+            self.read()?;
+        }
+        Ok(())
+    }
+}
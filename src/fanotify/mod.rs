@@ -1,14 +1,29 @@
 use std::convert::TryFrom;
+use std::fs;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
 use std::io;
+use std::os::unix::io::AsFd;
 use std::os::unix::io::AsRawFd;
+use std::os::unix::io::BorrowedFd;
 use std::os::unix::io::FromRawFd;
 use std::os::unix::io::IntoRawFd;
 use std::os::unix::io::RawFd;
+use std::sync::Arc;
+use std::time::Duration;
 
 use nix::errno::Errno;
 
+use crate::clock;
+use crate::clock::Clock;
 use crate::event::buffer::EventBuffer;
+use crate::event::buffer::EventBufferSize;
 use crate::event::events::Events;
+use crate::event::file::resolved::CloseOnParse;
+use crate::event::responses::FlushPolicy;
+use crate::event::sequence::SequenceCounter;
 use crate::fd::FD;
 use crate::init;
 use crate::init::Flags;
@@ -18,22 +33,174 @@ use crate::init::RawInit;
 use crate::libc::call::SysCall;
 use crate::mark;
 use crate::mark::Action::Add;
+use crate::mark::Action::Flush;
 use crate::mark::Action::Remove;
 use crate::mark::FanotifyMark;
 use crate::mark::Mark;
 use crate::mark::Markable;
+use crate::mark::Mask;
+use crate::mark::MarkRegistry;
+use crate::mark::ReapplyReport;
 
 pub mod buffered_fanotify;
+#[cfg(feature = "async")]
 pub mod async_fanotify;
+#[cfg(feature = "async")]
+pub mod async_fd;
+pub mod subtree;
+pub mod pipeline;
+pub mod self_test;
+pub mod stats;
+pub mod router;
+pub mod scoped;
+pub mod own_outputs;
+pub mod suppress;
+pub mod privilege;
+#[cfg(feature = "async")]
+pub mod group_by_path;
+pub mod verify;
+pub mod fd_passing;
+pub mod broker;
+pub mod drain;
+pub mod dual;
+pub mod fd_limit;
+pub mod adaptive;
+pub mod session;
+pub mod hub;
+pub mod pause;
 
 /// The main [`Fanotify`] struct, the primary entry point to the fanotify API.
-#[derive(Debug)]
 pub struct Fanotify {
     /// The fanotify descriptor/group.
     pub(super) fd: FD,
     
     /// The flags used to initialize it.
     pub(super) init: RawInit,
+    
+    /// An optional name to tell groups apart in logs and errors.
+    name: Option<String>,
+    
+    /// When to flush buffered permission responses early.
+    flush_policy: FlushPolicy,
+    
+    /// If notification events' fds are closed right after parsing.
+    close_on_parse: Option<CloseOnParse>,
+    
+    /// Times permission latencies and the [`FlushPolicy::max_delay`] of buffered responses.
+    clock: Arc<dyn Clock>,
+    
+    /// If marks' paths are [prechecked](Mark::precheck) before marking them.
+    precheck: bool,
+    
+    /// Numbers the batches and events read, see [`Sequence`](crate::event::sequence::Sequence).
+    pub(crate) sequence: SequenceCounter,
+}
+
+impl Debug for Fanotify {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Fanotify");
+        if let Some(name) = &self.name {
+            debug.field("name", name);
+        }
+        debug
+            .field("fd", &self.fd)
+            .field("init", &self.init)
+            .finish()
+    }
+}
+
+impl Display for Fanotify {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "fanotify group {:?} (fd {})", name, self.as_raw_fd()),
+            None => write!(f, "fanotify group (fd {})", self.as_raw_fd()),
+        }
+    }
+}
+
+impl Fanotify {
+    /// Give this [`Fanotify`] group a name, shown in its [`Debug`] and [`Display`] impls,
+    /// [`mark::Error`]s, and (with the `tracing` feature) tracing spans.
+    ///
+    /// This is purely for telling multiple groups apart, e.g. in logs.
+    pub fn named(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+    
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    
+    pub fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+    
+    /// Set the [`FlushPolicy`] for buffered permission responses from future reads.
+    pub fn with_flush_policy(mut self, flush_policy: FlushPolicy) -> Self {
+        self.flush_policy = flush_policy;
+        self
+    }
+    
+    pub fn flush_policy(&self) -> FlushPolicy {
+        self.flush_policy
+    }
+    
+    pub fn set_flush_policy(&mut self, flush_policy: FlushPolicy) {
+        self.flush_policy = flush_policy;
+    }
+    
+    /// Close notification events' fds right after parsing them from future reads,
+    /// after resolving what `close_on_parse` says, or [`None`] to leave them open (the default).
+    pub fn with_close_on_parse(mut self, close_on_parse: Option<CloseOnParse>) -> Self {
+        self.close_on_parse = close_on_parse;
+        self
+    }
+    
+    pub fn close_on_parse(&self) -> Option<CloseOnParse> {
+        self.close_on_parse
+    }
+    
+    pub fn set_close_on_parse(&mut self, close_on_parse: Option<CloseOnParse>) {
+        self.close_on_parse = close_on_parse;
+    }
+    
+    /// Time permission [latencies](crate::event::file::permission::FilePermission::latency)
+    /// and the [`FlushPolicy::max_delay`] of buffered responses with `clock`
+    /// instead of the [system](clock::SystemClock) one.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+    
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+    
+    /// [`Precheck`](Mark::precheck) marks' paths before marking them,
+    /// failing with a descriptive [`mark::RawError::Path`] instead of the kernel's errno.
+    /// This costs a `stat` or two per mark, so it's off by default.
+    pub fn with_precheck(mut self, precheck: bool) -> Self {
+        self.precheck = precheck;
+        self
+    }
+    
+    pub fn precheck(&self) -> bool {
+        self.precheck
+    }
+    
+    pub fn set_precheck(&mut self, precheck: bool) {
+        self.precheck = precheck;
+    }
+    
+    /// The [`RawInit`] flags this group was created with.
+    pub fn init(&self) -> RawInit {
+        self.init
+    }
 }
 
 impl AsRawFd for Fanotify {
@@ -42,6 +209,12 @@ impl AsRawFd for Fanotify {
     }
 }
 
+impl AsFd for Fanotify {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
 impl IntoRawFd for Fanotify {
     fn into_raw_fd(self) -> RawFd {
         self.fd.into_raw_fd()
@@ -54,12 +227,21 @@ impl Fanotify {
     /// Thus, we provide this analogous unsafe API for constructing a [`Fanotify`] from a [`RawFd`]
     /// and the corresponding [`RawInit`] flags used to create the [`RawFd`].
     ///
+    /// It's public as [`raw::fanotify_from_raw_fd`](crate::raw::fanotify_from_raw_fd),
+    /// and [`Fanotify::try_from_owned`] is the safe alternative.
+    ///
     /// # Safety
     /// See [`FromRawFd`].
-    pub unsafe fn from_raw_fd(fd: RawFd, init: RawInit) -> Self {
+    pub(crate) unsafe fn from_raw_fd(fd: RawFd, init: RawInit) -> Self {
         Self {
             fd: FD::from_raw_fd(fd),
             init,
+            name: None,
+            flush_policy: FlushPolicy::default(),
+            close_on_parse: None,
+            clock: clock::system(),
+            precheck: false,
+            sequence: SequenceCounter::default(),
         }
     }
 }
@@ -90,7 +272,48 @@ impl Init {
                 _ => error.impossible(),
             })
             .and_then(|fd| if fd.check() { Ok(fd) } else { Err(InvalidFd { fd }) })
-            .map(|fd| Fanotify { fd, init: self.as_raw() })
+            .map(|fd| Fanotify {
+                fd,
+                init: self.as_raw(),
+                name: None,
+                flush_policy: FlushPolicy::default(),
+                close_on_parse: None,
+                clock: clock::system(),
+                precheck: false,
+                sequence: SequenceCounter::default(),
+            })
+    }
+}
+
+impl Fanotify {
+    /// Create a new, separate [`Fanotify`] group with the same [`RawInit`] flags, name, and [`FlushPolicy`],
+    /// e.g. to replace this one after a fatal error.
+    ///
+    /// The new group has no marks, since they aren't shared between groups.
+    /// See [`Fanotify::recreate_with_marks`] to re-apply them, too.
+    pub fn recreate(&self) -> Result<Fanotify, init::Error> {
+        let mut fanotify = self.init.undo_raw().to_fanotify()?;
+        fanotify.name = self.name.clone();
+        fanotify.flush_policy = self.flush_policy;
+        fanotify.close_on_parse = self.close_on_parse;
+        fanotify.clock = self.clock.clone();
+        fanotify.precheck = self.precheck;
+        fanotify.sequence = SequenceCounter::continuing(&self.sequence);
+        Ok(fanotify)
+    }
+    
+    /// [`Recreate`](Fanotify::recreate) this [`Fanotify`] group
+    /// and re-apply all the marks recorded in the [`MarkRegistry`] to it,
+    /// only replacing this group once the new one is fully set up.
+    ///
+    /// If creating the new group fails, this one is left unchanged.
+    /// Otherwise, it's replaced even if some of the marks failed,
+    /// which are reported in the returned [`ReapplyReport`].
+    pub fn recreate_with_marks(&mut self, registry: &MarkRegistry) -> Result<ReapplyReport, init::Error> {
+        let fanotify = self.recreate()?;
+        let report = registry.reapply(&fanotify);
+        *self = fanotify;
+        Ok(report)
     }
 }
 
@@ -108,11 +331,9 @@ impl Fanotify {
     fn mark_raw_error(&self, mark: &Mark) -> Result<(), mark::RawError> {
         use crate::mark::RawError::*;
         use Errno::*;
-        let init = self.init.undo_raw();
-        if mark.mask.includes_permission() && init.notification_class == Notify {
-            // man page also says to include || init.flags & Flags::REPORT_FID,
-            // but that requires init.notification_class == Notify itself
-            return Err(InvalidArgument);
+        self.check_static(mark)?;
+        if self.precheck {
+            mark.precheck()?;
         }
         FanotifyMark {
             fanotify: self,
@@ -136,10 +357,62 @@ impl Fanotify {
     }
 }
 
+impl Fanotify {
+    /// The static checks for a [`Mark`], done before actually calling `fanotify_mark`.
+    fn check_static(&self, mark: &Mark) -> Result<(), mark::RawError> {
+        use crate::mark::RawError::*;
+        let init = self.init.undo_raw();
+        if mark.mask.includes_permission() && init.notification_class == Notify {
+            // man page also says to include || init.flags & Flags::REPORT_FID,
+            // but that requires init.notification_class == Notify itself
+            return Err(PermissionOnNotifyGroup { mask: mark.mask });
+        }
+        Ok(())
+    }
+    
+    /// The main method behind [`Markable::check`], only it returns just a [`mark::RawError`].
+    fn check_raw_error(&self, mark: &Mark) -> Result<(), mark::RawError> {
+        use crate::mark::RawError::*;
+        self.check_static(mark)?;
+        if mark.action == Flush {
+            return Ok(());
+        }
+        if self.precheck {
+            mark.precheck()?;
+        }
+        let supported = crate::supported::cached();
+        let init = self.init.undo_raw();
+        let needs_fid = !Mask::reportable_with_fd().contains(mark.mask);
+        if !supported.supports_what(mark.what)
+            || !supported.supports_mask(mark.mask)
+            || (needs_fid && !init.flags.contains(Flags::REPORT_FID)) {
+            return Err(FeatureUnsupported);
+        }
+        let path = mark.path.resolve();
+        let metadata = if mark.flags.contains(mark::Flags::DONT_FOLLOW) {
+            fs::symlink_metadata(&path)
+        } else {
+            fs::metadata(&path)
+        };
+        match metadata {
+            Err(_) if mark.action == Add => Err(PathDoesNotExist),
+            Ok(metadata) if mark.flags.contains(mark::Flags::ONLY_DIR) && !metadata.is_dir() => Err(NotADirectory),
+            _ => Ok(()),
+        }
+    }
+}
+
 impl Markable for Fanotify {
     fn mark<'a>(&self, mark: Mark<'a>) -> Result<(), mark::Error<'a>> {
         self.mark_raw_error(&mark)
-            .map_err(|error| mark::Error { error, mark })
+            .map_err(|error| mark::Error::new(error, mark).in_group(self.name.clone()))
+    }
+    
+    fn check<'a>(&self, mark: Mark<'a>) -> Result<Mark<'a>, mark::Error<'a>> {
+        match self.check_raw_error(&mark) {
+            Ok(()) => Ok(mark),
+            Err(error) => Err(mark::Error::new(error, mark).in_group(self.name.clone())),
+        }
     }
 }
 
@@ -148,11 +421,71 @@ impl Fanotify {
     ///
     /// Return an [`Events`] iterator over the individual events.
     ///
+    /// There is no multi-buffer version of this using [`FD::read_vectored`],
+    /// because the kernel only implements plain `read` for fanotify,
+    /// so `readv` reads whole events into each buffer separately
+    /// and stops at the first buffer that isn't filled exactly.
+    /// To read larger batches, grow the [`EventBuffer`] instead.
+    ///
     /// This method blocks.
     pub fn read<'a>(&'a self, buffer: &'a mut EventBuffer) -> io::Result<Events<'a>> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("fanotify_read", group = %self).entered();
         let events = Events::read(self, buffer)?;
         Ok(events)
     }
+    
+    /// Like [`Fanotify::read`], but after the first (blocking) read,
+    /// keep reading without blocking until no more events are pending,
+    /// concatenating all of them into the `buffer` (growing it as needed).
+    ///
+    /// This drains a whole burst of events at once,
+    /// so it can be handled before doing any expensive downstream work.
+    /// It stops once at least `max_bytes` have been read, though,
+    /// so that events arriving as fast as they're read can't grow the `buffer` forever.
+    /// Note that permission events aren't responded to until the [`Events`] are dropped,
+    /// so draining a large burst delays the first responses.
+    pub fn read_all_pending<'a>(&'a self, buffer: &'a mut EventBuffer, max_bytes: usize) -> io::Result<Events<'a>> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("fanotify_read_all_pending", group = %self).entered();
+        let chunk = buffer.events.capacity().max(EventBufferSize::default().events);
+        Events::read_raw(self, &mut buffer.events)?;
+        Events::read_pending_raw(self, &mut buffer.events, chunk, max_bytes)?;
+        Ok(Events::from_buffer(self, buffer))
+    }
+}
+
+impl Fanotify {
+    /// Wait until this [`Fanotify`] group has events to [`read`](Fanotify::read),
+    /// or until the `timeout` expires.
+    /// A `timeout` of [`None`] waits forever.
+    ///
+    /// Return if there are events ready to read.
+    ///
+    /// This is for integrating with `select`/`poll`/`epoll` loops by hand.
+    /// For async, see `AsyncFanotify` (with the `async` feature) instead.
+    pub fn readable(&self, timeout: Option<Duration>) -> Result<bool, Errno> {
+        self.fd.readable(timeout)
+    }
+    
+    /// Get the total size in bytes of all the events currently queued in this [`Fanotify`] group,
+    /// without consuming them.
+    ///
+    /// This is the size they would take up in an [`EventBuffer`],
+    /// so it can be used to monitor queue pressure,
+    /// e.g. to grow the buffer or shed load before the queue overflows.
+    pub fn pending_bytes(&self) -> Result<usize, Errno> {
+        self.fd.pending_bytes()
+    }
+    
+    /// Wait until permission responses can be written to this [`Fanotify`] group,
+    /// or until the `timeout` expires.
+    /// A `timeout` of [`None`] waits forever.
+    ///
+    /// Return if it's ready for writing.
+    pub fn writable(&self, timeout: Option<Duration>) -> Result<bool, Errno> {
+        self.fd.writable(timeout)
+    }
 }
 
 #[cfg(test)]
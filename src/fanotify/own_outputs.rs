@@ -0,0 +1,160 @@
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::event::event::Event;
+use crate::fd::Identity;
+use crate::mark;
+use crate::mark::Action;
+use crate::mark::Mark;
+use crate::mark::Markable;
+use crate::mark::Mask;
+use crate::mark::OwnedMark;
+use crate::mark::ReapplyReport;
+use crate::mark::What;
+
+use super::Fanotify;
+
+/// A file registered with [`OwnOutputs`].
+#[derive(Debug)]
+struct Output {
+    path: PathBuf,
+    /// The inode the ignore mark was added to, if it existed.
+    identity: Option<Identity>,
+}
+
+/// Prevents feedback loops from the files a consumer writes itself,
+/// like its own log file inside a watched tree,
+/// by adding ignore marks ([`IGNORED_MASK`](mark::Flags::IGNORED_MASK)) on them,
+/// so the kernel doesn't even queue their [`Event`]s.
+///
+/// Unlike checking [`is_generated_by_self`](crate::event::id::EventId::is_generated_by_self),
+/// this also drops [`Event`]s from other processes on these files,
+/// but it costs nothing per [`Event`] and the queue can't fill up with them.
+///
+/// An ignore mark is on an inode, not a path, so when an output is rotated
+/// (renamed away and recreated), the new file isn't ignored until [`OwnOutputs::refresh`] is called,
+/// e.g. after rotating it or periodically.
+/// The old inode's ignore mark is left in place until it's deleted.
+#[derive(Debug)]
+pub struct OwnOutputs<'f> {
+    fanotify: &'f Fanotify,
+    mask: Mask,
+    outputs: Vec<Output>,
+}
+
+impl<'f> OwnOutputs<'f> {
+    /// The default [`Mask`] to ignore: everything writing a file causes.
+    #[allow(clippy::identity_op)]
+    pub const MASK: Mask = Mask::from_bits_truncate(0
+        | Mask::OPEN.bits()
+        | Mask::ACCESS.bits()
+        | Mask::MODIFY.bits()
+        | Mask::CLOSE_WRITE.bits()
+        | Mask::CLOSE_NO_WRITE.bits()
+    );
+    
+    /// Ignore `mask` on all of the registered outputs.
+    ///
+    /// The `mask` can't include permission events on a [`Notify`](crate::init::NotificationClass::Notify) group.
+    pub fn new(fanotify: &'f Fanotify, mask: Mask) -> Self {
+        Self {
+            fanotify,
+            mask,
+            outputs: Vec::new(),
+        }
+    }
+    
+    pub fn fanotify(&self) -> &'f Fanotify {
+        self.fanotify
+    }
+    
+    pub fn mask(&self) -> Mask {
+        self.mask
+    }
+    
+    /// The paths of the registered outputs, in the order they were registered.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.outputs.iter().map(|it| it.path.as_path())
+    }
+    
+    /// The ignore [`Mark`] for the file at `path`.
+    ///
+    /// [`IGNORED_SURVIVE_MODIFY`](mark::Flags::IGNORED_SURVIVE_MODIFY) is needed,
+    /// since otherwise the first write to the file would clear it.
+    fn ignore_mark<'a>(&self, action: Action, path: &'a Path) -> Mark<'a> {
+        Mark {
+            action,
+            what: What::Inode,
+            flags: mark::Flags::IGNORED_MASK | mark::Flags::IGNORED_SURVIVE_MODIFY,
+            mask: self.mask,
+            path: mark::Path::absolute(path),
+        }
+    }
+    
+    fn identity_of(path: &Path) -> Option<Identity> {
+        let metadata = fs::metadata(path).ok()?;
+        Some((metadata.dev(), metadata.ino()))
+    }
+    
+    /// Register a file as one of this consumer's own outputs and ignore its [`Event`]s.
+    ///
+    /// `path` should be absolute.
+    /// The file must already exist; create it first if needed.
+    pub fn register<'a>(&mut self, path: &'a Path) -> Result<(), mark::Error<'a>> {
+        self.fanotify.mark(self.ignore_mark(Action::Add, path))?;
+        self.outputs.push(Output {
+            path: path.to_path_buf(),
+            identity: Self::identity_of(path),
+        });
+        Ok(())
+    }
+    
+    /// Unregister an output and remove its ignore mark, returning if it was registered.
+    ///
+    /// Errors removing the mark are ignored, since the file may have been deleted.
+    pub fn unregister(&mut self, path: &Path) -> bool {
+        let len = self.outputs.len();
+        self.outputs.retain(|it| it.path != path);
+        if self.outputs.len() == len {
+            return false;
+        }
+        let _ = self.fanotify.mark(self.ignore_mark(Action::Remove, path));
+        true
+    }
+    
+    /// Re-add the ignore marks of any outputs that are now a different file (i.e., they were rotated).
+    ///
+    /// Outputs that don't exist right now are skipped, and will be marked by a later [`OwnOutputs::refresh`].
+    pub fn refresh(&mut self) -> ReapplyReport {
+        let mut report = ReapplyReport::default();
+        for i in 0..self.outputs.len() {
+            let output = &self.outputs[i];
+            let identity = match Self::identity_of(&output.path) {
+                None => continue,
+                Some(identity) if Some(identity) == output.identity => continue,
+                Some(identity) => identity,
+            };
+            let mark = self.ignore_mark(Action::Add, &output.path);
+            match self.fanotify.mark(mark) {
+                Ok(()) => {
+                    report.applied += 1;
+                    self.outputs[i].identity = Some(identity);
+                }
+                Err(e) => report.failed.push((OwnedMark::new(&e.mark), e.error)),
+            }
+        }
+        report
+    }
+    
+    /// If an [`Event`] is for one of the registered outputs,
+    /// in case it was queued before the output was registered or refreshed.
+    pub fn is_own(&self, event: &Event<'_>) -> bool {
+        let identity = match event.file().get_fd().map(|fd| fd.identity()) {
+            Some(Ok(identity)) => identity,
+            _ => return false,
+        };
+        self.outputs.iter().any(|it| it.identity == Some(identity))
+    }
+}
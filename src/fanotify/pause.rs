@@ -0,0 +1,119 @@
+use std::io;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::time::Duration;
+
+use crate::event::events::Events;
+
+use super::buffered_fanotify::BufferedFanotify;
+
+/// What happens to events that arrive while reading is [`Pause`]d.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum PauseMode {
+    /// Stop reading, so the events are queued by the kernel and read after resuming.
+    ///
+    /// The kernel queue is bounded (16384 events by default, see [`init::Flags::UNLIMITED_QUEUE`](crate::init::Flags::UNLIMITED_QUEUE)),
+    /// so a long enough burst overflows it, which is reported as usual after resuming.
+    /// Permission events block their processes until they're read after resuming.
+    Queue,
+    /// Keep reading, but discard the events, counting them in [`Pause::dropped`].
+    ///
+    /// Permission events are [allowed](crate::event::file::permission::PermissionDecision::Allow).
+    Drop,
+}
+
+#[derive(Debug, Default)]
+struct PauseState {
+    mode: Option<PauseMode>,
+    dropped: u64,
+}
+
+/// A handle to pause and resume reading from another thread,
+/// e.g. during a maintenance window or a heavy batch job done by the monitor itself,
+/// without removing any marks.
+///
+/// Clones share the same state, so one can be given to the reading loop
+/// (see [`BufferedFanotify::read_or_pause`]) and another kept to control it.
+#[derive(Debug, Clone, Default)]
+pub struct Pause {
+    state: Arc<(Mutex<PauseState>, Condvar)>,
+}
+
+impl Pause {
+    /// How often a read that's [dropping](PauseMode::Drop) events checks if it's been resumed,
+    /// since it may be waiting for events that don't come.
+    pub const DROP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+    
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    fn state(&self) -> MutexGuard<'_, PauseState> {
+        self.state.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    
+    /// Pause reading in `mode`, or switch to it if already paused.
+    ///
+    /// A read that's already blocked waiting for events isn't interrupted,
+    /// so this takes effect at the next read.
+    pub fn pause(&self, mode: PauseMode) {
+        self.state().mode = Some(mode);
+        self.state.1.notify_all();
+    }
+    
+    /// Resume reading, including the events queued in the meantime if [`PauseMode::Queue`]d.
+    pub fn resume(&self) {
+        self.state().mode = None;
+        self.state.1.notify_all();
+    }
+    
+    /// The current [`PauseMode`], or [`None`] if not paused.
+    pub fn mode(&self) -> Option<PauseMode> {
+        self.state().mode
+    }
+    
+    pub fn is_paused(&self) -> bool {
+        self.mode().is_some()
+    }
+    
+    /// The total number of events (including errors) discarded while paused with [`PauseMode::Drop`].
+    pub fn dropped(&self) -> u64 {
+        self.state().dropped
+    }
+    
+    /// Block while paused with [`PauseMode::Queue`],
+    /// returning if it's then paused with [`PauseMode::Drop`].
+    fn wait_while_queueing(&self) -> bool {
+        let (state, condvar) = &*self.state;
+        let state = state.lock().unwrap_or_else(|e| e.into_inner());
+        let state = condvar
+            .wait_while(state, |it| it.mode == Some(PauseMode::Queue))
+            .unwrap_or_else(|e| e.into_inner());
+        state.mode == Some(PauseMode::Drop)
+    }
+}
+
+impl BufferedFanotify {
+    /// Read like [`BufferedFanotify::read`], unless `pause` is paused.
+    ///
+    /// While it's paused with [`PauseMode::Queue`], this blocks without reading,
+    /// and while it's paused with [`PauseMode::Drop`], this reads and discards any events,
+    /// until it's [resumed](Pause::resume) and there are events to return.
+    pub fn read_or_pause(&mut self, pause: &Pause) -> io::Result<Events<'_>> {
+        loop {
+            if !pause.wait_while_queueing() {
+                return self.read();
+            }
+            let readable = self.fanotify
+                .readable(Some(Pause::DROP_POLL_INTERVAL))
+                .map_err(|errno| io::Error::from_raw_os_error(errno as i32))?;
+            // it may have been resumed while waiting
+            if readable && pause.mode() == Some(PauseMode::Drop) {
+                let dropped = self.read()?.into_iter().count();
+                pause.state().dropped += dropped as u64;
+            }
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+
+use nix::unistd::Uid;
+
+use crate::event::cgroup::CgroupCache;
+use crate::event::credentials::CredentialsCache;
+use crate::event::error::EventError;
+use crate::event::event::Event;
+use crate::event::iterator_ext::IntoEvents;
+use crate::event::sink::Sink;
+use crate::event::sink::SinkError;
+#[cfg(feature = "async")]
+use crate::fanotify::async_fd::AsyncFdWrapper;
+#[cfg(feature = "async")]
+use crate::fanotify::buffered_fanotify::AsyncBufferedFanotify;
+use crate::fanotify::buffered_fanotify::BufferedFanotify;
+use crate::mark::Mask;
+#[cfg(feature = "systemd")]
+use crate::systemd::Notifier;
+
+/// A middleware layer in a [`Pipeline`].
+///
+/// Layers are run in order for each [`Event`],
+/// and each one decides whether to pass the [`Event`] on to the next one.
+/// A layer can filter, debounce, or enrich (e.g. log, count, or cache paths) [`Event`]s.
+///
+/// Any `FnMut(&Event) -> bool` is a [`Layer`].
+pub trait Layer {
+    /// Handle an [`Event`], returning whether to pass it on.
+    fn handle(&mut self, event: &Event<'_>) -> bool;
+}
+
+impl<F: FnMut(&Event<'_>) -> bool> Layer for F {
+    fn handle(&mut self, event: &Event<'_>) -> bool {
+        self(event)
+    }
+}
+
+/// A [`Layer`] that drops an [`Event`] if an [`Event`] with the same [`Mask`] on the same file
+/// (by `(device, inode)`) was passed on less than `window` ago.
+///
+/// [`Event`]s without an [`FD`](crate::fd::FD) are always passed on.
+pub struct Debounce {
+    window: Duration,
+    /// The keys passed on within the last `window`, oldest first.
+    last: VecDeque<(Instant, (libc::dev_t, libc::ino_t, Mask))>,
+    keys: HashSet<(libc::dev_t, libc::ino_t, Mask)>,
+}
+
+impl Debounce {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last: VecDeque::new(),
+            keys: HashSet::new(),
+        }
+    }
+}
+
+impl Layer for Debounce {
+    fn handle(&mut self, event: &Event<'_>) -> bool {
+        let (device, inode) = match event.file().get_fd().map(|fd| fd.identity()) {
+            Some(Ok(identity)) => identity,
+            _ => return true,
+        };
+        let now = Instant::now();
+        let window = self.window;
+        // expire the oldest keys, so this doesn't grow forever
+        while let Some((last, key)) = self.last.front() {
+            if now.duration_since(*last) < window {
+                break;
+            }
+            self.keys.remove(key);
+            self.last.pop_front();
+        }
+        let key = (device, inode, event.mask());
+        if !self.keys.insert(key) {
+            return false;
+        }
+        self.last.push_back((now, key));
+        true
+    }
+}
+
+/// A [`Layer`] that filters [`Event`]s by the effective user id of the process that caused them,
+/// e.g. to scope a multi-user server's monitor to specific users.
+///
+/// The user ids are read from `/proc` through a [`CredentialsCache`],
+/// so [`Event`]s from processes that have already exited can't be attributed to any user:
+/// they're dropped by [`UidFilter::filter_uid`] and passed on by [`UidFilter::exclude_uid`].
+#[derive(Debug)]
+pub struct UidFilter {
+    uids: HashSet<Uid>,
+    exclude: bool,
+    cache: CredentialsCache,
+}
+
+impl UidFilter {
+    /// Only pass on [`Event`]s from processes running as one of `uids`.
+    pub fn filter_uid(uids: impl IntoIterator<Item = Uid>) -> Self {
+        Self {
+            uids: uids.into_iter().collect(),
+            exclude: false,
+            cache: CredentialsCache::default(),
+        }
+    }
+    
+    /// Drop [`Event`]s from processes running as one of `uids`.
+    pub fn exclude_uid(uids: impl IntoIterator<Item = Uid>) -> Self {
+        Self {
+            exclude: true,
+            ..Self::filter_uid(uids)
+        }
+    }
+    
+    /// Use `cache` instead of the [default](CredentialsCache::default) one.
+    pub fn with_cache(mut self, cache: CredentialsCache) -> Self {
+        self.cache = cache;
+        self
+    }
+    
+    pub fn cache(&self) -> &CredentialsCache {
+        &self.cache
+    }
+}
+
+impl Layer for UidFilter {
+    fn handle(&mut self, event: &Event<'_>) -> bool {
+        match self.cache.get(event) {
+            Some(credentials) => self.uids.contains(&credentials.effective_uid) != self.exclude,
+            None => self.exclude,
+        }
+    }
+}
+
+/// A [`Layer`] that only passes on [`Event`]s from processes in one of a set of cgroups
+/// (or their descendants), e.g. `/system.slice/nginx.service` to only watch what that service
+/// or a container does.
+///
+/// The cgroup paths are read from `/proc` through a [`CgroupCache`] (see [`cgroup::of`](crate::event::cgroup::of)),
+/// and matched by whole path components, so `/system.slice/a.service` doesn't match `/system.slice/a.service2`.
+/// [`Event`]s from processes that have already exited can't be attributed to any cgroup, so they're dropped.
+#[derive(Debug)]
+pub struct CgroupFilter {
+    prefixes: Vec<PathBuf>,
+    cache: CgroupCache,
+}
+
+impl CgroupFilter {
+    /// Only pass on [`Event`]s from processes in one of the cgroups `prefixes`, or one of their descendants.
+    pub fn new(prefixes: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        Self {
+            prefixes: prefixes.into_iter().map(Into::into).collect(),
+            cache: CgroupCache::default(),
+        }
+    }
+    
+    /// Use `cache` instead of the [default](CgroupCache::default) one.
+    pub fn with_cache(mut self, cache: CgroupCache) -> Self {
+        self.cache = cache;
+        self
+    }
+    
+    pub fn prefixes(&self) -> &[PathBuf] {
+        &self.prefixes
+    }
+    
+    pub fn cache(&self) -> &CgroupCache {
+        &self.cache
+    }
+}
+
+impl Layer for CgroupFilter {
+    fn handle(&mut self, event: &Event<'_>) -> bool {
+        match self.cache.get(event) {
+            Some(cgroup) => self.prefixes.iter().any(|prefix| cgroup.starts_with(prefix)),
+            None => false,
+        }
+    }
+}
+
+/// An error from running a [`Pipeline`] over one [`Event`].
+#[derive(thiserror::Error, Debug)]
+pub enum PipelineError {
+    #[error("event error: {}", .0)]
+    Event(#[from] EventError),
+    #[error("sink error: {}", .0)]
+    Sink(SinkError),
+}
+
+/// A [`Pipeline`] under construction.  Add [`Layer`]s and then finish it with a [`Sink`].
+#[derive(Default)]
+pub struct PipelineBuilder<'s> {
+    layers: Vec<Box<dyn Layer + 's>>,
+}
+
+impl<'s> PipelineBuilder<'s> {
+    /// Add another [`Layer`], run after all the previous ones.
+    pub fn layer(mut self, layer: impl Layer + 's) -> Self {
+        self.layers.push(Box::new(layer));
+        self
+    }
+    
+    /// Add a [`UidFilter::filter_uid`] [`Layer`].
+    pub fn filter_uid(self, uids: impl IntoIterator<Item = Uid>) -> Self {
+        self.layer(UidFilter::filter_uid(uids))
+    }
+    
+    /// Add a [`UidFilter::exclude_uid`] [`Layer`].
+    pub fn exclude_uid(self, uids: impl IntoIterator<Item = Uid>) -> Self {
+        self.layer(UidFilter::exclude_uid(uids))
+    }
+    
+    /// Add a [`CgroupFilter`] [`Layer`].
+    pub fn filter_cgroup(self, prefixes: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        self.layer(CgroupFilter::new(prefixes))
+    }
+    
+    /// Finish the [`Pipeline`] with the [`Sink`] that consumes all the passed on [`Event`]s.
+    pub fn sink(self, sink: impl Sink + 's) -> Pipeline<'s> {
+        Pipeline {
+            layers: self.layers,
+            sink: Box::new(sink),
+        }
+    }
+}
+
+/// A composition of [`Layer`]s ending in a [`Sink`],
+/// driving the [`Event`]s read from a [`BufferedFanotify`] or `AsyncBufferedFanotify` through them.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use fanotify::fanotify::pipeline::Debounce;
+/// use fanotify::fanotify::pipeline::Pipeline;
+///
+/// let pipeline = Pipeline::new()
+///     .layer(|event: &fanotify::event::event::Event| !event.id().is_generated_by_self())
+///     .layer(Debounce::new(Duration::from_millis(100)))
+///     .sink(|event: &fanotify::event::event::Event| {
+///         println!("{}", event.display());
+///         Ok(())
+///     });
+/// ```
+pub struct Pipeline<'s> {
+    layers: Vec<Box<dyn Layer + 's>>,
+    sink: Box<dyn Sink + 's>,
+}
+
+impl<'s> Pipeline<'s> {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> PipelineBuilder<'s> {
+        PipelineBuilder::default()
+    }
+    
+    /// Run a single [`Event`] through the [`Layer`]s and into the [`Sink`].
+    ///
+    /// Return if the [`Event`] made it to the [`Sink`].
+    pub fn process(&mut self, event: &Event<'_>) -> Result<bool, SinkError> {
+        if !self.layers.iter_mut().all(|layer| layer.handle(event)) {
+            return Ok(false);
+        }
+        self.sink.consume(event)?;
+        Ok(true)
+    }
+    
+    fn process_all<'a>(&mut self, events: impl IntoEvents<'a>) -> Vec<PipelineError> {
+        events
+            .all()
+            .filter_map(|event| match event {
+                Err(e) => Some(e.into()),
+                Ok(event) => self.process(&event).err().map(PipelineError::Sink),
+            })
+            .collect()
+    }
+    
+    /// Read one batch of [`Event`]s and run them all through this [`Pipeline`].
+    ///
+    /// An error in one [`Event`] doesn't stop the rest of them from being processed,
+    /// so all the errors are returned.
+    pub fn run_once(&mut self, fanotify: &mut BufferedFanotify) -> io::Result<Vec<PipelineError>> {
+        Ok(self.process_all(fanotify.read()?))
+    }
+    
+    /// Like [`Pipeline::run_once`], but for a systemd service:
+    /// [keep the watchdog fed](Notifier::keep_alive) and wait for [`Event`]s
+    /// only until the watchdog needs to be pinged again,
+    /// returning no errors if no [`Event`]s arrived by then.
+    #[cfg(feature = "systemd")]
+    pub fn run_once_notified(
+        &mut self,
+        fanotify: &mut BufferedFanotify,
+        notifier: &mut Notifier,
+    ) -> io::Result<Vec<PipelineError>> {
+        notifier.keep_alive()?;
+        if !fanotify.fanotify.readable(notifier.next_keep_alive()).map_err(io::Error::from)? {
+            return Ok(Vec::new());
+        }
+        self.run_once(fanotify)
+    }
+    
+    /// An async version of [`Pipeline::run_once`].
+    #[cfg(feature = "async")]
+    pub async fn run_once_async<W: AsyncFdWrapper>(
+        &mut self,
+        fanotify: &mut AsyncBufferedFanotify<W>,
+    ) -> io::Result<Vec<PipelineError>> {
+        Ok(self.process_all(fanotify.read().await?))
+    }
+}
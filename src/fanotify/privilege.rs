@@ -0,0 +1,158 @@
+use std::fs;
+use std::time::Duration;
+
+use nix::errno::Errno;
+use nix::unistd;
+use nix::unistd::Gid;
+use nix::unistd::Uid;
+
+use crate::mark::What;
+use crate::proc;
+
+use super::Fanotify;
+
+/// An error from [`PrivilegeDrop::apply_to`].
+#[derive(thiserror::Error, Debug, Eq, PartialEq)]
+pub enum PrivilegeError {
+    #[error("{:?} marks will be needed later, but they require CAP_SYS_ADMIN", .0)]
+    MarksNeededLater(What),
+    #[error("failed to {}: {}", .step, .errno)]
+    Failed {
+        step: &'static str,
+        errno: Errno,
+    },
+    /// The old user could be switched back to, which it now is,
+    /// so the process is still privileged and must exit instead of continuing.
+    #[error("privileges could be regained after dropping them, so the process must exit")]
+    StillPrivileged,
+    #[error("the fanotify group doesn't work after dropping privileges: {}", .0)]
+    Unusable(Errno),
+}
+
+/// Dropping privileges after a [`Fanotify`] group is set up.
+///
+/// `fanotify_init` needs `CAP_SYS_ADMIN`, as do [`MountPoint`](What::MountPoint)
+/// and [`FileSystem`](What::FileSystem) marks, but reading [`Event`](crate::event::event::Event)s
+/// and responding to permission events only needs the already open group.
+/// So a daemon can initialize its group, add all of its marks,
+/// and then call [`PrivilegeDrop::apply_to`] with it
+/// to run the rest of the time as an unprivileged user.
+///
+/// Anything needing those privileges later, like
+/// [`Fanotify::recreate_with_marks`] or re-adding marks with a [`MarkRegistry`](crate::mark::MarkRegistry),
+/// will fail, so list what marks are still needed in [`PrivilegeDrop::future_marks`]
+/// to catch that up front.
+/// [`Inode`](What::Inode) marks can still be added without privileges on kernels that allow it (5.13+),
+/// as long as the new user can read the file.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PrivilegeDrop {
+    /// The user to switch to.
+    pub uid: Uid,
+    /// The group to switch to.
+    pub gid: Gid,
+    /// The supplementary groups to switch to, usually none.
+    pub groups: Vec<Gid>,
+    /// The kinds of marks that will still be added after dropping privileges.
+    pub future_marks: Vec<What>,
+    /// Also set `PR_SET_NO_NEW_PRIVS`, so privileges can't be regained through `execve`.
+    pub no_new_privileges: bool,
+}
+
+/// Check the return value of a step of dropping privileges.
+fn step(step: &'static str, result: libc::c_int) -> Result<(), PrivilegeError> {
+    if result == -1 {
+        Err(PrivilegeError::Failed {
+            step,
+            errno: Errno::last(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+impl PrivilegeDrop {
+    /// Switch to `uid` and `gid` with no supplementary groups, no future marks,
+    /// and `PR_SET_NO_NEW_PRIVS`.
+    pub fn new(uid: Uid, gid: Gid) -> Self {
+        Self {
+            uid,
+            gid,
+            groups: Vec::new(),
+            future_marks: Vec::new(),
+            no_new_privileges: true,
+        }
+    }
+    
+    /// Check that none of the [`PrivilegeDrop::future_marks`] need privileges.
+    pub fn check(&self) -> Result<(), PrivilegeError> {
+        match self.future_marks.iter().find(|&&it| it != What::Inode) {
+            Some(&what) => Err(PrivilegeError::MarksNeededLater(what)),
+            None => Ok(()),
+        }
+    }
+    
+    /// Drop all of the capabilities from the bounding set,
+    /// so they can't be regained by executing a setuid or file-capability binary.
+    fn drop_bounding_set() -> Result<(), PrivilegeError> {
+        let last = proc::root()
+            .ok()
+            .and_then(|root| fs::read_to_string(root.join("sys/kernel/cap_last_cap")).ok())
+            .and_then(|it| it.trim().parse::<libc::c_ulong>().ok())
+            // CAP_CHECKPOINT_RESTORE, the last one as of Linux 5.9
+            .unwrap_or(40);
+        for cap in 0..=last {
+            let result = unsafe { libc::prctl(libc::PR_CAPBSET_DROP, cap, 0, 0, 0) };
+            // without proc, `last` is a guess, and capabilities past the kernel's last one don't exist
+            if result == -1 && cap != 0 && Errno::last() == Errno::EINVAL {
+                break;
+            }
+            step("drop the capability bounding set", result)?;
+        }
+        Ok(())
+    }
+    
+    /// Drop privileges for the whole process, and then check `fanotify` still works.
+    ///
+    /// This [checks](PrivilegeDrop::check) the future marks first,
+    /// then drops the capability bounding set, switches the groups and then the user
+    /// (which clears all capabilities, since the new user isn't root),
+    /// and makes sure the old user can't be switched back to.
+    ///
+    /// The C library applies each change to every thread in the process,
+    /// but not atomically, so call this before starting any other threads,
+    /// or at least before they do anything that depends on their credentials.
+    ///
+    /// If this fails with [`PrivilegeError::StillPrivileged`], the process is privileged again,
+    /// and must exit rather than continue, e.g. by treating it as fatal.
+    pub fn apply_to(&self, fanotify: &Fanotify) -> Result<(), PrivilegeError> {
+        self.check()?;
+        let privileged = unistd::geteuid();
+        Self::drop_bounding_set()?;
+        let groups = self.groups.iter().map(|it| it.as_raw()).collect::<Vec<_>>();
+        step("set the supplementary groups", unsafe { libc::setgroups(groups.len(), groups.as_ptr()) })?;
+        let gid = self.gid.as_raw();
+        step("set the group", unsafe { libc::setresgid(gid, gid, gid) })?;
+        let uid = self.uid.as_raw();
+        step("set the user", unsafe { libc::setresuid(uid, uid, uid) })?;
+        if self.no_new_privileges {
+            step("set no new privileges", unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) })?;
+        }
+        if privileged != self.uid && unsafe { libc::setuid(privileged.as_raw()) } == 0 {
+            return Err(PrivilegeError::StillPrivileged);
+        }
+        fanotify.after_privilege_drop().map_err(PrivilegeError::Unusable)
+    }
+}
+
+impl Fanotify {
+    /// Check that this [`Fanotify`] group still works after dropping privileges,
+    /// e.g. with a [`PrivilegeDrop`].
+    ///
+    /// Reading and writing the group only needs the file descriptor,
+    /// so this should always succeed unless something closed or replaced it.
+    pub fn after_privilege_drop(&self) -> Result<(), Errno> {
+        self.fd.pending_bytes()?;
+        self.readable(Some(Duration::from_secs(0)))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::event::event::Event;
+use crate::event::file::File;
+use crate::event::file::fid::FileSystemId;
+use crate::libc::call::libc_call;
+
+/// A handler for the [`Event`]s routed to it by a [`Router`].
+///
+/// Any `FnMut(Event) -> R` is a [`Route`].
+pub trait Route<R> {
+    /// Handle an [`Event`] routed here.
+    fn handle(&mut self, event: Event<'_>) -> R;
+}
+
+impl<R, F: FnMut(Event<'_>) -> R> Route<R> for F {
+    fn handle(&mut self, event: Event<'_>) -> R {
+        self(event)
+    }
+}
+
+/// A mounted filesystem that [`Event`]s can be routed by,
+/// identified both by its device (for [`Event`]s with an [`FD`](crate::fd::FD))
+/// and by its [`FileSystemId`] (for [`REPORT_FID`](crate::init::Flags::REPORT_FID) [`Event`]s).
+struct Mount<'h, R> {
+    path: PathBuf,
+    file_system_id: FileSystemId,
+    route: Box<dyn Route<R> + 'h>,
+}
+
+/// Routes each [`Event`] to the [`Route`] registered for the mounted filesystem it's on,
+/// e.g. to apply different policies to `/home` and `/srv`.
+///
+/// The device and [`FileSystemId`] of each mount are looked up once when it's registered,
+/// so routing an [`Event`] only needs an [`fstat`](crate::fd::FD::stat) of its [`FD`](crate::fd::FD)
+/// (or nothing for [`REPORT_FID`](crate::init::Flags::REPORT_FID) [`Event`]s).
+///
+/// Note that bind mounts of the same filesystem have the same device and [`FileSystemId`],
+/// so they can't be told apart; the first one registered wins.
+/// [`Event`]s on any other filesystem go to the [`Router::fallback`] [`Route`], if there is one.
+pub struct Router<'h, R = ()> {
+    mounts: Vec<Mount<'h, R>>,
+    devices: HashMap<libc::dev_t, usize>,
+    fallback: Option<Box<dyn Route<R> + 'h>>,
+}
+
+impl<'h, R> Router<'h, R> {
+    pub fn new() -> Self {
+        Self {
+            mounts: Vec::new(),
+            devices: HashMap::new(),
+            fallback: None,
+        }
+    }
+    
+    /// Route [`Event`]s on the filesystem mounted at (or containing) `path` to `route`.
+    pub fn route<P: AsRef<Path>>(&mut self, path: P, route: impl Route<R> + 'h) -> io::Result<&mut Self> {
+        let path = path.as_ref();
+        let c_path = CString::new(path.as_os_str().as_bytes())?;
+        let mut stat = mem::MaybeUninit::<libc::stat>::uninit();
+        libc_call(|| unsafe { libc::stat(c_path.as_ptr(), stat.as_mut_ptr()) })?;
+        let device = unsafe { stat.assume_init() }.st_dev;
+        let mut statfs = mem::MaybeUninit::<libc::statfs>::uninit();
+        libc_call(|| unsafe { libc::statfs(c_path.as_ptr(), statfs.as_mut_ptr()) })?;
+        let fsid = unsafe { statfs.assume_init() }.f_fsid;
+        self.devices.entry(device).or_insert(self.mounts.len());
+        self.mounts.push(Mount {
+            path: path.to_path_buf(),
+            file_system_id: FileSystemId { fsid },
+            route: Box::new(route),
+        });
+        Ok(self)
+    }
+    
+    /// Route [`Event`]s that aren't on any registered filesystem to `route`.
+    pub fn fallback(&mut self, route: impl Route<R> + 'h) -> &mut Self {
+        self.fallback = Some(Box::new(route));
+        self
+    }
+    
+    /// The paths of the registered filesystems, in the order they were registered.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.mounts.iter().map(|it| it.path.as_path())
+    }
+    
+    /// Find the index of the registered filesystem an [`Event`] is on.
+    fn find(&self, event: &Event<'_>) -> Option<usize> {
+        match event.file() {
+            File::FID(file) => {
+                let file_system_id = file.file_system_id();
+                self.mounts.iter().position(|it| it.file_system_id == file_system_id)
+            }
+            file => {
+                let stat = file.get_fd()?.stat().ok()?;
+                self.devices.get(&stat.st_dev).copied()
+            }
+        }
+    }
+    
+    /// The path of the registered filesystem an [`Event`] would be routed to, if any.
+    pub fn mount_of(&self, event: &Event<'_>) -> Option<&Path> {
+        self.find(event).map(|i| self.mounts[i].path.as_path())
+    }
+    
+    /// Route an [`Event`] to its [`Route`].
+    ///
+    /// If there's no [`Route`] for it (and no [`Router::fallback`]),
+    /// the [`Event`] is dropped and [`None`] is returned.
+    pub fn dispatch(&mut self, event: Event<'_>) -> Option<R> {
+        let route = match self.find(&event) {
+            Some(i) => &mut self.mounts[i].route,
+            None => self.fallback.as_mut()?,
+        };
+        Some(route.handle(event))
+    }
+}
+
+impl<R> Default for Router<'_, R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,82 @@
+use std::cell::RefCell;
+
+use crate::mark;
+use crate::mark::Action;
+use crate::mark::Mark;
+use crate::mark::MarkRegistry;
+use crate::mark::Markable;
+
+use super::Fanotify;
+
+/// A [`Markable`] view of a [`Fanotify`] group that removes all the [`Mark`]s added through it
+/// once it's dropped, which [`Fanotify::scoped`] does when its closure exits, even on panic.
+///
+/// This prevents leaking [`Mark`]s when only watching something temporarily, e.g. in library code.
+/// Note that removing a [`Mark`] removes its [`Mask`](mark::Mask) bits from the group,
+/// even if they were also added outside of the scope.
+#[derive(Debug)]
+pub struct ScopedWatcher<'f> {
+    fanotify: &'f Fanotify,
+    marks: RefCell<MarkRegistry>,
+}
+
+impl<'f> ScopedWatcher<'f> {
+    pub fn fanotify(&self) -> &'f Fanotify {
+        self.fanotify
+    }
+    
+    /// The number of [`Mark`]s that will be removed when this is dropped.
+    pub fn len(&self) -> usize {
+        self.marks
+            .borrow()
+            .marks()
+            .iter()
+            .filter(|it| it.action == Action::Add)
+            .count()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Markable for ScopedWatcher<'_> {
+    fn mark<'a>(&self, mark: Mark<'a>) -> Result<(), mark::Error<'a>> {
+        self.marks.borrow_mut().mark(self.fanotify, mark)
+    }
+    
+    fn check<'a>(&self, mark: Mark<'a>) -> Result<Mark<'a>, mark::Error<'a>> {
+        self.fanotify.check(mark)
+    }
+}
+
+impl Drop for ScopedWatcher<'_> {
+    /// Remove all the added [`Mark`]s, in reverse order.
+    ///
+    /// Errors are ignored, since a [`Mark`] may have already been removed
+    /// (e.g. if its inode was deleted).
+    fn drop(&mut self) {
+        for mark in self.marks.get_mut().marks().iter().rev() {
+            if mark.action != Action::Add {
+                continue;
+            }
+            let mark = Mark {
+                action: Action::Remove,
+                ..mark.as_mark()
+            };
+            let _ = self.fanotify.mark(mark);
+        }
+    }
+}
+
+impl Fanotify {
+    /// Run `f` with a [`ScopedWatcher`], whose [`Mark`]s are all removed when `f` exits,
+    /// whether it returns or panics.
+    pub fn scoped<R>(&self, f: impl FnOnce(&ScopedWatcher<'_>) -> R) -> R {
+        let watcher = ScopedWatcher {
+            fanotify: self,
+            marks: RefCell::new(MarkRegistry::new()),
+        };
+        f(&watcher)
+    }
+}
@@ -0,0 +1,109 @@
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use nix::unistd::getpid;
+use nix::unistd::gettid;
+
+use crate::event::buffer::EventBuffer;
+use crate::event::id::Id;
+use crate::event::iterator_ext::IntoEvents;
+use crate::fanotify::Fanotify;
+use crate::mark::Mask;
+
+/// An error from [`Fanotify::self_test`].
+#[derive(thiserror::Error, Debug)]
+pub enum SelfTestError {
+    #[error("self test IO failed: {}", .0)]
+    Io(#[from] io::Error),
+    #[error("self test timed out after {:?}: expected {:?}, but only received {:?}", .timeout, .expected, .received)]
+    Missing {
+        expected: Mask,
+        received: Mask,
+        timeout: Duration,
+    },
+}
+
+impl Fanotify {
+    /// Check end-to-end that this group's marks actually deliver events for `dir`,
+    /// e.g. as a startup health check for daemons.
+    ///
+    /// This creates, writes to, and closes a temporary file inside `dir` (and then removes it),
+    /// and waits up to `timeout` for all of the `expected` events on it to be read,
+    /// like [`Mask::OPEN`]` | `[`Mask::MODIFY`]` | `[`Mask::CLOSE_WRITE`].
+    /// `dir` must already be marked (including [`Mask::EVENT_ON_CHILD`] for inode marks).
+    ///
+    /// The file operations are done on another thread,
+    /// so that permission events for them can be responded to (allowed) here.
+    /// Note that this reads events from this group like any other read,
+    /// so any other events read in the meantime are discarded (and permission events allowed).
+    ///
+    /// Return how long it took for all the `expected` events to arrive.
+    pub fn self_test(&self, dir: impl AsRef<Path>, expected: Mask, timeout: Duration) -> Result<Duration, SelfTestError> {
+        let path = dir.as_ref().join(format!(".fanotify-self-test-{}", getpid()));
+        let start = Instant::now();
+        let (tid_sender, tid_receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            let worker = scope.spawn(|| -> io::Result<()> {
+                let _ = tid_sender.send(gettid());
+                let mut file = File::create(&path)?;
+                file.write_all(b"fanotify self test\n")?;
+                drop(file);
+                std::fs::remove_file(&path)
+            });
+            let result = self.wait_for_self_test(
+                tid_receiver.recv().ok(),
+                expected,
+                start,
+                timeout,
+            );
+            let io_result = worker.join().unwrap_or_else(|_| Err(io::Error::other("self test thread panicked")));
+            let elapsed = result?;
+            io_result?;
+            Ok(elapsed)
+        })
+    }
+    
+    fn wait_for_self_test(
+        &self,
+        worker_tid: Option<nix::unistd::Pid>,
+        expected: Mask,
+        start: Instant,
+        timeout: Duration,
+    ) -> Result<Duration, SelfTestError> {
+        let pid = getpid();
+        let is_worker = |id: Id| match id {
+            Id::Pid(id) => id == pid,
+            Id::Tid(id) => Some(id) == worker_tid,
+        };
+        let mut buffer = EventBuffer::default();
+        let mut received = Mask::empty();
+        while !received.contains(expected) {
+            let remaining = match timeout.checked_sub(start.elapsed()) {
+                Some(remaining) => remaining,
+                None => break,
+            };
+            if !self.readable(Some(remaining)).map_err(io::Error::from)? {
+                break;
+            }
+            for event in self.read(&mut buffer)?.all().flatten() {
+                if is_worker(event.id().id()) {
+                    received |= event.mask();
+                }
+            }
+        }
+        if !received.contains(expected) {
+            return Err(SelfTestError::Missing {
+                expected,
+                received,
+                timeout,
+            });
+        }
+        Ok(start.elapsed())
+    }
+}
@@ -0,0 +1,136 @@
+use std::io;
+
+use crate::event::error::EventError;
+use crate::event::event::Event;
+use crate::event::iterator::EventIterator;
+use crate::event::sequence::Gap;
+use crate::event::sequence::GapDetector;
+
+use super::buffered_fanotify::BufferedFanotify;
+
+/// An item in the continuous sequence of a [`ReadSession`].
+#[derive(Debug)]
+pub enum SessionItem<'a> {
+    Event(Event<'a>),
+    /// Events were lost right before the next [`Event`](SessionItem::Event),
+    /// e.g. because the queue overflowed, possibly in an earlier read.
+    Gap(Gap),
+    /// An event that couldn't be parsed, other than a queue overflow, which is reported as a [`Gap`].
+    Error(EventError),
+}
+
+/// Successive reads from a [`BufferedFanotify`] presented as one continuous sequence of events.
+///
+/// Each [`ReadSession::read`] yields its events annotated with the [`Gap`]s before them,
+/// tracked by a [`GapDetector`] across reads,
+/// so a queue overflow at the end of one read is reported before the first event of the next.
+/// An event truncated at the end of a read is completed by the next one
+/// (see [`EventBuffer::carry`](crate::event::buffer::EventBuffer::carry)),
+/// so it never shows up as an error.
+pub struct ReadSession<'f> {
+    fanotify: &'f mut BufferedFanotify,
+    gaps: GapDetector,
+    reads: u64,
+    overflows: u64,
+    carried_over: u64,
+}
+
+impl BufferedFanotify {
+    /// Start a [`ReadSession`] of successive reads from this group.
+    pub fn session(&mut self) -> ReadSession<'_> {
+        ReadSession {
+            fanotify: self,
+            gaps: GapDetector::new(),
+            reads: 0,
+            overflows: 0,
+            carried_over: 0,
+        }
+    }
+}
+
+impl<'f> ReadSession<'f> {
+    pub fn fanotify(&self) -> &BufferedFanotify {
+        self.fanotify
+    }
+    
+    /// The [`GapDetector`] tracking the gaps across all the reads so far.
+    pub fn gaps(&self) -> &GapDetector {
+        &self.gaps
+    }
+    
+    /// The number of successful reads.
+    pub fn reads(&self) -> u64 {
+        self.reads
+    }
+    
+    /// The number of queue overflows read.
+    pub fn overflows(&self) -> u64 {
+        self.overflows
+    }
+    
+    /// The number of reads that ended with a truncated event carried over to the next one.
+    pub fn carried_over(&self) -> u64 {
+        self.carried_over
+    }
+    
+    /// Read the next batch of events, like [`BufferedFanotify::read`].
+    ///
+    /// This blocks.
+    pub fn read(&mut self) -> io::Result<SessionEvents<'_>> {
+        let Self {
+            fanotify,
+            gaps,
+            reads,
+            overflows,
+            carried_over,
+        } = self;
+        let events = fanotify.read()?;
+        *reads += 1;
+        if events.carried_over() != 0 {
+            *carried_over += 1;
+        }
+        Ok(SessionEvents {
+            events: events.into_iter(),
+            gaps,
+            overflows,
+            next: None,
+        })
+    }
+}
+
+/// The [`SessionItem`]s of one [`ReadSession::read`].
+pub struct SessionEvents<'a> {
+    events: EventIterator<'a>,
+    gaps: &'a mut GapDetector,
+    overflows: &'a mut u64,
+    /// The event after a [`Gap`] that was just yielded.
+    next: Option<Event<'a>>,
+}
+
+impl<'a> Iterator for SessionEvents<'a> {
+    type Item = SessionItem<'a>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.next.take() {
+            return Some(SessionItem::Event(event));
+        }
+        loop {
+            let event = match self.events.next()? {
+                Ok(event) => event,
+                Err(EventError::QueueOverflowed | EventError::UnlimitedQueueButQueueStillOverflowed) => {
+                    self.gaps.observe_overflow();
+                    *self.overflows += 1;
+                    continue;
+                }
+                Err(e) => return Some(SessionItem::Error(e)),
+            };
+            return match self.gaps.observe(event.sequence().event) {
+                None => Some(SessionItem::Event(event)),
+                Some(gap) => {
+                    self.next = Some(event);
+                    Some(SessionItem::Gap(gap))
+                }
+            };
+        }
+    }
+}
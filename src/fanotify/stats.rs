@@ -0,0 +1,166 @@
+use std::io;
+use std::mem::size_of;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::event::events::Events;
+use crate::libc::mark::mask::FAN_Q_OVERFLOW;
+use crate::libc::read::fanotify_event_metadata;
+
+/// Statistics about the reads done by a
+/// [`BufferedFanotify`](super::buffered_fanotify::BufferedFanotify)
+/// or `AsyncBufferedFanotify` (with the `async` feature),
+/// so that long-running services can export their health.
+///
+/// Events are counted from the raw buffer as soon as they're read,
+/// so they don't depend on how much of each batch is iterated over.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Stats {
+    /// The number of successful reads.
+    pub reads: u64,
+    /// The number of failed reads.
+    pub read_errors: u64,
+    /// The number of events read, including queue overflow events.
+    pub events: u64,
+    /// The number of queue overflow events read.
+    pub overflows: u64,
+    /// The total number of bytes read.
+    pub bytes: u64,
+    /// The number of bytes read in the last successful read.
+    pub last_batch_bytes: usize,
+    /// The capacity of the event buffer at the last successful read.
+    pub buffer_capacity: usize,
+    /// The number of reads that ended with a truncated event,
+    /// which was carried over to be completed by the next read.
+    /// See [`Events::carried_over`].
+    pub truncations: u64,
+    /// The number of bytes still queued right after the last successful read,
+    /// i.e. the events that read couldn't fit.
+    /// See [`Fanotify::pending_bytes`](super::Fanotify::pending_bytes).
+    ///
+    /// Checking this takes an extra syscall per read, so it's only done once there's a lag hook
+    /// (see [`BufferedFanotify::on_lag`](super::buffered_fanotify::BufferedFanotify::on_lag)),
+    /// and it's 0 otherwise.
+    pub pending_bytes: usize,
+    /// The time between the last two successful reads.
+    pub read_interval: Duration,
+    /// When the last successful read was.
+    pub last_read_at: Option<Instant>,
+}
+
+impl Stats {
+    /// The average number of events per read, or 0 if nothing has been read yet.
+    pub fn average_batch_size(&self) -> f64 {
+        if self.reads == 0 {
+            return 0.0;
+        }
+        self.events as f64 / self.reads as f64
+    }
+    
+    /// How full the event buffer was in the last read, from 0 to 1.
+    ///
+    /// If this is often close to 1, the buffer is probably too small to keep up.
+    pub fn buffer_utilization(&self) -> f64 {
+        if self.buffer_capacity == 0 {
+            return 0.0;
+        }
+        self.last_batch_bytes as f64 / self.buffer_capacity as f64
+    }
+    
+    /// An estimate of how far behind the reads are:
+    /// how long it would take to read the [`pending_bytes`](Stats::pending_bytes)
+    /// at the pace of the last read, i.e. [`last_batch_bytes`](Stats::last_batch_bytes)
+    /// per [`read_interval`](Stats::read_interval).
+    ///
+    /// This is [`None`] until there have been two reads,
+    /// and zero once the reads have caught up or if there's no lag hook to check the [`pending_bytes`](Stats::pending_bytes).
+    pub fn lag(&self) -> Option<Duration> {
+        if self.reads < 2 || self.last_batch_bytes == 0 {
+            return None;
+        }
+        let batches = self.pending_bytes as f64 / self.last_batch_bytes as f64;
+        Some(self.read_interval.mul_f64(batches))
+    }
+    
+    /// Record the result of a read, and then call the `lag_warning`'s hook if it's lagging.
+    ///
+    /// The queued bytes are only checked if there's a `lag_warning`, since nothing else needs them.
+    pub(super) fn record_and_check(&mut self, result: &io::Result<Events>, lag_warning: &mut Option<LagWarning>) {
+        self.record(result, lag_warning.is_some());
+        if let (Ok(_), Some(warning)) = (result, lag_warning) {
+            warning.check(self);
+        }
+    }
+    
+    /// Record the result of a read, checking the queued bytes if `pending`.
+    fn record(&mut self, result: &io::Result<Events>, pending: bool) {
+        let events = match result {
+            Ok(events) => events,
+            Err(_) => {
+                self.read_errors += 1;
+                return;
+            }
+        };
+        let now = Instant::now();
+        if let Some(last) = self.last_read_at.replace(now) {
+            self.read_interval = now.duration_since(last);
+        }
+        // if the queued bytes can't be checked, there's no lag to report
+        self.pending_bytes = match pending {
+            true => events.fanotify().pending_bytes().unwrap_or(0),
+            false => 0,
+        };
+        let buffer = events.raw_bytes();
+        self.reads += 1;
+        self.bytes += buffer.len() as u64;
+        self.last_batch_bytes = buffer.len();
+        self.buffer_capacity = events.buffer_capacity();
+        if events.carried_over() != 0 {
+            self.truncations += 1;
+        }
+        let mut remaining = buffer;
+        // only the fixed-size metadata at the front of each event is needed,
+        // and it's read unaligned since the buffer is just bytes
+        while remaining.len() >= size_of::<fanotify_event_metadata>() {
+            let event = unsafe { (remaining.as_ptr() as *const fanotify_event_metadata).read_unaligned() };
+            let event_len = event.event_len as usize;
+            if event_len < size_of::<fanotify_event_metadata>() || event_len > remaining.len() {
+                break;
+            }
+            self.events += 1;
+            if event.mask & FAN_Q_OVERFLOW != 0 {
+                self.overflows += 1;
+            }
+            remaining = &remaining[event_len..];
+        }
+    }
+}
+
+/// The hook of a [`LagWarning`], called with the [`Stats::lag`] and the [`Stats`].
+type LagHook = Box<dyn FnMut(Duration, &Stats) + Send>;
+
+/// A hook called with the [`Stats::lag`] after a read whenever it exceeds a threshold,
+/// e.g. to log a warning before the queue overflows.
+///
+/// See [`BufferedFanotify::on_lag`](super::buffered_fanotify::BufferedFanotify::on_lag).
+pub(super) struct LagWarning {
+    threshold: Duration,
+    hook: LagHook,
+}
+
+impl LagWarning {
+    pub fn new(threshold: Duration, hook: impl FnMut(Duration, &Stats) + Send + 'static) -> Self {
+        Self {
+            threshold,
+            hook: Box::new(hook),
+        }
+    }
+    
+    /// Call the hook if the `stats`' [`Stats::lag`] exceeds the threshold.
+    pub fn check(&mut self, stats: &Stats) {
+        match stats.lag() {
+            Some(lag) if lag > self.threshold => (self.hook)(lag, stats),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::event::error::EventResult;
+use crate::event::event::Event;
+use crate::event::file::path_cache::PathCache;
+use crate::event::iterator_ext::IntoEvents;
+use crate::fanotify::buffered_fanotify::BufferedFanotify;
+use crate::mark;
+use crate::mark::Mark;
+use crate::mark::Markable;
+use crate::mark::Mask;
+use crate::mark::OneAction::Add;
+use crate::mark::What;
+
+/// Monitors a whole directory subtree recursively
+/// without having to add an inode mark to every directory in it.
+///
+/// Instead, a single [`MountPoint`](What::MountPoint) or [`FileSystem`](What::FileSystem) mark
+/// is added on the subtree's root, which fires for the whole mount or filesystem,
+/// and events outside of the subtree are filtered out client-side by their resolved paths.
+///
+/// The resolved paths are cached in a [`PathCache`],
+/// so hot files only need an [`fstat`](crate::fd::FD::stat) and not a full path resolution.
+/// Note that this means a file moved in or out of the subtree
+/// can be misclassified until [`SubtreeMonitor::clear_cache`] is called.
+///
+/// Events whose paths can't be resolved, like [`FID`](crate::event::file::File::FID) events,
+/// can't be filtered, so they are always yielded.
+pub struct SubtreeMonitor {
+    fanotify: BufferedFanotify,
+    root: PathBuf,
+    cache: PathCache,
+}
+
+/// An error from [`SubtreeMonitor::watch`].
+#[derive(thiserror::Error, Debug, Eq, PartialEq, Hash)]
+pub enum WatchError {
+    #[error("{}", .0)]
+    Static(#[from] mark::StaticError),
+    #[error("{}", .0)]
+    Mark(#[from] mark::RawError),
+}
+
+impl SubtreeMonitor {
+    /// The default maximum number of cached files.
+    pub const DEFAULT_CACHE_CAPACITY: usize = 4096;
+    
+    /// Create a [`SubtreeMonitor`] for the subtree at `root`.
+    ///
+    /// `root` should be absolute and canonical, since event paths are.
+    pub fn new(fanotify: BufferedFanotify, root: impl Into<PathBuf>) -> Self {
+        Self {
+            fanotify,
+            root: root.into(),
+            cache: PathCache::new(Self::DEFAULT_CACHE_CAPACITY),
+        }
+    }
+    
+    /// Set the maximum number of cached paths.
+    /// Once full, the least recently used one is evicted.
+    pub fn with_cache_capacity(self, cache_capacity: usize) -> Self {
+        Self {
+            cache: PathCache::new(cache_capacity),
+            ..self
+        }
+    }
+    
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    
+    pub fn fanotify(&self) -> &BufferedFanotify {
+        &self.fanotify
+    }
+    
+    pub fn into_fanotify(self) -> BufferedFanotify {
+        self.fanotify
+    }
+    
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+    
+    /// Add a mark on the mount point or filesystem ([`What`]) containing the subtree's root.
+    pub fn watch(&self, what: What, mask: Mask) -> Result<(), WatchError> {
+        let mark = Mark::one(mark::One {
+            action: Add,
+            what,
+            flags: mark::Flags::empty(),
+            mask,
+            path: mark::Path::absolute(&self.root),
+        })?;
+        self.fanotify.mark(mark).map_err(|it| it.error)?;
+        Ok(())
+    }
+    
+    /// Read events like [`BufferedFanotify::read`],
+    /// but only yield those within the subtree (and all errors).
+    pub fn read(&mut self) -> io::Result<impl Iterator<Item=EventResult<'_>>> {
+        let Self {
+            fanotify,
+            root,
+            cache,
+        } = self;
+        let events = fanotify
+            .read()?
+            .all()
+            .filter(move |it| match it {
+                Err(_) => true,
+                Ok(event) => is_in_subtree(root, cache, event),
+            });
+        Ok(events)
+    }
+}
+
+fn is_in_subtree(root: &Path, cache: &mut PathCache, event: &Event<'_>) -> bool {
+    let fd = match event.file().get_fd() {
+        Some(fd) => fd,
+        None => return true,
+    };
+    // can't tell if it can't be resolved
+    cache.path(fd).map_or(true, |path| path.starts_with(root))
+}
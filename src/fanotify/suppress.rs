@@ -0,0 +1,176 @@
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use nix::unistd::getpid;
+use nix::unistd::gettid;
+use nix::unistd::Pid;
+
+use crate::clock;
+use crate::clock::Clock;
+use crate::event::event::Event;
+use crate::event::id::Id;
+
+use super::pipeline::Layer;
+
+/// The paths touched in a [`Suppressor::suppress`] scope.
+#[derive(Debug, Default)]
+pub struct SuppressScope {
+    paths: Vec<PathBuf>,
+}
+
+impl SuppressScope {
+    /// Record that `path` (a file, or a directory for everything under it) is touched in this scope,
+    /// so that only its [`Event`]s are suppressed.
+    ///
+    /// `path` should be absolute and canonical, since event paths are.
+    pub fn touch(&mut self, path: impl Into<PathBuf>) {
+        self.paths.push(path.into());
+    }
+    
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+}
+
+/// A finished [`Suppressor::suppress`] scope.
+#[derive(Debug)]
+struct Scope {
+    tid: Pid,
+    paths: Vec<PathBuf>,
+    expires: Instant,
+}
+
+/// Filters out the [`Event`]s caused by this process's own changes,
+/// so that tools that both watch and modify a tree (formatters, sync clients)
+/// don't react to their own writes.
+///
+/// The changes are made in a [`Suppressor::suppress`] scope,
+/// and its [`Event`]s are suppressed until [`Suppressor::with_grace`] after it ends,
+/// which should be long enough for them to be read.
+/// If any paths are [touched](SuppressScope::touch) in the scope,
+/// only this process's [`Event`]s on those paths are suppressed,
+/// and otherwise all of this process's [`Event`]s are,
+/// including those from other threads unless the group uses [`REPORT_TID`](crate::init::Flags::REPORT_TID).
+///
+/// Unlike [`OwnOutputs`](super::own_outputs::OwnOutputs), this is for one-off changes
+/// to files that are otherwise watched, and the kernel still queues their [`Event`]s.
+#[derive(Debug)]
+pub struct Suppressor {
+    scopes: Vec<Scope>,
+    grace: Duration,
+    suppressed: u64,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for Suppressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Suppressor {
+    /// The default time [`Event`]s are still suppressed after their scope ends.
+    pub const DEFAULT_GRACE: Duration = Duration::from_secs(1);
+    
+    pub fn new() -> Self {
+        Self {
+            scopes: Vec::new(),
+            grace: Self::DEFAULT_GRACE,
+            suppressed: 0,
+            clock: clock::system(),
+        }
+    }
+    
+    /// Suppress [`Event`]s for `grace` after their scope ends instead of [`Suppressor::DEFAULT_GRACE`].
+    pub fn with_grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+    
+    /// Use `clock` for the grace periods instead of the [system](clock::SystemClock) one.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    /// Run `f`, suppressing the [`Event`]s of the changes it makes.
+    ///
+    /// `f` can [touch](SuppressScope::touch) the paths it changes to only suppress their [`Event`]s.
+    pub fn suppress<R>(&mut self, f: impl FnOnce(&mut SuppressScope) -> R) -> R {
+        let mut scope = SuppressScope::default();
+        let result = f(&mut scope);
+        self.scopes.push(Scope {
+            tid: gettid(),
+            paths: scope.paths,
+            expires: self.clock.now() + self.grace,
+        });
+        result
+    }
+    
+    /// The number of scopes whose [`Event`]s are still being suppressed.
+    pub fn active(&self) -> usize {
+        let now = self.clock.now();
+        self.scopes.iter().filter(|it| it.expires > now).count()
+    }
+    
+    /// The number of [`Event`]s suppressed so far.
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed
+    }
+    
+    /// Forget the scopes whose grace periods are over.
+    pub fn prune(&mut self) {
+        let now = self.clock.now();
+        self.scopes.retain(|it| it.expires > now);
+    }
+    
+    /// If `event` was caused by the changes in an active scope, counting it if so.
+    pub fn is_suppressed(&mut self, event: &Event<'_>) -> bool {
+        self.prune();
+        if self.scopes.is_empty() {
+            return false;
+        }
+        let id = event.id().id();
+        let mut path = None;
+        let suppressed = self.scopes.iter().any(|scope| {
+            let is_own = match id {
+                Id::Pid(pid) => pid == getpid(),
+                Id::Tid(tid) => tid == scope.tid,
+            };
+            if !is_own {
+                return false;
+            }
+            if scope.paths.is_empty() {
+                return true;
+            }
+            let path = path.get_or_insert_with(|| event.file().path().and_then(Result::ok));
+            match path {
+                None => false,
+                Some(path) => scope.paths.iter().any(|it| path.starts_with(it)),
+            }
+        });
+        if suppressed {
+            self.suppressed += 1;
+        }
+        suppressed
+    }
+    
+    /// If `path` was touched in an active scope.
+    pub fn is_touched(&self, path: &Path) -> bool {
+        let now = self.clock.now();
+        self.scopes
+            .iter()
+            .filter(|it| it.expires > now)
+            .any(|scope| scope.paths.iter().any(|it| path.starts_with(it)))
+    }
+}
+
+/// Drop the [`Event`]s that are [suppressed](Suppressor::is_suppressed).
+impl Layer for Suppressor {
+    fn handle(&mut self, event: &Event<'_>) -> bool {
+        !self.is_suppressed(event)
+    }
+}
@@ -0,0 +1,171 @@
+use std::fs;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::IntoRawFd;
+use std::os::unix::io::OwnedFd;
+use std::os::unix::io::RawFd;
+use std::path::PathBuf;
+
+use nix::errno::Errno;
+
+use crate::init::Flags;
+use crate::init::InitDiff;
+use crate::init::RawInit;
+use crate::libc::call::libc_call;
+use crate::proc;
+
+use super::Fanotify;
+
+/// The kernel's `O_LARGEFILE`, which it always adds to the event flags on 64-bit systems,
+/// even though it's 0 in [`libc`] there.
+const KERNEL_O_LARGEFILE: u32 = 0o100000;
+
+/// An error from verifying that a [`RawInit`] matches the group of a fanotify fd.
+#[derive(thiserror::Error, Debug)]
+pub enum InitVerifyError {
+    #[error("fd {} isn't a fanotify group, but {:?}", .fd, .target)]
+    NotFanotify { fd: RawFd, target: PathBuf },
+    #[error("the init flags given for fd {} don't match its group's: {}", .fd, .diff)]
+    Mismatch {
+        fd: RawFd,
+        /// The group's actual [`RawInit`], if it could be read from `/proc` (not just [`fcntl`](libc::fcntl)).
+        actual: Option<RawInit>,
+        /// From the given [`RawInit`] (this) to the actual one (other).
+        diff: InitDiff,
+    },
+    #[error("couldn't inspect fd {}: {}", .fd, .error)]
+    Inspect { fd: RawFd, error: io::Error },
+}
+
+/// Parse the `fanotify flags:{:x} event-flags:{:x}` line of a fanotify fd's `fdinfo`.
+fn parse_fdinfo(fdinfo: &str) -> Option<RawInit> {
+    let line = fdinfo.lines().find_map(|it| it.strip_prefix("fanotify flags:"))?;
+    let (flags, event_flags) = line.split_once(" event-flags:")?;
+    Some(RawInit {
+        flags: u32::from_str_radix(flags.trim(), 16).ok()?,
+        event_flags: u32::from_str_radix(event_flags.trim(), 16).ok()? & !KERNEL_O_LARGEFILE,
+    })
+}
+
+/// Get the [`fcntl`](libc::fcntl) `cmd` flags of `fd`.
+fn fcntl_flags(fd: RawFd, cmd: libc::c_int) -> io::Result<libc::c_int> {
+    libc_call(|| unsafe { libc::fcntl(fd, cmd) })
+        .map_err(|errno: Errno| io::Error::from_raw_os_error(errno as i32))
+}
+
+impl RawInit {
+    /// Read the actual [`RawInit`] of the fanotify group `fd` from `/proc/self/fdinfo`,
+    /// e.g. for an fd received from another process.
+    ///
+    /// The group's flags there are the ones it was created with,
+    /// but [`NON_BLOCKING`](Flags::NON_BLOCKING) can be changed with [`fcntl`](libc::fcntl) afterwards,
+    /// so it's taken from `fd` itself instead.
+    ///
+    /// This returns [`None`] if `fd` isn't a fanotify group
+    /// (or if the kernel is too old to report it, before Linux 3.8).
+    pub fn of_fd(fd: RawFd) -> io::Result<Option<Self>> {
+        let fdinfo = fs::read_to_string(proc::self_fdinfo(fd)?)?;
+        let mut init = match parse_fdinfo(&fdinfo) {
+            None => return Ok(None),
+            Some(init) => init,
+        };
+        if fcntl_flags(fd, libc::F_GETFL)? & libc::O_NONBLOCK != 0 {
+            init.flags |= Flags::NON_BLOCKING.bits();
+        } else {
+            init.flags &= !Flags::NON_BLOCKING.bits();
+        }
+        Ok(Some(init))
+    }
+}
+
+/// Verify that `init` is consistent with the fanotify group `fd`.  See [`Fanotify::verify_init`].
+pub fn verify_raw_fd(fd: RawFd, init: RawInit) -> Result<(), InitVerifyError> {
+    let inspect = |error: io::Error| InitVerifyError::Inspect { fd, error };
+    if proc::is_available() {
+        let target = fs::read_link(proc::self_fd(fd).map_err(io::Error::from).map_err(inspect)?)
+            .map_err(inspect)?;
+        if target.as_os_str() != "anon_inode:[fanotify]" {
+            return Err(InitVerifyError::NotFanotify { fd, target });
+        }
+        if let Some(actual) = RawInit::of_fd(fd).map_err(inspect)? {
+            let diff = init.diff(&actual);
+            if diff.is_empty() {
+                return Ok(());
+            }
+            return Err(InitVerifyError::Mismatch {
+                fd,
+                actual: Some(actual),
+                diff,
+            });
+        }
+    }
+    // Without /proc, only the fd's own flags can be checked.
+    // It can be made non-blocking after fanotify_init(), e.g. for async reads, but not the other way around.
+    let status = fcntl_flags(fd, libc::F_GETFL).map_err(inspect)?;
+    if init.flags().contains(Flags::NON_BLOCKING) && status & libc::O_NONBLOCK == 0 {
+        let mut actual = init.undo_raw();
+        actual.flags.remove(Flags::NON_BLOCKING);
+        return Err(InitVerifyError::Mismatch {
+            fd,
+            actual: None,
+            diff: init.undo_raw().diff(&actual),
+        });
+    }
+    Ok(())
+}
+
+impl Fanotify {
+    /// See [`raw::fanotify_from_raw_fd_verified`](crate::raw::fanotify_from_raw_fd_verified).
+    ///
+    /// # Safety
+    /// See [`FromRawFd`](std::os::unix::io::FromRawFd).
+    pub(crate) unsafe fn from_raw_fd_verified(fd: RawFd, init: RawInit) -> Result<Self, InitVerifyError> {
+        verify_raw_fd(fd, init)?;
+        Ok(Self::from_raw_fd(fd, init))
+    }
+    
+    /// Create a [`Fanotify`] from an owned fanotify group `fd` and the [`RawInit`] flags it was created with,
+    /// after [verifying](Fanotify::verify_init) that they match, since a mismatched [`RawInit`] would mis-parse its events.
+    ///
+    /// This is the safe alternative to [`raw::fanotify_from_raw_fd`](crate::raw::fanotify_from_raw_fd).
+    /// If the [`RawInit`] isn't known, it can be read with [`RawInit::of_fd`].
+    /// If this fails, `fd` is closed.
+    pub fn try_from_owned(fd: OwnedFd, init: RawInit) -> Result<Self, InitVerifyError> {
+        verify_raw_fd(fd.as_raw_fd(), init)?;
+        Ok(unsafe { Self::from_raw_fd(fd.into_raw_fd(), init) })
+    }
+    
+    /// Verify that this group's [`RawInit`] is consistent with its fd,
+    /// which matters for groups created with [`raw::fanotify_from_raw_fd`](crate::raw::fanotify_from_raw_fd).
+    ///
+    /// This compares it with the group's actual init flags (see [`RawInit::of_fd`]).
+    /// If `/proc` is [unavailable](proc::set_unavailable), only the fd's own flags can be checked
+    /// with [`fcntl`](libc::fcntl), which only detects an obviously inconsistent
+    /// [`NON_BLOCKING`](Flags::NON_BLOCKING) flag.
+    pub fn verify_init(&self) -> Result<(), InitVerifyError> {
+        verify_raw_fd(self.fd.as_raw_fd(), self.init)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::init::EventFlags;
+    use crate::init::Flags;
+    use crate::init::Init;
+    use crate::init::NotificationClass;
+    use crate::init::ReadWrite;
+    
+    use super::parse_fdinfo;
+    
+    #[test]
+    fn parse_fanotify_fdinfo() {
+        let fdinfo = "pos:\t0\nflags:\t02004002\nmnt_id:\t17\nino:\t26\nfanotify flags:1b event-flags:88002\n";
+        assert_eq!(parse_fdinfo(fdinfo), Some(Init {
+            notification_class: NotificationClass::PreContent,
+            flags: Flags::CLOSE_ON_EXEC | Flags::NON_BLOCKING | Flags::UNLIMITED_QUEUE,
+            rw: ReadWrite::ReadAndWrite,
+            event_flags: EventFlags::CLOSE_ON_EXEC,
+        }.as_raw()));
+        assert_eq!(parse_fdinfo("pos:\t0\nflags:\t02\n"), None);
+    }
+}
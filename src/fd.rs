@@ -1,20 +1,64 @@
 use std::cmp;
+use std::ffi::CStr;
 use std::fmt;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::io;
+use std::io::IoSliceMut;
 use std::mem;
 use std::os::raw::c_void;
+use std::os::unix::io::AsFd;
 use std::os::unix::io::AsRawFd;
+use std::os::unix::io::BorrowedFd;
 use std::os::unix::io::FromRawFd;
 use std::os::unix::io::IntoRawFd;
 use std::os::unix::io::RawFd;
-use std::path::Path;
 use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
 
 use nix::errno::Errno;
 
 use crate::libc::call::libc_call;
+use crate::libc::call::libc_call_once;
+use crate::libc::call::RetryPolicy;
+use crate::proc;
+
+/// The identity of a file, its `(device, inode)`, as returned by [`FD::identity`].
+///
+/// Unlike a path, it can't change out from under you (except through inode reuse after deletion),
+/// so it's good for deduplicating files and matching them against known sets of files.
+pub type Identity = (libc::dev_t, libc::ino_t);
+
+/// The type of a file, as returned by [`FD::file_type`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum FileType {
+    Regular,
+    Directory,
+    Symlink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharacterDevice,
+    /// A file type not known to this crate.
+    Unknown,
+}
+
+impl FileType {
+    /// Get the [`FileType`] from the `st_mode` of a [`libc::stat`](struct@libc::stat).
+    pub const fn from_mode(mode: libc::mode_t) -> Self {
+        match mode & libc::S_IFMT {
+            libc::S_IFREG => Self::Regular,
+            libc::S_IFDIR => Self::Directory,
+            libc::S_IFLNK => Self::Symlink,
+            libc::S_IFIFO => Self::Fifo,
+            libc::S_IFSOCK => Self::Socket,
+            libc::S_IFBLK => Self::BlockDevice,
+            libc::S_IFCHR => Self::CharacterDevice,
+            _ => Self::Unknown,
+        }
+    }
+}
 
 /// A wrapper around an open [`RawFd`] file descriptor with RAII semantics
 /// and generic file descriptor related functions
@@ -41,6 +85,13 @@ impl AsRawFd for FD {
     }
 }
 
+impl AsFd for FD {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // the fd is open for as long as self is borrowed
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
+    }
+}
+
 impl IntoRawFd for FD {
     fn into_raw_fd(self) -> RawFd {
         let fd = self.fd;
@@ -69,6 +120,8 @@ impl FD {
     ///
     /// Return the number of bytes read like [`libc::read`]
     /// or the libc [`Errno`] if there was an error.
+    ///
+    /// [`EINTR`](Errno::EINTR) is handled according to the [`RetryPolicy`](crate::libc::RetryPolicy).
     pub fn read(&self, buf: &mut [u8]) -> Result<usize, Errno> {
         if buf.is_empty() {
             return Ok(0);
@@ -79,10 +132,43 @@ impl FD {
         Ok(bytes_read as usize)
     }
     
+    /// Like [`FD::read`], but reading at `offset` (with [`libc::pread`])
+    /// instead of at (and advancing) the file offset.
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize, Errno> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let len = cmp::min(buf.len(), libc::ssize_t::MAX as usize) as libc::size_t;
+        let buf = buf.as_mut_ptr() as *mut c_void;
+        let offset = offset as libc::off_t;
+        let bytes_read = libc_call(|| unsafe { libc::pread(self.fd, buf, len, offset) })?;
+        Ok(bytes_read as usize)
+    }
+    
+    /// Read from this file descriptor into the given buffers in order as much as possible,
+    /// using a single [`libc::readv`] call.
+    ///
+    /// Return the total number of bytes read like [`libc::readv`]
+    /// or the libc [`Errno`] if there was an error.
+    ///
+    /// [`EINTR`](Errno::EINTR) is handled according to the [`RetryPolicy`](crate::libc::RetryPolicy).
+    pub fn read_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> Result<usize, Errno> {
+        if bufs.is_empty() {
+            return Ok(0);
+        }
+        // IoSliceMut is guaranteed to be ABI compatible with iovec on unix
+        let iov = bufs.as_mut_ptr() as *const libc::iovec;
+        let iov_count = cmp::min(bufs.len(), libc::c_int::MAX as usize) as libc::c_int;
+        let bytes_read = libc_call(|| unsafe { libc::readv(self.fd, iov, iov_count) })?;
+        Ok(bytes_read as usize)
+    }
+    
     /// Write from given buffer to this file descriptor as much as possible.
     ///
     /// Return the number of bytes written like [`libc::write`]
     /// or the libc [`Errno`] if there was an error.
+    ///
+    /// [`EINTR`](Errno::EINTR) is handled according to the [`RetryPolicy`](crate::libc::RetryPolicy).
     pub fn write(&self, buf: &[u8]) -> Result<usize, Errno> {
         if buf.is_empty() {
             return Ok(0);
@@ -93,11 +179,146 @@ impl FD {
         Ok(bytes_written as usize)
     }
     
+    /// Put this file descriptor in non-blocking mode ([`libc::O_NONBLOCK`]).
+    pub fn set_non_blocking(&self) -> Result<(), Errno> {
+        let flags = libc_call(|| unsafe { libc::fcntl(self.fd, libc::F_GETFL) })?;
+        if flags & libc::O_NONBLOCK == 0 {
+            libc_call(|| unsafe { libc::fcntl(self.fd, libc::F_SETFL, flags | libc::O_NONBLOCK) })?;
+        }
+        Ok(())
+    }
+    
+    /// Wait until this file descriptor is ready for any of the given [`libc::poll`] `events`
+    /// (e.g. [`libc::POLLIN`]), or until the `timeout` expires.
+    /// A `timeout` of [`None`] waits forever,
+    /// and others are rounded up to whole milliseconds (up to [`libc::c_int::MAX`] of them).
+    ///
+    /// If the wait is interrupted by a signal and the [`RetryPolicy`] retries it,
+    /// it only waits for what's left of the `timeout`, so regular signals can't extend it.
+    ///
+    /// Return if the file descriptor became ready before the `timeout`
+    /// or the libc [`Errno`] if there was an error.
+    pub fn poll(&self, events: libc::c_short, timeout: Option<Duration>) -> Result<bool, Errno> {
+        let mut poll_fd = libc::pollfd {
+            fd: self.fd,
+            events,
+            revents: 0,
+        };
+        let start = Instant::now();
+        let policy = RetryPolicy::get();
+        loop {
+            let timeout = match timeout {
+                None => -1,
+                // round up, so that a sub-millisecond timeout still waits instead of returning immediately,
+                // and clamp, since poll only takes an int of milliseconds
+                Some(timeout) => {
+                    let remaining = timeout.saturating_sub(start.elapsed());
+                    let millis = remaining.as_nanos().div_ceil(1_000_000);
+                    cmp::min(millis, libc::c_int::MAX as u128) as libc::c_int
+                }
+            };
+            match libc_call_once(|| unsafe { libc::poll(&mut poll_fd, 1, timeout) }) {
+                Err(errno) if policy.should_retry(errno) => continue,
+                result => return Ok(result? > 0),
+            }
+        }
+    }
+    
+    /// Wait until this file descriptor is readable.  See [`FD::poll`].
+    pub fn readable(&self, timeout: Option<Duration>) -> Result<bool, Errno> {
+        self.poll(libc::POLLIN, timeout)
+    }
+    
+    /// Wait until this file descriptor is writable.  See [`FD::poll`].
+    pub fn writable(&self, timeout: Option<Duration>) -> Result<bool, Errno> {
+        self.poll(libc::POLLOUT, timeout)
+    }
+    
+    /// Get the number of bytes available to read from this file descriptor
+    /// without blocking, using [`libc::ioctl`] with [`libc::FIONREAD`].
+    pub fn pending_bytes(&self) -> Result<usize, Errno> {
+        let mut bytes: libc::c_int = 0;
+        libc_call(|| unsafe { libc::ioctl(self.fd, libc::FIONREAD, &mut bytes as *mut libc::c_int) })?;
+        Ok(bytes as usize)
+    }
+    
+    /// Get the value of the extended attribute `name` of this file descriptor using [`libc::fgetxattr`],
+    /// or [`None`] if it doesn't have one ([`ENODATA`](Errno::ENODATA)).
+    pub fn xattr(&self, name: &CStr) -> Result<Option<Vec<u8>>, Errno> {
+        let get = |value: &mut [u8]| {
+            let ptr = value.as_mut_ptr() as *mut c_void;
+            libc_call(|| unsafe { libc::fgetxattr(self.fd, name.as_ptr(), ptr, value.len()) })
+        };
+        loop {
+            let len = match get(&mut []) {
+                Err(Errno::ENODATA) => return Ok(None),
+                result => result? as usize,
+            };
+            let mut value = vec![0; len];
+            match get(&mut value) {
+                Ok(len) => {
+                    value.truncate(len as usize);
+                    return Ok(Some(value));
+                }
+                // it grew in between
+                Err(Errno::ERANGE) => continue,
+                Err(Errno::ENODATA) => return Ok(None),
+                Err(errno) => return Err(errno),
+            }
+        }
+    }
+    
+    /// Duplicate this file descriptor (with [`libc::FD_CLOEXEC`] set) using [`libc::fcntl`].
+    pub fn try_clone(&self) -> Result<Self, Errno> {
+        let fd = libc_call(|| unsafe { libc::fcntl(self.fd, libc::F_DUPFD_CLOEXEC, 0) })?;
+        Ok(Self { fd })
+    }
+    
+    /// Get the [`libc::stat`](struct@libc::stat) of this file descriptor using [`libc::fstat`].
+    pub fn stat(&self) -> Result<libc::stat, Errno> {
+        let mut stat = mem::MaybeUninit::<libc::stat>::uninit();
+        libc_call(|| unsafe { libc::fstat(self.fd, stat.as_mut_ptr()) })?;
+        // fstat succeeded, so it's initialized
+        Ok(unsafe { stat.assume_init() })
+    }
+    
+    /// Get the [`Identity`] (`(device, inode)`) of this file descriptor using [`FD::stat`].
+    pub fn identity(&self) -> Result<Identity, Errno> {
+        let stat = self.stat()?;
+        Ok((stat.st_dev, stat.st_ino))
+    }
+    
+    /// Get the [`FileType`] of this file descriptor using [`FD::stat`].
+    pub fn file_type(&self) -> Result<FileType, Errno> {
+        Ok(FileType::from_mode(self.stat()?.st_mode))
+    }
+    
     /// Resolve this file descriptor to its path using the `/proc` filesystem.
+    ///
+    /// See [`proc::root`] for where `/proc` is.
+    /// If it's [unavailable](proc::set_unavailable),
+    /// this returns a [`ProcUnavailable`](proc::ProcUnavailable) error.
+    ///
+    /// If the file has been deleted, the `" (deleted)"` suffix the kernel adds is removed,
+    /// so this is the path it had.  Use [`FD::resolve`] to tell if it was.
     pub fn path(&self) -> io::Result<PathBuf> {
-        Path::new("/proc/self/fd")
-            .join(self.fd.to_string())
-            .read_link()
+        let target = proc::self_fd(self.fd)?.read_link()?;
+        match proc::split_deleted(&target) {
+            (path, proc::LinkState::Deleted) if self.stat().map_or(true, |it| it.st_nlink == 0) => {
+                Ok(path.to_path_buf())
+            }
+            _ => Ok(target),
+        }
+    }
+    
+    /// Like [`FD::path`], but with control over symlink normalization
+    /// and detecting if the file has been [deleted](proc::LinkState::Deleted).
+    ///
+    /// A file that's just named with a `" (deleted)"` suffix is told apart by its link count.
+    pub fn resolve(&self, symlinks: proc::Symlinks) -> io::Result<proc::ResolvedPath> {
+        proc::resolve_link_checked(&proc::self_fd(self.fd)?, symlinks, || {
+            self.stat().map_or(true, |it| it.st_nlink == 0)
+        })
     }
 }
 
@@ -0,0 +1,138 @@
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use super::EventFlags;
+use super::Flags;
+use super::Init;
+use super::NotificationClass;
+use super::RawInit;
+use super::ReadWrite;
+
+/// One field that differs between two [`Init`]s, from [`Init::diff`].
+///
+/// For the flags, only the flags set in just one of the [`Init`]s are included.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum InitDifference {
+    NotificationClass { this: NotificationClass, other: NotificationClass },
+    Flags { only_this: Flags, only_other: Flags },
+    ReadWrite { this: ReadWrite, other: ReadWrite },
+    EventFlags { only_this: EventFlags, only_other: EventFlags },
+}
+
+/// Format the flags set in just one of the [`Init`]s, which are [`None`] if there are none.
+fn fmt_flags(f: &mut Formatter<'_>, name: &str, only_this: Option<&dyn Debug>, only_other: Option<&dyn Debug>) -> fmt::Result {
+    write!(f, "{}:", name)?;
+    if let Some(only_this) = only_this {
+        write!(f, " {:?} only in this", only_this)?;
+        if only_other.is_some() {
+            write!(f, ",")?;
+        }
+    }
+    if let Some(only_other) = only_other {
+        write!(f, " {:?} only in other", only_other)?;
+    }
+    Ok(())
+}
+
+impl Display for InitDifference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::NotificationClass { this, other } => write!(f, "notification_class: {:?} != {:?}", this, other),
+            Self::Flags { only_this, only_other } => fmt_flags(
+                f,
+                "flags",
+                (!only_this.is_empty()).then_some(&only_this as &dyn Debug),
+                (!only_other.is_empty()).then_some(&only_other as &dyn Debug),
+            ),
+            Self::ReadWrite { this, other } => write!(f, "rw: {:?} != {:?}", this, other),
+            Self::EventFlags { only_this, only_other } => fmt_flags(
+                f,
+                "event_flags",
+                (!only_this.is_empty()).then_some(&only_this as &dyn Debug),
+                (!only_other.is_empty()).then_some(&only_other as &dyn Debug),
+            ),
+        }
+    }
+}
+
+/// The differences between two [`Init`]s (or [`RawInit`]s), from [`Init::diff`] or [`RawInit::diff`],
+/// for debugging why two groups behave differently.
+///
+/// It's [displayed](Display) as a human-readable list of the [`InitDifference`]s,
+/// like `notification_class: Notify != PreContent; flags: REPORT_FID only in this`.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct InitDiff {
+    /// In the order of [`Init`]'s fields.
+    pub differences: Vec<InitDifference>,
+}
+
+impl InitDiff {
+    /// If the [`Init`]s are the same.
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+    
+    pub fn len(&self) -> usize {
+        self.differences.len()
+    }
+    
+    pub fn iter(&self) -> impl Iterator<Item = &InitDifference> {
+        self.differences.iter()
+    }
+}
+
+impl Display for InitDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no differences");
+        }
+        for (i, difference) in self.iter().enumerate() {
+            if i != 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", difference)?;
+        }
+        Ok(())
+    }
+}
+
+impl Init {
+    /// Compare this [`Init`] to `other`, field by field.  See [`InitDiff`].
+    pub fn diff(&self, other: &Self) -> InitDiff {
+        let mut differences = Vec::new();
+        if self.notification_class != other.notification_class {
+            differences.push(InitDifference::NotificationClass {
+                this: self.notification_class,
+                other: other.notification_class,
+            });
+        }
+        if self.flags != other.flags {
+            differences.push(InitDifference::Flags {
+                only_this: self.flags - other.flags,
+                only_other: other.flags - self.flags,
+            });
+        }
+        if self.rw != other.rw {
+            differences.push(InitDifference::ReadWrite {
+                this: self.rw,
+                other: other.rw,
+            });
+        }
+        if self.event_flags != other.event_flags {
+            differences.push(InitDifference::EventFlags {
+                only_this: self.event_flags - other.event_flags,
+                only_other: other.event_flags - self.event_flags,
+            });
+        }
+        InitDiff { differences }
+    }
+}
+
+impl RawInit {
+    /// Like [`Init::diff`].
+    pub fn diff(&self, other: &Self) -> InitDiff {
+        self.undo_raw().diff(&other.undo_raw())
+    }
+}
@@ -1,6 +1,12 @@
 use bitflags::bitflags;
 
 bitflags! {
+    /// The flags that event fds are opened with.
+    ///
+    /// These are all of the flags the kernel allows for event fds;
+    /// any others, like `O_PATH` or `O_DIRECTORY`, make `fanotify_init` fail with `EINVAL`.
+    /// In [`REPORT_FID`](super::Flags::REPORT_FID) mode, there are no event fds,
+    /// and [`HandleOpen`](crate::event::file::fid::HandleOpen) chooses how to open each file handle instead.
     pub struct EventFlags: u32 {
         const LARGE_FILE = libc::O_LARGEFILE as u32;
         const CLOSE_ON_EXEC = libc::O_CLOEXEC as u32;
@@ -12,6 +12,7 @@ bitflags! {
         const REPORT_FID = flag::FAN_REPORT_FID;
         const REPORT_DIR_FID = flag::FAN_REPORT_DIR_FID;
         const REPORT_NAME = flag::FAN_REPORT_NAME;
+        const ENABLE_AUDIT = flag::FAN_ENABLE_AUDIT;
     }
 }
 
@@ -24,6 +24,68 @@ impl Init {
             event_flags: EventFlags::const_default(),
         }
     }
+    
+    /// Plain notifications of file accesses and modifications, with an fd for each event.
+    ///
+    /// Uses [`NotificationClass::Notify`] and opens the event fds with [`EventFlags::CLOSE_ON_EXEC`].
+    /// Requires Linux 2.6.37 and `CAP_SYS_ADMIN`.
+    pub const fn notification() -> Self {
+        Self {
+            notification_class: NotificationClass::Notify,
+            flags: Flags::CLOSE_ON_EXEC,
+            rw: ReadWrite::Read,
+            event_flags: EventFlags::CLOSE_ON_EXEC,
+        }
+    }
+    
+    /// Allow or deny file accesses before the content of the file is accessed,
+    /// i.e. for [`OPEN_PERMISSION`](crate::mark::Mask::OPEN_PERMISSION) and [`ACCESS_PERMISSION`](crate::mark::Mask::ACCESS_PERMISSION) events,
+    /// like an access gate or a hierarchical storage manager.
+    ///
+    /// Uses [`NotificationClass::PreContent`] and an [`unlimited`](Flags::unlimited) queue,
+    /// since a lost permission event can't be responded to.
+    /// Requires Linux 2.6.37, `CAP_SYS_ADMIN`, and a kernel with `CONFIG_FANOTIFY_ACCESS_PERMISSIONS`.
+    pub const fn permission_gate() -> Self {
+        Self {
+            notification_class: NotificationClass::PreContent,
+            flags: Flags::from_bits_truncate(Flags::CLOSE_ON_EXEC.bits() | Flags::unlimited().bits()),
+            rw: ReadWrite::Read,
+            event_flags: EventFlags::CLOSE_ON_EXEC,
+        }
+    }
+    
+    /// Track files by [`FileHandle`](crate::event::file::fid::FileHandle)s instead of fds,
+    /// which also supports directory entry events
+    /// like [`CREATE`](crate::mark::Mask::CREATE), [`DELETE`](crate::mark::Mask::DELETE), and moves.
+    ///
+    /// Uses [`Flags::REPORT_FID`] with [`NotificationClass::Notify`],
+    /// since [`Flags::REPORT_FID`] can't be used with permission events.
+    /// Requires Linux 5.1 and `CAP_SYS_ADMIN`.
+    pub const fn fid_tracking() -> Self {
+        Self {
+            notification_class: NotificationClass::Notify,
+            flags: Flags::from_bits_truncate(Flags::CLOSE_ON_EXEC.bits() | Flags::REPORT_FID.bits()),
+            rw: ReadWrite::Read,
+            event_flags: EventFlags::const_default(),
+        }
+    }
+    
+    /// Like [`Init::permission_gate`], but also allows permission decisions to be audited
+    /// by setting the [`FilePermission::audit`](crate::event::file::permission::FilePermission#structfield.audit) field
+    /// or calling [`FilePermission::audit_rule`](crate::event::file::permission::FilePermission::audit_rule).
+    ///
+    /// Also uses [`Flags::ENABLE_AUDIT`].
+    /// Requires Linux 4.15, `CAP_AUDIT_WRITE`, and a kernel with `CONFIG_FANOTIFY_ACCESS_PERMISSIONS`.
+    pub const fn audit() -> Self {
+        Self {
+            notification_class: NotificationClass::PreContent,
+            flags: Flags::from_bits_truncate(
+                Flags::CLOSE_ON_EXEC.bits() | Flags::unlimited().bits() | Flags::ENABLE_AUDIT.bits()
+            ),
+            rw: ReadWrite::Read,
+            event_flags: EventFlags::CLOSE_ON_EXEC,
+        }
+    }
 }
 
 impl Default for Init {
@@ -1,3 +1,5 @@
+pub use diff::InitDiff;
+pub use diff::InitDifference;
 pub use error::Error;
 pub use event_flags::EventFlags;
 pub use flags::Flags;
@@ -14,6 +16,7 @@ mod event_flags;
 mod init;
 mod raw;
 mod error;
+mod diff;
 
 #[cfg(test)]
 mod tests {
@@ -35,4 +38,18 @@ mod tests {
             }",
         );
     }
+    
+    #[test]
+    fn init_diff() {
+        let diff = Init::notification().diff(&Init::audit());
+        assert_eq!(diff.len(), 2);
+        assert_eq!(
+            diff.to_string(),
+            "notification_class: Notify != PreContent; \
+            flags: UNLIMITED_QUEUE | UNLIMITED_MARKS | ENABLE_AUDIT only in other",
+        );
+        let diff = Init::fid_tracking().as_raw().diff(&Init::notification().as_raw());
+        assert_eq!(diff.to_string(), "flags: REPORT_FID only in this; event_flags: CLOSE_ON_EXEC only in other");
+        assert!(Init::audit().diff(&Init::audit()).is_empty());
+    }
 }
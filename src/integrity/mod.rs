@@ -0,0 +1,314 @@
+//! Baseline integrity monitoring: detecting changes to a known set of files,
+//! a minimal building block for file integrity monitoring (FIM).
+//!
+//! Requires the `integrity` feature.
+//!
+//! A [`Manifest`] maps each file's path to its expected [`Digest`], like the output of `sha256sum`.
+//! An [`IntegrityMonitor`] [marks](IntegrityMonitor::mark) the parent directory of each file,
+//! and then [checks](IntegrityMonitor::check) the [`MODIFY`](Mask::MODIFY)
+//! and [`CLOSE_WRITE`](Mask::CLOSE_WRITE) [`Event`]s for them by re-hashing the file,
+//! reporting any mismatch as an [`IntegrityViolation`].
+//!
+//! Since the parent directories are marked, only [`FD`](crate::event::file::File::FD) (and permission)
+//! [`Event`]s can be checked, so the group must not use [`REPORT_FID`](crate::init::Flags::REPORT_FID).
+//! Those don't include replacing a file by renaming another one over it,
+//! so [`IntegrityMonitor::verify_all`] should also be run once at startup and then periodically.
+//!
+//! [`MODIFY`](Mask::MODIFY) [`Event`]s can be checked while a file is still being written,
+//! so a violation can be reported for an intermediate state of the file.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::event::event::Event;
+use crate::event::sink::Sink;
+use crate::event::sink::SinkError;
+use crate::fd::FD;
+use crate::mark;
+use crate::mark::Mark;
+use crate::mark::Markable;
+use crate::mark::Mask;
+use crate::mark::OneAction::Add;
+use crate::mark::What;
+
+pub use sha256::Digest;
+
+pub mod sha256;
+pub mod xattr;
+
+#[derive(Error, Debug)]
+pub enum ManifestError {
+    #[error("line {}: {}", .line, .reason)]
+    Parse { line: usize, reason: &'static str },
+    #[error("{}", .0)]
+    Io(#[from] io::Error),
+}
+
+/// The expected [`Digest`] of each file in a known set of files.
+///
+/// Its text format is the same as `sha256sum`'s: a line of `<hex digest>  <path>` per file,
+/// where the path can also be preceded by a `*` instead of a space (binary mode).
+/// Blank lines and lines starting with `#` are ignored.
+///
+/// The paths should be absolute, since they're compared with the paths of [`Event`]s.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Manifest {
+    digests: BTreeMap<PathBuf, Digest>,
+}
+
+impl Manifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Hash each of `paths` as it is now.
+    pub fn generate(paths: impl IntoIterator<Item = impl Into<PathBuf>>) -> io::Result<Self> {
+        let mut manifest = Self::new();
+        for path in paths {
+            let path = path.into();
+            let digest = sha256::hash_reader(fs::File::open(&path)?)?;
+            manifest.insert(path, digest);
+        }
+        Ok(manifest)
+    }
+    
+    /// Parse a manifest in the `sha256sum` format.
+    pub fn parse(text: &str) -> Result<Self, ManifestError> {
+        let mut manifest = Self::new();
+        for (i, line) in text.lines().enumerate() {
+            let error = |reason| ManifestError::Parse { line: i + 1, reason };
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.len() < 66 || !line.is_char_boundary(64) {
+                return Err(error("expected a digest and a path"));
+            }
+            let digest = sha256::from_hex(&line[..64]).ok_or_else(|| error("invalid digest"))?;
+            let path = match line[64..].strip_prefix(' ') {
+                Some(path) => path.strip_prefix(|c| c == ' ' || c == '*'),
+                None => None,
+            };
+            match path {
+                Some(path) if !path.is_empty() => manifest.insert(path, digest),
+                _ => return Err(error("expected two spaces or \" *\" between the digest and the path")),
+            };
+        }
+        Ok(manifest)
+    }
+    
+    /// Read and [parse](Manifest::parse) a manifest file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ManifestError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+    
+    /// Set the expected [`Digest`] of `path`, returning the previous one, if any.
+    pub fn insert(&mut self, path: impl Into<PathBuf>, digest: Digest) -> Option<Digest> {
+        self.digests.insert(path.into(), digest)
+    }
+    
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> Option<Digest> {
+        self.digests.remove(path.as_ref())
+    }
+    
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&Digest> {
+        self.digests.get(path.as_ref())
+    }
+    
+    pub fn len(&self) -> usize {
+        self.digests.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.digests.is_empty()
+    }
+    
+    /// Iterate over the paths and their expected [`Digest`]s, sorted by path.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &Digest)> {
+        self.digests.iter().map(|(path, digest)| (path.as_path(), digest))
+    }
+}
+
+/// Formatted in the `sha256sum` format, so it can be [parsed](Manifest::parse) back.
+impl Display for Manifest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (path, digest) in self.iter() {
+            writeln!(f, "{}  {}", sha256::to_hex(digest), path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// A file in a [`Manifest`] that doesn't match it.
+#[derive(Error, Debug)]
+pub enum IntegrityViolation {
+    #[error("{:?} was modified: expected sha256 {}, but found {}", .path, sha256::to_hex(.expected), sha256::to_hex(.actual))]
+    Modified {
+        path: PathBuf,
+        expected: Digest,
+        actual: Digest,
+    },
+    #[error("{:?} is missing", .path)]
+    Missing { path: PathBuf },
+    #[error("{:?} couldn't be read to verify it: {}", .path, .error)]
+    Unreadable { path: PathBuf, error: io::Error },
+}
+
+impl IntegrityViolation {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Modified { path, .. } => path,
+            Self::Missing { path } => path,
+            Self::Unreadable { path, .. } => path,
+        }
+    }
+}
+
+/// Hash the whole file an event `fd` refers to, without moving its file offset.
+fn hash_fd(fd: &FD) -> io::Result<Digest> {
+    let mut hasher = sha256::Sha256::new();
+    let mut buffer = [0; 8192];
+    let mut offset = 0;
+    loop {
+        match fd.read_at(&mut buffer, offset) {
+            Ok(0) => return Ok(hasher.finish()),
+            Ok(n) => {
+                hasher.update(&buffer[..n]);
+                offset += n as u64;
+            }
+            Err(errno) => return Err(io::Error::from_raw_os_error(errno as i32)),
+        }
+    }
+}
+
+/// Verifies the files in a [`Manifest`] on the [`Event`]s that modify them.  See the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct IntegrityMonitor {
+    manifest: Manifest,
+    directories: BTreeSet<PathBuf>,
+}
+
+impl IntegrityMonitor {
+    pub fn new(manifest: Manifest) -> Self {
+        let directories = manifest
+            .iter()
+            .filter_map(|(path, _)| path.parent())
+            .map(Path::to_path_buf)
+            .collect();
+        Self {
+            manifest,
+            directories,
+        }
+    }
+    
+    /// The [`Mask`] of [`Event`]s that are checked.
+    pub fn mask() -> Mask {
+        Mask::MODIFY | Mask::CLOSE_WRITE
+    }
+    
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+    
+    /// The parent directories of the files in the [`Manifest`], which are what's [marked](IntegrityMonitor::mark).
+    pub fn directories(&self) -> impl Iterator<Item = &Path> {
+        self.directories.iter().map(PathBuf::as_path)
+    }
+    
+    /// Mark each of the [`directories`](IntegrityMonitor::directories) for the [`mask`](IntegrityMonitor::mask)
+    /// [`Event`]s of their children.
+    ///
+    /// This stops at the first error, though earlier directories will have been marked already.
+    pub fn mark(&self, markable: &(impl Markable + ?Sized)) -> Result<(), mark::Error<'_>> {
+        for directory in &self.directories {
+            let mark = Mark::one(mark::One {
+                action: Add,
+                what: What::Inode,
+                flags: mark::Flags::ONLY_DIR,
+                mask: Self::mask() | Mask::EVENT_ON_CHILD,
+                path: mark::Path::absolute(directory),
+            })
+            .expect("mask is not empty");
+            markable.mark(mark)?;
+        }
+        Ok(())
+    }
+    
+    /// Check an [`Event`], re-hashing its file if it's in the [`Manifest`]
+    /// and the [`Event`] is one that [modifies](IntegrityMonitor::mask) it.
+    ///
+    /// The file is hashed through the [`Event`]'s file descriptor,
+    /// so it's the file the [`Event`] was for even if it's since been replaced.
+    pub fn check(&self, event: &Event<'_>) -> Option<IntegrityViolation> {
+        if !event.mask().intersects(Self::mask()) {
+            return None;
+        }
+        let fd = event.file().get_fd()?;
+        let path = event.file().path()?.ok()?;
+        let expected = *self.manifest.get(&path)?;
+        match hash_fd(fd) {
+            Ok(actual) if actual == expected => None,
+            Ok(actual) => Some(IntegrityViolation::Modified { path, expected, actual }),
+            Err(error) => Some(IntegrityViolation::Unreadable { path, error }),
+        }
+    }
+    
+    /// Verify every file in the [`Manifest`] by path,
+    /// e.g. to establish the baseline before any [`Event`]s are read.
+    pub fn verify_all(&self) -> Vec<IntegrityViolation> {
+        self.manifest
+            .iter()
+            .filter_map(|(path, &expected)| {
+                let path = path.to_path_buf();
+                let result = fs::File::open(&path).and_then(sha256::hash_reader);
+                match result {
+                    Ok(actual) if actual == expected => None,
+                    Ok(actual) => Some(IntegrityViolation::Modified { path, expected, actual }),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => Some(IntegrityViolation::Missing { path }),
+                    Err(error) => Some(IntegrityViolation::Unreadable { path, error }),
+                }
+            })
+            .collect()
+    }
+    
+    /// A [`Sink`] that [checks](IntegrityMonitor::check) each [`Event`]
+    /// and passes any [`IntegrityViolation`] to `handler`.
+    pub fn sink<F>(self, handler: F) -> IntegritySink<F>
+    where
+        F: FnMut(IntegrityViolation) -> Result<(), SinkError>,
+    {
+        IntegritySink {
+            monitor: self,
+            handler,
+        }
+    }
+}
+
+/// Created by [`IntegrityMonitor::sink`].
+pub struct IntegritySink<F> {
+    monitor: IntegrityMonitor,
+    handler: F,
+}
+
+impl<F> IntegritySink<F> {
+    pub fn monitor(&self) -> &IntegrityMonitor {
+        &self.monitor
+    }
+}
+
+impl<F: FnMut(IntegrityViolation) -> Result<(), SinkError>> Sink for IntegritySink<F> {
+    fn consume(&mut self, event: &Event<'_>) -> Result<(), SinkError> {
+        match self.monitor.check(event) {
+            None => Ok(()),
+            Some(violation) => (self.handler)(violation),
+        }
+    }
+}
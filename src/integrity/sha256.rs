@@ -0,0 +1,113 @@
+//! SHA-256 (FIPS 180-4), for hashing the files in a [`Manifest`](super::Manifest),
+//! using the [`sha2`] crate.
+
+use std::io;
+use std::io::Read;
+
+use sha2::Digest as _;
+
+/// A SHA-256 digest.
+pub type Digest = [u8; 32];
+
+/// An incremental SHA-256 hasher.
+#[derive(Debug, Clone, Default)]
+pub struct Sha256 {
+    hasher: sha2::Sha256,
+}
+
+impl Sha256 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.hasher.update(bytes);
+    }
+    
+    pub fn finish(self) -> Digest {
+        self.hasher.finalize().into()
+    }
+}
+
+/// Hash `bytes`.
+pub fn hash(bytes: &[u8]) -> Digest {
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+    hasher.finish()
+}
+
+/// Hash everything read from `reader`.
+pub fn hash_reader(mut reader: impl Read) -> io::Result<Digest> {
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 8192];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => return Ok(hasher.finish()),
+            Ok(n) => hasher.update(&buffer[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Format a [`Digest`] as lowercase hex, like `sha256sum`.
+pub fn to_hex(digest: &Digest) -> String {
+    digest.iter().map(|it| format!("{:02x}", it)).collect()
+}
+
+/// Parse a [`Digest`] from hex.
+pub fn from_hex(hex: &str) -> Option<Digest> {
+    // `from_str_radix` also accepts a leading `+`
+    if hex.len() != 64 || !hex.bytes().all(|it| it.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut digest = [0; 32];
+    for (i, byte) in digest.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some(digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sha256;
+    use super::from_hex;
+    use super::hash;
+    use super::to_hex;
+    
+    #[test]
+    fn test_vectors() {
+        let cases = [
+            ("", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+            ("abc", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+            (
+                "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            ),
+        ];
+        for (input, expected) in cases.iter() {
+            assert_eq!(to_hex(&hash(input.as_bytes())), *expected);
+            assert_eq!(from_hex(expected), Some(hash(input.as_bytes())));
+        }
+        // a million 'a's, fed in uneven pieces
+        let mut hasher = Sha256::new();
+        let piece = [b'a'; 999];
+        for _ in 0..1000 {
+            hasher.update(&piece);
+        }
+        hasher.update(&[b'a'; 1000]);
+        assert_eq!(
+            to_hex(&hasher.finish()),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0",
+        );
+    }
+    
+    #[test]
+    fn invalid_hex() {
+        let valid = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        assert!(from_hex(valid).is_some());
+        assert_eq!(from_hex(&valid[1..]), None);
+        assert_eq!(from_hex(&format!("+{}", &valid[1..])), None);
+        assert_eq!(from_hex(&valid.replace('e', "g")), None);
+    }
+}
@@ -0,0 +1,160 @@
+//! Verifying files against a SHA-256 [`Digest`] stored in their own extended attribute,
+//! like IMA's `security.ima`, but without needing kernel IMA.
+
+use std::convert::TryFrom;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::io;
+
+use thiserror::Error;
+
+use crate::event::event::Event;
+use crate::event::file::decision_cache::DecisionCache;
+use crate::event::file::permission::PermissionDecision;
+use crate::fd::FD;
+
+use super::hash_fd;
+use super::sha256;
+use super::Digest;
+
+/// The result of an [`XattrVerifier`] checking a file.
+#[derive(Error, Debug)]
+pub enum XattrMismatch {
+    #[error("the file doesn't have the extended attribute")]
+    Missing,
+    #[error("the extended attribute isn't a SHA-256 digest in hex or raw bytes")]
+    Invalid,
+    #[error("expected sha256 {}, but found {}", sha256::to_hex(.expected), sha256::to_hex(.actual))]
+    Modified { expected: Digest, actual: Digest },
+    #[error("the file couldn't be read to verify it: {}", .0)]
+    Unreadable(#[from] io::Error),
+}
+
+/// A permission decision source that allows a file only if its SHA-256 hash
+/// matches the [`Digest`] stored in one of its extended attributes,
+/// [`XattrVerifier::DEFAULT_NAME`] by default.
+///
+/// The digest can be stored either as 64 hex characters (e.g. with `setfattr -n user.integrity.sha256 -v <hex>`)
+/// or as the raw 32 bytes.
+/// Both the attribute and the hash are read through the [`Event`]'s file descriptor,
+/// so they're of the file being accessed even if its path has since been replaced,
+/// and decisions are cached in a [`DecisionCache`] until the file or its extended attributes change.
+///
+/// A `user.*` attribute, like the default, can be set by anyone who can write the file,
+/// so it only detects accidental changes, not tampering by the file's writers,
+/// who can just store the new file's digest.
+/// To protect against them, use a `trusted.*` attribute (settable only with `CAP_SYS_ADMIN`)
+/// or a `security.*` one guarded by an LSM, with [`XattrVerifier::with_name`].
+#[derive(Debug)]
+pub struct XattrVerifier {
+    name: CString,
+    cache: DecisionCache,
+    missing: PermissionDecision,
+}
+
+impl Default for XattrVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XattrVerifier {
+    /// The default extended attribute holding the [`Digest`].
+    ///
+    /// It's writable by the file's writers, so see [`XattrVerifier`] for stronger alternatives.
+    pub const DEFAULT_NAME: &'static str = "user.integrity.sha256";
+    
+    /// An [`XattrVerifier`] using [`XattrVerifier::DEFAULT_NAME`].
+    pub fn new() -> Self {
+        Self::with_name(Self::DEFAULT_NAME).expect("no nul bytes")
+    }
+    
+    /// An [`XattrVerifier`] using the extended attribute `name`,
+    /// or [`None`] if `name` contains a nul byte.
+    pub fn with_name(name: &str) -> Option<Self> {
+        Some(Self {
+            name: CString::new(name).ok()?,
+            cache: DecisionCache::default(),
+            missing: PermissionDecision::Deny,
+        })
+    }
+    
+    /// Use this [`DecisionCache`] instead of the default one.
+    pub fn with_cache(mut self, cache: DecisionCache) -> Self {
+        self.cache = cache;
+        self
+    }
+    
+    /// The decision for files without the extended attribute, [`Deny`](PermissionDecision::Deny) by default.
+    ///
+    /// [`Allow`](PermissionDecision::Allow) only enforces the digests of the files that have one.
+    pub fn with_missing(mut self, decision: PermissionDecision) -> Self {
+        self.missing = decision;
+        self
+    }
+    
+    pub fn name(&self) -> &str {
+        self.name.to_str().expect("created from a str")
+    }
+    
+    pub fn cache(&self) -> &DecisionCache {
+        &self.cache
+    }
+    
+    /// Parse the value of the extended attribute as a [`Digest`].
+    pub fn parse(value: &[u8]) -> Option<Digest> {
+        if let Ok(digest) = <Digest>::try_from(value) {
+            return Some(digest);
+        }
+        let hex = std::str::from_utf8(value).ok()?;
+        // setfattr and other tools may add a trailing nul or newline
+        sha256::from_hex(hex.trim_end_matches(|c: char| c == '\0' || c.is_ascii_whitespace()))
+    }
+    
+    /// Verify the file `fd` refers to against the [`Digest`] in its extended attribute, uncached.
+    pub fn verify(&self, fd: &FD) -> Result<(), XattrMismatch> {
+        verify(&self.name, fd)
+    }
+    
+    /// The decision for the result of [`XattrVerifier::verify`].
+    pub fn decision(&self, result: &Result<(), XattrMismatch>) -> PermissionDecision {
+        decision(self.missing, result)
+    }
+    
+    /// [`Verify`](XattrVerifier::verify) the file of `fd` and decide, using the [`DecisionCache`].
+    pub fn decide_fd(&mut self, fd: &FD) -> PermissionDecision {
+        let Self { name, cache, missing } = self;
+        let missing = *missing;
+        cache.decide(fd, || decision(missing, &verify(name, fd)))
+    }
+    
+    /// Decide the file of `event`, allowing [`Event`]s without a file descriptor,
+    /// since there's nothing to verify.
+    pub fn decide(&mut self, event: &Event<'_>) -> PermissionDecision {
+        match event.file().get_fd() {
+            Some(fd) => self.decide_fd(fd),
+            None => PermissionDecision::Allow,
+        }
+    }
+}
+
+fn verify(name: &CStr, fd: &FD) -> Result<(), XattrMismatch> {
+    let value = fd
+        .xattr(name)
+        .map_err(|errno| io::Error::from_raw_os_error(errno as i32))?
+        .ok_or(XattrMismatch::Missing)?;
+    let expected = XattrVerifier::parse(&value).ok_or(XattrMismatch::Invalid)?;
+    let actual = hash_fd(fd)?;
+    match actual == expected {
+        true => Ok(()),
+        false => Err(XattrMismatch::Modified { expected, actual }),
+    }
+}
+
+fn decision(missing: PermissionDecision, result: &Result<(), XattrMismatch>) -> PermissionDecision {
+    match result {
+        Ok(()) => PermissionDecision::Allow,
+        Err(XattrMismatch::Missing) => missing,
+        Err(_) => PermissionDecision::Deny,
+    }
+}
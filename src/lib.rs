@@ -1,8 +1,56 @@
 #![deny(warnings)]
 
+#[cfg(not(any(target_os = "linux", feature = "unsupported-stub")))]
+compile_error!(
+    "fanotify is only supported on Linux; \
+    enable the `unsupported-stub` feature to build a stub that reports it as unsupported at runtime instead"
+);
+
+#[cfg(target_os = "linux")]
 pub mod fd;
+#[cfg(target_os = "linux")]
 pub mod libc;
+#[cfg(target_os = "linux")]
 pub mod init;
+#[cfg(target_os = "linux")]
 pub mod mark;
+#[cfg(target_os = "linux")]
 pub mod event;
+#[cfg(target_os = "linux")]
 pub mod fanotify;
+#[cfg(target_os = "linux")]
+pub mod proc;
+#[cfg(target_os = "linux")]
+pub mod clock;
+#[cfg(target_os = "linux")]
+pub mod restricted;
+#[cfg(target_os = "linux")]
+pub mod raw;
+#[cfg(target_os = "linux")]
+pub mod supported;
+#[cfg(target_os = "linux")]
+pub mod reconcile;
+#[cfg(target_os = "linux")]
+pub mod export;
+#[cfg(target_os = "linux")]
+pub mod alert;
+#[cfg(all(target_os = "linux", feature = "integrity"))]
+pub mod integrity;
+#[cfg(all(target_os = "linux", feature = "testkit"))]
+pub mod testkit;
+#[cfg(all(target_os = "linux", feature = "server"))]
+pub mod server;
+#[cfg(all(target_os = "linux", feature = "systemd"))]
+pub mod systemd;
+#[cfg(all(target_os = "linux", feature = "trigger"))]
+pub mod trigger;
+
+#[cfg(target_os = "linux")]
+pub use supported::supported;
+
+#[cfg(not(target_os = "linux"))]
+mod unsupported;
+#[cfg(not(target_os = "linux"))]
+pub use unsupported::*;
+#[cfg(not(target_os = "linux"))]
+pub use unsupported::supported::supported;
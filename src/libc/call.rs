@@ -1,5 +1,7 @@
 use std::fmt::Debug;
 use std::ops::Neg;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 
 use nix::errno::Errno;
 use thiserror::Error;
@@ -22,9 +24,69 @@ macro_rules! impl_zero_one {
 
 impl_zero_one! { u8 i8 u16 i16 u32 i32 u64 i64 usize isize f32 f64 }
 
-/// Make a libc call, detecting -1 return values
+/// What to do when a libc call fails with [`EINTR`](Errno::EINTR),
+/// i.e., when it was interrupted by a signal before it could complete.
+///
+/// The policy is global and applies to every libc call made by this crate,
+/// which includes all reads, writes, and syscalls like `fanotify_init` and `fanotify_mark`.
+/// It defaults to [`RetryPolicy::RetryOnInterrupt`], so that consumers using signals
+/// don't have to handle [`EINTR`](Errno::EINTR) themselves.
+///
+/// Note that `close` is never retried (see [`FD`](crate::fd::FD)'s [`Drop`] impl).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum RetryPolicy {
+    /// Return [`EINTR`](Errno::EINTR) to the caller like any other [`Errno`].
+    Never,
+    /// Retry the call until it returns something other than [`EINTR`](Errno::EINTR).
+    RetryOnInterrupt,
+}
+
+static RETRY_ON_INTERRUPT: AtomicBool = AtomicBool::new(true);
+
+impl RetryPolicy {
+    pub const fn const_default() -> Self {
+        Self::RetryOnInterrupt
+    }
+    
+    /// The current global [`RetryPolicy`].
+    pub fn get() -> Self {
+        if RETRY_ON_INTERRUPT.load(Ordering::Relaxed) {
+            Self::RetryOnInterrupt
+        } else {
+            Self::Never
+        }
+    }
+    
+    /// Set the global [`RetryPolicy`], returning the previous one.
+    pub fn set(self) -> Self {
+        let retry = self == Self::RetryOnInterrupt;
+        if RETRY_ON_INTERRUPT.swap(retry, Ordering::Relaxed) {
+            Self::RetryOnInterrupt
+        } else {
+            Self::Never
+        }
+    }
+    
+    /// Whether a call that failed with this [`Errno`] should be retried.
+    pub fn should_retry(&self, errno: Errno) -> bool {
+        match self {
+            Self::Never => false,
+            Self::RetryOnInterrupt => errno == Errno::EINTR,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::const_default()
+    }
+}
+
+/// Make a single libc call, detecting -1 return values
 /// and return an [`Err`] with an [`Errno`] in that case.
-pub fn libc_call<T: ZeroOne + Copy + Eq + Neg<Output=T>, F: FnOnce() -> T>(f: F) -> Result<T, Errno> {
+///
+/// Unlike [`libc_call`], this never retries.
+pub fn libc_call_once<T: ZeroOne + Copy + Eq + Neg<Output=T>, F: FnOnce() -> T>(f: F) -> Result<T, Errno> {
     Errno::clear();
     let result = f();
     if result == T::ONE.neg() {
@@ -36,9 +98,32 @@ pub fn libc_call<T: ZeroOne + Copy + Eq + Neg<Output=T>, F: FnOnce() -> T>(f: F)
     }
 }
 
+/// Make a libc call like [`libc_call_once`],
+/// retrying it according to the given [`RetryPolicy`].
+pub fn libc_call_with<T: ZeroOne + Copy + Eq + Neg<Output=T>, F: FnMut() -> T>(
+    policy: RetryPolicy,
+    mut f: F,
+) -> Result<T, Errno> {
+    loop {
+        match libc_call_once(&mut f) {
+            Err(errno) if policy.should_retry(errno) => continue,
+            result => return result,
+        }
+    }
+}
+
+/// Make a libc call, detecting -1 return values
+/// and return an [`Err`] with an [`Errno`] in that case.
+///
+/// The call is retried according to the global [`RetryPolicy`],
+/// so by default it is never interrupted by signals.
+pub fn libc_call<T: ZeroOne + Copy + Eq + Neg<Output=T>, F: FnMut() -> T>(f: F) -> Result<T, Errno> {
+    libc_call_with(RetryPolicy::get(), f)
+}
+
 /// Make a libc call like [`libc_call`], except throw away the return value.
 #[allow(unused)]
-pub fn libc_void_call<T: ZeroOne + Copy + Eq + Neg<Output=T>, F: FnOnce() -> T>(f: F) -> Result<(), Errno> {
+pub fn libc_void_call<T: ZeroOne + Copy + Eq + Neg<Output=T>, F: FnMut() -> T>(f: F) -> Result<(), Errno> {
     if libc_call(f)? == T::ZERO {
         Ok(())
     } else {
@@ -55,7 +140,8 @@ pub trait RawSysCall: Debug {
     /// What it does depends on the syscall,
     /// but generally it can do anything since it's a syscall.
     ///
-    /// It is always wrapped in [`libc_call`] to safely handle any errors.
+    /// It is always wrapped in [`libc_call`] to safely handle any errors
+    /// and to retry it according to the [`RetryPolicy`].
     unsafe fn unsafe_call(&self) -> Self::Output;
     
     fn call(&self) -> Result<Self::Output, Errno> {
@@ -5,6 +5,8 @@
 
 pub(crate) mod call;
 
+pub use call::RetryPolicy;
+
 /// for fanotify_init
 pub mod init {
     /// Flags
@@ -17,6 +19,10 @@ pub mod init {
         pub const FAN_UNLIMITED_QUEUE: u32 = 0x00000010;
         /// Remove the limit of 8192 marks. Requires CAP_SYS_ADMIN
         pub const FAN_UNLIMITED_MARKS: u32 = 0x00000020;
+        /// Enable generation of audit log records about access mediation performed by permission events
+        pub const FAN_ENABLE_AUDIT: u32 = 0x00000040;
+        /// Report a pidfd for the event's process in an additional info record
+        pub const FAN_REPORT_PIDFD: u32 = 0x00000080;
         /// Report TID instead PID in PID field of the fanotify_event_metadata supplied to read
         pub const FAN_REPORT_TID: u32 = 0x00000100;
         /// Allows the receipt of events which contain additional info about
@@ -28,6 +34,18 @@ pub mod init {
         /// Initialized fanotify groups with this flag will contain additional
         /// info about the name of the directory entry correlated to an event
         pub const FAN_REPORT_NAME: u32 = 0x00000800;
+        /// Report the file id of the target (child) object of directory entry events
+        /// in addition to the directory's
+        pub const FAN_REPORT_TARGET_FID: u32 = 0x00001000;
+        /// Report the errno from opening the event's fd in the fd field instead of failing the read
+        pub const FAN_REPORT_FD_ERROR: u32 = 0x00002000;
+        /// Report mount ids for mount namespace events
+        pub const FAN_REPORT_MNT: u32 = 0x00004000;
+        /// Convenience combination of [`FAN_REPORT_DIR_FID`] and [`FAN_REPORT_NAME`]
+        pub const FAN_REPORT_DFID_NAME: u32 = FAN_REPORT_DIR_FID | FAN_REPORT_NAME;
+        /// Convenience combination of [`FAN_REPORT_DFID_NAME`], [`FAN_REPORT_FID`],
+        /// and [`FAN_REPORT_TARGET_FID`]
+        pub const FAN_REPORT_DFID_NAME_TARGET: u32 = FAN_REPORT_DFID_NAME | FAN_REPORT_FID | FAN_REPORT_TARGET_FID;
     }
     
     /// NotificationClass < Flags
@@ -69,6 +87,8 @@ pub mod mark {
         pub const FAN_MARK_MOUNT: u32 = 0x00000010;
         /// Mark filesystem specified by pathname
         pub const FAN_MARK_FILESYSTEM: u32 = 0x00000100;
+        /// Mark the mount namespace specified by an nsfs file descriptor
+        pub const FAN_MARK_MNTNS: u32 = 0x00000110;
     }
     
     /// MarkFlags < CombinedMarkFlags
@@ -81,6 +101,13 @@ pub mod mark {
         pub const FAN_MARK_IGNORED_MASK: u32 = 0x00000020;
         /// The ignore mask shall survive modify events
         pub const FAN_MARK_IGNORED_SURV_MODIFY: u32 = 0x00000040;
+        /// The inode mark may be evicted along with the inode when it's not in use
+        pub const FAN_MARK_EVICTABLE: u32 = 0x00000200;
+        /// The events in mask shall be added to or removed from the ignore mask,
+        /// with stricter semantics than [`FAN_MARK_IGNORED_MASK`] (e.g. for directories)
+        pub const FAN_MARK_IGNORE: u32 = 0x00000400;
+        /// Convenience combination of [`FAN_MARK_IGNORE`] and [`FAN_MARK_IGNORED_SURV_MODIFY`]
+        pub const FAN_MARK_IGNORE_SURV: u32 = FAN_MARK_IGNORE | FAN_MARK_IGNORED_SURV_MODIFY;
     }
     
     /// mark::Mask
@@ -113,22 +140,33 @@ pub mod mark {
         pub const FAN_OPEN_EXEC: u64 = 0x00001000;
         /// Create an event when an overflow of the event queue occurs
         pub const FAN_Q_OVERFLOW: u64 = 0x00004000;
+        /// Create an event when a filesystem error is detected
+        pub const FAN_FS_ERROR: u64 = 0x00008000;
         /// Create an event when a permission to open a file or directory is requested
         pub const FAN_OPEN_PERM: u64 = 0x00010000;
         /// Create an event when a permission to read a file or directory is requested
         pub const FAN_ACCESS_PERM: u64 = 0x00020000;
         /// Create an event when a permission to open a file for execution is requested
         pub const FAN_OPEN_EXEC_PERM: u64 = 0x00040000;
+        /// Create an event when a permission to read a file range is requested, before the read
+        pub const FAN_PRE_ACCESS: u64 = 0x00100000;
+        /// Create an event when a mount is attached to a marked mount namespace
+        pub const FAN_MNT_ATTACH: u64 = 0x01000000;
+        /// Create an event when a mount is detached from a marked mount namespace
+        pub const FAN_MNT_DETACH: u64 = 0x02000000;
         /// Events for the immediate children of marked directories shall be created
         pub const FAN_EVENT_ON_CHILD: u64 = 0x08000000;
+        /// Create an event when a file or directory has been renamed, with both old and new names
+        pub const FAN_RENAME: u64 = 0x10000000;
         /// Create events for directories
         pub const FAN_ONDIR: u64 = 0x40000000;
     }
 }
 
 pub mod read {
+    /// The kernel declares `mask` as `__aligned_u64`, so this is 8-byte aligned even on 32-bit.
     #[allow(non_camel_case_types)]
-    #[repr(C)]
+    #[repr(C, align(8))]
     pub struct fanotify_event_metadata {
         pub event_len: u32,
         pub vers: u8,
@@ -150,9 +188,8 @@ pub mod read {
     #[allow(non_camel_case_types)]
     #[repr(C)]
     pub struct fanotify_event_file_handle {
-        // TODO am I doing the variable sized type from C right here?
-        // I know it's not zero-sized at least
-        opaque: [libc::c_char; 1], // C VLA
+        // a C flexible array member, so it doesn't count towards the struct size
+        opaque: [libc::c_uchar; 0],
     }
     
     #[allow(non_camel_case_types)]
@@ -163,13 +200,49 @@ pub mod read {
         pub handle: fanotify_event_file_handle,
     }
     
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct fanotify_event_info_pidfd {
+        pub hdr: fanotify_event_info_header,
+        pub pidfd: i32,
+    }
+    
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct fanotify_event_info_error {
+        pub hdr: fanotify_event_info_header,
+        pub error: i32,
+        pub error_count: u32,
+    }
+    
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct fanotify_event_info_range {
+        pub hdr: fanotify_event_info_header,
+        pub pad: u32,
+        pub offset: u64,
+        pub count: u64,
+    }
+    
     pub const FANOTIFY_METADATA_VERSION: u8 = 3;
     
     pub const FAN_NOFD: i32 = -1;
+    /// No pidfd could be created because the process already exited
+    pub const FAN_NOPIDFD: i32 = FAN_NOFD;
+    /// Creating the pidfd failed for some other reason
+    pub const FAN_EPIDFD: i32 = -2;
     
     pub const FAN_EVENT_INFO_TYPE_FID: u8 = 1;
     pub const FAN_EVENT_INFO_TYPE_DFID_NAME: u8 = 2;
     pub const FAN_EVENT_INFO_TYPE_DFID: u8 = 3;
+    pub const FAN_EVENT_INFO_TYPE_PIDFD: u8 = 4;
+    pub const FAN_EVENT_INFO_TYPE_ERROR: u8 = 5;
+    pub const FAN_EVENT_INFO_TYPE_RANGE: u8 = 6;
+    pub const FAN_EVENT_INFO_TYPE_MNT: u8 = 7;
+    /// For [`FAN_RENAME`](super::mark::mask::FAN_RENAME)
+    pub const FAN_EVENT_INFO_TYPE_OLD_DFID_NAME: u8 = 10;
+    /// For [`FAN_RENAME`](super::mark::mask::FAN_RENAME)
+    pub const FAN_EVENT_INFO_TYPE_NEW_DFID_NAME: u8 = 12;
 }
 
 pub mod write {
@@ -180,7 +253,289 @@ pub mod write {
         pub response: u32,
     }
     
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct fanotify_response_info_header {
+        pub type_: u8,
+        pub pad: u8,
+        pub len: u16,
+    }
+    
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct fanotify_response_info_audit_rule {
+        pub hdr: fanotify_response_info_header,
+        pub rule_number: u32,
+        pub subj_trust: u32,
+        pub obj_trust: u32,
+    }
+    
     pub const FAN_ALLOW: u32 = 0x01;
     pub const FAN_DENY: u32 = 0x02;
     pub const FAN_AUDIT: u32 = 0x10;
+    /// Extra info records follow the [`fanotify_response`]
+    pub const FAN_INFO: u32 = 0x20;
+    
+    pub const FAN_ERRNO_BITS: u32 = 8;
+    pub const FAN_ERRNO_SHIFT: u32 = 32 - FAN_ERRNO_BITS;
+    pub const FAN_ERRNO_MASK: u32 = (1 << FAN_ERRNO_BITS) - 1;
+    
+    /// Deny with a custom errno instead of `EPERM`
+    pub const fn fan_deny_errno(errno: u32) -> u32 {
+        FAN_DENY | ((errno & FAN_ERRNO_MASK) << FAN_ERRNO_SHIFT)
+    }
+    
+    pub const FAN_RESPONSE_INFO_NONE: u8 = 0;
+    pub const FAN_RESPONSE_INFO_AUDIT_RULE: u8 = 1;
+}
+
+/// All of the above constants and structs, organized by the Linux version that introduced them,
+/// so higher layers can tell what a given kernel supports.
+///
+/// Only versions that introduced something are included.
+pub mod since {
+    /// The original fanotify API.
+    pub mod linux_2_6_37 {
+        pub use super::super::init::flag::FAN_CLOEXEC;
+        pub use super::super::init::flag::FAN_NONBLOCK;
+        pub use super::super::init::flag::FAN_UNLIMITED_QUEUE;
+        pub use super::super::init::flag::FAN_UNLIMITED_MARKS;
+        pub use super::super::init::notification_class::*;
+        pub use super::super::mark::action::*;
+        pub use super::super::mark::what::FAN_MARK_INODE;
+        pub use super::super::mark::what::FAN_MARK_MOUNT;
+        pub use super::super::mark::flag::FAN_MARK_DONT_FOLLOW;
+        pub use super::super::mark::flag::FAN_MARK_ONLYDIR;
+        pub use super::super::mark::flag::FAN_MARK_IGNORED_MASK;
+        pub use super::super::mark::flag::FAN_MARK_IGNORED_SURV_MODIFY;
+        pub use super::super::mark::mask::FAN_ACCESS;
+        pub use super::super::mark::mask::FAN_MODIFY;
+        pub use super::super::mark::mask::FAN_CLOSE_WRITE;
+        pub use super::super::mark::mask::FAN_CLOSE_NOWRITE;
+        pub use super::super::mark::mask::FAN_OPEN;
+        pub use super::super::mark::mask::FAN_Q_OVERFLOW;
+        pub use super::super::mark::mask::FAN_OPEN_PERM;
+        pub use super::super::mark::mask::FAN_ACCESS_PERM;
+        pub use super::super::mark::mask::FAN_EVENT_ON_CHILD;
+        pub use super::super::mark::mask::FAN_ONDIR;
+        pub use super::super::read::fanotify_event_metadata;
+        pub use super::super::read::FANOTIFY_METADATA_VERSION;
+        pub use super::super::read::FAN_NOFD;
+        pub use super::super::write::fanotify_response;
+        pub use super::super::write::FAN_ALLOW;
+        pub use super::super::write::FAN_DENY;
+    }
+    
+    pub mod linux_4_15 {
+        pub use super::super::init::flag::FAN_ENABLE_AUDIT;
+        pub use super::super::write::FAN_AUDIT;
+    }
+    
+    pub mod linux_4_20 {
+        pub use super::super::init::flag::FAN_REPORT_TID;
+        pub use super::super::mark::what::FAN_MARK_FILESYSTEM;
+    }
+    
+    pub mod linux_5_0 {
+        pub use super::super::mark::mask::FAN_OPEN_EXEC;
+        pub use super::super::mark::mask::FAN_OPEN_EXEC_PERM;
+    }
+    
+    pub mod linux_5_1 {
+        pub use super::super::init::flag::FAN_REPORT_FID;
+        pub use super::super::mark::mask::FAN_ATTRIB;
+        pub use super::super::mark::mask::FAN_MOVED_FROM;
+        pub use super::super::mark::mask::FAN_MOVED_TO;
+        pub use super::super::mark::mask::FAN_CREATE;
+        pub use super::super::mark::mask::FAN_DELETE;
+        pub use super::super::mark::mask::FAN_DELETE_SELF;
+        pub use super::super::mark::mask::FAN_MOVE_SELF;
+        pub use super::super::read::fanotify_event_info_header;
+        pub use super::super::read::fanotify_event_info_fid;
+        pub use super::super::read::FAN_EVENT_INFO_TYPE_FID;
+    }
+    
+    pub mod linux_5_9 {
+        pub use super::super::init::flag::FAN_REPORT_DIR_FID;
+        pub use super::super::init::flag::FAN_REPORT_NAME;
+        pub use super::super::init::flag::FAN_REPORT_DFID_NAME;
+        pub use super::super::read::FAN_EVENT_INFO_TYPE_DFID_NAME;
+        pub use super::super::read::FAN_EVENT_INFO_TYPE_DFID;
+    }
+    
+    pub mod linux_5_15 {
+        pub use super::super::init::flag::FAN_REPORT_PIDFD;
+        pub use super::super::read::fanotify_event_info_pidfd;
+        pub use super::super::read::FAN_EVENT_INFO_TYPE_PIDFD;
+        pub use super::super::read::FAN_NOPIDFD;
+        pub use super::super::read::FAN_EPIDFD;
+    }
+    
+    pub mod linux_5_16 {
+        pub use super::super::mark::mask::FAN_FS_ERROR;
+        pub use super::super::read::fanotify_event_info_error;
+        pub use super::super::read::FAN_EVENT_INFO_TYPE_ERROR;
+    }
+    
+    pub mod linux_5_17 {
+        pub use super::super::init::flag::FAN_REPORT_TARGET_FID;
+        pub use super::super::init::flag::FAN_REPORT_DFID_NAME_TARGET;
+        pub use super::super::mark::mask::FAN_RENAME;
+        pub use super::super::read::FAN_EVENT_INFO_TYPE_OLD_DFID_NAME;
+        pub use super::super::read::FAN_EVENT_INFO_TYPE_NEW_DFID_NAME;
+    }
+    
+    pub mod linux_5_19 {
+        pub use super::super::mark::flag::FAN_MARK_EVICTABLE;
+    }
+    
+    pub mod linux_6_0 {
+        pub use super::super::mark::flag::FAN_MARK_IGNORE;
+        pub use super::super::mark::flag::FAN_MARK_IGNORE_SURV;
+    }
+    
+    pub mod linux_6_3 {
+        pub use super::super::write::fanotify_response_info_header;
+        pub use super::super::write::fanotify_response_info_audit_rule;
+        pub use super::super::write::FAN_INFO;
+        pub use super::super::write::FAN_RESPONSE_INFO_NONE;
+        pub use super::super::write::FAN_RESPONSE_INFO_AUDIT_RULE;
+    }
+    
+    pub mod linux_6_13 {
+        pub use super::super::init::flag::FAN_REPORT_FD_ERROR;
+    }
+    
+    pub mod linux_6_14 {
+        pub use super::super::init::flag::FAN_REPORT_MNT;
+        pub use super::super::mark::what::FAN_MARK_MNTNS;
+        pub use super::super::mark::mask::FAN_PRE_ACCESS;
+        pub use super::super::mark::mask::FAN_MNT_ATTACH;
+        pub use super::super::mark::mask::FAN_MNT_DETACH;
+        pub use super::super::read::fanotify_event_info_range;
+        pub use super::super::read::FAN_EVENT_INFO_TYPE_RANGE;
+        pub use super::super::read::FAN_EVENT_INFO_TYPE_MNT;
+        pub use super::super::write::FAN_ERRNO_BITS;
+        pub use super::super::write::FAN_ERRNO_SHIFT;
+        pub use super::super::write::FAN_ERRNO_MASK;
+        pub use super::super::write::fan_deny_errno;
+    }
+}
+
+/// Check that the hand-written structs and constants above match
+/// the `libc` crate's definitions, and with the `bindgen` feature,
+/// the ones generated from the installed kernel headers (`<linux/fanotify.h>`),
+/// so that any drift is a compile error instead of silently corrupted parsing.
+mod checks {
+    #[allow(unused_imports)]
+    use std::mem::align_of;
+    #[allow(unused_imports)]
+    use std::mem::size_of;
+    
+    use static_assertions::const_assert_eq;
+    
+    use super::read;
+    use super::write;
+    
+    macro_rules! assert_same_layout {
+        ($($ours:ty = $theirs:ty,)*) => ($(
+            const_assert_eq!(size_of::<$ours>(), size_of::<$theirs>());
+            const_assert_eq!(align_of::<$ours>(), align_of::<$theirs>());
+        )*)
+    }
+    
+    // FAN_EVENT_METADATA_LEN
+    const_assert_eq!(size_of::<read::fanotify_event_metadata>(), 24);
+    
+    #[cfg(not(target_env = "musl"))]
+    assert_same_layout! {
+        read::fanotify_event_metadata = libc::fanotify_event_metadata,
+    }
+    
+    assert_same_layout! {
+        read::fanotify_event_info_header = libc::fanotify_event_info_header,
+        read::fanotify_event_info_fid = libc::fanotify_event_info_fid,
+        write::fanotify_response = libc::fanotify_response,
+    }
+    
+    const_assert_eq!(size_of::<read::fanotify_event_info_pidfd>(), 8);
+    const_assert_eq!(size_of::<read::fanotify_event_info_error>(), 12);
+    const_assert_eq!(size_of::<read::fanotify_event_info_range>(), 24);
+    const_assert_eq!(size_of::<write::fanotify_response_info_header>(), 4);
+    const_assert_eq!(size_of::<write::fanotify_response_info_audit_rule>(), 16);
+    
+    #[cfg(feature = "bindgen")]
+    #[allow(non_camel_case_types, non_upper_case_globals, dead_code, clippy::all)]
+    mod bindings {
+        include!(concat!(env!("OUT_DIR"), "/fanotify.rs"));
+    }
+    
+    #[cfg(feature = "bindgen")]
+    assert_same_layout! {
+        read::fanotify_event_metadata = bindings::fanotify_event_metadata,
+        read::fanotify_event_info_header = bindings::fanotify_event_info_header,
+        read::fanotify_event_info_fid = bindings::fanotify_event_info_fid,
+        write::fanotify_response = bindings::fanotify_response,
+    }
+    
+    #[cfg(feature = "bindgen")]
+    macro_rules! assert_same_constants {
+        ($($module:path => [$($name:ident)*],)*) => ($($(
+            const_assert_eq!({ use $module as m; m::$name } as u64, bindings::$name as u64);
+        )*)*)
+    }
+    
+    #[cfg(feature = "bindgen")]
+    assert_same_constants! {
+        super::init::flag => [
+            FAN_CLOEXEC FAN_NONBLOCK FAN_UNLIMITED_QUEUE FAN_UNLIMITED_MARKS
+            FAN_REPORT_TID FAN_REPORT_FID FAN_REPORT_DIR_FID FAN_REPORT_NAME
+        ],
+        super::init::notification_class => [
+            FAN_CLASS_NOTIF FAN_CLASS_CONTENT FAN_CLASS_PRE_CONTENT
+        ],
+        super::mark::action => [FAN_MARK_ADD FAN_MARK_REMOVE FAN_MARK_FLUSH],
+        super::mark::what => [FAN_MARK_INODE FAN_MARK_MOUNT FAN_MARK_FILESYSTEM],
+        super::mark::flag => [
+            FAN_MARK_DONT_FOLLOW FAN_MARK_ONLYDIR FAN_MARK_IGNORED_MASK FAN_MARK_IGNORED_SURV_MODIFY
+        ],
+        super::mark::mask => [
+            FAN_ACCESS FAN_MODIFY FAN_ATTRIB FAN_CLOSE_WRITE FAN_CLOSE_NOWRITE FAN_OPEN
+            FAN_MOVED_FROM FAN_MOVED_TO FAN_CREATE FAN_DELETE FAN_DELETE_SELF FAN_MOVE_SELF
+            FAN_OPEN_EXEC FAN_Q_OVERFLOW FAN_OPEN_PERM FAN_ACCESS_PERM FAN_OPEN_EXEC_PERM
+            FAN_EVENT_ON_CHILD FAN_ONDIR
+        ],
+        super::read => [
+            FANOTIFY_METADATA_VERSION FAN_EVENT_INFO_TYPE_FID
+            FAN_EVENT_INFO_TYPE_DFID_NAME FAN_EVENT_INFO_TYPE_DFID
+        ],
+        super::write => [FAN_ALLOW FAN_DENY FAN_AUDIT],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::errno::Errno;
+    
+    use super::call::libc_call_with;
+    use super::RetryPolicy;
+    
+    fn interrupted_once() -> impl FnMut() -> i32 {
+        let mut interrupted = false;
+        move || {
+            if interrupted {
+                0
+            } else {
+                interrupted = true;
+                unsafe { *libc::__errno_location() = libc::EINTR };
+                -1
+            }
+        }
+    }
+    
+    #[test]
+    fn retry_on_interrupt() {
+        assert_eq!(libc_call_with(RetryPolicy::RetryOnInterrupt, interrupted_once()), Ok(0));
+        assert_eq!(libc_call_with(RetryPolicy::Never, interrupted_once()), Err(Errno::EINTR));
+    }
 }
@@ -0,0 +1,362 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use super::Action;
+use super::Flags;
+use super::Markable;
+use super::Mask;
+use super::OwnedMark;
+use super::RawError;
+use super::What;
+
+/// The progress of a [`MarkBatch::apply_all`], passed to [`MarkBatch::on_progress`] after each [`OwnedMark`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct BatchProgress {
+    /// The number of [`OwnedMark`]s done, successfully or not.
+    pub done: usize,
+    pub total: usize,
+    /// The number of [`OwnedMark`]s that failed so far.
+    pub failed: usize,
+    /// The number of retries after transient errors so far.
+    pub retries: usize,
+}
+
+/// The result of a [`MarkBatch::apply_all`].
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    /// The number of [`OwnedMark`]s successfully applied.
+    pub applied: usize,
+    /// The [`OwnedMark`]s that failed to be applied and why, in their original order.
+    pub failed: Vec<(OwnedMark, RawError)>,
+    /// The number of retries after transient errors.
+    pub retries: usize,
+    pub elapsed: Duration,
+}
+
+impl BatchReport {
+    /// If every [`OwnedMark`] was applied.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+#[derive(Default)]
+struct BatchState {
+    applied: usize,
+    failed: Vec<(usize, OwnedMark, RawError)>,
+    retries: usize,
+    done: usize,
+}
+
+/// Applies many [`OwnedMark`]s, like the thousands a recursive watch can take,
+/// with bounded concurrency, an optional rate limit,
+/// retries with exponential backoff on [transient](RawError::is_transient) errors,
+/// and progress callbacks.
+///
+/// Unlike marking them one by one, a batch continues past failures and reports all of them.
+/// With a [`MarkBatch::with_concurrency`] above 1, the marks are applied in parallel and out of order,
+/// so they should be independent, e.g. all [`Add`](Action::Add)s.
+pub struct MarkBatch<'h> {
+    concurrency: usize,
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    interval: Option<Duration>,
+    on_progress: Box<dyn Fn(&BatchProgress) + Send + Sync + 'h>,
+}
+
+impl Default for MarkBatch<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'h> MarkBatch<'h> {
+    pub const DEFAULT_MAX_ATTEMPTS: u32 = 8;
+    
+    /// A [`MarkBatch`] applying one mark at a time, without a rate limit,
+    /// and trying each mark up to [`MarkBatch::DEFAULT_MAX_ATTEMPTS`] times.
+    pub fn new() -> Self {
+        Self {
+            concurrency: 1,
+            max_attempts: Self::DEFAULT_MAX_ATTEMPTS,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_secs(1),
+            interval: None,
+            on_progress: Box::new(|_| {}),
+        }
+    }
+    
+    /// Apply up to `concurrency` marks at once, each on its own thread (at least 1).
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+    
+    /// Try each mark up to `max_attempts` times (at least once) if it fails with a transient error.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+    
+    /// Back off for `initial` after the first transient error of a mark,
+    /// doubling for every further attempt up to `max`.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+    
+    /// Make at most `per_second` marking calls per second across all threads, including retries.
+    pub fn with_rate_limit(mut self, per_second: u32) -> Self {
+        self.interval = match per_second {
+            0 => None,
+            n => Some(Duration::from_secs(1) / n),
+        };
+        self
+    }
+    
+    /// Call `on_progress` after each mark is done.
+    ///
+    /// It's called from the applying threads, but never concurrently.
+    pub fn on_progress(mut self, on_progress: impl Fn(&BatchProgress) + Send + Sync + 'h) -> Self {
+        self.on_progress = Box::new(on_progress);
+        self
+    }
+    
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+    
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+    
+    /// The backoff before the retry after the `attempt`th (starting at 1) failed attempt.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .checked_mul(1 << (attempt - 1).min(31))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+    
+    /// Apply all of `marks` to `markable`, continuing past failures.
+    pub fn apply_all(&self, markable: &(impl Markable + Sync + ?Sized), marks: &[OwnedMark]) -> BatchReport {
+        let start = Instant::now();
+        let next = AtomicUsize::new(0);
+        let next_call = Mutex::new(start);
+        let state = Mutex::new(BatchState::default());
+        let worker = || loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            let mark = match marks.get(i) {
+                Some(mark) => mark,
+                None => break,
+            };
+            let (result, retries) = self.apply_one(markable, mark, &next_call);
+            let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+            match result {
+                Ok(()) => state.applied += 1,
+                Err(e) => state.failed.push((i, mark.clone(), e)),
+            }
+            state.retries += retries;
+            state.done += 1;
+            (self.on_progress)(&BatchProgress {
+                done: state.done,
+                total: marks.len(),
+                failed: state.failed.len(),
+                retries: state.retries,
+            });
+        };
+        let threads = self.concurrency.min(marks.len());
+        if threads <= 1 {
+            worker();
+        } else {
+            thread::scope(|scope| {
+                for _ in 0..threads {
+                    scope.spawn(worker);
+                }
+            });
+        }
+        let mut state = state.into_inner().unwrap_or_else(|e| e.into_inner());
+        state.failed.sort_by_key(|&(i, _, _)| i);
+        BatchReport {
+            applied: state.applied,
+            failed: state.failed.into_iter().map(|(_, mark, e)| (mark, e)).collect(),
+            retries: state.retries,
+            elapsed: start.elapsed(),
+        }
+    }
+    
+    /// Apply one mark, retrying transient errors, and return the result and the number of retries.
+    fn apply_one(
+        &self,
+        markable: &(impl Markable + ?Sized),
+        mark: &OwnedMark,
+        next_call: &Mutex<Instant>,
+    ) -> (Result<(), RawError>, usize) {
+        let mut attempt = 1;
+        loop {
+            self.wait_for_rate_limit(next_call);
+            let error = match markable.mark(mark.as_mark()) {
+                Ok(()) => return (Ok(()), attempt as usize - 1),
+                Err(e) => e.error,
+            };
+            if !error.is_transient() || attempt >= self.max_attempts {
+                return (Err(error), attempt as usize - 1);
+            }
+            thread::sleep(self.backoff(attempt));
+            attempt += 1;
+        }
+    }
+    
+    /// Reserve the next slot for a marking call and sleep until it.
+    fn wait_for_rate_limit(&self, next_call: &Mutex<Instant>) {
+        let interval = match self.interval {
+            Some(interval) => interval,
+            None => return,
+        };
+        let now = Instant::now();
+        let at = {
+            let mut next_call = next_call.lock().unwrap_or_else(|e| e.into_inner());
+            let at = (*next_call).max(now);
+            *next_call = at + interval;
+            at
+        };
+        thread::sleep(at - now);
+    }
+}
+
+/// The [`Inode`](What::Inode) marks to watch the whole directory tree at `root` recursively,
+/// i.e. a mark with `mask` and [`EVENT_ON_CHILD`](Mask::EVENT_ON_CHILD) on every directory in it,
+/// to be applied with a [`MarkBatch`].
+///
+/// Symlinks aren't followed, and subdirectories that can't be read are still marked, but not descended into.
+/// Directories created later aren't included, so they have to be marked as they're created.
+pub fn directory_marks(root: impl AsRef<Path>, mask: Mask) -> io::Result<Vec<OwnedMark>> {
+    let root = root.as_ref();
+    let mark = |path| OwnedMark {
+        action: Action::Add,
+        what: What::Inode,
+        flags: Flags::ONLY_DIR,
+        mask: mask | Mask::EVENT_ON_CHILD,
+        path,
+    };
+    let mut marks = vec![mark(root.to_path_buf())];
+    let mut dirs = vec![fs::read_dir(root)?];
+    while let Some(entries) = dirs.pop() {
+        for entry in entries.filter_map(Result::ok) {
+            if !entry.file_type().is_ok_and(|it| it.is_dir()) {
+                continue;
+            }
+            let path = entry.path();
+            if let Ok(entries) = fs::read_dir(&path) {
+                dirs.push(entries);
+            }
+            marks.push(mark(path));
+        }
+    }
+    Ok(marks)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+    
+    use crate::mark::Action;
+    use crate::mark::Error;
+    use crate::mark::Flags;
+    use crate::mark::Mark;
+    use crate::mark::Markable;
+    use crate::mark::Mask;
+    use crate::mark::OwnedMark;
+    use crate::mark::RawError;
+    use crate::mark::What;
+    
+    use super::MarkBatch;
+    
+    /// Fails the first `transient` calls with [`RawError::ExceededMarkLimit`],
+    /// and marks on `/bad` always with [`RawError::PathDoesNotExist`].
+    struct Flaky {
+        transient: usize,
+        calls: AtomicUsize,
+    }
+    
+    impl Markable for Flaky {
+        fn mark<'a>(&self, mark: Mark<'a>) -> Result<(), Error<'a>> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            let error = if mark.path.resolve().as_ref() == std::path::Path::new("/bad") {
+                RawError::PathDoesNotExist
+            } else if call < self.transient {
+                RawError::ExceededMarkLimit
+            } else {
+                return Ok(());
+            };
+            Err(Error::new(error, mark))
+        }
+    }
+    
+    fn mark(path: &str) -> OwnedMark {
+        OwnedMark {
+            action: Action::Add,
+            what: What::Inode,
+            flags: Flags::empty(),
+            mask: Mask::OPEN,
+            path: PathBuf::from(path),
+        }
+    }
+    
+    #[test]
+    fn retries_transient_errors() {
+        let markable = Flaky {
+            transient: 2,
+            calls: AtomicUsize::new(0),
+        };
+        let progress = AtomicUsize::new(0);
+        let batch = MarkBatch::new()
+            .with_backoff(Duration::ZERO, Duration::ZERO)
+            .on_progress(|it| {
+                assert_eq!(it.total, 3);
+                progress.fetch_add(1, Ordering::SeqCst);
+            });
+        let report = batch.apply_all(&markable, &[mark("/a"), mark("/bad"), mark("/c")]);
+        assert_eq!(report.applied, 2);
+        assert_eq!(report.retries, 2);
+        assert_eq!(report.failed, vec![(mark("/bad"), RawError::PathDoesNotExist)]);
+        assert_eq!(markable.calls.load(Ordering::SeqCst), 5);
+        assert_eq!(progress.load(Ordering::SeqCst), 3);
+    }
+    
+    #[test]
+    fn gives_up_after_max_attempts() {
+        let markable = Flaky {
+            transient: usize::MAX,
+            calls: AtomicUsize::new(0),
+        };
+        let marks = (0..8).map(|i| mark(&format!("/{}", i))).collect::<Vec<_>>();
+        let report = MarkBatch::new()
+            .with_concurrency(4)
+            .with_max_attempts(3)
+            .with_backoff(Duration::ZERO, Duration::ZERO)
+            .apply_all(&markable, &marks);
+        assert_eq!(report.applied, 0);
+        assert_eq!(report.retries, 8 * 2);
+        assert_eq!(report.failed.iter().map(|(it, _)| it.clone()).collect::<Vec<_>>(), marks);
+    }
+    
+    #[test]
+    fn backoff() {
+        let batch = MarkBatch::new().with_backoff(Duration::from_millis(10), Duration::from_millis(50));
+        let backoffs = (1..=4).map(|it| batch.backoff(it).as_millis()).collect::<Vec<_>>();
+        assert_eq!(backoffs, vec![10, 20, 40, 50]);
+    }
+}
@@ -0,0 +1,83 @@
+use super::Error;
+use super::Mark;
+use super::Markable;
+use super::Mask;
+use super::RawError::FeatureUnsupported;
+
+/// The result of [`Markable::mark_degraded`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Degraded {
+    /// The reduced [`Mask`] that was actually marked.
+    pub mask: Mask,
+    /// The [`Mask`] bits that were unsupported and thus dropped.
+    pub unsupported: Mask,
+}
+
+impl Degraded {
+    /// If the full [`Mask`] was marked without dropping anything.
+    pub fn is_full(&self) -> bool {
+        self.unsupported.is_empty()
+    }
+}
+
+/// Try to mark `events | modifiers`, bisecting `events` on [`FeatureUnsupported`]
+/// and adding the bits that are unsupported on their own to `unsupported`.
+fn bisect<'a, M: Markable + ?Sized>(
+    markable: &M,
+    mark: &Mark<'a>,
+    events: Mask,
+    modifiers: Mask,
+    unsupported: &mut Mask,
+) -> Result<(), Error<'a>> {
+    let attempt = match mark.with_mask(events | modifiers) {
+        Ok(attempt) => attempt,
+        Err(_) => return Ok(()), // nothing to mark
+    };
+    match markable.mark(attempt) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.error == FeatureUnsupported => {}
+        Err(e) => return Err(e),
+    }
+    let flags = events.flags().collect::<Vec<_>>();
+    if flags.len() <= 1 {
+        *unsupported |= events;
+        return Ok(());
+    }
+    let (left, right) = flags.split_at(flags.len() / 2);
+    for half in &[left, right] {
+        let half = half.iter().fold(Mask::empty(), |a, &b| a | b);
+        bisect(markable, mark, half, modifiers, unsupported)?;
+    }
+    Ok(())
+}
+
+pub(super) fn mark_degraded<'a, M: Markable + ?Sized>(markable: &M, mark: Mark<'a>) -> Result<Degraded, Error<'a>> {
+    let all_modifiers = Mask::ON_DIR | Mask::EVENT_ON_CHILD;
+    let modifiers = mark.mask & all_modifiers;
+    let events = mark.mask - all_modifiers;
+    if events.is_empty() {
+        // nothing to bisect
+        markable.mark(mark)?;
+        return Ok(Degraded {
+            mask: modifiers,
+            unsupported: Mask::empty(),
+        });
+    }
+    let mut unsupported = Mask::empty();
+    bisect(markable, &mark, events, modifiers, &mut unsupported)?;
+    if unsupported == events && !modifiers.is_empty() {
+        // nothing worked, so maybe it's the modifiers that are unsupported
+        unsupported = Mask::empty();
+        bisect(markable, &mark, events, Mask::empty(), &mut unsupported)?;
+        if unsupported != events {
+            unsupported |= modifiers;
+        }
+    }
+    if unsupported == events {
+        unsupported |= modifiers;
+    }
+    Ok(Degraded {
+        mask: mark.mask - unsupported,
+        unsupported,
+    })
+}
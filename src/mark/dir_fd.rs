@@ -2,12 +2,25 @@ use std::borrow::Cow;
 use std::fmt;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
 use std::marker::PhantomData;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsFd;
 use std::os::unix::io::AsRawFd;
+use std::os::unix::io::BorrowedFd;
 use std::os::unix::io::FromRawFd;
 use std::os::unix::io::IntoRawFd;
+use std::os::unix::io::OwnedFd;
 use std::os::unix::io::RawFd;
 
+use nix::errno::Errno;
+
+use crate::fd::FD;
+use crate::fd::FileType;
+use crate::proc;
+
 /// A borrowed directory file descriptor with lifetime `'a`.
 ///
 /// It contains a [`RawFd`] for the directory file descriptor, which outlives this [`DirFd`].
@@ -54,9 +67,12 @@ impl DirFd<'static> {
 }
 
 impl<'a> DirFd<'a> {
+    /// It's public as [`raw::dir_fd`](crate::raw::dir_fd),
+    /// and [`DirFd::try_from_fd`] is the safe alternative.
+    ///
     /// # Safety
     /// See [`FromRawFd`].  This is just a `const` version of that.
-    pub const unsafe fn const_from_raw_fd(fd: RawFd) -> Self {
+    pub(crate) const unsafe fn const_from_raw_fd(fd: RawFd) -> Self {
         Self {
             fd,
             phantom: PhantomData,
@@ -72,6 +88,21 @@ impl<'a> DirFd<'a> {
         }
     }
 
+    /// Create a [`DirFd`] from a borrowed fd, checking that it's a directory,
+    /// or else returning [`ENOTDIR`](Errno::ENOTDIR).
+    pub fn try_from_fd(fd: BorrowedFd<'a>) -> Result<Self, Errno> {
+        let fd = fd.as_raw_fd();
+        // borrowed just to fstat it, so it must not be closed
+        let file_type = std::mem::ManuallyDrop::new(unsafe { FD::from_raw_fd(fd) }).file_type()?;
+        if file_type != FileType::Directory {
+            return Err(Errno::ENOTDIR);
+        }
+        Ok(Self {
+            fd,
+            phantom: PhantomData,
+        })
+    }
+    
     /// Check if this [`DirFd`] represents the special current working directory file descriptor.
     ///
     /// It could be the case that this [`DirFd`] represents the current working directory as `open(".")`,
@@ -82,13 +113,19 @@ impl<'a> DirFd<'a> {
 
     /// Resolve this [`DirFd`] to its absolute path,
     /// attempting to use the `/proc` filesystem to resolve the file descriptor.
+    ///
+    /// See [`proc::root`] for where `/proc` is.
     pub fn resolve(&self) -> Cow<std::path::Path> {
         if self.is_current_working_directory() {
             Cow::Borrowed(std::path::Path::new("."))
         } else {
-            let link = std::path::Path::new("/proc/self/fd")
-                .join(format!("{}", self.fd));
-            let link = link.read_link().unwrap_or(link);
+            // fallback to the unresolved default link if proc is unavailable
+            let link = match proc::self_fd(self.fd) {
+                Ok(link) => link.read_link().unwrap_or(link),
+                Err(_) => std::path::Path::new(proc::DEFAULT_ROOT)
+                    .join("self/fd")
+                    .join(self.fd.to_string()),
+            };
             Cow::Owned(link)
         }
     }
@@ -102,3 +139,83 @@ impl Display for DirFd<'_> {
         }
     }
 }
+
+/// An owned directory file descriptor, closed when dropped.
+///
+/// Unlike a [`DirFd`], it has no lifetime, so it can be stored in long-lived structs
+/// like watch configurations, and then [borrowed](OwnedDirFd::as_dir_fd) as a [`DirFd`]
+/// (or used in an [`OwnedPath`](super::OwnedPath)) when marking.
+#[derive(Debug)]
+pub struct OwnedDirFd {
+    fd: OwnedFd,
+}
+
+impl OwnedDirFd {
+    /// Open the directory at `path`, failing if it isn't a directory.
+    pub fn open(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_DIRECTORY | libc::O_CLOEXEC)
+            .open(path)?;
+        Ok(file.into())
+    }
+
+    /// Take ownership of an fd, checking that it's a directory,
+    /// or else returning [`ENOTDIR`](Errno::ENOTDIR) along with the fd.
+    pub fn try_new(fd: OwnedFd) -> Result<Self, (Errno, OwnedFd)> {
+        match DirFd::try_from_fd(fd.as_fd()) {
+            Ok(_) => Ok(Self { fd }),
+            Err(errno) => Err((errno, fd)),
+        }
+    }
+
+    /// Borrow this as a [`DirFd`].
+    pub fn as_dir_fd(&self) -> DirFd<'_> {
+        DirFd::directory(self)
+    }
+}
+
+/// The fd must point to a directory for things to work correctly, like [`DirFd::directory`].
+/// See [`OwnedDirFd::try_new`] for a checked version.
+impl From<OwnedFd> for OwnedDirFd {
+    fn from(fd: OwnedFd) -> Self {
+        Self { fd }
+    }
+}
+
+/// The file must be a directory, like with the [`OwnedFd`] conversion.
+impl From<File> for OwnedDirFd {
+    fn from(file: File) -> Self {
+        OwnedFd::from(file).into()
+    }
+}
+
+impl From<OwnedDirFd> for OwnedFd {
+    fn from(dir: OwnedDirFd) -> Self {
+        dir.fd
+    }
+}
+
+impl AsFd for OwnedDirFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRawFd for OwnedDirFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl IntoRawFd for OwnedDirFd {
+    fn into_raw_fd(self) -> RawFd {
+        self.fd.into_raw_fd()
+    }
+}
+
+impl Display for OwnedDirFd {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_dir_fd())
+    }
+}
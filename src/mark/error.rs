@@ -3,9 +3,12 @@ use std::os::unix::io::RawFd;
 use thiserror::Error;
 
 use crate::init;
+use crate::init::NotificationClass;
 
 use super::Flags;
 use super::Mark;
+use super::Mask;
+use super::PathError;
 
 #[derive(Error, Debug, Eq, PartialEq, Hash)]
 pub enum StaticError {
@@ -17,6 +20,16 @@ pub enum StaticError {
 pub enum RawError {
     #[error("invalid argument specified")]
     InvalidArgument,
+    #[error(
+        "permission events {:?} can't be marked on a {:?} group, since it can't respond to them; \
+        use {:?} or {:?} instead (without {:?})",
+        *.mask & Mask::all_permissions(),
+        NotificationClass::Notify,
+        NotificationClass::Content,
+        NotificationClass::PreContent,
+        init::Flags::REPORT_FID,
+    )]
+    PermissionOnNotifyGroup { mask: Mask },
     #[error("bad dir fd specified: {}", .fd)]
     BadDirFd { fd: RawFd },
     #[error("not a directory, but {:?} specified", Flags::ONLY_DIR)]
@@ -37,11 +50,53 @@ pub enum RawError {
     OutOfMemory,
     #[error("the kernel does not support a certain feature for fanotify_mark()")]
     FeatureUnsupported,
+    /// From [`Mark::precheck`], boxed since it's much larger than the other variants.
+    #[error("{}", .0)]
+    Path(Box<PathError>),
+}
+
+impl RawError {
+    /// If this error may go away by retrying the same [`Mark`] later,
+    /// i.e. running out of kernel memory or the mark limit,
+    /// which other marks being removed in the meantime can free up.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::ExceededMarkLimit | Self::OutOfMemory)
+    }
+}
+
+impl From<PathError> for RawError {
+    fn from(e: PathError) -> Self {
+        Self::Path(Box::new(e))
+    }
 }
 
 #[derive(thiserror::Error, Debug, Eq, PartialEq, Hash)]
-#[error("{:?}: {:?}", .error, .mark)]
+#[error("{}{:?}: {:?}", .group.as_ref().map(|it| format!("{:?}: ", it)).unwrap_or_default(), .error, .mark)]
 pub struct Error<'a> {
     pub error: RawError,
     pub mark: Mark<'a>,
+    group: Option<String>,
+}
+
+impl<'a> Error<'a> {
+    pub fn new(error: RawError, mark: Mark<'a>) -> Self {
+        Self {
+            error,
+            mark,
+            group: None,
+        }
+    }
+    
+    /// Attribute this to the group named `group`.
+    pub(crate) fn in_group(self, group: Option<String>) -> Self {
+        Self {
+            group,
+            ..self
+        }
+    }
+    
+    /// The [`name`](crate::fanotify::Fanotify::name) of the group the mark was for, if any.
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
 }
\ No newline at end of file
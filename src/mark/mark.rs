@@ -104,6 +104,20 @@ impl<'a> Mark<'a> {
         Ok(this)
     }
 
+    /// This [`Mark`] with a different [`Mask`].
+    ///
+    /// This can only fail if the [`Mask`] is empty, like [`Mark::one`].
+    pub fn with_mask(&self, mask: Mask) -> Result<Self, StaticError> {
+        if mask.is_empty() {
+            return Err(EmptyMask);
+        }
+        Ok(Self {
+            mask,
+            path: self.path,
+            ..*self
+        })
+    }
+
     pub const fn flush(what: What) -> Self {
         Self {
             action: Flush,
@@ -1,8 +1,66 @@
+use std::rc::Rc;
+use std::sync::Arc;
+
+use static_assertions::assert_obj_safe;
+
 use super::Mark;
+use super::degrade::Degraded;
 
+/// Something that [`Mark`]s can be added to, i.e., a fanotify group or a wrapper around one.
+///
+/// It's object safe, so it can be used as a `dyn Markable`,
+/// and it's implemented for references and smart pointers to [`Markable`]s,
+/// so shared wrappers like an `Arc<Fanotify>` or a `Box<dyn Markable>` can be passed directly.
 pub trait Markable {
     /// Add a [`Mark`].
     ///
     /// See [`Mark`] for more details.
     fn mark<'a>(&self, mark: Mark<'a>) -> Result<(), super::Error<'a>>;
+    
+    /// Check if a [`Mark`] could be added with [`Markable::mark`] without actually adding it,
+    /// returning it back if it could, or the error [`Markable::mark`] would likely return.
+    ///
+    /// This checks the static rules for marks and what the kernel [supports](crate::supported::cached),
+    /// and that the path exists, but it doesn't make a `fanotify_mark` call,
+    /// so it doesn't change any kernel state and can be used to validate configurations.
+    /// Things like the mark limit can't be checked, though, so [`Markable::mark`] can still fail.
+    ///
+    /// By default, nothing is checked, so that existing implementations don't break,
+    /// and the [`Mark`] is always returned back.
+    fn check<'a>(&self, mark: Mark<'a>) -> Result<Mark<'a>, super::Error<'a>> {
+        Ok(mark)
+    }
+    
+    /// Like [`Markable::mark`], but if the [`Mask`](super::Mask) isn't fully supported
+    /// ([`FeatureUnsupported`](super::RawError::FeatureUnsupported)),
+    /// bisect it by retrying with subsets to find the unsupported bits,
+    /// and mark only the supported ones.
+    ///
+    /// This allows automatically degrading on old kernels or partial implementations like WSL 2.
+    /// If nothing in the [`Mask`](super::Mask) is supported,
+    /// this still succeeds with an empty [`Degraded::mask`].
+    /// Any other error is returned immediately, though some subsets may have been marked already.
+    fn mark_degraded<'a>(&self, mark: Mark<'a>) -> Result<Degraded, super::Error<'a>> {
+        super::degrade::mark_degraded(self, mark)
+    }
 }
+
+assert_obj_safe!(Markable);
+
+macro_rules! impl_markable_for_pointer {
+    ($($pointer:ty)*) => ($(impl<T: Markable + ?Sized> Markable for $pointer {
+        fn mark<'a>(&self, mark: Mark<'a>) -> Result<(), super::Error<'a>> {
+            (**self).mark(mark)
+        }
+        
+        fn check<'a>(&self, mark: Mark<'a>) -> Result<Mark<'a>, super::Error<'a>> {
+            (**self).check(mark)
+        }
+        
+        fn mark_degraded<'a>(&self, mark: Mark<'a>) -> Result<Degraded, super::Error<'a>> {
+            (**self).mark_degraded(mark)
+        }
+    })*)
+}
+
+impl_markable_for_pointer! { &T &mut T Box<T> Rc<T> Arc<T> }
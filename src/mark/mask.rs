@@ -74,8 +74,53 @@ impl Mask {
         )
     }
 
+    /// The events that can be reported with an fd, i.e. without [`REPORT_FID`](crate::init::Flags::REPORT_FID)
+    /// (plus the permission events).
+    pub const fn reportable_with_fd() -> Self {
+        Self::from_bits_truncate(0
+            | Self::ACCESS.bits
+            | Self::MODIFY.bits
+            | Self::CLOSE_WRITE.bits
+            | Self::CLOSE_NO_WRITE.bits
+            | Self::OPEN.bits
+            | Self::OPEN_EXEC.bits
+            | Self::all_permissions().bits
+            | Self::ON_DIR.bits
+            | Self::EVENT_ON_CHILD.bits
+        )
+    }
+
+    /// If this includes any of the [permission events](Self::all_permissions).
+    ///
+    /// This used to require all of them, which an event's mask never has,
+    /// so permission events weren't recognized as such, and it now only requires one.
     pub const fn includes_permission(&self) -> bool {
-        self.contains(Self::all_permissions())
+        self.intersects(Self::all_permissions())
+    }
+
+    // predicates, mostly for event masks
+
+    /// If this includes a [`CLOSE_WRITE`](Self::CLOSE_WRITE) or [`CLOSE_NO_WRITE`](Self::CLOSE_NO_WRITE) event.
+    pub const fn is_close(&self) -> bool {
+        self.intersects(Self::close())
+    }
+
+    /// If this includes a [`MOVED_FROM`](Self::MOVED_FROM), [`MOVED_TO`](Self::MOVED_TO),
+    /// or [`MOVE_SELF`](Self::MOVE_SELF) event.
+    pub const fn is_move(&self) -> bool {
+        self.intersects(Self::from_bits_truncate(Self::moved().bits | Self::MOVE_SELF.bits))
+    }
+
+    /// If the event is for a directory, i.e. [`ON_DIR`](Self::ON_DIR) is set.
+    pub const fn is_directory_event(&self) -> bool {
+        self.contains(Self::ON_DIR)
+    }
+
+    /// If this includes any of the events in `interest`, ignoring the [`ON_DIR`](Self::ON_DIR)
+    /// and [`EVENT_ON_CHILD`](Self::EVENT_ON_CHILD) flags, which aren't events themselves.
+    pub const fn relevant_to(&self, interest: Self) -> bool {
+        let flags = Self::ON_DIR.bits | Self::EVENT_ON_CHILD.bits;
+        self.bits & interest.bits & !flags != 0
     }
 
     pub const fn path_changed(&self) -> Self {
@@ -1,6 +1,12 @@
 pub use action::Action;
 pub use action::OneAction;
+pub use batch::BatchProgress;
+pub use batch::BatchReport;
+pub use batch::MarkBatch;
+pub use batch::directory_marks;
+pub use degrade::Degraded;
 pub use dir_fd::DirFd;
+pub use dir_fd::OwnedDirFd;
 pub use error::Error;
 pub use error::RawError;
 pub use error::StaticError;
@@ -9,10 +15,19 @@ pub use mark::Mark;
 pub use mark::OneMark as One;
 pub use markable::Markable;
 pub use mask::Mask;
+pub use path::OwnedPath;
 pub use path::Path;
+pub use precheck::PathError;
 pub(crate) use raw::FanotifyMark;
 pub use raw::RawFlags;
 pub use raw::RawMark;
+pub use registry::MarkRegistry;
+pub use registry::OwnedMark;
+pub use registry::ReapplyReport;
+pub use template::MarkTemplate;
+pub use template::TemplateError;
+#[cfg(feature = "trigger")]
+pub(crate) use template::expand_vars;
 pub use what::What;
 
 mod dir_fd;
@@ -26,6 +41,11 @@ mod what;
 mod flags;
 mod mask;
 mod markable;
+mod registry;
+mod degrade;
+mod template;
+mod precheck;
+mod batch;
 
 #[cfg(test)]
 mod tests {
@@ -43,6 +63,27 @@ mod tests {
         What::{FileSystem, MountPoint},
     };
 
+    #[test]
+    fn mask_predicates() {
+        use mark::Mask;
+        let mask = Mask::CLOSE_WRITE | Mask::ON_DIR;
+        assert!(mask.is_close());
+        assert!(!mask.is_move());
+        assert!(mask.is_directory_event());
+        assert!(mask.relevant_to(Mask::close() | Mask::MODIFY));
+        assert!(!mask.relevant_to(Mask::OPEN | Mask::ON_DIR));
+        assert!(Mask::MOVE_SELF.is_move());
+    }
+
+    #[test]
+    fn includes_permission() {
+        use mark::Mask;
+        assert!(Mask::OPEN_PERMISSION.includes_permission());
+        assert!((Mask::OPEN_EXEC_PERMISSION | Mask::ON_DIR).includes_permission());
+        assert!(Mask::all_permissions().includes_permission());
+        assert!(!(Mask::OPEN | Mask::ACCESS).includes_permission());
+    }
+
     #[test]
     fn mark_static_error() {
         assert_eq!(Mark::one(mark::mark::OneMark {
@@ -117,4 +158,24 @@ mod tests {
             }",
         );
     }
+
+    #[test]
+    fn mask_format() {
+        use crate::event::display::Format;
+        
+        let mask = mark::Mask::OPEN | mark::Mask::close();
+        assert_eq!(format!("{}", mask.format(Format::Compact)), "OPEN | CLOSE_NO_WRITE | CLOSE_WRITE");
+        assert_eq!(format!("{}", mask.format(Format::Verbose)), "OPEN | CLOSE_NO_WRITE | CLOSE_WRITE (0x38)");
+        assert_eq!(format!("{}", mask.format(Format::Json)), r#"["CLOSE_WRITE","CLOSE_NO_WRITE","OPEN"]"#);
+    }
+
+    #[test]
+    fn mask_kinds() {
+        use crate::event::kind::EventKind;
+        
+        let mask = mark::Mask::OPEN | mark::Mask::CLOSE_WRITE | mark::Mask::ON_DIR;
+        let kinds = mask.kinds().collect::<Vec<_>>();
+        assert_eq!(kinds, vec![EventKind::Closed { write: true }, EventKind::Opened { exec: false }]);
+        assert_eq!(kinds.into_iter().map(mark::Mask::from).collect::<mark::Mask>(), mask - mark::Mask::ON_DIR);
+    }
 }
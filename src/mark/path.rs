@@ -3,12 +3,16 @@ use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::fs;
+use std::io;
 use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
 
 use super::DirFd;
+use super::OwnedDirFd;
 
 /// A path that is either absolute or relative to a directory file descriptor ([`DirFd`]).
-#[derive(Eq, PartialEq, Hash)]
+#[derive(Eq, PartialEq, Hash, Copy, Clone)]
 pub struct Path<'a> {
     pub(super) dir: DirFd<'a>,
     pub(super) path: Option<&'a std::path::Path>,
@@ -69,6 +73,12 @@ impl<'a> Path<'a> {
             }
         }
     }
+
+    /// [`Resolve`](Path::resolve) this [`Path`] and then canonicalize it,
+    /// following all symlinks, like [`std::fs::canonicalize`].
+    pub fn canonicalize(&self) -> io::Result<PathBuf> {
+        fs::canonicalize(self.resolve())
+    }
 }
 
 impl Display for Path<'_> {
@@ -92,3 +102,57 @@ impl Debug for Path<'_> {
         write!(f, "{}", self)
     }
 }
+
+/// An owned version of a [`Path`], owning its directory ([`OwnedDirFd`]) and relative path,
+/// so that it can be stored without a lifetime and [borrowed](OwnedPath::as_path) when marking.
+#[derive(Debug)]
+pub struct OwnedPath {
+    dir: Option<OwnedDirFd>,
+    path: Option<PathBuf>,
+}
+
+impl OwnedPath {
+    /// An owned [`Path::directory`].
+    pub fn directory(dir: impl Into<OwnedDirFd>) -> Self {
+        Self {
+            dir: Some(dir.into()),
+            path: None,
+        }
+    }
+
+    /// An owned [`Path::relative_to`].
+    pub fn relative_to(dir: impl Into<OwnedDirFd>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Some(dir.into()),
+            path: Some(path.into()),
+        }
+    }
+
+    /// An owned [`Path::absolute`].
+    pub fn absolute(path: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: None,
+            path: Some(path.into()),
+        }
+    }
+
+    pub fn dir(&self) -> Option<&OwnedDirFd> {
+        self.dir.as_ref()
+    }
+
+    /// Borrow this as a [`Path`].
+    pub fn as_path(&self) -> Path<'_> {
+        match (&self.dir, &self.path) {
+            (Some(dir), Some(path)) => Path::relative_to(dir, path),
+            (Some(dir), None) => Path::directory(dir),
+            (None, Some(path)) => Path::absolute(path),
+            (None, None) => Path::current_working_directory(),
+        }
+    }
+}
+
+impl Display for OwnedPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_path())
+    }
+}
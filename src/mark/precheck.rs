@@ -0,0 +1,50 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use super::Action;
+use super::Flags;
+use super::Mark;
+
+/// A descriptive error from [`Mark::precheck`]ing a [`Mark`]'s path before the `fanotify_mark` call,
+/// whose errno alone (e.g. `ENOENT` or `ENOTDIR`) doesn't say which path or why.
+#[derive(thiserror::Error, Debug, Clone, Eq, PartialEq, Hash)]
+pub enum PathError {
+    #[error("path {:?} does not exist", .path)]
+    Missing { path: PathBuf },
+    #[error("path {:?} is not a directory, but {:?} was specified", .path, Flags::ONLY_DIR)]
+    NotADirectory { path: PathBuf },
+    #[error("path {:?} could not be checked: {:?}", .path, .kind)]
+    Inaccessible { path: PathBuf, kind: io::ErrorKind },
+}
+
+impl Mark<'_> {
+    /// Check this [`Mark`]'s path before marking it, for a more descriptive error than the kernel gives.
+    ///
+    /// For an [`Add`](Action::Add), this checks that the resolved path exists,
+    /// and that it's a directory if [`ONLY_DIR`](Flags::ONLY_DIR) is specified.
+    /// Other [`Action`]s are always okay.
+    ///
+    /// Enable [`Fanotify::set_precheck`](crate::fanotify::Fanotify::set_precheck)
+    /// to do this automatically on every [`mark`](super::Markable::mark).
+    pub fn precheck(&self) -> Result<(), PathError> {
+        if self.action != Action::Add {
+            return Ok(());
+        }
+        let path = self.path.resolve().into_owned();
+        let error = |kind: io::ErrorKind| match kind {
+            io::ErrorKind::NotFound => PathError::Missing { path: path.clone() },
+            kind => PathError::Inaccessible { path: path.clone(), kind },
+        };
+        let link = fs::symlink_metadata(&path).map_err(|e| error(e.kind()))?;
+        let metadata = if self.flags.contains(Flags::DONT_FOLLOW) || !link.file_type().is_symlink() {
+            link
+        } else {
+            fs::metadata(&path).map_err(|e| error(e.kind()))?
+        };
+        if self.flags.contains(Flags::ONLY_DIR) && !metadata.is_dir() {
+            return Err(PathError::NotADirectory { path });
+        }
+        Ok(())
+    }
+}
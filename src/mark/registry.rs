@@ -0,0 +1,119 @@
+use std::path::PathBuf;
+
+use super::Action;
+use super::Error;
+use super::Flags;
+use super::Mark;
+use super::Markable;
+use super::Mask;
+use super::Path;
+use super::RawError;
+use super::What;
+
+/// An owned version of a [`Mark`], with its [`Path`] resolved to an absolute [`PathBuf`]
+/// so that it can outlive any directory file descriptors the [`Mark`] was relative to.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct OwnedMark {
+    pub action: Action,
+    pub what: What,
+    pub flags: Flags,
+    pub mask: Mask,
+    pub path: PathBuf,
+}
+
+impl OwnedMark {
+    pub fn new(mark: &Mark) -> Self {
+        Self {
+            action: mark.action,
+            what: mark.what,
+            flags: mark.flags,
+            mask: mark.mask,
+            path: mark.path.resolve().into_owned(),
+        }
+    }
+    
+    /// Borrow this as a [`Mark`] again.
+    pub fn as_mark(&self) -> Mark<'_> {
+        Mark {
+            action: self.action,
+            what: self.what,
+            flags: self.flags,
+            mask: self.mask,
+            path: Path::absolute(&self.path),
+        }
+    }
+}
+
+/// A record of all the [`Mark`]s applied to a fanotify group, in order,
+/// so that they can be [re-applied](MarkRegistry::reapply) to a new group
+/// if the original one has to be [recreated](crate::fanotify::Fanotify::recreate).
+///
+/// The kernel doesn't let us list a group's marks, so they have to be recorded as they're made,
+/// either by marking through [`MarkRegistry::mark`] or by calling [`MarkRegistry::record`].
+#[derive(Debug, Default, Clone)]
+pub struct MarkRegistry {
+    marks: Vec<OwnedMark>,
+}
+
+/// The result of [re-applying](MarkRegistry::reapply) all the [`Mark`]s in a [`MarkRegistry`].
+#[derive(Debug, Default)]
+pub struct ReapplyReport {
+    /// The number of [`Mark`]s successfully re-applied.
+    pub applied: usize,
+    /// The [`Mark`]s that failed to be re-applied and why.
+    pub failed: Vec<(OwnedMark, RawError)>,
+}
+
+impl ReapplyReport {
+    /// If every [`Mark`] was re-applied.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl MarkRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn marks(&self) -> &[OwnedMark] {
+        &self.marks
+    }
+    
+    pub fn clear(&mut self) {
+        self.marks.clear();
+    }
+    
+    /// Record a [`Mark`] that was successfully applied.
+    ///
+    /// A [`Flush`](Action::Flush) removes all the previously recorded marks of the same [`What`],
+    /// since they don't need to be re-applied anymore.
+    pub fn record(&mut self, mark: &Mark) {
+        if mark.action == Action::Flush {
+            self.marks.retain(|it| it.what != mark.what);
+            return;
+        }
+        self.marks.push(OwnedMark::new(mark));
+    }
+    
+    /// Apply a [`Mark`] to a [`Markable`] and [`record`](MarkRegistry::record) it if successful.
+    pub fn mark<'a>(&mut self, markable: &(impl Markable + ?Sized), mark: Mark<'a>) -> Result<(), Error<'a>> {
+        let owned = OwnedMark::new(&mark);
+        markable.mark(mark)?;
+        self.record(&owned.as_mark());
+        Ok(())
+    }
+    
+    /// Re-apply all the recorded [`Mark`]s, in order, to a [`Markable`],
+    /// continuing past any failures and reporting all of them.
+    pub fn reapply(&self, markable: &(impl Markable + ?Sized)) -> ReapplyReport {
+        let mut report = ReapplyReport::default();
+        for mark in &self.marks {
+            match markable.mark(mark.as_mark()) {
+                Ok(()) => report.applied += 1,
+                Err(e) => report.failed.push((mark.clone(), e.error)),
+            }
+        }
+        report
+    }
+}
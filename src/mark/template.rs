@@ -0,0 +1,482 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Component;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use super::Action;
+use super::BatchReport;
+use super::Flags;
+use super::MarkBatch;
+use super::Markable;
+use super::Mask;
+use super::OwnedMark;
+use super::RawError;
+use super::What;
+
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum TemplateError {
+    #[error("invalid mark template {:?}: {}", .template, .reason)]
+    Syntax { template: String, reason: &'static str },
+    #[error("unknown mask flag {:?}", .0)]
+    UnknownFlag(String),
+    #[error("the mask must not be empty")]
+    EmptyMask,
+    #[error("undefined variable ${}", .0)]
+    UndefinedVariable(String),
+    #[error("unmatched brace in {:?}", .0)]
+    UnmatchedBrace(String),
+    #[error("{:?} expanded to a relative path", .0)]
+    RelativePath(PathBuf),
+    #[error("{:?} didn't match any paths", .0)]
+    NoMatches(String),
+    #[error("{:?}: {:?}", .error, .mark)]
+    Mark { mark: OwnedMark, error: RawError },
+}
+
+/// A pattern for [`Mark`](super::Mark)s that's expanded into concrete ones at marking time,
+/// like `$HOME/{Downloads,Documents} for MODIFY | CREATE`.
+///
+/// The path pattern is expanded like in a shell, in this order:
+/// * `~` (at the start) and environment variables (`$NAME` or `${NAME}`, and `$$` for a `$`),
+///   which must all be defined
+/// * braces, like `{Downloads,Documents}`, which can be nested
+/// * globs, i.e. `*`, `?`, and `[...]` (`[!...]` to negate) in any path component,
+///   which don't match hidden files unless the component starts with a `.`
+///
+/// The expanded paths must be absolute.
+/// Paths without globs are kept even if they don't exist, so that marking them reports it.
+///
+/// Use [`MarkTemplate::expand`], [`MarkTemplate::marks`], or [`MarkTemplate::check`]
+/// to see what a template expands to without marking anything.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct MarkTemplate {
+    pattern: String,
+    what: What,
+    flags: Flags,
+    mask: Mask,
+}
+
+impl MarkTemplate {
+    /// Create a [`MarkTemplate`] for [`Inode`](What::Inode) marks without any [`Flags`].
+    pub fn new(pattern: impl Into<String>, mask: Mask) -> Self {
+        Self {
+            pattern: pattern.into(),
+            what: What::Inode,
+            flags: Flags::empty(),
+            mask,
+        }
+    }
+    
+    /// Parse a template like `watch $HOME/{Downloads,Documents} for MODIFY | CREATE`,
+    /// where the `watch` is optional and the [`Mask`] flags are named like they're [formatted](Mask::format)
+    /// (case-insensitively).
+    pub fn parse(template: &str) -> Result<Self, TemplateError> {
+        let syntax = |reason| TemplateError::Syntax {
+            template: template.to_owned(),
+            reason,
+        };
+        let trimmed = template.trim();
+        let trimmed = trimmed.strip_prefix("watch ").unwrap_or(trimmed);
+        let (pattern, flags) = trimmed
+            .rsplit_once(" for ")
+            .ok_or_else(|| syntax("expected `<path pattern> for <mask>`"))?;
+        let pattern = pattern.trim();
+        if pattern.is_empty() {
+            return Err(syntax("missing the path pattern"));
+        }
+        let mut mask = Mask::empty();
+        for name in flags.split('|').map(str::trim) {
+            mask |= Mask::from_name(&name.to_ascii_uppercase())
+                .ok_or_else(|| TemplateError::UnknownFlag(name.to_owned()))?;
+        }
+        Ok(Self::new(pattern, mask))
+    }
+    
+    pub fn with_what(mut self, what: What) -> Self {
+        self.what = what;
+        self
+    }
+    
+    pub fn with_flags(mut self, flags: Flags) -> Self {
+        self.flags = flags;
+        self
+    }
+    
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+    
+    pub fn what(&self) -> What {
+        self.what
+    }
+    
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
+    
+    pub fn mask(&self) -> Mask {
+        self.mask
+    }
+    
+    /// Expand the path pattern into concrete paths, using the process's environment variables.
+    pub fn expand(&self) -> Result<Vec<PathBuf>, TemplateError> {
+        self.expand_with(|name| std::env::var(name).ok())
+    }
+    
+    /// Like [`MarkTemplate::expand`], but looking up variables with `var` instead.
+    ///
+    /// The paths are in the order their braces and globs expand to, with duplicates removed,
+    /// and there must be at least one.
+    pub fn expand_with(&self, var: impl Fn(&str) -> Option<String>) -> Result<Vec<PathBuf>, TemplateError> {
+        let expanded = expand_variables(&self.pattern, &var)?;
+        let mut paths = Vec::new();
+        for pattern in expand_braces(&expanded)? {
+            let pattern = PathBuf::from(pattern);
+            if !pattern.is_absolute() {
+                return Err(TemplateError::RelativePath(pattern));
+            }
+            for path in expand_globs(&pattern) {
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+        if paths.is_empty() {
+            return Err(TemplateError::NoMatches(self.pattern.clone()));
+        }
+        Ok(paths)
+    }
+    
+    /// Expand into the concrete [`OwnedMark`]s that [`MarkTemplate::mark`] would add.
+    pub fn marks(&self) -> Result<Vec<OwnedMark>, TemplateError> {
+        if self.mask.is_empty() {
+            return Err(TemplateError::EmptyMask);
+        }
+        let marks = self
+            .expand()?
+            .into_iter()
+            .map(|path| OwnedMark {
+                action: Action::Add,
+                what: self.what,
+                flags: self.flags,
+                mask: self.mask,
+                path,
+            })
+            .collect();
+        Ok(marks)
+    }
+    
+    /// Expand and [check](Markable::check) each of the [`OwnedMark`]s without adding them,
+    /// to validate a configuration.
+    pub fn check(&self, markable: &(impl Markable + ?Sized)) -> Result<Vec<OwnedMark>, TemplateError> {
+        let marks = self.marks()?;
+        for mark in &marks {
+            if let Err(e) = markable.check(mark.as_mark()) {
+                return Err(TemplateError::Mark {
+                    mark: mark.clone(),
+                    error: e.error,
+                });
+            }
+        }
+        Ok(marks)
+    }
+    
+    /// Expand and add each of the [`OwnedMark`]s, returning them.
+    ///
+    /// This stops at the first error, though earlier [`OwnedMark`]s will have been added already.
+    pub fn mark(&self, markable: &(impl Markable + ?Sized)) -> Result<Vec<OwnedMark>, TemplateError> {
+        let marks = self.marks()?;
+        for mark in &marks {
+            if let Err(e) = markable.mark(mark.as_mark()) {
+                return Err(TemplateError::Mark {
+                    mark: mark.clone(),
+                    error: e.error,
+                });
+            }
+        }
+        Ok(marks)
+    }
+    
+    /// Expand and apply all the [`OwnedMark`]s with a [`MarkBatch`],
+    /// for globs that match many paths.
+    ///
+    /// Unlike [`MarkTemplate::mark`], this continues past marks that fail,
+    /// reporting them in the [`BatchReport`].
+    pub fn mark_batch(
+        &self,
+        markable: &(impl Markable + Sync + ?Sized),
+        batch: &MarkBatch,
+    ) -> Result<BatchReport, TemplateError> {
+        let marks = self.marks()?;
+        Ok(batch.apply_all(markable, &marks))
+    }
+}
+
+/// Expand `$NAME`, `${NAME}`, and `$$` (to `$`) in `s`.
+///
+/// Each variable is expanded by `var`, which is given its name (empty if there's none after the `$`,
+/// or [`None`] if its `${` is never closed), the variable as written,
+/// and the expanded string to push its value to.
+///
+/// This is shared with `trigger::expand`, which leaves unknown variables as is.
+pub(crate) fn expand_vars<E>(
+    s: &str,
+    mut var: impl FnMut(Option<&str>, &str, &mut String) -> Result<(), E>,
+) -> Result<String, E> {
+    let mut expanded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('$') {
+        expanded.push_str(&rest[..i]);
+        let variable = &rest[i..];
+        let after_dollar = &variable[1..];
+        if let Some(after) = after_dollar.strip_prefix('$') {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+        let (name, len) = match after_dollar.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (Some(&braced[..end]), end + 3),
+                None => (None, 1),
+            },
+            None => {
+                let end = after_dollar
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after_dollar.len());
+                (Some(&after_dollar[..end]), end + 1)
+            }
+        };
+        var(name, &variable[..len], &mut expanded)?;
+        rest = &variable[len..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn expand_variables(pattern: &str, var: &impl Fn(&str) -> Option<String>) -> Result<String, TemplateError> {
+    let lookup = |name: &str| var(name).ok_or_else(|| TemplateError::UndefinedVariable(name.to_owned()));
+    let (home, rest) = match pattern.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => (Some(lookup("HOME")?), rest),
+        _ => (None, pattern),
+    };
+    let expanded = expand_vars(rest, |name, _, expanded| {
+        match name {
+            None => return Err(TemplateError::UnmatchedBrace(pattern.to_owned())),
+            Some("") => {
+                return Err(TemplateError::Syntax {
+                    template: pattern.to_owned(),
+                    reason: "expected a variable name after `$`",
+                });
+            }
+            Some(name) => expanded.push_str(&lookup(name)?),
+        }
+        Ok(())
+    })?;
+    Ok(home.unwrap_or_default() + &expanded)
+}
+
+/// Expand the first (outermost) braces in `pattern`, and then recursively the rest.
+///
+/// Braces without a `,` are kept as is, like in a shell.
+fn expand_braces(pattern: &str) -> Result<Vec<String>, TemplateError> {
+    let open = match pattern.find('{') {
+        None => return Ok(vec![pattern.to_owned()]),
+        Some(open) => open,
+    };
+    let mut depth = 0;
+    let mut close = None;
+    let mut commas = Vec::new();
+    for (i, c) in pattern[open..].char_indices().map(|(i, c)| (open + i, c)) {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(i);
+                    break;
+                }
+            }
+            ',' if depth == 1 => commas.push(i),
+            _ => {}
+        }
+    }
+    let close = close.ok_or_else(|| TemplateError::UnmatchedBrace(pattern.to_owned()))?;
+    let prefix = &pattern[..open];
+    let suffixes = expand_braces(&pattern[close + 1..])?;
+    let alternatives = if commas.is_empty() {
+        expand_braces(&pattern[open + 1..close])?
+            .into_iter()
+            .map(|it| format!("{{{}}}", it))
+            .collect::<Vec<_>>()
+    } else {
+        let mut alternatives = Vec::new();
+        let mut start = open + 1;
+        for end in commas.into_iter().chain(Some(close)) {
+            alternatives.extend(expand_braces(&pattern[start..end])?);
+            start = end + 1;
+        }
+        alternatives
+    };
+    let mut expanded = Vec::with_capacity(alternatives.len() * suffixes.len());
+    for alternative in &alternatives {
+        for suffix in &suffixes {
+            expanded.push(format!("{}{}{}", prefix, alternative, suffix));
+        }
+    }
+    Ok(expanded)
+}
+
+fn is_glob(component: &[u8]) -> bool {
+    component.iter().any(|it| matches!(it, b'*' | b'?' | b'['))
+}
+
+/// Match a file `name` against a glob `pattern` of `*`, `?`, and `[...]`.
+fn glob_matches(pattern: &[u8], name: &[u8]) -> bool {
+    // the position in the pattern after the last `*` and the position in the name it's matched up to
+    let mut star = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        let matched = match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p + 1, n));
+                p += 1;
+                continue;
+            }
+            Some(b'?') => Some(p + 1),
+            Some(b'[') => match_class(&pattern[p..], name[n]).map(|len| p + len),
+            Some(&c) => if c == name[n] { Some(p + 1) } else { None },
+            None => None,
+        };
+        match (matched, star) {
+            (Some(next), _) => {
+                p = next;
+                n += 1;
+            }
+            (None, Some((after_star, matched_to))) => {
+                // let the last `*` match one more byte
+                p = after_star;
+                n = matched_to + 1;
+                star = Some((after_star, n));
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p..].iter().all(|&it| it == b'*')
+}
+
+/// Match `c` against the `[...]` class at the start of `pattern`,
+/// returning the length of the class if it matches.
+///
+/// An unterminated `[` is matched literally.
+fn match_class(pattern: &[u8], c: u8) -> Option<usize> {
+    let negated = matches!(pattern.get(1), Some(b'!') | Some(b'^'));
+    let start = if negated { 2 } else { 1 };
+    // a `]` right at the start is part of the class
+    let rest = pattern.get(start + 1..).unwrap_or_default();
+    let end = match rest.iter().position(|&it| it == b']') {
+        Some(end) => start + 1 + end,
+        None => return if c == b'[' { Some(1) } else { None },
+    };
+    let class = &pattern[start..end];
+    let mut matched = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == b'-' {
+            matched |= (class[i]..=class[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            matched |= class[i] == c;
+            i += 1;
+        }
+    }
+    if matched != negated {
+        Some(end + 1)
+    } else {
+        None
+    }
+}
+
+/// Expand the globs in an absolute `pattern` into the existing paths they match, in sorted order.
+fn expand_globs(pattern: &std::path::Path) -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let component = match component {
+            Component::Normal(component) => component.as_bytes(),
+            other => {
+                for path in &mut paths {
+                    path.push(other);
+                }
+                continue;
+            }
+        };
+        if !is_glob(component) {
+            for path in &mut paths {
+                path.push(OsStr::from_bytes(component));
+            }
+            continue;
+        }
+        let mut matches = Vec::new();
+        for path in &paths {
+            let entries = match fs::read_dir(path) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            let mut names = entries
+                .filter_map(Result::ok)
+                .map(|it| it.file_name())
+                .filter(|name| {
+                    let name = name.as_bytes();
+                    (!name.starts_with(b".") || component.starts_with(b".")) && glob_matches(component, name)
+                })
+                .collect::<Vec<_>>();
+            names.sort();
+            matches.extend(names.into_iter().map(|name| path.join(name)));
+        }
+        paths = matches;
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand_braces;
+    use super::expand_variables;
+    use super::glob_matches;
+    use super::TemplateError;
+    
+    #[test]
+    fn braces() {
+        assert_eq!(expand_braces("/home/{a,b{1,2}}/x{y}").unwrap(), vec!["/home/a/x{y}", "/home/b1/x{y}", "/home/b2/x{y}"]);
+        assert_eq!(expand_braces("/{a,b}/{c,d}").unwrap(), vec!["/a/c", "/a/d", "/b/c", "/b/d"]);
+        assert_eq!(expand_braces("/{a,b"), Err(TemplateError::UnmatchedBrace("/{a,b".to_owned())));
+    }
+    
+    #[test]
+    fn variables() {
+        let var = |name: &str| if name == "HOME" { Some("/home/me".to_owned()) } else { None };
+        assert_eq!(expand_variables("~/$HOME/${HOME}x/$$", &var).unwrap(), "/home/me//home/me//home/mex/$");
+        assert_eq!(expand_variables("/$USER", &var), Err(TemplateError::UndefinedVariable("USER".to_owned())));
+    }
+    
+    #[test]
+    fn globs() {
+        let cases = [
+            ("*.txt", "notes.txt", true),
+            ("*.txt", "notes.txt.bak", false),
+            ("a*b*c", "aXbYbZc", true),
+            ("?.rs", "a.rs", true),
+            ("?.rs", "ab.rs", false),
+            ("[a-c]x", "bx", true),
+            ("[!a-c]x", "bx", false),
+            ("[]]", "]", true),
+            ("[", "[", true),
+            ("*", "", true),
+        ];
+        for &(pattern, name, matches) in cases.iter() {
+            assert_eq!(glob_matches(pattern.as_bytes(), name.as_bytes()), matches, "{} {}", pattern, name);
+        }
+    }
+}
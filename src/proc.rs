@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt::Debug;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::RawFd;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
+
+use thiserror::Error;
+
+use crate::restricted;
+
+/// Where the `proc` filesystem is mounted.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+enum Root {
+    /// At [`DEFAULT_ROOT`].
+    Default,
+    /// Somewhere else.
+    Custom(PathBuf),
+    /// Not mounted at all.
+    Unavailable,
+}
+
+static ROOT: RwLock<Root> = RwLock::new(Root::Default);
+
+/// The default mount point of the `proc` filesystem.
+pub const DEFAULT_ROOT: &str = "/proc";
+
+/// An error for when something needs the `proc` filesystem,
+/// but it has been marked unavailable with [`set_unavailable`].
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[error("the proc filesystem is unavailable, so paths can't be resolved")]
+pub struct ProcUnavailable;
+
+/// Since most path resolution APIs return an [`io::Error`],
+/// a [`ProcUnavailable`] is wrapped in an [`io::ErrorKind::Unsupported`] [`io::Error`].
+/// It can be recovered with [`io::Error::get_ref`] and `downcast_ref`,
+/// or checked with [`is_unavailable`].
+impl From<ProcUnavailable> for io::Error {
+    fn from(e: ProcUnavailable) -> Self {
+        Self::new(io::ErrorKind::Unsupported, e)
+    }
+}
+
+/// Check if an [`io::Error`] was caused by [`ProcUnavailable`].
+pub fn is_unavailable(e: &io::Error) -> bool {
+    e.get_ref()
+        .is_some_and(|e| e.is::<ProcUnavailable>())
+}
+
+/// The mount point of the `proc` filesystem used for resolving paths,
+/// like in [`FD::path`](crate::fd::FD::path) and [`DirFd::resolve`](crate::mark::DirFd::resolve).
+///
+/// This is [`DEFAULT_ROOT`] unless changed by [`set_root`] or [`set_unavailable`],
+/// and it's always unavailable in [restricted mode](crate::restricted).
+pub fn root() -> Result<PathBuf, ProcUnavailable> {
+    if restricted::is_enabled() {
+        return Err(ProcUnavailable);
+    }
+    match &*ROOT.read().unwrap_or_else(|e| e.into_inner()) {
+        Root::Default => Ok(PathBuf::from(DEFAULT_ROOT)),
+        Root::Custom(root) => Ok(root.clone()),
+        Root::Unavailable => Err(ProcUnavailable),
+    }
+}
+
+fn set(root: Root) {
+    *ROOT.write().unwrap_or_else(|e| e.into_inner()) = root;
+}
+
+/// Set the mount point of the `proc` filesystem used for resolving paths.
+///
+/// This is for chroots, minimal containers,
+/// and other places where `proc` is not mounted at [`DEFAULT_ROOT`].
+/// It is global, so it applies to the whole crate.
+pub fn set_root(root: impl AsRef<Path>) {
+    set(Root::Custom(root.as_ref().to_path_buf()));
+}
+
+/// Mark the `proc` filesystem as unavailable, i.e. proc-free mode,
+/// for hardened environments that don't mount it at all.
+///
+/// Path resolution is then disabled, returning [`ProcUnavailable`] errors
+/// instead of confusing [`ENOENT`](nix::errno::Errno::ENOENT)s.
+/// Nothing else depends on `proc`; for example,
+/// events generated by this process are detected using `getpid`/`gettid`
+/// (see [`Id::current`](crate::event::id::Id::current)).
+///
+/// Undo this with [`set_root`].
+pub fn set_unavailable() {
+    set(Root::Unavailable);
+}
+
+/// Check if the `proc` filesystem hasn't been marked unavailable by [`set_unavailable`].
+pub fn is_available() -> bool {
+    root().is_ok()
+}
+
+/// The `/proc/self/fd` directory under the current [`root`].
+pub fn self_fd_dir() -> Result<PathBuf, ProcUnavailable> {
+    Ok(root()?.join("self").join("fd"))
+}
+
+/// The `/proc/self/fd/{fd}` symlink for `fd` under the current [`root`].
+pub fn self_fd(fd: RawFd) -> Result<PathBuf, ProcUnavailable> {
+    Ok(self_fd_dir()?.join(fd.to_string()))
+}
+
+/// The `/proc/self/fdinfo/{fd}` file for `fd` under the current [`root`].
+pub fn self_fdinfo(fd: RawFd) -> Result<PathBuf, ProcUnavailable> {
+    Ok(root()?.join("self").join("fdinfo").join(fd.to_string()))
+}
+
+/// The `/proc/{pid}` directory for the process (or thread) `pid` under the current [`root`].
+pub fn pid_dir(pid: libc::pid_t) -> Result<PathBuf, ProcUnavailable> {
+    Ok(root()?.join(pid.to_string()))
+}
+
+/// A source of the per-process files under `/proc/{pid}`, like `status` and `cgroup`,
+/// so that what's read from them (see [`Credentials`](crate::event::credentials::Credentials)
+/// and [`cgroup`](crate::event::cgroup)) can be tested deterministically with a [`MockProcFs`].
+///
+/// See [`Clock`](crate::clock::Clock) for the same for time.
+pub trait ProcFs: Debug + Send + Sync {
+    /// Read the file `name` in the `/proc/{pid}` directory of the process (or thread) `pid`.
+    fn read_pid_file(&self, pid: libc::pid_t, name: &str) -> io::Result<String>;
+}
+
+/// The real [`ProcFs`], under the current [`root`].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct SystemProcFs;
+
+impl ProcFs for SystemProcFs {
+    fn read_pid_file(&self, pid: libc::pid_t, name: &str) -> io::Result<String> {
+        fs::read_to_string(pid_dir(pid)?.join(name))
+    }
+}
+
+/// The default [`ProcFs`] to share, the [`SystemProcFs`].
+pub fn system() -> Arc<dyn ProcFs> {
+    Arc::new(SystemProcFs)
+}
+
+/// A [`ProcFs`] of in-memory files, where missing files (e.g. of exited processes) are [`io::ErrorKind::NotFound`].
+///
+/// Clones share the same files, so one can be passed to what's being tested
+/// while another is kept to change them.
+#[derive(Debug, Clone, Default)]
+pub struct MockProcFs {
+    files: Arc<Mutex<HashMap<(libc::pid_t, String), String>>>,
+}
+
+impl MockProcFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Set the contents of `/proc/{pid}/{name}`.
+    pub fn insert(&self, pid: libc::pid_t, name: impl Into<String>, contents: impl Into<String>) {
+        self.files
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert((pid, name.into()), contents.into());
+    }
+    
+    /// Remove all the files of `pid`, like when it exits.
+    pub fn remove_pid(&self, pid: libc::pid_t) {
+        self.files
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|(it, _), _| *it != pid);
+    }
+    
+    /// This [`MockProcFs`] as a shared [`ProcFs`], still controlled by `self`.
+    pub fn shared(&self) -> Arc<dyn ProcFs> {
+        Arc::new(self.clone())
+    }
+}
+
+impl ProcFs for MockProcFs {
+    fn read_pid_file(&self, pid: libc::pid_t, name: &str) -> io::Result<String> {
+        self.files
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&(pid, name.to_string()))
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no /proc/{}/{}", pid, name)))
+    }
+}
+
+/// How to normalize symlinks when resolving a `/proc` link with [`resolve_link`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Symlinks {
+    /// Return the literal link target, as the kernel reports it.
+    ///
+    /// This is the path the file was opened through (relative to the process's root),
+    /// so it's cheap and always available, but it may differ from other paths to the same file,
+    /// e.g. through bind mounts.
+    Literal,
+    /// Return the [canonicalized](fs::canonicalize) link target, with all symlinks resolved.
+    ///
+    /// This costs a syscall per path component and fails if the target is no longer reachable
+    /// (e.g. it was moved or is in another mount namespace).
+    Canonical,
+    /// Return both the literal and the canonicalized link target.
+    Both,
+}
+
+/// Whether the target of a `/proc` link still exists.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum LinkState {
+    Present,
+    /// The target was deleted, which the kernel reports with a `" (deleted)"` suffix.
+    Deleted,
+}
+
+/// A `/proc` link resolved by [`resolve_link`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ResolvedPath {
+    /// The literal link target, without any `" (deleted)"` suffix,
+    /// if [`Symlinks::Literal`] or [`Symlinks::Both`] was requested.
+    pub literal: Option<PathBuf>,
+    /// The canonicalized link target,
+    /// if [`Symlinks::Canonical`] or [`Symlinks::Both`] was requested and the target isn't [deleted](LinkState::Deleted).
+    pub canonical: Option<PathBuf>,
+    pub state: LinkState,
+}
+
+impl ResolvedPath {
+    pub fn is_deleted(&self) -> bool {
+        self.state == LinkState::Deleted
+    }
+    
+    /// The canonicalized path if there is one, or else the literal one.
+    pub fn path(&self) -> Option<&Path> {
+        self.canonical.as_deref().or(self.literal.as_deref())
+    }
+}
+
+/// The suffix the kernel appends to the `/proc` link target of a deleted file.
+const DELETED_SUFFIX: &[u8] = b" (deleted)";
+
+/// Split the `" (deleted)"` suffix off of a `/proc` link target.
+///
+/// Note that a file can also just be named like that,
+/// so check something like [`FD::stat`](crate::fd::FD::stat)'s `st_nlink` when it matters.
+pub fn split_deleted(target: &Path) -> (&Path, LinkState) {
+    let bytes = target.as_os_str().as_bytes();
+    match bytes.strip_suffix(DELETED_SUFFIX) {
+        Some(path) => (Path::new(OsStr::from_bytes(path)), LinkState::Deleted),
+        None => (target, LinkState::Present),
+    }
+}
+
+/// Read a `/proc` link (like [`self_fd`]) and resolve its target according to `symlinks`.
+pub fn resolve_link(link: &Path, symlinks: Symlinks) -> io::Result<ResolvedPath> {
+    resolve_link_checked(link, symlinks, || true)
+}
+
+/// Like [`resolve_link`], but only trusting a `" (deleted)"` suffix if `is_deleted` confirms it.
+pub(crate) fn resolve_link_checked(
+    link: &Path,
+    symlinks: Symlinks,
+    is_deleted: impl FnOnce() -> bool,
+) -> io::Result<ResolvedPath> {
+    let target = link.read_link()?;
+    let (literal, state) = match split_deleted(&target) {
+        (literal, LinkState::Deleted) if is_deleted() => (literal, LinkState::Deleted),
+        _ => (target.as_path(), LinkState::Present),
+    };
+    let canonical = match (symlinks, state) {
+        (Symlinks::Literal, _) | (_, LinkState::Deleted) => None,
+        (Symlinks::Canonical, _) | (Symlinks::Both, _) => Some(fs::canonicalize(literal)?),
+    };
+    let literal = match symlinks {
+        Symlinks::Canonical => None,
+        Symlinks::Literal | Symlinks::Both => Some(literal.to_path_buf()),
+    };
+    Ok(ResolvedPath {
+        literal,
+        canonical,
+        state,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    
+    use super::LinkState;
+    use super::split_deleted;
+    
+    #[test]
+    fn split_deleted_suffix() {
+        assert_eq!(split_deleted(Path::new("/tmp/a (deleted)")), (Path::new("/tmp/a"), LinkState::Deleted));
+        assert_eq!(split_deleted(Path::new("/tmp/a")), (Path::new("/tmp/a"), LinkState::Present));
+    }
+}
@@ -0,0 +1,43 @@
+//! The `unsafe` constructors from raw file descriptors,
+//! kept here so that every `unsafe` entry point into this crate is in one clearly named place.
+//!
+//! Prefer the safe constructors where possible, which take ownership of (or borrow) an fd instead:
+//! [`Fanotify::try_from_owned`] and [`DirFd::try_from_fd`] (or [`DirFd::directory`]).
+
+use std::os::unix::io::RawFd;
+
+use crate::fanotify::Fanotify;
+use crate::fanotify::verify::InitVerifyError;
+use crate::init::RawInit;
+use crate::mark::DirFd;
+
+/// Create a [`Fanotify`] from a [`RawFd`] and the corresponding [`RawInit`] flags used to create it,
+/// like [`FromRawFd`](std::os::unix::io::FromRawFd), which [`Fanotify`] can't implement because of the [`RawInit`].
+///
+/// # Safety
+/// See [`FromRawFd`](std::os::unix::io::FromRawFd).
+/// `init` must also match the group, or its events will be mis-parsed;
+/// see [`fanotify_from_raw_fd_verified`].
+pub unsafe fn fanotify_from_raw_fd(fd: RawFd, init: RawInit) -> Fanotify {
+    Fanotify::from_raw_fd(fd, init)
+}
+
+/// Like [`fanotify_from_raw_fd`], but first [verify](Fanotify::verify_init) that `init` matches the group,
+/// instead of mis-parsing its events later.
+///
+/// If this fails, the fd isn't taken ownership of, so it's still the caller's to close.
+///
+/// # Safety
+/// See [`FromRawFd`](std::os::unix::io::FromRawFd).
+pub unsafe fn fanotify_from_raw_fd_verified(fd: RawFd, init: RawInit) -> Result<Fanotify, InitVerifyError> {
+    Fanotify::from_raw_fd_verified(fd, init)
+}
+
+/// Create a [`DirFd`] from a [`RawFd`] in a `const` context.
+///
+/// # Safety
+/// See [`FromRawFd`](std::os::unix::io::FromRawFd).
+/// `fd` must also stay open for `'a`.
+pub const unsafe fn dir_fd<'a>(fd: RawFd) -> DirFd<'a> {
+    DirFd::const_from_raw_fd(fd)
+}
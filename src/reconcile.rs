@@ -0,0 +1,294 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::FileType;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::event::error::EventError;
+use crate::event::error::EventResult;
+use crate::event::event::Event;
+use crate::mark::Mask;
+use crate::proc::ResolvedPath;
+use crate::proc::Symlinks;
+use crate::restricted;
+
+/// The metadata of a file in a [`Snapshot`], enough to tell if it changed.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Metadata {
+    pub file_type: FileType,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    pub device: u64,
+    pub inode: u64,
+}
+
+impl From<&fs::Metadata> for Metadata {
+    fn from(this: &fs::Metadata) -> Self {
+        Self {
+            file_type: this.file_type(),
+            len: this.len(),
+            modified: this.modified().ok(),
+            device: this.dev(),
+            inode: this.ino(),
+        }
+    }
+}
+
+/// A change to a [`Snapshot`].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Change {
+    Created {
+        path: PathBuf,
+        metadata: Metadata,
+    },
+    Modified {
+        path: PathBuf,
+        old: Metadata,
+        new: Metadata,
+    },
+    Removed {
+        path: PathBuf,
+        metadata: Metadata,
+    },
+}
+
+impl Change {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Created { path, .. } => path,
+            Self::Modified { path, .. } => path,
+            Self::Removed { path, .. } => path,
+        }
+    }
+}
+
+/// An in-memory snapshot of a directory tree, mapping each path in it to its [`Metadata`].
+///
+/// Symlinks are not followed.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Snapshot {
+    root: PathBuf,
+    entries: BTreeMap<PathBuf, Metadata>,
+}
+
+impl Snapshot {
+    /// Recursively scan the directory tree at `root`.
+    ///
+    /// Files that are removed during the scan are skipped.
+    /// This is disabled in [restricted mode](crate::restricted).
+    pub fn scan(root: impl Into<PathBuf>) -> io::Result<Self> {
+        restricted::check("scanning a directory tree")?;
+        let mut this = Self {
+            root: root.into(),
+            entries: BTreeMap::new(),
+        };
+        let mut dirs = vec![this.root.clone()];
+        while let Some(dir) = dirs.pop() {
+            let entries = match fs::read_dir(&dir) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound && dir != this.root => continue,
+                result => result?,
+            };
+            for entry in entries {
+                let entry = entry?;
+                let metadata = match entry.metadata() {
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    result => result?,
+                };
+                let path = entry.path();
+                if metadata.is_dir() {
+                    dirs.push(path.clone());
+                }
+                this.entries.insert(path, (&metadata).into());
+            }
+        }
+        Ok(this)
+    }
+    
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&Metadata> {
+        self.entries.get(path.as_ref())
+    }
+    
+    /// Iterate over all of the paths and their [`Metadata`] in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &Metadata)> {
+        self.entries.iter().map(|(path, metadata)| (path.as_path(), metadata))
+    }
+    
+    /// The [`Change`]s from this [`Snapshot`] to a newer one, in path order.
+    pub fn diff(&self, new: &Self) -> Vec<Change> {
+        let mut changes = Vec::new();
+        for (path, &old) in &self.entries {
+            match new.entries.get(path) {
+                None => changes.push(Change::Removed { path: path.clone(), metadata: old }),
+                Some(&new) if new != old => changes.push(Change::Modified { path: path.clone(), old, new }),
+                Some(_) => {}
+            }
+        }
+        for (path, &metadata) in &new.entries {
+            if !self.entries.contains_key(path) {
+                changes.push(Change::Created { path: path.clone(), metadata });
+            }
+        }
+        changes.sort_by(|a, b| a.path().cmp(b.path()));
+        changes
+    }
+    
+    /// Re-stat a single path, returning the [`Change`] to it, if any.
+    fn refresh(&mut self, path: &Path) -> io::Result<Option<Change>> {
+        let new = match fs::symlink_metadata(path) {
+            Ok(metadata) => Some(Metadata::from(&metadata)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        let path = path.to_path_buf();
+        let change = match (self.entries.get(&path).copied(), new) {
+            (None, None) => None,
+            (None, Some(metadata)) => Some(Change::Created { path: path.clone(), metadata }),
+            (Some(metadata), None) => Some(Change::Removed { path: path.clone(), metadata }),
+            (Some(old), Some(new)) if old != new => Some(Change::Modified { path: path.clone(), old, new }),
+            (Some(_), Some(_)) => None,
+        };
+        match new {
+            Some(metadata) => self.entries.insert(path, metadata),
+            None => self.entries.remove(&path),
+        };
+        Ok(change)
+    }
+}
+
+/// Maintains a [`Snapshot`] of a directory tree by applying fanotify [`Event`]s to it,
+/// calling a callback for every [`Change`].
+///
+/// [`Event`]s with a resolvable path in the tree (e.g. [`MODIFY`](Mask::MODIFY) or [`CLOSE_WRITE`](Mask::CLOSE_WRITE))
+/// just re-stat that path.
+/// Directory entry [`Event`]s (create, delete, and move) and [`Event`]s without a path
+/// (i.e. [`FID`](crate::event::file::File::FID) events, whose file handles can't be resolved yet)
+/// trigger a full rescan instead, as do queue overflows, since events were lost.
+pub struct Reconciler<'h> {
+    snapshot: Snapshot,
+    on_change: Box<dyn FnMut(&Change) + 'h>,
+    rescans: u64,
+}
+
+impl<'h> Reconciler<'h> {
+    /// The [`Event`]s that change directory entries, which can't be applied to a single path.
+    #[allow(clippy::identity_op)]
+    pub const ENTRY_EVENTS: Mask = Mask::from_bits_truncate(0
+        | Mask::CREATE.bits()
+        | Mask::DELETE.bits()
+        | Mask::DELETE_SELF.bits()
+        | Mask::MOVED_FROM.bits()
+        | Mask::MOVED_TO.bits()
+        | Mask::MOVE_SELF.bits()
+    );
+    
+    /// Do the initial [`Snapshot::scan`] of `root`.
+    /// `root` should be absolute and canonical, since event paths are.
+    ///
+    /// `on_change` is only called for [`Change`]s after the initial scan.
+    pub fn new(root: impl Into<PathBuf>, on_change: impl FnMut(&Change) + 'h) -> io::Result<Self> {
+        Ok(Self {
+            snapshot: Snapshot::scan(root)?,
+            on_change: Box::new(on_change),
+            rescans: 0,
+        })
+    }
+    
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+    
+    pub fn into_snapshot(self) -> Snapshot {
+        self.snapshot
+    }
+    
+    /// The number of full rescans done (not counting the initial scan).
+    pub fn rescans(&self) -> u64 {
+        self.rescans
+    }
+    
+    /// Rescan the whole tree and report the [`Change`]s since the last [`Snapshot`].
+    pub fn rescan(&mut self) -> io::Result<()> {
+        let new = Snapshot::scan(self.snapshot.root.clone())?;
+        self.rescans += 1;
+        for change in self.snapshot.diff(&new) {
+            (self.on_change)(&change);
+        }
+        self.snapshot = new;
+        Ok(())
+    }
+    
+    /// Apply an [`Event`] to the [`Snapshot`].
+    pub fn apply(&mut self, event: &Event<'_>) -> io::Result<()> {
+        if self.refresh(event)? {
+            self.rescan()?;
+        }
+        Ok(())
+    }
+    
+    /// Apply an [`EventError`], rescanning if the queue overflowed.
+    pub fn apply_error(&mut self, error: &EventError) -> io::Result<()> {
+        if Self::overflowed(error) {
+            self.rescan()?;
+        }
+        Ok(())
+    }
+    
+    /// Apply all of the [`EventResult`]s, e.g. from one [`Fanotify::read`](crate::fanotify::Fanotify::read).
+    ///
+    /// If any of them need a rescan, only one is done, after all the others are applied.
+    pub fn apply_all<'a>(&mut self, events: impl IntoIterator<Item = EventResult<'a>>) -> io::Result<()> {
+        let mut rescan = false;
+        for event in events {
+            rescan |= match event {
+                Ok(event) => self.refresh(&event)?,
+                Err(e) => Self::overflowed(&e),
+            };
+        }
+        if rescan {
+            self.rescan()?;
+        }
+        Ok(())
+    }
+    
+    fn overflowed(error: &EventError) -> bool {
+        matches!(error, EventError::QueueOverflowed | EventError::UnlimitedQueueButQueueStillOverflowed)
+    }
+    
+    /// Re-stat the path of an [`Event`] if it can be, returning if a rescan is needed instead.
+    fn refresh(&mut self, event: &Event<'_>) -> io::Result<bool> {
+        if event.mask().intersects(Self::ENTRY_EVENTS) {
+            return Ok(true);
+        }
+        // a deleted file's path still resolves (with a " (deleted)" suffix),
+        // which re-stating turns into a Change::Removed
+        let path = match event.file().resolve(Symlinks::Literal) {
+            None => return Ok(true),
+            // the file was already closed
+            Some(Err(_)) => return Ok(false),
+            Some(Ok(ResolvedPath { literal: Some(path), .. })) => path,
+            Some(Ok(_)) => return Ok(false),
+        };
+        if !path.starts_with(&self.snapshot.root) || path == self.snapshot.root {
+            return Ok(false);
+        }
+        if let Some(change) = self.snapshot.refresh(&path)? {
+            (self.on_change)(&change);
+        }
+        Ok(false)
+    }
+}
@@ -0,0 +1,95 @@
+//! A restricted mode for consumers that are sandboxed aggressively,
+//! e.g. with Landlock, seccomp, or in a mount namespace without most of the filesystem,
+//! where the process should only read and respond to events.
+//!
+//! Once [enabled](enable), this crate stops the path lookups it'd otherwise do implicitly:
+//! * `/proc` is treated as [unavailable](crate::proc::set_unavailable),
+//!   so resolving paths (like [`FD::path`](crate::fd::FD::path)) fails with a
+//!   [`ProcUnavailable`](crate::proc::ProcUnavailable) error,
+//!   and [`DirFd`](crate::mark::DirFd)s are displayed unresolved.
+//! * [`FileHandle::open`](crate::event::file::fid::FileHandle::open) (`open_by_handle_at`)
+//!   fails with [`EOPNOTSUPP`](Errno::EOPNOTSUPP).
+//! * Directory scans, like [`Snapshot::scan`](crate::reconcile::Snapshot::scan), fail with a [`Restricted`] error.
+//!
+//! Each of these fails up front, instead of with whatever confusing error the sandbox would cause
+//! (or by getting the process killed by a seccomp filter).
+//! Reading events, responding to permission events,
+//! and anything done through an event's own [`FD`](crate::fd::FD) (like [`FD::stat`](crate::fd::FD::stat))
+//! still work, since they only use already open file descriptors.
+//!
+//! This doesn't stop APIs that are explicitly given paths from using them, though,
+//! so they still open, stat, or read paths, which the sandbox has to allow or fail:
+//! e.g. [prechecking](crate::mark::Mark::precheck) and [checking](crate::mark::Markable::check) marks,
+//! which stat the marked paths (and the latter [probes](crate::supported()) support in a temporary directory),
+//! [`DecisionCache::prewarm`](crate::event::file::decision_cache::DecisionCache::prewarm),
+//! [`OriginClassifier::new`](crate::event::origin::OriginClassifier::new),
+//! and hashing executables for an allowlist.
+//! Do these before sandboxing the process.
+
+use std::io;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+use nix::errno::Errno;
+use thiserror::Error;
+
+static RESTRICTED: AtomicBool = AtomicBool::new(false);
+
+/// An error for when an operation is disabled because restricted mode is [enabled](enable).
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[error("{} is disabled in restricted mode", .operation)]
+pub struct Restricted {
+    pub operation: &'static str,
+}
+
+/// Like a [`ProcUnavailable`](crate::proc::ProcUnavailable),
+/// a [`Restricted`] is wrapped in an [`io::ErrorKind::Unsupported`] [`io::Error`].
+/// It can be checked with [`is_restricted`].
+impl From<Restricted> for io::Error {
+    fn from(e: Restricted) -> Self {
+        Self::new(io::ErrorKind::Unsupported, e)
+    }
+}
+
+/// APIs that return an [`Errno`] fail with [`EOPNOTSUPP`](Errno::EOPNOTSUPP),
+/// which a sandbox wouldn't cause (it'd be [`EACCES`](Errno::EACCES) or [`EPERM`](Errno::EPERM)).
+impl From<Restricted> for Errno {
+    fn from(_: Restricted) -> Self {
+        Self::EOPNOTSUPP
+    }
+}
+
+/// Check if an [`io::Error`] was caused by [`Restricted`].
+pub fn is_restricted(e: &io::Error) -> bool {
+    e.get_ref()
+        .is_some_and(|e| e.is::<Restricted>())
+}
+
+/// Enable restricted mode for the whole process.
+///
+/// Do this before sandboxing the process.
+pub fn enable() {
+    RESTRICTED.store(true, Ordering::SeqCst);
+}
+
+/// Disable restricted mode.
+///
+/// This doesn't undo the sandbox, of course,
+/// so it's only useful if the process wasn't actually sandboxed, like in tests.
+pub fn disable() {
+    RESTRICTED.store(false, Ordering::SeqCst);
+}
+
+/// Check if restricted mode is [enabled](enable).
+pub fn is_enabled() -> bool {
+    RESTRICTED.load(Ordering::SeqCst)
+}
+
+/// Fail with [`Restricted`] if restricted mode is [enabled](enable).
+pub(crate) fn check(operation: &'static str) -> Result<(), Restricted> {
+    if is_enabled() {
+        Err(Restricted { operation })
+    } else {
+        Ok(())
+    }
+}
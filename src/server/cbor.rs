@@ -0,0 +1,73 @@
+//! The [`Cbor`](super::Encoding::Cbor) encoding of an [`EventRecord`], using [`ciborium`].
+//!
+//! Each [`EventRecord`] is a CBOR map with the text keys
+//! `mask`, `device`, `inode`, `pid`, `flags`, and `path` (a byte string).
+//! Unknown keys are ignored when decoding, so fields can be added without breaking older consumers.
+
+use std::convert::TryFrom;
+use std::io;
+
+use ciborium::value::Integer;
+use ciborium::value::Value;
+
+use crate::export::EventRecord;
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Encode an [`EventRecord`] as CBOR.
+pub fn encode(record: &EventRecord) -> Vec<u8> {
+    let path = &record.path[..record.path_len as usize];
+    let map = Value::Map(vec![
+        (Value::from("mask"), Value::from(record.mask)),
+        (Value::from("device"), Value::from(record.device)),
+        (Value::from("inode"), Value::from(record.inode)),
+        (Value::from("pid"), Value::from(record.pid)),
+        (Value::from("flags"), Value::from(record.flags)),
+        (Value::from("path"), Value::from(path)),
+    ]);
+    let mut bytes = Vec::new();
+    ciborium::ser::into_writer(&map, &mut bytes).expect("writing to a Vec can't fail");
+    bytes
+}
+
+fn integer<T: TryFrom<Integer>>(key: &str, value: &Value) -> io::Result<T> {
+    value
+        .as_integer()
+        .and_then(|it| T::try_from(it).ok())
+        .ok_or_else(|| invalid(format!("invalid {:?}", key)))
+}
+
+/// Decode an [`EventRecord`] from CBOR.
+pub fn decode(bytes: &[u8]) -> io::Result<EventRecord> {
+    let value = ciborium::de::from_reader::<Value, _>(bytes).map_err(|e| invalid(e.to_string()))?;
+    let map = value.as_map().ok_or_else(|| invalid("expected a map"))?;
+    let mut record = EventRecord::empty();
+    let mut has_mask = false;
+    for (key, value) in map {
+        match key.as_text() {
+            Some("mask") => {
+                record.mask = integer("mask", value)?;
+                has_mask = true;
+            }
+            Some("device") => record.device = integer("device", value)?,
+            Some("inode") => record.inode = integer("inode", value)?,
+            Some("pid") => record.pid = integer("pid", value)?,
+            Some("flags") => record.flags = integer("flags", value)?,
+            Some("path") => {
+                let path = value.as_bytes().ok_or_else(|| invalid("invalid \"path\""))?;
+                if path.len() > EventRecord::PATH_CAPACITY {
+                    return Err(invalid("\"path\" is too long"));
+                }
+                record.path[..path.len()].copy_from_slice(path);
+                record.path_len = path.len() as u16;
+            }
+            _ => {}
+        }
+    }
+    if !has_mask {
+        return Err(invalid("missing \"mask\""));
+    }
+    Ok(record)
+}
@@ -0,0 +1,73 @@
+use std::io;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+use crate::export::EventRecord;
+
+use super::Encoding;
+use super::cbor;
+use super::json;
+use super::read_frame;
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// A consumer of an [`EventServer`](super::EventServer)'s [`EventRecord`]s.
+#[derive(Debug)]
+pub struct EventClient {
+    stream: UnixStream,
+    encoding: Encoding,
+}
+
+impl EventClient {
+    /// Connect to the [`EventServer`](super::EventServer) at `path` and check its handshake.
+    ///
+    /// This blocks until the server [`accept`](super::EventServer::accept)s the connection.
+    pub fn connect(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut stream = UnixStream::connect(path)?;
+        let handshake = read_frame(&mut stream)?.ok_or_else(|| invalid("connection closed before the handshake"))?;
+        let encoding = Encoding::ALL
+            .iter()
+            .copied()
+            .find(|it| it.handshake().as_bytes() == handshake.as_slice())
+            .ok_or_else(|| invalid(format!(
+                "unsupported handshake {}",
+                String::from_utf8_lossy(&handshake),
+            )))?;
+        Ok(Self { stream, encoding })
+    }
+    
+    pub fn stream(&self) -> &UnixStream {
+        &self.stream
+    }
+    
+    /// The [`Encoding`] the server sends.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+    
+    /// Receive the next [`EventRecord`], blocking until there is one,
+    /// or return [`None`] if the server closed the connection.
+    pub fn recv(&mut self) -> io::Result<Option<EventRecord>> {
+        let payload = match read_frame(&mut self.stream)? {
+            None => return Ok(None),
+            Some(payload) => payload,
+        };
+        let record = match self.encoding {
+            Encoding::Json => std::str::from_utf8(&payload)
+                .map_err(|e| invalid(e.to_string()))
+                .and_then(|payload| json::decode(payload).map_err(|e| invalid(e.to_string())))?,
+            Encoding::Cbor => cbor::decode(&payload)?,
+        };
+        Ok(Some(record))
+    }
+}
+
+impl Iterator for EventClient {
+    type Item = io::Result<EventRecord>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv().transpose()
+    }
+}
@@ -0,0 +1,240 @@
+//! Streaming [`Event`]s over a unix domain socket,
+//! so that non-Rust consumers can subscribe to a privileged fanotify daemon built with this crate.
+//!
+//! Requires the `server` feature.
+//!
+//! The daemon [`EventServer::bind`]s a socket, and each consumer connects to it
+//! (with an [`EventClient`] or anything else that can read a unix socket).
+//! The protocol is a stream of frames, each a big-endian `u32` length followed by that many bytes.
+//! The first frame is a JSON handshake, `{"protocol":"fanotify","version":2,"encoding":"json"}`,
+//! and every following frame is an [`EventRecord`] in the announced [`Encoding`].
+//!
+//! Version 2 replaced the `compact` encoding with the standard [`cbor`] one.
+//! Consumers never send anything.
+//!
+//! An [`EventServer`] never blocks on a consumer; each one has a bounded queue of frames
+//! that haven't been written yet, and a consumer whose queue is full, or that disconnects, is dropped.
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+use crate::event::event::Event;
+use crate::export::EventRecord;
+
+pub use client::EventClient;
+pub use crate::export::json;
+
+pub mod client;
+pub mod cbor;
+
+/// The version of the protocol, sent in the handshake.
+pub const VERSION: u32 = 2;
+
+/// The maximum length of a frame, which an [`EventClient`] enforces.
+pub const MAX_FRAME_LEN: u32 = 64 * 1024;
+
+/// The encoding of the [`EventRecord`] in each frame.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Encoding {
+    /// The [`json`] encoding, for consumers in any language.
+    Json,
+    /// The [`cbor`] binary encoding, for consumers where the overhead of JSON matters.
+    Cbor,
+}
+
+impl Encoding {
+    pub const ALL: [Self; 2] = [Self::Json, Self::Cbor];
+    
+    /// The name of the [`Encoding`] in the handshake.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Cbor => "cbor",
+        }
+    }
+    
+    fn handshake(self) -> String {
+        format!(r#"{{"protocol":"fanotify","version":{},"encoding":"{}"}}"#, VERSION, self.name())
+    }
+    
+    fn encode(self, record: &EventRecord) -> Vec<u8> {
+        match self {
+            Self::Json => json::encode(record).into_bytes(),
+            Self::Cbor => cbor::encode(record),
+        }
+    }
+}
+
+fn frame(payload: &[u8]) -> Vec<u8> {
+    let len = payload.len() as u32;
+    let mut frame = Vec::with_capacity(4 + payload.len());
+    frame.extend_from_slice(&len.to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn read_frame(stream: &mut UnixStream) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match stream.read_exact(&mut len) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    let len = u32::from_be_bytes(len);
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("frame of {} bytes is too long", len)));
+    }
+    let mut payload = vec![0; len as usize];
+    stream.read_exact(&mut payload)?;
+    Ok(Some(payload))
+}
+
+/// A connected consumer and the frames that haven't been written to it yet.
+#[derive(Debug)]
+struct Client {
+    stream: UnixStream,
+    encoding: Encoding,
+    queue: VecDeque<Vec<u8>>,
+    /// How much of the first frame in the queue has been written.
+    written: usize,
+}
+
+impl Client {
+    /// Write as much of the queue as possible without blocking,
+    /// returning `Err` if the consumer should be dropped.
+    fn flush(&mut self) -> io::Result<()> {
+        while let Some(frame) = self.queue.front() {
+            match self.stream.write(&frame[self.written..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.written += n;
+                    if self.written == frame.len() {
+                        self.queue.pop_front();
+                        self.written = 0;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The daemon side of the [protocol](self), broadcasting [`Event`]s to every connected consumer.
+///
+/// It doesn't spawn any threads; call [`EventServer::accept`] periodically
+/// (e.g. before each [`EventServer::broadcast`] or when the [`EventServer::listener`] is readable)
+/// to add newly connected consumers.
+///
+/// Frames a consumer isn't ready for are queued and written by later
+/// [`EventServer::broadcast`]s or [`EventServer::flush`]es.
+#[derive(Debug)]
+pub struct EventServer {
+    listener: UnixListener,
+    queue_limit: usize,
+    encoding: Encoding,
+    clients: Vec<Client>,
+}
+
+impl EventServer {
+    /// The default number of frames queued for a consumer before it's dropped.
+    pub const QUEUE_LIMIT: usize = 1024;
+    
+    /// Bind a new socket at `path`, which mustn't exist yet.
+    ///
+    /// Restrict who can subscribe with the permissions of `path` or its directory,
+    /// since every consumer sees every [`Event`] (including paths) that the daemon can.
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<Self> {
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            queue_limit: Self::QUEUE_LIMIT,
+            encoding: Encoding::Json,
+            clients: Vec::new(),
+        })
+    }
+    
+    pub fn listener(&self) -> &UnixListener {
+        &self.listener
+    }
+    
+    /// Set the number of frames that may be queued for a consumer before it's dropped
+    /// ([`EventServer::QUEUE_LIMIT`] by default).
+    pub fn set_queue_limit(&mut self, limit: usize) {
+        self.queue_limit = limit;
+    }
+    
+    /// Set the [`Encoding`] to send ([`Json`](Encoding::Json) by default),
+    /// which applies to consumers accepted after this.
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.encoding = encoding;
+    }
+    
+    /// The number of connected consumers.
+    pub fn clients(&self) -> usize {
+        self.clients.len()
+    }
+    
+    /// Accept all pending consumers, sending them the handshake,
+    /// and return how many were accepted.
+    pub fn accept(&mut self) -> io::Result<usize> {
+        let mut accepted = 0;
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(accepted),
+                Err(e) => return Err(e),
+            };
+            stream.set_nonblocking(true)?;
+            let mut client = Client {
+                stream,
+                encoding: self.encoding,
+                queue: VecDeque::from([frame(self.encoding.handshake().as_bytes())]),
+                written: 0,
+            };
+            if client.flush().is_ok() {
+                self.clients.push(client);
+                accepted += 1;
+            }
+        }
+    }
+    
+    /// Send an [`EventRecord`] to every consumer, without blocking,
+    /// and return how many it was sent or queued to.
+    ///
+    /// Consumers that fail or whose queues are full are dropped.
+    pub fn broadcast(&mut self, record: &EventRecord) -> usize {
+        // each encoding is only encoded if some consumer wants it
+        let mut frames = [None, None];
+        let queue_limit = self.queue_limit;
+        self.clients.retain_mut(|client| {
+            if client.queue.len() >= queue_limit {
+                return false;
+            }
+            let encoding = client.encoding;
+            let frame = frames[encoding as usize].get_or_insert_with(|| frame(&encoding.encode(record)));
+            client.queue.push_back(frame.clone());
+            client.flush().is_ok()
+        });
+        self.clients.len()
+    }
+    
+    /// Write as much of every consumer's queue as possible without blocking,
+    /// dropping the ones that fail, and return how many frames are still queued.
+    pub fn flush(&mut self) -> usize {
+        self.clients.retain_mut(|client| client.flush().is_ok());
+        self.clients.iter().map(|it| it.queue.len()).sum()
+    }
+    
+    /// [Record](EventRecord::new) and [broadcast](Self::broadcast) an [`Event`].
+    pub fn export(&mut self, event: &Event<'_>, with_path: bool) -> usize {
+        self.broadcast(&EventRecord::new(event, with_path))
+    }
+}
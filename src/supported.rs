@@ -0,0 +1,220 @@
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::BuildHasher;
+use std::hash::Hasher;
+use std::io;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::fanotify::Fanotify;
+use crate::init;
+use crate::init::Init;
+use crate::init::NotificationClass;
+use crate::mark;
+use crate::mark::Mark;
+use crate::mark::Markable;
+use crate::mark::Mask;
+use crate::mark::OneAction::Add;
+use crate::mark::What;
+
+/// A rough level of fanotify support, like what's used to decide which tests to run.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Level {
+    /// fanotify can't be used at all.
+    None,
+    /// Basic fanotify works, but not everything, like on WSL 2 or before Linux 5.1.
+    Partial,
+    /// [`REPORT_FID`](init::Flags::REPORT_FID) groups and [`FileSystem`](What::FileSystem) marks work, too.
+    Full,
+}
+
+/// A report of what fanotify features are supported, as returned by [`supported`].
+#[derive(Debug, Eq, PartialEq)]
+pub struct Supported {
+    /// Why fanotify can't be used at all, e.g. if it's unsupported
+    /// or if this process doesn't have the `CAP_SYS_ADMIN` capability.
+    /// Nothing else could be probed in this case, so everything else is empty.
+    pub error: Option<init::Error>,
+    pub notification_classes: Vec<NotificationClass>,
+    /// The supported [`init::Flags`].
+    pub flags: init::Flags,
+    pub whats: Vec<What>,
+    /// The supported [`Mask`] flags for inode marks.
+    pub mask: Mask,
+    /// If permission responses can include extra info records.
+    /// See [`Fanotify::supports_response_info`].
+    pub response_info: bool,
+}
+
+impl Supported {
+    pub fn supports_class(&self, notification_class: NotificationClass) -> bool {
+        self.notification_classes.contains(&notification_class)
+    }
+    
+    pub fn supports_flags(&self, flags: init::Flags) -> bool {
+        self.flags.contains(flags)
+    }
+    
+    pub fn supports_what(&self, what: What) -> bool {
+        self.whats.contains(&what)
+    }
+    
+    pub fn supports_mask(&self, mask: Mask) -> bool {
+        self.mask.contains(mask)
+    }
+    
+    pub fn level(&self) -> Level {
+        if self.error.is_some() {
+            Level::None
+        } else if self.supports_flags(init::Flags::REPORT_FID) && self.supports_what(What::FileSystem) {
+            Level::Full
+        } else {
+            Level::Partial
+        }
+    }
+}
+
+/// A temporary directory to probe marks on,
+/// so that probing doesn't affect anything else on the system.
+struct ProbeDir {
+    path: PathBuf,
+}
+
+impl ProbeDir {
+    /// How many random names to try before giving up.
+    const ATTEMPTS: usize = 16;
+    
+    /// Create a new private directory with a random name,
+    /// so that nothing else can predict it and create it (or a symlink) first.
+    fn new() -> Option<Self> {
+        for _ in 0..Self::ATTEMPTS {
+            // RandomState is randomly seeded, so this is unpredictable
+            let random = RandomState::new().build_hasher().finish();
+            let path = std::env::temp_dir().join(format!(".fanotify-probe-{:016x}", random));
+            match fs::DirBuilder::new().mode(0o700).create(&path) {
+                Ok(()) => return Some(Self { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(_) => return None,
+            }
+        }
+        None
+    }
+}
+
+impl Drop for ProbeDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir(&self.path);
+    }
+}
+
+fn probe_init(notification_class: NotificationClass, flags: init::Flags) -> Option<Fanotify> {
+    Init {
+        notification_class,
+        flags,
+        ..Init::const_default()
+    }.to_fanotify().ok()
+}
+
+fn probe_mark(fanotify: &Fanotify, what: What, mask: Mask, path: &Path) -> bool {
+    Mark::one(mark::One {
+        action: Add,
+        what,
+        flags: mark::Flags::empty(),
+        mask,
+        path: mark::Path::absolute(path),
+    }).is_ok_and(|mark| fanotify.mark(mark).is_ok())
+}
+
+/// Probe what fanotify features are supported by actually trying them.
+///
+/// This can be used by applications to adapt their behavior
+/// on old kernels or partial implementations like WSL 2.
+///
+/// It's safe to call since it only creates and marks its own temporary directory.
+/// Note that since `fanotify_init` requires the `CAP_SYS_ADMIN` capability,
+/// without it nothing will be reported as supported.
+pub fn supported() -> Supported {
+    use NotificationClass::*;
+    
+    let mut this = Supported {
+        error: None,
+        notification_classes: Vec::new(),
+        flags: init::Flags::empty(),
+        whats: Vec::new(),
+        mask: Mask::empty(),
+        response_info: false,
+    };
+    if let Err(e) = Init::const_default().to_fanotify() {
+        this.error = Some(e);
+        return this;
+    }
+    
+    this.notification_classes = [Notify, Content, PreContent]
+        .iter()
+        .copied()
+        .filter(|&class| probe_init(class, init::Flags::empty()).is_some())
+        .collect();
+    
+    for flags in (0..u32::BITS).filter_map(|i| init::Flags::from_bits(1 << i)) {
+        // REPORT_NAME only works with REPORT_DIR_FID
+        let required = if flags == init::Flags::REPORT_NAME {
+            init::Flags::REPORT_DIR_FID
+        } else {
+            init::Flags::empty()
+        };
+        if probe_init(Notify, flags | required).is_some() {
+            this.flags |= flags;
+        }
+    }
+    
+    let dir = match ProbeDir::new() {
+        Some(dir) => dir,
+        None => return this,
+    };
+    
+    if let Some(fanotify) = probe_init(Notify, init::Flags::empty()) {
+        this.whats = [What::Inode, What::MountPoint, What::FileSystem]
+            .iter()
+            .copied()
+            .filter(|&what| probe_mark(&fanotify, what, Mask::OPEN, &dir.path))
+            .collect();
+    }
+    
+    let notify = probe_init(Notify, init::Flags::empty());
+    let notify_fid = probe_init(Notify, init::Flags::REPORT_FID);
+    let content = probe_init(Content, init::Flags::empty());
+    this.response_info = content.as_ref().is_some_and(|it| it.supports_response_info());
+    // only these events can be reported with an fd, the rest need REPORT_FID
+    let fd_events = Mask::reportable_with_fd();
+    let modifiers = Mask::ON_DIR | Mask::EVENT_ON_CHILD;
+    for flag in Mask::all().flags() {
+        // permission events need a content class,
+        // and modifiers need an actual event
+        let fanotify = if flag.intersects(Mask::all_permissions()) {
+            content.as_ref()
+        } else if flag.intersects(fd_events | modifiers) {
+            notify.as_ref()
+        } else {
+            notify_fid.as_ref()
+        };
+        let mask = if flag.intersects(modifiers) {
+            flag | Mask::OPEN
+        } else {
+            flag
+        };
+        let supported = fanotify.is_some_and(|it| probe_mark(it, What::Inode, mask, &dir.path));
+        if supported {
+            this.mask |= flag;
+        }
+    }
+    
+    this
+}
+
+/// Like [`supported`], but only probed once and then cached for the rest of the process.
+pub fn cached() -> &'static Supported {
+    static SUPPORTED: OnceLock<Supported> = OnceLock::new();
+    SUPPORTED.get_or_init(supported)
+}
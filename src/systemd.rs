@@ -0,0 +1,325 @@
+//! Integration with systemd, for fanotify daemons run as `Type=notify` services.
+//!
+//! Requires the `systemd` feature.
+//!
+//! A [`Notifier`] sends readiness, status, and watchdog notifications (like `sd_notify(3)`),
+//! and a [`JournalSink`] logs [`Event`]s to the journal as structured entries.
+//! Both speak systemd's datagram protocols directly, so they don't need `libsystemd`.
+//! A [`Notifier`] does nothing when not run under systemd.
+//!
+//! A typical service initializes its [`Fanotify`](crate::fanotify::Fanotify) group,
+//! adds its marks, calls [`Notifier::ready`], and then loops on
+//! [`Pipeline::run_once_notified`](crate::fanotify::pipeline::Pipeline::run_once_notified),
+//! which keeps the watchdog fed even when no [`Event`]s arrive.
+//!
+//! A group can also be created by a privileged process and handed to the service
+//! through socket activation or the fd store: see [`listen_fds`] and [`Notifier::store_fd`].
+
+use std::env;
+use std::ffi::OsString;
+use std::io;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::SocketAddr;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::OwnedFd;
+use std::os::unix::io::RawFd;
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::event::event::Event;
+use crate::event::file::File;
+use crate::event::sink::Sink;
+use crate::event::sink::SinkError;
+use crate::export::EventRecord;
+use crate::fanotify::fd_passing::send_fd;
+
+/// Sends notifications to the service manager over `$NOTIFY_SOCKET`, like `sd_notify(3)`.
+#[derive(Debug)]
+pub struct Notifier {
+    socket: Option<(UnixDatagram, SocketAddr)>,
+    watchdog: Option<Duration>,
+    last_keep_alive: Option<Instant>,
+}
+
+impl Notifier {
+    /// A [`Notifier`] that does nothing, for when not run under systemd.
+    pub fn inactive() -> Self {
+        Self {
+            socket: None,
+            watchdog: None,
+            last_keep_alive: None,
+        }
+    }
+    
+    /// Send notifications to the socket at `address`,
+    /// pinging the watchdog at half of `watchdog`, if given.
+    pub fn new(address: SocketAddr, watchdog: Option<Duration>) -> io::Result<Self> {
+        Ok(Self {
+            socket: Some((UnixDatagram::unbound()?, address)),
+            watchdog,
+            last_keep_alive: None,
+        })
+    }
+    
+    fn address(socket: &OsString) -> io::Result<SocketAddr> {
+        match socket.as_bytes() {
+            [b'@', name @ ..] => SocketAddr::from_abstract_name(name),
+            _ => SocketAddr::from_pathname(Path::new(socket)),
+        }
+    }
+    
+    /// Connect to `$NOTIFY_SOCKET`, and read the watchdog interval
+    /// from `$WATCHDOG_USEC` (if `$WATCHDOG_PID` is unset or this process).
+    ///
+    /// If `$NOTIFY_SOCKET` isn't set, the [`Notifier`] is [inactive](Self::inactive).
+    pub fn from_env() -> io::Result<Self> {
+        let socket = match env::var_os("NOTIFY_SOCKET") {
+            None => return Ok(Self::inactive()),
+            Some(socket) => socket,
+        };
+        let address = Self::address(&socket)?;
+        let for_this_process = match env::var("WATCHDOG_PID") {
+            Err(_) => true,
+            Ok(pid) => pid.parse() == Ok(std::process::id()),
+        };
+        let watchdog = env::var("WATCHDOG_USEC")
+            .ok()
+            .filter(|_| for_this_process)
+            .and_then(|usec| usec.parse().ok())
+            .filter(|&usec| usec != 0)
+            .map(Duration::from_micros);
+        Self::new(address, watchdog)
+    }
+    
+    /// If this [`Notifier`] actually sends notifications.
+    pub fn is_active(&self) -> bool {
+        self.socket.is_some()
+    }
+    
+    /// The watchdog interval, if the service has `WatchdogSec=` set.
+    pub fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog
+    }
+    
+    /// Send a raw notification, like `READY=1\nSTATUS=...`.
+    pub fn notify(&self, state: &str) -> io::Result<()> {
+        match &self.socket {
+            None => Ok(()),
+            Some((socket, address)) => socket.send_to_addr(state.as_bytes(), address).map(|_| ()),
+        }
+    }
+    
+    /// Tell the service manager startup is finished,
+    /// which should be after all the marks are added, so no [`Event`]s are missed.
+    pub fn ready(&self) -> io::Result<()> {
+        self.notify("READY=1")
+    }
+    
+    /// Tell the service manager the service is shutting down.
+    pub fn stopping(&self) -> io::Result<()> {
+        self.notify("STOPPING=1")
+    }
+    
+    /// Set the status shown by `systemctl status`.
+    pub fn status(&self, status: &str) -> io::Result<()> {
+        // a status can't span lines
+        self.notify(&format!("STATUS={}", status.replace('\n', " ")))
+    }
+    
+    /// Store `fd` in the service manager's fd store under `name` (`FDSTORE=1`),
+    /// so it's passed back through [`listen_fds`] when the service is restarted.
+    ///
+    /// This lets a restarted service keep its fanotify group and marks, and not miss [`Event`]s in between.
+    /// The service needs `FileDescriptorStoreMax=` set.
+    pub fn store_fd(&self, fd: &impl AsRawFd, name: &str) -> io::Result<()> {
+        let (_, address) = match &self.socket {
+            None => return Ok(()),
+            Some(socket) => socket,
+        };
+        // `sendmsg` needs a connected socket to omit the address
+        let socket = UnixDatagram::unbound()?;
+        socket.connect_addr(address)?;
+        let state = format!("FDSTORE=1\nFDNAME={}", name);
+        send_fd(socket.as_raw_fd(), state.as_bytes(), fd.as_raw_fd())
+    }
+    
+    /// Ping the watchdog.
+    pub fn watchdog(&mut self) -> io::Result<()> {
+        self.notify("WATCHDOG=1")?;
+        self.last_keep_alive = Some(Instant::now());
+        Ok(())
+    }
+    
+    /// How long until the watchdog should be pinged again (at half its interval),
+    /// or [`None`] if there's no watchdog.
+    pub fn next_keep_alive(&self) -> Option<Duration> {
+        let period = self.watchdog? / 2;
+        let elapsed = match self.last_keep_alive {
+            None => return Some(Duration::from_secs(0)),
+            Some(last) => last.elapsed(),
+        };
+        Some(period.checked_sub(elapsed).unwrap_or_default())
+    }
+    
+    /// [Ping the watchdog](Self::watchdog) if it's [due](Self::next_keep_alive).
+    pub fn keep_alive(&mut self) -> io::Result<()> {
+        match self.next_keep_alive() {
+            Some(next) if next == Duration::from_secs(0) => self.watchdog(),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The first fd passed by the service manager, like `SD_LISTEN_FDS_START`.
+pub const LISTEN_FDS_START: RawFd = 3;
+
+static LISTEN_FDS_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Take the fds passed by the service manager through socket activation or its fd store
+/// (`$LISTEN_FDS`, if `$LISTEN_PID` is this process), like `sd_listen_fds_with_names(3)`,
+/// along with their names from `$LISTEN_FDNAMES`, if given.
+///
+/// The fds are made close-on-exec.
+/// Since they're owned once taken, they can only be taken once, so later calls return no fds.
+///
+/// A fanotify group received this way can be used with
+/// [`Fanotify::from_received_fd`](crate::fanotify::Fanotify::from_received_fd),
+/// reading its [`RawInit`](crate::init::RawInit) with [`RawInit::of_fd`](crate::init::RawInit::of_fd)
+/// if it isn't otherwise known.
+pub fn listen_fds() -> io::Result<Vec<(Option<String>, OwnedFd)>> {
+    let for_this_process = match env::var("LISTEN_PID") {
+        Err(_) => false,
+        Ok(pid) => pid.parse() == Ok(std::process::id()),
+    };
+    if !for_this_process {
+        return Ok(Vec::new());
+    }
+    let n = env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|n| n.parse::<RawFd>().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid $LISTEN_FDS"))?;
+    if LISTEN_FDS_TAKEN.swap(true, Ordering::SeqCst) {
+        return Ok(Vec::new());
+    }
+    let names = env::var("LISTEN_FDNAMES").unwrap_or_default();
+    let mut names = names.split(':').map(String::from);
+    (LISTEN_FDS_START..LISTEN_FDS_START + n)
+        .map(|fd| {
+            // take ownership first, so it's closed on error
+            let owned = unsafe { OwnedFd::from_raw_fd(fd) };
+            if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok((names.next().filter(|name| !name.is_empty()), owned))
+        })
+        .collect()
+}
+
+/// A syslog priority, as used by the journal's `PRIORITY` field.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Priority {
+    Emergency = 0,
+    Alert = 1,
+    Critical = 2,
+    Error = 3,
+    Warning = 4,
+    Notice = 5,
+    Info = 6,
+    Debug = 7,
+}
+
+/// The path of the journal's native protocol socket.
+pub const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// A [`Sink`] that logs each [`Event`] as a structured journal entry.
+///
+/// Besides `MESSAGE` (the [`Event`]'s [`display`](Event::display)), `PRIORITY`, and `SYSLOG_IDENTIFIER`,
+/// each entry has `FANOTIFY_MASK`, `FANOTIFY_PID`, `FANOTIFY_FLAGS`,
+/// and `FANOTIFY_DEVICE`, `FANOTIFY_INODE`, and `FANOTIFY_PATH` when they're known,
+/// so they can be queried with e.g. `journalctl FANOTIFY_PATH=/etc/passwd`.
+pub struct JournalSink {
+    socket: UnixDatagram,
+    identifier: String,
+    priority: Box<dyn FnMut(&Event<'_>) -> Priority + Send>,
+    entry: Vec<u8>,
+}
+
+impl JournalSink {
+    /// Log to the journal as `identifier`, with permission events at [`Priority::Notice`]
+    /// and everything else at [`Priority::Info`].
+    pub fn new(identifier: impl Into<String>) -> io::Result<Self> {
+        Self::connect(identifier, JOURNAL_SOCKET)
+    }
+    
+    /// Like [`JournalSink::new`], but log to the native protocol socket at `socket`
+    /// instead of [`JOURNAL_SOCKET`].
+    pub fn connect(identifier: impl Into<String>, socket: impl AsRef<Path>) -> io::Result<Self> {
+        let datagram = UnixDatagram::unbound()?;
+        datagram.connect(socket)?;
+        Ok(Self {
+            socket: datagram,
+            identifier: identifier.into(),
+            priority: Box::new(|event| match event.file() {
+                File::Permission(_) => Priority::Notice,
+                _ => Priority::Info,
+            }),
+            entry: Vec::new(),
+        })
+    }
+    
+    /// Choose the [`Priority`] of each [`Event`]'s entry.
+    pub fn with_priority(mut self, priority: impl FnMut(&Event<'_>) -> Priority + Send + 'static) -> Self {
+        self.priority = Box::new(priority);
+        self
+    }
+    
+    /// Encode the journal entry for an [`Event`].
+    fn encode(&mut self, event: &Event<'_>) {
+        // the path is resolved separately, since a record truncates it
+        let record = EventRecord::new(event, false);
+        let priority = (self.priority)(event);
+        let entry = &mut self.entry;
+        entry.clear();
+        field(entry, "MESSAGE", event.display().to_string().as_bytes());
+        field(entry, "PRIORITY", (priority as u8).to_string().as_bytes());
+        field(entry, "SYSLOG_IDENTIFIER", self.identifier.as_bytes());
+        field(entry, "FANOTIFY_MASK", format!("{:?}", record.mask()).as_bytes());
+        field(entry, "FANOTIFY_PID", record.pid.to_string().as_bytes());
+        field(entry, "FANOTIFY_FLAGS", record.flags.to_string().as_bytes());
+        if let Some((device, inode)) = record.identity() {
+            field(entry, "FANOTIFY_DEVICE", device.to_string().as_bytes());
+            field(entry, "FANOTIFY_INODE", inode.to_string().as_bytes());
+        }
+        if let Some(Ok(path)) = event.file().path() {
+            field(entry, "FANOTIFY_PATH", path.as_os_str().as_bytes());
+        }
+    }
+}
+
+/// Append a field to a journal entry, in the native protocol's binary-safe format
+/// if `value` contains a newline.
+fn field(entry: &mut Vec<u8>, name: &str, value: &[u8]) {
+    entry.extend_from_slice(name.as_bytes());
+    if value.contains(&b'\n') {
+        entry.push(b'\n');
+        entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        entry.push(b'=');
+    }
+    entry.extend_from_slice(value);
+    entry.push(b'\n');
+}
+
+impl Sink for JournalSink {
+    fn consume(&mut self, event: &Event<'_>) -> Result<(), SinkError> {
+        self.encode(event);
+        self.socket.send(&self.entry)?;
+        Ok(())
+    }
+}
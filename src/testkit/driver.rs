@@ -0,0 +1,118 @@
+use std::io;
+
+use apply::Apply;
+#[cfg(feature = "async")]
+use async_io::Async;
+
+use crate::event::display::DisplayEvents;
+use crate::event::error::EventResult;
+use crate::event::event::Event;
+use crate::event::iterator_ext::IntoEvents;
+#[cfg(feature = "async")]
+use crate::fanotify::async_fd::AsyncFdWrapper;
+#[cfg(feature = "async")]
+use crate::fanotify::buffered_fanotify::AsyncBufferedFanotify;
+use crate::fanotify::buffered_fanotify::BufferedFanotify;
+#[cfg(feature = "async")]
+use crate::fanotify::Fanotify;
+
+/// Only keep [`Event`]s generated by this process, panicking on any event errors.
+fn from_self(event: EventResult) -> Option<Event> {
+    Some(event.expect("event error"))
+        .filter(|it| it.id().is_generated_by_self())
+}
+
+/// Check that there are exactly `n` events, panicking with all of them displayed if not.
+fn check_n<'a>(events: impl Iterator<Item=Event<'a>>, n: usize) -> Vec<Event<'a>> {
+    let events = events.collect::<Vec<_>>();
+    assert_eq!(events.len(), n,
+               "\nactual len {} != {}: {}",
+               events.len(), n, DisplayEvents(&events),
+    );
+    events
+}
+
+/// A test driver around a [`BufferedFanotify`].  See the [module docs](super).
+pub struct Driver {
+    pub fanotify: BufferedFanotify,
+}
+
+impl From<BufferedFanotify> for Driver {
+    fn from(this: BufferedFanotify) -> Self {
+        Self { fanotify: this }
+    }
+}
+
+impl Driver {
+    /// Read all the events generated by this process.
+    pub fn read(&mut self) -> io::Result<impl Iterator<Item=Event<'_>>> {
+        self
+            .fanotify
+            .read()?
+            .all()
+            .filter_map(from_self)
+            .apply(Ok)
+    }
+    
+    /// Read exactly `n` events generated by this process.
+    pub fn read_n(&mut self, n: usize) -> io::Result<Vec<Event<'_>>> {
+        Ok(check_n(self.read()?, n))
+    }
+    
+    /// Read exactly one event generated by this process.
+    pub fn read1(&mut self) -> io::Result<Event<'_>> {
+        let events = self.read_n(1)?;
+        Ok(events.into_iter().next().unwrap())
+    }
+    
+    #[cfg(feature = "async")]
+    pub fn into_async(self) -> io::Result<AsyncDriver> {
+        AsyncDriver {
+            fanotify: self.fanotify.into_async()?,
+        }.apply(Ok)
+    }
+}
+
+/// An async version of [`Driver`].
+#[cfg(feature = "async")]
+pub struct AsyncDriver<W: AsyncFdWrapper = Async<Fanotify>> {
+    pub fanotify: AsyncBufferedFanotify<W>,
+}
+
+#[cfg(feature = "async")]
+impl<W: AsyncFdWrapper> From<AsyncBufferedFanotify<W>> for AsyncDriver<W> {
+    fn from(this: AsyncBufferedFanotify<W>) -> Self {
+        Self { fanotify: this }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<W: AsyncFdWrapper> AsyncDriver<W> {
+    /// See [`Driver::read`].
+    pub async fn read(&mut self) -> io::Result<impl Iterator<Item=Event<'_>>> {
+        self
+            .fanotify
+            .read()
+            .await?
+            .all()
+            .filter_map(from_self)
+            .apply(Ok)
+    }
+    
+    /// See [`Driver::read_n`].
+    pub async fn read_n(&mut self, n: usize) -> io::Result<Vec<Event<'_>>> {
+        Ok(check_n(self.read().await?, n))
+    }
+    
+    /// See [`Driver::read1`].
+    pub async fn read1(&mut self) -> io::Result<Event<'_>> {
+        let events = self.read_n(1).await?;
+        Ok(events.into_iter().next().unwrap())
+    }
+    
+    pub fn into_sync(self) -> io::Result<Driver> {
+        Driver {
+            fanotify: self.fanotify.into_sync()?,
+        }.apply(Ok)
+    }
+}
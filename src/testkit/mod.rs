@@ -0,0 +1,19 @@
+//! Utilities for integration-testing code that uses fanotify.
+//!
+//! Requires the `testkit` feature.
+//!
+//! The [`Driver`]s read events like the buffered [`Fanotify`](crate::fanotify::Fanotify)s,
+//! except they only yield events generated by this process,
+//! so that tests aren't affected by whatever else is happening on the system,
+//! and they panic on event errors and unexpected numbers of events,
+//! so that tests can just assert on the events they expect.
+//!
+//! [`TempDir`] is a fixture for creating files to generate events on.
+
+#[cfg(feature = "async")]
+pub use driver::AsyncDriver;
+pub use driver::Driver;
+pub use temp_dir::TempDir;
+
+mod driver;
+mod temp_dir;
@@ -0,0 +1,72 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::mark;
+use crate::mark::Mark;
+use crate::mark::Markable;
+use crate::mark::Mask;
+use crate::mark::OneAction::Add;
+use crate::mark::What;
+
+/// A temporary directory fixture to generate events in, deleted on [`Drop`].
+pub struct TempDir {
+    dir: tempfile::TempDir,
+}
+
+impl TempDir {
+    /// Create a [`TempDir`] in the default temporary directory, usually `/tmp`.
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            dir: tempfile::tempdir()?,
+        })
+    }
+    
+    /// Create a [`TempDir`] inside of `parent`,
+    /// e.g. to put it on a specific mount point or filesystem.
+    pub fn new_in(parent: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            dir: tempfile::tempdir_in(parent)?,
+        })
+    }
+    
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+    
+    /// The path of `name` within this [`TempDir`].
+    pub fn join(&self, name: impl AsRef<Path>) -> PathBuf {
+        self.path().join(name)
+    }
+    
+    /// Create (or overwrite) the file `name` with the given `contents`,
+    /// returning its path.
+    pub fn file(&self, name: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<PathBuf> {
+        let path = self.join(name);
+        fs::write(&path, contents)?;
+        Ok(path)
+    }
+    
+    /// Create the directory `name`, returning its path.
+    pub fn dir(&self, name: impl AsRef<Path>) -> io::Result<PathBuf> {
+        let path = self.join(name);
+        fs::create_dir(&path)?;
+        Ok(path)
+    }
+    
+    /// Add a [`Mark`] for this [`TempDir`].
+    ///
+    /// Note that [`What::MountPoint`] and [`What::FileSystem`] marks
+    /// will include events outside of this directory.
+    pub fn mark(&self, fanotify: &(impl Markable + ?Sized), what: What, mask: Mask) -> Result<(), mark::RawError> {
+        let mark = Mark::one(mark::One {
+            action: Add,
+            what,
+            flags: mark::Flags::empty(),
+            mask,
+            path: mark::Path::absolute(self.path()),
+        }).map_err(|_| mark::RawError::InvalidArgument)?;
+        fanotify.mark(mark).map_err(|it| it.error)
+    }
+}
@@ -0,0 +1,318 @@
+//! Running a command when matching [`Event`]s occur, like `watchexec` or `entr`.
+//!
+//! Requires the `trigger` feature.
+//!
+//! A [`Trigger`] is a [`Sink`], so it can end a [`Pipeline`](crate::fanotify::pipeline::Pipeline)
+//! (or be one of the sinks of a [`FanOut`](crate::event::sink::FanOut)).
+//! Each matching [`Event`] is coalesced into the next run of the command,
+//! which starts once no more have matched for the [debounce](Trigger::debounce) period
+//! and fewer than the [maximum](Trigger::max_concurrency) number of runs are still running.
+//!
+//! The command gets these environment variables, which can also be whole arguments
+//! written as `$NAME` or `${NAME}` (see [`expand_arg`]):
+//! * `FANOTIFY_PATH`: the path of the last matching [`Event`] that has one, if any
+//! * `FANOTIFY_MASK`: the union of the [`Mask`]s of the matching [`Event`]s, like `MODIFY | CLOSE_WRITE`
+//! * `FANOTIFY_PID`: the process (or thread) id that caused the last matching [`Event`]
+//! * `FANOTIFY_COUNT`: the number of matching [`Event`]s coalesced into this run
+//!
+//! Since paths can contain anything, the variables are never substituted into part of an argument,
+//! like a `sh -c` script, which would let a file name inject commands.
+//! Scripts should use the environment variables instead, quoted like `"$FANOTIFY_PATH"`.
+
+use std::convert::Infallible;
+use std::ffi::OsString;
+use std::io;
+use std::path::PathBuf;
+use std::process::Child;
+use std::process::Command;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::event::event::Event;
+use crate::event::sink::Sink;
+use crate::event::sink::SinkError;
+use crate::fanotify::pipeline::Layer;
+use crate::mark::expand_vars;
+use crate::mark::Mask;
+
+/// The matching [`Event`]s waiting for the next run of a [`Trigger`]'s command.
+#[derive(Debug)]
+struct Pending {
+    last: Instant,
+    path: Option<PathBuf>,
+    mask: Mask,
+    pid: i32,
+    count: usize,
+}
+
+impl Pending {
+    fn vars(&self) -> Vec<(&'static str, String)> {
+        let mut vars = vec![
+            ("FANOTIFY_MASK", format!("{:?}", self.mask)),
+            ("FANOTIFY_PID", self.pid.to_string()),
+            ("FANOTIFY_COUNT", self.count.to_string()),
+        ];
+        if let Some(path) = &self.path {
+            vars.push(("FANOTIFY_PATH", path.to_string_lossy().into_owned()));
+        }
+        vars
+    }
+}
+
+/// Expand `$NAME` and `${NAME}` in `template` using `vars`, and `$$` to `$`.
+///
+/// Unknown variables are left as is, so a template can still be a shell script using its own variables.
+pub fn expand(template: &str, vars: &[(&str, String)]) -> String {
+    let expanded = expand_vars(template, |name, variable, expanded| {
+        match name.and_then(|name| vars.iter().find(|(it, _)| !name.is_empty() && *it == name)) {
+            Some((_, value)) => expanded.push_str(value),
+            None => expanded.push_str(variable),
+        }
+        Ok::<_, Infallible>(())
+    });
+    match expanded {
+        Ok(expanded) => expanded,
+        Err(never) => match never {},
+    }
+}
+
+/// Expand `arg` if it's entirely one `$NAME` or `${NAME}` placeholder for one of `vars`,
+/// and otherwise leave it as is.
+///
+/// Unlike [`expand`], this never puts a value inside a larger argument,
+/// so it's safe for values like paths that the command doesn't control,
+/// since each one stays a single argument.
+pub fn expand_arg<'a>(arg: &'a str, vars: &'a [(&str, String)]) -> &'a str {
+    let name = match arg.strip_prefix('$') {
+        None => return arg,
+        Some(name) => name
+            .strip_prefix('{')
+            .and_then(|it| it.strip_suffix('}'))
+            .unwrap_or(name),
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return arg;
+    }
+    match vars.iter().find(|(it, _)| *it == name) {
+        Some((_, value)) => value,
+        None => arg,
+    }
+}
+
+/// The counts of what a [`Trigger`] has done.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct TriggerStats {
+    /// The number of [`Event`]s that matched the filter.
+    pub matched: u64,
+    /// The number of times the command was started.
+    pub runs: u64,
+    /// The number of runs that exited unsuccessfully or couldn't be waited on.
+    pub failures: u64,
+}
+
+/// A [`Sink`] that runs a command when matching [`Event`]s occur.  See the [module docs](self).
+///
+/// The command is only started when an [`Event`] is consumed or when [`Trigger::poll`] is called,
+/// so call [`Trigger::poll`] after [`Trigger::next_poll`] has passed to not wait for the next [`Event`].
+pub struct Trigger {
+    program: OsString,
+    args: Vec<String>,
+    filter: Box<dyn Layer + Send>,
+    debounce: Duration,
+    max_concurrency: usize,
+    pending: Option<Pending>,
+    running: Vec<Child>,
+    stats: TriggerStats,
+}
+
+impl Trigger {
+    /// Run `program` with `args` (whose whole-argument placeholders are [expanded](expand_arg)) on every [`Event`],
+    /// debounced by 50 ms, with one run at a time.
+    pub fn new(program: impl Into<OsString>, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+            filter: Box::new(|_: &Event<'_>| true),
+            debounce: Duration::from_millis(50),
+            max_concurrency: 1,
+            pending: None,
+            running: Vec::new(),
+            stats: TriggerStats::default(),
+        }
+    }
+    
+    /// Only run the command for [`Event`]s passed on by `filter`.
+    pub fn with_filter(mut self, filter: impl Layer + Send + 'static) -> Self {
+        self.filter = Box::new(filter);
+        self
+    }
+    
+    /// Wait until no [`Event`]s have matched for `debounce` before running the command.
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+    
+    /// Run at most `max_concurrency` (at least 1) instances of the command at once.
+    ///
+    /// Once that many are running, matching [`Event`]s are coalesced until one exits.
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency.max(1);
+        self
+    }
+    
+    pub fn debounce(&self) -> Duration {
+        self.debounce
+    }
+    
+    pub fn max_concurrency(&self) -> usize {
+        self.max_concurrency
+    }
+    
+    pub fn stats(&self) -> TriggerStats {
+        self.stats
+    }
+    
+    /// The number of runs of the command still running, as of the last [`Trigger::poll`].
+    pub fn running(&self) -> usize {
+        self.running.len()
+    }
+    
+    /// If there are matching [`Event`]s waiting for the next run of the command.
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+    
+    /// How long until [`Trigger::poll`] should be called to run the command for pending [`Event`]s,
+    /// or [`None`] if there are none.
+    ///
+    /// If the maximum number of runs are running, this is when the debounce period is over,
+    /// but the command still won't run until one of them exits.
+    pub fn next_poll(&self) -> Option<Duration> {
+        let pending = self.pending.as_ref()?;
+        Some((pending.last + self.debounce).saturating_duration_since(Instant::now()))
+    }
+    
+    /// Wait on any runs that have exited.
+    fn reap(&mut self) {
+        let stats = &mut self.stats;
+        self.running.retain_mut(|child| match child.try_wait() {
+            Ok(None) => true,
+            Ok(Some(status)) => {
+                if !status.success() {
+                    stats.failures += 1;
+                }
+                false
+            }
+            Err(_) => {
+                stats.failures += 1;
+                false
+            }
+        });
+    }
+    
+    fn spawn(&mut self, pending: &Pending) -> io::Result<()> {
+        let vars = pending.vars();
+        let child = Command::new(&self.program)
+            .args(self.args.iter().map(|it| expand_arg(it, &vars)))
+            .envs(vars.iter().map(|(name, value)| (name, value)))
+            .spawn()?;
+        self.running.push(child);
+        self.stats.runs += 1;
+        Ok(())
+    }
+    
+    /// Run the command for the pending [`Event`]s if they're done being debounced
+    /// and fewer than the maximum number of runs are running.
+    ///
+    /// Return whether the command was run.
+    pub fn poll(&mut self) -> io::Result<bool> {
+        self.reap();
+        match self.next_poll() {
+            Some(next) if next == Duration::from_secs(0) => {}
+            _ => return Ok(false),
+        }
+        self.run_pending()
+    }
+    
+    /// Like [`Trigger::poll`], but without waiting for the debounce period.
+    pub fn flush(&mut self) -> io::Result<bool> {
+        self.reap();
+        self.run_pending()
+    }
+    
+    fn run_pending(&mut self) -> io::Result<bool> {
+        if self.running.len() >= self.max_concurrency {
+            return Ok(false);
+        }
+        let pending = match self.pending.take() {
+            None => return Ok(false),
+            Some(pending) => pending,
+        };
+        self.spawn(&pending)?;
+        Ok(true)
+    }
+    
+    /// Wait for all of the runs of the command to exit, without running any pending ones.
+    pub fn wait(&mut self) -> io::Result<()> {
+        for mut child in self.running.drain(..) {
+            if !child.wait()?.success() {
+                self.stats.failures += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Sink for Trigger {
+    fn consume(&mut self, event: &Event<'_>) -> Result<(), SinkError> {
+        if self.filter.handle(event) {
+            self.stats.matched += 1;
+            let path = event.file().path().and_then(Result::ok);
+            let pid = event.id().pid().or_else(|| event.id().tid()).map_or(0, |it| it.as_raw());
+            let pending = self.pending.get_or_insert(Pending {
+                last: Instant::now(),
+                path: None,
+                mask: Mask::empty(),
+                pid,
+                count: 0,
+            });
+            pending.last = Instant::now();
+            pending.path = path.or_else(|| pending.path.take());
+            pending.mask |= event.mask();
+            pending.pid = pid;
+            pending.count += 1;
+        }
+        self.poll()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand;
+    use super::expand_arg;
+    
+    #[test]
+    fn expand_variables() {
+        let vars = [
+            ("FANOTIFY_PATH", "/etc/passwd".to_owned()),
+            ("FANOTIFY_COUNT", "3".to_owned()),
+        ];
+        assert_eq!(expand("$FANOTIFY_PATH changed", &vars), "/etc/passwd changed");
+        assert_eq!(expand("${FANOTIFY_COUNT}x", &vars), "3x");
+        assert_eq!(expand("$FANOTIFY_COUNTx $$FANOTIFY_PATH", &vars), "$FANOTIFY_COUNTx $FANOTIFY_PATH");
+        assert_eq!(expand("echo $1 ${HOME} $ ${", &vars), "echo $1 ${HOME} $ ${");
+    }
+    
+    #[test]
+    fn expand_whole_arguments() {
+        let vars = [("FANOTIFY_PATH", "/tmp/$(reboot); reboot".to_owned())];
+        assert_eq!(expand_arg("$FANOTIFY_PATH", &vars), "/tmp/$(reboot); reboot");
+        assert_eq!(expand_arg("${FANOTIFY_PATH}", &vars), "/tmp/$(reboot); reboot");
+        assert_eq!(expand_arg("echo $FANOTIFY_PATH", &vars), "echo $FANOTIFY_PATH");
+        assert_eq!(expand_arg("${FANOTIFY_PATH}x", &vars), "${FANOTIFY_PATH}x");
+        assert_eq!(expand_arg("$HOME", &vars), "$HOME");
+        assert_eq!(expand_arg("$", &vars), "$");
+    }
+}
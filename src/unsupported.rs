@@ -0,0 +1,580 @@
+//! What this crate compiles to on non-Linux targets with the `unsupported-stub` feature,
+//! so that cross-platform workspaces that depend on it can still build everywhere.
+//!
+//! The core types ([`Init`](init::Init), [`Fanotify`](fanotify::Fanotify), [`Mask`](mark::Mask),
+//! and the rest of [`mark`]) have the same signatures as on Linux,
+//! but a [`Fanotify`](fanotify::Fanotify) can never be created,
+//! since [`Init::to_fanotify`](init::Init::to_fanotify) always fails with
+//! [`FanotifyUnsupported`](init::Error::FanotifyUnsupported).
+//! [`supported`](supported::supported) reports that nothing is supported,
+//! so dependents should check it at runtime (or `cfg(target_os = "linux")` their other uses).
+//!
+//! The flags have the same values as on Linux, though they don't mean anything here.
+
+use std::io;
+
+use thiserror::Error;
+
+/// An error for when fanotify is used on a non-Linux target.
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[error("fanotify is only supported on Linux")]
+pub struct Unsupported;
+
+/// Like a `ProcUnavailable` on Linux,
+/// an [`Unsupported`] is wrapped in an [`io::ErrorKind::Unsupported`] [`io::Error`].
+impl From<Unsupported> for io::Error {
+    fn from(e: Unsupported) -> Self {
+        Self::new(io::ErrorKind::Unsupported, e)
+    }
+}
+
+pub mod init {
+    use bitflags::bitflags;
+    
+    use super::fanotify::Fanotify;
+    
+    bitflags! {
+        pub struct Flags: u32 {
+            const CLOSE_ON_EXEC = 0x00000001;
+            const NON_BLOCKING = 0x00000002;
+            const UNLIMITED_QUEUE = 0x00000010;
+            const UNLIMITED_MARKS = 0x00000020;
+            const REPORT_TID = 0x00000100;
+            const REPORT_FID = 0x00000200;
+            const REPORT_DIR_FID = 0x00000400;
+            const REPORT_NAME = 0x00000800;
+            const ENABLE_AUDIT = 0x00000040;
+        }
+    }
+    
+    impl Flags {
+        pub const fn const_default() -> Self {
+            Self::empty()
+        }
+        
+        pub const fn unlimited() -> Self {
+            Self::from_bits_truncate(Self::UNLIMITED_QUEUE.bits | Self::UNLIMITED_MARKS.bits)
+        }
+    }
+    
+    impl Default for Flags {
+        fn default() -> Self {
+            Self::const_default()
+        }
+    }
+    
+    bitflags! {
+        /// The flags that event fds are opened with.
+        pub struct EventFlags: u32 {
+            const LARGE_FILE = 0o100000;
+            const CLOSE_ON_EXEC = 0o2000000;
+            const APPEND = 0o2000;
+            const DATA_SYNC = 0o10000;
+            const SYNC = 0o4010000;
+            const NO_UPDATE_ACCESS_TIME = 0o1000000;
+            const NON_BLOCKING = 0o4000;
+        }
+    }
+    
+    impl EventFlags {
+        pub const fn const_default() -> Self {
+            Self::empty()
+        }
+    }
+    
+    impl Default for EventFlags {
+        fn default() -> Self {
+            Self::const_default()
+        }
+    }
+    
+    #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+    #[repr(u32)]
+    pub enum NotificationClass {
+        PreContent = 0x00000008,
+        Content = 0x00000004,
+        Notify = 0x00000000,
+    }
+    
+    impl NotificationClass {
+        pub const fn const_default() -> Self {
+            Self::Notify
+        }
+    }
+    
+    impl Default for NotificationClass {
+        fn default() -> Self {
+            Self::const_default()
+        }
+    }
+    
+    #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+    #[repr(u32)]
+    pub enum ReadWrite {
+        Read = 0,
+        Write = 1,
+        ReadAndWrite = 2,
+    }
+    
+    impl ReadWrite {
+        pub const fn const_default() -> Self {
+            Self::Read
+        }
+    }
+    
+    impl Default for ReadWrite {
+        fn default() -> Self {
+            Self::const_default()
+        }
+    }
+    
+    /// Like on Linux, but without `InvalidFd`, since nothing is ever called.
+    #[derive(thiserror::Error, Debug, Eq, PartialEq, Hash)]
+    pub enum Error {
+        #[error("invalid argument specified")]
+        InvalidArgument,
+        #[error("exceeded the per-process limit on fanotify groups")]
+        ExceededFanotifyGroupPerProcessLimit,
+        #[error("exceeded the per-process limit on open file descriptors")]
+        ExceededOpenFileDescriptorPerProcessLimit,
+        #[error("kernel out of memory")]
+        OutOfMemory,
+        #[error("user does not have the required CAP_SYS_ADMIN capability")]
+        PermissionDenied,
+        #[error("the kernel does not support the fanotify_init() syscall")]
+        FanotifyUnsupported,
+        #[error("the kernel does not support a certain feature for fanotify_init()")]
+        FeatureUnsupported,
+    }
+    
+    #[derive(Debug, Eq, PartialEq, Hash)]
+    pub struct Init {
+        pub notification_class: NotificationClass,
+        pub flags: Flags,
+        pub rw: ReadWrite,
+        pub event_flags: EventFlags,
+    }
+    
+    impl Init {
+        pub const fn const_default() -> Self {
+            Self {
+                notification_class: NotificationClass::const_default(),
+                flags: Flags::const_default(),
+                rw: ReadWrite::const_default(),
+                event_flags: EventFlags::const_default(),
+            }
+        }
+        
+        pub const fn notification() -> Self {
+            Self {
+                notification_class: NotificationClass::Notify,
+                flags: Flags::CLOSE_ON_EXEC,
+                rw: ReadWrite::Read,
+                event_flags: EventFlags::CLOSE_ON_EXEC,
+            }
+        }
+        
+        pub const fn permission_gate() -> Self {
+            Self {
+                notification_class: NotificationClass::PreContent,
+                flags: Flags::from_bits_truncate(Flags::CLOSE_ON_EXEC.bits() | Flags::unlimited().bits()),
+                rw: ReadWrite::Read,
+                event_flags: EventFlags::CLOSE_ON_EXEC,
+            }
+        }
+        
+        pub const fn fid_tracking() -> Self {
+            Self {
+                notification_class: NotificationClass::Notify,
+                flags: Flags::from_bits_truncate(Flags::CLOSE_ON_EXEC.bits() | Flags::REPORT_FID.bits()),
+                rw: ReadWrite::Read,
+                event_flags: EventFlags::const_default(),
+            }
+        }
+        
+        pub const fn audit() -> Self {
+            Self {
+                notification_class: NotificationClass::PreContent,
+                flags: Flags::from_bits_truncate(
+                    Flags::CLOSE_ON_EXEC.bits() | Flags::unlimited().bits() | Flags::ENABLE_AUDIT.bits()
+                ),
+                rw: ReadWrite::Read,
+                event_flags: EventFlags::CLOSE_ON_EXEC,
+            }
+        }
+        
+        /// Always fails with [`Error::FanotifyUnsupported`].
+        pub fn to_fanotify(&self) -> Result<Fanotify, Error> {
+            Err(Error::FanotifyUnsupported)
+        }
+    }
+    
+    impl Default for Init {
+        fn default() -> Self {
+            Self::const_default()
+        }
+    }
+}
+
+pub mod fanotify {
+    use std::convert::Infallible;
+    
+    use super::mark;
+    use super::mark::Mark;
+    use super::mark::Markable;
+    
+    /// A fanotify group, which can never be created here
+    /// (see [`Init::to_fanotify`](super::init::Init::to_fanotify)).
+    #[derive(Debug)]
+    pub struct Fanotify {
+        never: Infallible,
+    }
+    
+    impl Markable for Fanotify {
+        fn mark<'a>(&self, _: Mark<'a>) -> Result<(), mark::Error<'a>> {
+            match self.never {}
+        }
+        
+        fn check<'a>(&self, _: Mark<'a>) -> Result<Mark<'a>, mark::Error<'a>> {
+            match self.never {}
+        }
+    }
+}
+
+pub mod mark {
+    use std::convert::TryFrom;
+    
+    use bitflags::bitflags;
+    
+    pub use OneMark as One;
+    
+    bitflags! {
+        pub struct Mask: u64 {
+            const ACCESS = 0x00000001;
+            const OPEN = 0x00000020;
+            const OPEN_EXEC = 0x00001000;
+            const CLOSE_NO_WRITE = 0x00000010;
+            const CLOSE_WRITE = 0x00000008;
+            const MODIFY = 0x00000002;
+            const ATTRIBUTE_CHANGED = 0x00000004;
+            const CREATE = 0x00000100;
+            const DELETE = 0x00000200;
+            const DELETE_SELF = 0x00000400;
+            const MOVED_FROM = 0x00000040;
+            const MOVED_TO = 0x00000080;
+            const MOVE_SELF = 0x00000800;
+            const ACCESS_PERMISSION = 0x00020000;
+            const OPEN_PERMISSION = 0x00010000;
+            const OPEN_EXEC_PERMISSION = 0x00040000;
+            const ON_DIR = 0x40000000;
+            const EVENT_ON_CHILD = 0x08000000;
+        }
+    }
+    
+    impl Mask {
+        pub const fn close() -> Self {
+            Self::from_bits_truncate(Self::CLOSE_NO_WRITE.bits | Self::CLOSE_WRITE.bits)
+        }
+        
+        pub const fn moved() -> Self {
+            Self::from_bits_truncate(Self::MOVED_FROM.bits | Self::MOVED_TO.bits)
+        }
+        
+        pub const fn all_permissions() -> Self {
+            Self::from_bits_truncate(
+                Self::ACCESS_PERMISSION.bits | Self::OPEN_PERMISSION.bits | Self::OPEN_EXEC_PERMISSION.bits
+            )
+        }
+    }
+    
+    bitflags! {
+        pub struct Flags: u32 {
+            const DONT_FOLLOW = 0x00000004;
+            const ONLY_DIR = 0x00000008;
+            const IGNORED_MASK = 0x00000020;
+            const IGNORED_SURVIVE_MODIFY = 0x00000040;
+        }
+    }
+    
+    impl Flags {
+        pub const fn const_default() -> Self {
+            Self::empty()
+        }
+    }
+    
+    impl Default for Flags {
+        fn default() -> Self {
+            Self::const_default()
+        }
+    }
+    
+    #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+    #[repr(u32)]
+    pub enum What {
+        Inode = 0x00000000,
+        MountPoint = 0x00000010,
+        FileSystem = 0x00000100,
+    }
+    
+    #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+    pub enum OneAction {
+        Add,
+        Remove,
+    }
+    
+    #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+    #[repr(u32)]
+    pub enum Action {
+        Add = 0x00000001,
+        Remove = 0x00000002,
+        Flush = 0x00000080,
+    }
+    
+    impl OneAction {
+        pub const fn const_into(self) -> Action {
+            match self {
+                Self::Add => Action::Add,
+                Self::Remove => Action::Remove,
+            }
+        }
+    }
+    
+    impl From<OneAction> for Action {
+        fn from(it: OneAction) -> Self {
+            it.const_into()
+        }
+    }
+    
+    /// Only absolute paths and the current working directory are supported here,
+    /// since directory fds are Linux-specific.
+    #[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+    pub struct Path<'a> {
+        path: Option<&'a std::path::Path>,
+    }
+    
+    impl Path<'static> {
+        pub const fn current_working_directory() -> Self {
+            Self { path: None }
+        }
+    }
+    
+    impl<'a> Path<'a> {
+        pub fn absolute<P: AsRef<std::path::Path> + 'a + ?Sized>(path: &'a P) -> Self {
+            Self {
+                path: Some(path.as_ref()),
+            }
+        }
+    }
+    
+    #[derive(Debug, Eq, PartialEq, Hash)]
+    pub struct OneMark<'a> {
+        pub action: OneAction,
+        pub what: What,
+        pub flags: Flags,
+        pub mask: Mask,
+        pub path: Path<'a>,
+    }
+    
+    #[derive(Debug, Eq, PartialEq, Hash)]
+    pub struct Mark<'a> {
+        action: Action,
+        what: What,
+        flags: Flags,
+        mask: Mask,
+        path: Path<'a>,
+    }
+    
+    impl<'a> Mark<'a> {
+        pub const fn one(mark: OneMark<'a>) -> Result<Self, StaticError> {
+            let OneMark {
+                action,
+                what,
+                flags,
+                mask,
+                path,
+            } = mark;
+            if mask.is_empty() {
+                return Err(StaticError::EmptyMask);
+            }
+            Ok(Self {
+                action: action.const_into(),
+                what,
+                flags,
+                mask,
+                path,
+            })
+        }
+        
+        pub const fn flush(what: What) -> Self {
+            Self {
+                action: Action::Flush,
+                what,
+                flags: Flags::empty(),
+                mask: Mask::all(),
+                path: Path::current_working_directory(),
+            }
+        }
+        
+        pub fn action(&self) -> Action {
+            self.action
+        }
+        
+        pub fn what(&self) -> What {
+            self.what
+        }
+        
+        pub fn flags(&self) -> Flags {
+            self.flags
+        }
+        
+        pub fn mask(&self) -> Mask {
+            self.mask
+        }
+        
+        pub fn path(&self) -> &Path<'a> {
+            &self.path
+        }
+    }
+    
+    impl<'a> TryFrom<OneMark<'a>> for Mark<'a> {
+        type Error = StaticError;
+        
+        fn try_from(this: OneMark<'a>) -> Result<Self, Self::Error> {
+            Mark::one(this)
+        }
+    }
+    
+    #[derive(thiserror::Error, Debug, Eq, PartialEq, Hash)]
+    pub enum StaticError {
+        #[error("mask must not be empty for add or remove")]
+        EmptyMask,
+    }
+    
+    /// Like on Linux, but without the variants for directory fds and path prechecks.
+    #[derive(thiserror::Error, Debug, Eq, PartialEq, Hash)]
+    pub enum RawError {
+        #[error("invalid argument specified")]
+        InvalidArgument,
+        #[error("permission events {:?} can't be marked on a notification group", .mask)]
+        PermissionOnNotifyGroup { mask: Mask },
+        #[error("not a directory, but {:?} specified", Flags::ONLY_DIR)]
+        NotADirectory,
+        #[error("path does not exist")]
+        PathDoesNotExist,
+        #[error("path is on a filesystem that doesn't support fsid")]
+        PathDoesNotSupportFSID,
+        #[error("path is on a filesystem that doesn't support the encoding of file handles")]
+        PathNotSupported,
+        #[error("path resides on a subvolume that uses a different fsid than its root superblock")]
+        PathUsesDifferentFSID,
+        #[error("cannot remove mark that doesn't exist yet")]
+        CannotRemoveNonExistentMark,
+        #[error("exceeded the per-fanotify group mark limit")]
+        ExceededMarkLimit,
+        #[error("kernel out of memory")]
+        OutOfMemory,
+        #[error("the kernel does not support a certain feature for fanotify_mark()")]
+        FeatureUnsupported,
+    }
+    
+    #[derive(thiserror::Error, Debug, Eq, PartialEq, Hash)]
+    #[error("{:?}: {:?}", .error, .mark)]
+    pub struct Error<'a> {
+        pub error: RawError,
+        pub mark: Mark<'a>,
+    }
+    
+    impl<'a> Error<'a> {
+        pub fn new(error: RawError, mark: Mark<'a>) -> Self {
+            Self { error, mark }
+        }
+        
+        /// Groups can't be created, so this is always [`None`].
+        pub fn group(&self) -> Option<&str> {
+            None
+        }
+    }
+    
+    /// Something that [`Mark`]s can be added to, like a [`Fanotify`](super::fanotify::Fanotify).
+    pub trait Markable {
+        fn mark<'a>(&self, mark: Mark<'a>) -> Result<(), Error<'a>>;
+        
+        fn check<'a>(&self, mark: Mark<'a>) -> Result<Mark<'a>, Error<'a>> {
+            Ok(mark)
+        }
+    }
+}
+
+pub mod supported {
+    use std::sync::OnceLock;
+    
+    use super::init;
+    use super::init::NotificationClass;
+    use super::mark::Mask;
+    use super::mark::What;
+    
+    /// A rough level of fanotify support, which is always [`Level::None`] here.
+    #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+    pub enum Level {
+        /// fanotify can't be used at all.
+        None,
+        /// Basic fanotify works, but not everything.
+        Partial,
+        /// Everything works.
+        Full,
+    }
+    
+    /// A report of what fanotify features are supported, as returned by [`supported`],
+    /// with the same fields as on Linux.
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct Supported {
+        /// Why fanotify can't be used at all,
+        /// which is always [`FanotifyUnsupported`](init::Error::FanotifyUnsupported) here.
+        pub error: Option<init::Error>,
+        pub notification_classes: Vec<NotificationClass>,
+        pub flags: init::Flags,
+        pub whats: Vec<What>,
+        pub mask: Mask,
+        pub response_info: bool,
+    }
+    
+    impl Supported {
+        pub fn supports_class(&self, notification_class: NotificationClass) -> bool {
+            self.notification_classes.contains(&notification_class)
+        }
+        
+        pub fn supports_flags(&self, flags: init::Flags) -> bool {
+            self.flags.contains(flags)
+        }
+        
+        pub fn supports_what(&self, what: What) -> bool {
+            self.whats.contains(&what)
+        }
+        
+        pub fn supports_mask(&self, mask: Mask) -> bool {
+            self.mask.contains(mask)
+        }
+        
+        pub fn level(&self) -> Level {
+            Level::None
+        }
+    }
+    
+    /// Check what fanotify features are supported, which is nothing on non-Linux targets.
+    pub fn supported() -> Supported {
+        Supported {
+            error: Some(init::Error::FanotifyUnsupported),
+            notification_classes: Vec::new(),
+            flags: init::Flags::empty(),
+            whats: Vec::new(),
+            mask: Mask::empty(),
+            response_info: false,
+        }
+    }
+    
+    /// Like [`supported`], but only checked once and then cached for the rest of the process.
+    pub fn cached() -> &'static Supported {
+        static SUPPORTED: OnceLock<Supported> = OnceLock::new();
+        SUPPORTED.get_or_init(supported)
+    }
+}
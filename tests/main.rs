@@ -1,3 +1,5 @@
+#![cfg(target_os = "linux")]
+
 use std::convert::TryInto;
 use std::fs;
 use std::io;
@@ -5,17 +7,57 @@ use std::io::Read;
 use std::io::Seek;
 use std::io::SeekFrom;
 use std::io::Write;
+use std::net::Shutdown;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::IntoRawFd;
+use std::os::unix::net::UnixStream;
 use std::path::Path;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use apply::Apply;
+#[cfg(feature = "async")]
 use async_io::block_on;
+use nix::errno::Errno;
+use nix::unistd::Gid;
+use nix::unistd::Uid;
 use tempfile::NamedTempFile;
 use tempfile::tempfile;
 use to_trait::To;
 
+use fanotify::event::arena::EventArena;
+use fanotify::event::buffer::EventBufferSize;
+use fanotify::event::error::EventError;
+use fanotify::event::file::GetFD;
+use fanotify::event::file::decision_cache::DecisionCache;
+use fanotify::event::file::fid::HandleOpen;
+use fanotify::event::file::permission::PermissionDecision;
+use fanotify::event::origin::Origin;
+use fanotify::event::origin::OriginClassifier;
+use fanotify::event::responses::FlushPolicy;
+use fanotify::event::sequence::GapDetector;
+use fanotify::event::event::Event;
+use fanotify::event::events::Events;
 use fanotify::event::iterator_ext::IntoEvents;
+use fanotify::fd::FD;
+use fanotify::fd::FileType;
+use fanotify::export::EventRecord;
+use fanotify::export::archive::ArchiveReader;
+use fanotify::export::archive::ArchiveWriter;
+use fanotify::export::archive::Rotation;
+use fanotify::export::compact;
+use fanotify::export::ring::RingReader;
+use fanotify::export::ring::RingWriter;
 use fanotify::fanotify::buffered_fanotify::IntoBufferedFanotify;
+use fanotify::fanotify::own_outputs::OwnOutputs;
+use fanotify::fanotify::privilege::PrivilegeDrop;
+use fanotify::fanotify::privilege::PrivilegeError;
+use fanotify::fanotify::router::Router;
+use fanotify::fanotify::subtree::SubtreeMonitor;
+use fanotify::fanotify::subtree::WatchError;
 use fanotify::init;
 use fanotify::init::Flags;
 use fanotify::init::Init;
@@ -25,10 +67,15 @@ use fanotify::mark::Mask;
 use fanotify::mark::OneAction::Add;
 use fanotify::mark::What::FileSystem;
 use fanotify::mark::What::MountPoint;
+use fanotify::proc;
+use fanotify::reconcile::Change;
+use fanotify::reconcile::Reconciler;
+use fanotify::reconcile::Snapshot;
+use fanotify::testkit::Driver;
 
 use crate::util::AnyResult;
-use crate::util::driver::Driver;
 use crate::util::get_init;
+use crate::util::run_alone;
 use crate::util::supported::Supported;
 use crate::util::supported::Supported::Full;
 use crate::util::supported::Supported::Partial;
@@ -65,6 +112,19 @@ fn report_fid_unsupported() {
     );
 }
 
+#[test]
+fn init_presets() -> AnyResult {
+    if !supports(Full) {
+        return Ok(());
+    }
+    for init in [Init::notification(), Init::permission_gate(), Init::fid_tracking()].iter() {
+        init.to_fanotify()?;
+    }
+    assert_eq!(Init::fid_tracking().notification_class, init::NotificationClass::Notify);
+    assert!(Init::audit().flags.contains(Flags::ENABLE_AUDIT));
+    Ok(())
+}
+
 fn mark_unsupported(error: mark::RawError, mark: mark::One) -> AnyResult {
     if !supports(Partial) {
         return Ok(());
@@ -93,6 +153,243 @@ fn filesystem_mark_unsupported() -> AnyResult {
     })
 }
 
+#[test]
+fn permission_on_notify_group() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let fanotify = get_init().to_fanotify()?.named("notify");
+    assert!(fanotify.to_string().contains("\"notify\""));
+    let e = fanotify.mark(mark::One {
+        action: Add,
+        what: MountPoint,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN | Mask::OPEN_PERMISSION,
+        path: mark::Path::absolute("/home"),
+    }.try_into()?).unwrap_err();
+    assert_eq!(e.group(), Some("notify"));
+    assert_eq!(
+        e.error,
+        mark::RawError::PermissionOnNotifyGroup { mask: Mask::OPEN | Mask::OPEN_PERMISSION },
+    );
+    Ok(())
+}
+
+#[test]
+fn dyn_markable() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let fanotify = std::sync::Arc::new(get_init().to_fanotify()?);
+    let markables: Vec<Box<dyn Markable>> = vec![Box::new(fanotify.clone()), Box::new(&*fanotify)];
+    let mut registry = mark::MarkRegistry::new();
+    for markable in &markables {
+        registry.mark(markable, mark::One {
+            action: Add,
+            what: mark::What::Inode,
+            flags: mark::Flags::empty(),
+            mask: Mask::OPEN | Mask::ON_DIR,
+            path: mark::Path::absolute(dir.path()),
+        }.try_into()?).map_err(|e| e.error)?;
+    }
+    assert_eq!(registry.marks().len(), 2);
+    let _ = fs::read_dir(dir.path())?;
+    assert!(fanotify.readable(Some(Duration::from_secs(1)))?);
+    Ok(())
+}
+
+#[test]
+fn check_mark() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let fanotify = get_init().to_fanotify()?;
+    let mark = |path| mark::One {
+        action: Add,
+        what: MountPoint,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(path),
+    }.try_into();
+    assert!(fanotify.check(mark("/etc")?).is_ok());
+    assert_eq!(
+        fanotify.check(mark("/does/not/exist")?).err().map(|it| it.error),
+        Some(mark::RawError::PathDoesNotExist),
+    );
+    // nothing was actually marked
+    let _ = fs::read("/etc/passwd")?;
+    assert!(!fanotify.readable(Some(Duration::from_millis(0)))?);
+    Ok(())
+}
+
+#[test]
+fn mark_precheck() -> AnyResult {
+    use mark::PathError;
+    
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let file = root.join("file");
+    fs::write(&file, "")?;
+    let link = root.join("proc");
+    std::os::unix::fs::symlink("/proc", &link)?;
+    let dir_fd = fs::File::open(&root)?;
+    assert_eq!(mark::Path::relative_to(&dir_fd, "proc").canonicalize()?, PathBuf::from("/proc"));
+    fn mark(what: mark::What, flags: mark::Flags, path: &Path) -> Result<mark::Mark<'_>, mark::StaticError> {
+        mark::Mark::one(mark::One {
+            action: Add,
+            what,
+            flags,
+            mask: Mask::OPEN,
+            path: mark::Path::absolute(path),
+        })
+    }
+    let missing = root.join("missing");
+    let inode = mark::What::Inode;
+    let none = mark::Flags::empty();
+    assert_eq!(mark(inode, none, &file)?.precheck(), Ok(()));
+    assert_eq!(
+        mark(inode, none, &missing)?.precheck(),
+        Err(PathError::Missing { path: missing.clone() }),
+    );
+    assert_eq!(
+        mark(inode, mark::Flags::ONLY_DIR, &file)?.precheck(),
+        Err(PathError::NotADirectory { path: file.clone() }),
+    );
+    assert_eq!(mark(inode, none, &link)?.precheck(), Ok(()));
+    assert_eq!(mark(MountPoint, mark::Flags::DONT_FOLLOW, &link)?.precheck(), Ok(()));
+    // following a symlink onto another device is legitimate, e.g. for `/var/run`
+    assert_eq!(mark(MountPoint, none, &link)?.precheck(), Ok(()));
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let mut fanotify = get_init().to_fanotify()?;
+    assert_eq!(
+        fanotify.mark(mark(inode, none, &missing)?).err().map(|it| it.error),
+        Some(mark::RawError::PathDoesNotExist),
+    );
+    fanotify.set_precheck(true);
+    assert_eq!(
+        fanotify.mark(mark(inode, none, &missing)?).err().map(|it| it.error),
+        Some(mark::RawError::Path(Box::new(PathError::Missing { path: missing.clone() }))),
+    );
+    assert_eq!(
+        fanotify.check(mark(inode, mark::Flags::ONLY_DIR, &file)?).err().map(|it| it.error),
+        Some(mark::RawError::Path(Box::new(PathError::NotADirectory { path: file.clone() }))),
+    );
+    fanotify.mark(mark(inode, none, &file)?).map_err(|e| e.error)?;
+    Ok(())
+}
+
+#[test]
+fn owned_dir_fd() -> AnyResult {
+    use mark::OwnedDirFd;
+    use mark::OwnedPath;
+    
+    // a long-lived watch configuration owning its directory handle
+    struct Watch {
+        path: OwnedPath,
+        mask: Mask,
+    }
+    
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let file = root.join("file");
+    fs::write(&file, "")?;
+    assert_eq!(
+        OwnedDirFd::open(&file).err().and_then(|e| e.raw_os_error()),
+        Some(Errno::ENOTDIR as i32),
+    );
+    let (errno, _) = OwnedDirFd::try_new(fs::File::open(&file)?.into()).expect_err("not a directory");
+    assert_eq!(errno, Errno::ENOTDIR);
+    let owned = OwnedDirFd::try_new(fs::File::open(&root)?.into()).map_err(|(errno, _)| errno)?;
+    assert_eq!(owned.to_string(), root.display().to_string());
+    assert_eq!(owned.as_dir_fd().resolve(), root);
+    let watch = Watch {
+        path: OwnedPath::relative_to(OwnedDirFd::open(&root)?, "file"),
+        mask: Mask::OPEN,
+    };
+    assert_eq!(watch.path.as_path().resolve(), file);
+    assert_eq!(OwnedPath::directory(fs::File::open(&root)?).as_path().resolve(), root);
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: watch.mask,
+        path: watch.path.as_path(),
+    }.try_into()?).map_err(|e| e.error)?;
+    fs::File::open(&file)?;
+    let events = fanotify.read()?.all().collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].file().path().transpose()?, Some(file));
+    Ok(())
+}
+
+#[test]
+fn recreate_with_marks() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let mut fanotify = get_init().to_fanotify()?.named("recreated");
+    let mut registry = mark::MarkRegistry::new();
+    registry.mark(&fanotify, mark::One {
+        action: Add,
+        what: MountPoint,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute("/etc"),
+    }.try_into()?)?;
+    let report = fanotify.recreate_with_marks(&registry)?;
+    assert_eq!(report.applied, 1);
+    assert!(report.is_complete());
+    assert_eq!(fanotify.name(), Some("recreated"));
+    let _ = fs::read("/etc/passwd")?;
+    assert!(fanotify.readable(Some(Duration::from_secs(1)))?);
+    Ok(())
+}
+
+#[test]
+fn self_test() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let fanotify = get_init().to_fanotify()?;
+    fanotify.mark(mark::One {
+        action: Add,
+        what: MountPoint,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN | Mask::MODIFY | Mask::CLOSE_WRITE,
+        path: mark::Path::absolute(dir.path()),
+    }.try_into()?).map_err(|e| e.error)?;
+    fanotify.self_test(dir.path(), Mask::OPEN | Mask::MODIFY | Mask::CLOSE_WRITE, Duration::from_secs(5))?;
+    Ok(())
+}
+
+#[test]
+fn mark_degraded() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let fanotify = get_init().to_fanotify()?;
+    // CREATE needs REPORT_FID
+    let degraded = fanotify.mark_degraded(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN | Mask::CREATE | Mask::EVENT_ON_CHILD,
+        path: mark::Path::absolute(dir.path()),
+    }.try_into()?).map_err(|e| e.error)?;
+    assert_eq!(degraded.mask, Mask::OPEN | Mask::EVENT_ON_CHILD);
+    assert_eq!(degraded.unsupported, Mask::CREATE);
+    Ok(())
+}
+
 #[test]
 #[ignore]
 fn create_mask_unsupported() -> AnyResult {
@@ -143,6 +440,7 @@ fn sync_api() -> AnyResult {
     })
 }
 
+#[cfg(feature = "async")]
 #[test]
 fn async_api() -> AnyResult {
     mark_and_read(|driver| {
@@ -156,6 +454,3239 @@ fn async_api() -> AnyResult {
     })
 }
 
+#[cfg(feature = "async")]
+#[test]
+fn async_shutdown() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let mut fanotify = Init {
+        notification_class: init::NotificationClass::Content,
+        ..get_init()
+    }
+        .to_fanotify()?
+        .into_async()?
+        .buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN_PERMISSION,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    let opener = std::thread::spawn(move || fs::File::open(path).map(|_| ()));
+    let (mut events, result) = block_on(fanotify.read())?.drain_owned();
+    result?;
+    let ticket = events
+        .pop()
+        .expect("permission event")?
+        .into_file()
+        .permission()
+        .expect("permission ticket");
+    // pretend the response couldn't be written without blocking
+    let response = fanotify::libc::write::fanotify_response {
+        fd: ticket.fd().as_raw_fd(),
+        response: fanotify::libc::write::FAN_ALLOW,
+    };
+    std::mem::forget(ticket);
+    let bytes = unsafe {
+        std::slice::from_raw_parts(&response as *const _ as *const u8, std::mem::size_of_val(&response))
+    };
+    fanotify.buffer.responses.extend_from_slice(bytes);
+    assert!(fanotify.has_pending_responses());
+    block_on(fanotify.shutdown())?;
+    opener.join().unwrap()?;
+    Ok(())
+}
+
+#[test]
+fn flush_policy() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let mut fanotify = Init {
+        notification_class: init::NotificationClass::Content,
+        ..get_init()
+    }
+        .to_fanotify()?
+        .with_flush_policy(FlushPolicy::immediate())
+        .buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN_PERMISSION,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    let opener = std::thread::spawn(move || fs::File::open(path).map(|_| ()));
+    let mut events = fanotify.read()?.permissions();
+    let mut permission = events.next().expect("permission event").into_file();
+    assert!(permission.write_buffered());
+    // the response was flushed even though the batch is still alive
+    opener.join().unwrap()?;
+    drop(permission);
+    drop(events);
+    Ok(())
+}
+
+#[test]
+fn permission_latency_clock() -> AnyResult {
+    use fanotify::clock::MockClock;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let clock = MockClock::new();
+    let mut fanotify = Init {
+        notification_class: init::NotificationClass::Content,
+        ..get_init()
+    }
+        .to_fanotify()?
+        .with_clock(clock.shared())
+        .buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN_PERMISSION,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    let opener = std::thread::spawn(move || fs::File::open(path).map(|_| ()));
+    let mut events = fanotify.read()?.permissions();
+    let mut permission = events.next().expect("permission event").into_file();
+    assert_eq!(permission.latency(), Duration::ZERO);
+    clock.advance(Duration::from_secs(5));
+    assert_eq!(permission.latency(), Duration::from_secs(5));
+    assert!(permission.write_immediately()?);
+    opener.join().unwrap()?;
+    // the latency stops when the response is written
+    clock.advance(Duration::from_secs(5));
+    assert_eq!(permission.latency(), Duration::from_secs(5));
+    drop(permission);
+    drop(events);
+    Ok(())
+}
+
+#[test]
+fn interleaved_permission_responses() -> AnyResult {
+    use fanotify::event::file::File;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let paths = ["a", "b", "c"].iter().map(|name| root.join(name)).collect::<Vec<_>>();
+    let mut fanotify = Init {
+        notification_class: init::NotificationClass::Content,
+        ..get_init()
+    }
+        .to_fanotify()?
+        .buffered_default();
+    for path in &paths {
+        fs::write(path, "")?;
+        fanotify.mark(mark::One {
+            action: Add,
+            what: mark::What::Inode,
+            flags: mark::Flags::empty(),
+            mask: Mask::OPEN_PERMISSION | Mask::CLOSE_NO_WRITE,
+            path: mark::Path::absolute(path),
+        }.try_into()?).map_err(|e| e.error)?;
+    }
+    // each open blocks until allowed, and its close then lands in the same batch as the next open
+    let opener = {
+        let paths = paths.clone();
+        std::thread::spawn(move || paths.iter().try_for_each(|path| fs::File::open(path).map(|_| ())))
+    };
+    let mut seen = Vec::new();
+    while seen.len() < 2 * paths.len() {
+        assert!(fanotify.fanotify.readable(Some(Duration::from_secs(5)))?, "timed out");
+        for event in fanotify.read()?.all() {
+            let event = event?;
+            let path = event.file().path().transpose()?.expect("fd");
+            let is_permission = matches!(event.file(), File::Permission(_));
+            assert_eq!(is_permission, event.mask().includes_permission());
+            // permission events are allowed (into the separate responses buffer) when dropped,
+            // which mustn't disturb the rest of the batch still being parsed
+            seen.push((path, is_permission));
+        }
+        assert!(fanotify.buffer.responses.is_empty());
+    }
+    opener.join().unwrap()?;
+    let expected = paths
+        .iter()
+        .flat_map(|path| vec![(path.clone(), true), (path.clone(), false)])
+        .collect::<Vec<_>>();
+    assert_eq!(seen, expected);
+    Ok(())
+}
+
+#[test]
+fn prioritized_permissions() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let (notified, permitted) = (root.join("notified"), root.join("permitted"));
+    let mut fanotify = Init {
+        notification_class: init::NotificationClass::Content,
+        ..get_init()
+    }
+        .to_fanotify()?
+        .buffered_default();
+    for (path, mask) in [(&notified, Mask::CLOSE_NO_WRITE), (&permitted, Mask::OPEN_PERMISSION)] {
+        fs::write(path, "")?;
+        fanotify.mark(mark::One {
+            action: Add,
+            what: mark::What::Inode,
+            flags: mark::Flags::empty(),
+            mask,
+            path: mark::Path::absolute(path),
+        }.try_into()?).map_err(|e| e.error)?;
+    }
+    // the notification event is queued before the permission event
+    fs::File::open(&notified)?;
+    let queued = fanotify.fanotify.pending_bytes()?;
+    let opener = {
+        let permitted = permitted.clone();
+        std::thread::spawn(move || fs::File::open(permitted).map(|_| ()))
+    };
+    while fanotify.fanotify.pending_bytes()? == queued {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    let events = fanotify
+        .read()?
+        .prioritized()
+        .map(|event| {
+            let event = event?;
+            Ok((event.file().path().transpose()?.expect("fd"), event.mask()))
+        })
+        .collect::<AnyResult<Vec<_>>>()?;
+    assert_eq!(events, vec![(permitted, Mask::OPEN_PERMISSION), (notified, Mask::CLOSE_NO_WRITE)]);
+    opener.join().unwrap()?;
+    Ok(())
+}
+
+#[cfg(feature = "integrity")]
+#[test]
+fn exe_allowlist() -> AnyResult {
+    use fanotify::event::file::exe_allowlist::ExeAllowlist;
+    use fanotify::event::file::exe_allowlist::ExeMatch;
+    
+    let exe = std::env::current_exe()?.canonicalize()?;
+    let own = nix::unistd::getpid();
+    let mut allowlist = ExeAllowlist::new();
+    assert_eq!(allowlist.check_pid(own), ExeMatch::NotAllowed(exe.clone()));
+    allowlist.allow_path(&exe);
+    assert_eq!(allowlist.check_pid(own), ExeMatch::Path(exe.clone()));
+    let mut allowlist = ExeAllowlist::parse(&format!(
+        "# allowed\n\n{}  {}\n",
+        fanotify::integrity::sha256::to_hex(&fanotify::integrity::sha256::hash(b"")),
+        "/empty",
+    ))?;
+    assert_eq!(allowlist.hashes().len(), 1);
+    assert_eq!(allowlist.check_pid(own), ExeMatch::NotAllowed(exe.clone()));
+    allowlist.allow_file(&exe)?;
+    assert_eq!(allowlist.check_pid(own), ExeMatch::Hash(exe.clone()));
+    assert_eq!(allowlist.check_pid(own), ExeMatch::Hash(exe.clone()));
+    assert_eq!((allowlist.cache().hits(), allowlist.cache().misses()), (1, 2));
+    assert_eq!(allowlist.check_pid(nix::unistd::Pid::from_raw(i32::MAX)), ExeMatch::Unresolved);
+    assert_eq!(ExeAllowlist::parse("/usr/bin/true\nnot hex").err().map(|it| it.line), Some(2));
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let cat = PathBuf::from("/usr/bin/cat");
+    if !cat.exists() {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let mut fanotify = Init {
+        notification_class: init::NotificationClass::Content,
+        ..get_init()
+    }
+        .to_fanotify()?
+        .buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN_PERMISSION,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    let mut allowlist = ExeAllowlist::parse(&format!("{}\n", cat.display()))?;
+    for allowed in [true, false].iter().copied() {
+        if !allowed {
+            allowlist = ExeAllowlist::new();
+        }
+        let mut child = std::process::Command::new(&cat)
+            .arg(&path)
+            .stderr(std::process::Stdio::null())
+            .spawn()?;
+        let mut decided = Vec::new();
+        for event in fanotify.read()?.all() {
+            let event = event?;
+            let exe = allowlist.check(&event);
+            let decision = allowlist.decision(&exe);
+            decided.push(exe);
+            let mut permission = event.into_file().permission().expect("permission event");
+            match decision {
+                PermissionDecision::Allow => permission.allow(),
+                PermissionDecision::Deny => permission.deny(),
+            }
+        }
+        let expected = match allowed {
+            true => ExeMatch::Path(cat.clone()),
+            false => ExeMatch::NotAllowed(cat.clone()),
+        };
+        assert_eq!(decided, vec![expected]);
+        assert_eq!(child.wait()?.success(), allowed);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "integrity")]
+#[test]
+fn protected_paths() -> AnyResult {
+    use fanotify::event::file::exe_allowlist::ExeAllowlist;
+    use fanotify::event::file::exe_allowlist::ExeMatch;
+    use fanotify::event::file::protect::ProtectError;
+    use fanotify::event::file::protect::ProtectedPaths;
+    
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let protected = ProtectedPaths::new(vec![root.join("protected")], ExeAllowlist::new());
+    assert_eq!(protected.protecting(&root.join("protected/file")), Some(root.join("protected").as_path()));
+    assert_eq!(protected.protecting(&root.join("other")), None);
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let cat = PathBuf::from("/usr/bin/cat");
+    if !cat.exists() {
+        return Ok(());
+    }
+    let protected_dir = root.join("protected");
+    // nested, so that every subdirectory has to be marked
+    fs::create_dir_all(protected_dir.join("sub"))?;
+    let path = protected_dir.join("sub/file");
+    fs::write(&path, "")?;
+    let mut fanotify = Init {
+        notification_class: init::NotificationClass::Content,
+        ..get_init()
+    }
+        .to_fanotify()?
+        .buffered_default();
+    let audits = std::cell::RefCell::new(Vec::new());
+    
+    // only audited by default
+    let mut protected = ProtectedPaths::new(vec![protected_dir.clone()], ExeAllowlist::new())
+        .with_mask(Mask::OPEN_PERMISSION)
+        .on_audit(|it| audits.borrow_mut().push(it.clone()));
+    protected.mark(&fanotify)?;
+    
+    // why a protected path couldn't be marked is kept, along with the path
+    let missing = root.join("missing");
+    assert_eq!(
+        ProtectedPaths::new(vec![missing.clone()], ExeAllowlist::new()).mark(&fanotify),
+        Err(ProtectError::Mark { path: missing.clone(), error: mark::RawError::PathDoesNotExist }),
+    );
+    assert_eq!(
+        ProtectedPaths::new(vec![missing.clone()], ExeAllowlist::new()).with_mask(Mask::empty()).mark(&fanotify),
+        Err(ProtectError::Static { path: missing, error: mark::StaticError::EmptyMask }),
+    );
+    let mut cat_protected = |protected: &mut ProtectedPaths| -> AnyResult<bool> {
+        let mut child = std::process::Command::new(&cat)
+            .arg(&path)
+            .stderr(std::process::Stdio::null())
+            .spawn()?;
+        let mut decisions = Vec::new();
+        for event in fanotify.read()?.all() {
+            decisions.extend(protected.respond(event?));
+        }
+        let success = child.wait()?.success();
+        assert_eq!(decisions, vec![match success {
+            true => PermissionDecision::Allow,
+            false => PermissionDecision::Deny,
+        }]);
+        Ok(success)
+    };
+    assert!(cat_protected(&mut protected)?);
+    assert_eq!((protected.allowed(), protected.denied()), (1, 0));
+    drop(protected);
+    
+    let mut protected = ProtectedPaths::new(vec![protected_dir.clone()], ExeAllowlist::new())
+        .with_mask(Mask::OPEN_PERMISSION)
+        .deny_all_opens()
+        .on_audit(|it| audits.borrow_mut().push(it.clone()));
+    assert!(!cat_protected(&mut protected)?);
+    protected.allowlist_mut().allow_path(&cat);
+    assert!(cat_protected(&mut protected)?);
+    assert_eq!((protected.allowed(), protected.denied()), (1, 1));
+    drop(protected);
+    
+    let audits = audits.into_inner();
+    let summary = audits
+        .iter()
+        .map(|it| (it.decision, it.enforced))
+        .collect::<Vec<_>>();
+    assert_eq!(summary, vec![
+        (PermissionDecision::Deny, false),
+        (PermissionDecision::Deny, true),
+        (PermissionDecision::Allow, false),
+    ]);
+    assert_eq!(audits[0].path, path);
+    assert_eq!(audits[1].exe, ExeMatch::NotAllowed(cat.clone()));
+    assert_eq!(audits[2].exe, ExeMatch::Path(cat));
+    Ok(())
+}
+
+#[test]
+fn readable_timeout() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let fanotify = get_init().to_fanotify()?;
+    assert!(!fanotify.readable(Some(Duration::from_millis(0)))?);
+    assert_eq!(fanotify.pending_bytes()?, 0);
+    // a sub-millisecond timeout still waits
+    let start = std::time::Instant::now();
+    assert!(!fanotify.readable(Some(Duration::from_micros(100)))?);
+    assert!(start.elapsed() >= Duration::from_micros(100));
+    Ok(())
+}
+
+#[test]
+fn poll_interrupted() -> AnyResult {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::time::Instant;
+    
+    use nix::sys::signal;
+    use nix::sys::signal::SaFlags;
+    use nix::sys::signal::SigAction;
+    use nix::sys::signal::SigHandler;
+    use nix::sys::signal::SigSet;
+    use nix::sys::signal::Signal;
+    
+    extern "C" fn ignore(_: libc::c_int) {}
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    // installs a signal handler
+    if !run_alone("poll_interrupted")? {
+        return Ok(());
+    }
+    let action = SigAction::new(SigHandler::Handler(ignore), SaFlags::empty(), SigSet::empty());
+    unsafe { signal::sigaction(Signal::SIGUSR1, &action) }?;
+    let fanotify = get_init().to_fanotify()?;
+    // interrupt the poll more often than its timeout, which mustn't restart it each time
+    let poller = unsafe { libc::pthread_self() };
+    let done = Arc::new(AtomicBool::new(false));
+    let signaler = {
+        let done = done.clone();
+        std::thread::spawn(move || {
+            let start = Instant::now();
+            while !done.load(Ordering::Relaxed) && start.elapsed() < Duration::from_secs(2) {
+                unsafe { libc::pthread_kill(poller, libc::SIGUSR1) };
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        })
+    };
+    let start = Instant::now();
+    assert!(!fanotify.readable(Some(Duration::from_millis(100)))?);
+    let elapsed = start.elapsed();
+    done.store(true, Ordering::Relaxed);
+    signaler.join().unwrap();
+    assert!(elapsed >= Duration::from_millis(100));
+    assert!(elapsed < Duration::from_secs(1), "waited {:?}", elapsed);
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_read_cancelled() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let mut fanotify = get_init()
+        .to_fanotify()?
+        .into_async()?
+        .buffered_default();
+    let cancelled = block_on(fanotify.read_cancellable(std::future::ready(())))?.is_none();
+    assert!(cancelled);
+    assert!(fanotify.buffer.events.is_empty());
+    Ok(())
+}
+
+#[test]
+fn read_all_pending() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    // only room for 2 events per read
+    let mut fanotify = get_init()
+        .to_fanotify()?
+        .buffered_with_size(EventBufferSize { events: 64, responses: 0 });
+    let paths = (0..8)
+        .map(|i| dir.path().join(i.to_string()))
+        .collect::<Vec<_>>();
+    for path in &paths {
+        fs::write(path, "")?;
+    }
+    fanotify.mark(mark::One {
+        action: Add,
+        what: MountPoint,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(dir.path()),
+    }.try_into()?).map_err(|e| e.error)?;
+    for path in &paths {
+        fs::File::open(path)?;
+    }
+    assert!(fanotify.fanotify.pending_bytes()? > 0);
+    assert!(paths.len() > 2);
+    let num_events = fanotify
+        .read_all_pending(64 * 1024)?
+        .all()
+        .map(|it| it.expect("event error"))
+        .filter(|it| it.id().is_generated_by_self())
+        .count();
+    assert!(num_events >= paths.len());
+    let stats = fanotify.stats();
+    assert_eq!(stats.reads, 1);
+    assert!(stats.events >= paths.len() as u64);
+    assert!(stats.average_batch_size() >= paths.len() as f64);
+    // it stops once it's read enough, leaving the rest pending
+    let mut fanotify = get_init()
+        .to_fanotify()?
+        .buffered_with_size(EventBufferSize { events: 64, responses: 0 });
+    fanotify.mark(mark::One {
+        action: Add,
+        what: MountPoint,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(dir.path()),
+    }.try_into()?).map_err(|e| e.error)?;
+    for path in &paths {
+        fs::File::open(path)?;
+    }
+    // the first read has already read that much
+    let num_events = fanotify
+        .read_all_pending(1)?
+        .all()
+        .map(|it| it.expect("event error"))
+        .filter(|it| it.id().is_generated_by_self())
+        .count();
+    assert!(num_events < paths.len());
+    assert!(fanotify.fanotify.pending_bytes()? > 0);
+    Ok(())
+}
+
+#[test]
+fn read_lag() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    // only room for 2 events per read
+    let mut fanotify = get_init()
+        .to_fanotify()?
+        .buffered_with_size(EventBufferSize { events: 64, responses: 0 });
+    let lags = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let hook_lags = lags.clone();
+    fanotify.on_lag(Duration::from_secs(0), move |lag, stats| {
+        assert!(stats.pending_bytes > 0);
+        hook_lags.lock().unwrap().push(lag);
+    });
+    let paths = (0..8)
+        .map(|i| dir.path().join(i.to_string()))
+        .collect::<Vec<_>>();
+    for path in &paths {
+        fs::write(path, "")?;
+    }
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN | Mask::EVENT_ON_CHILD,
+        path: mark::Path::absolute(dir.path()),
+    }.try_into()?).map_err(|e| e.error)?;
+    for path in &paths {
+        fs::File::open(path)?;
+    }
+    fanotify.read()?;
+    assert!(fanotify.stats().pending_bytes > 0);
+    assert_eq!(fanotify.stats().lag(), None);
+    std::thread::sleep(Duration::from_millis(10));
+    fanotify.read()?;
+    let stats = fanotify.stats();
+    assert!(stats.pending_bytes > 0);
+    assert!(stats.read_interval >= Duration::from_millis(10));
+    let lag = stats.lag().expect("lag after two reads");
+    assert!(lag > Duration::from_secs(0));
+    assert_eq!(*lags.lock().unwrap(), vec![lag]);
+    Ok(())
+}
+
+#[test]
+fn open_file_handle() -> AnyResult {
+    if !supports(Full) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let mut fanotify = Init::fid_tracking().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::MODIFY,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    fs::write(&path, "modified")?;
+    let mount = fs::File::open(dir.path())?.apply(|it| unsafe { FD::from_raw_fd(it.into_raw_fd()) });
+    let metadata = fs::metadata(&path)?;
+    let events = fanotify.read()?;
+    let event = events.into_iter().next().expect("one event")?;
+    let file = event.into_file().fid().expect("fid event");
+    let fd = file.handle().open(&mount, HandleOpen::Path)?;
+    assert_eq!(fd.identity()?, (metadata.dev(), metadata.ino()));
+    assert_eq!(fd.path()?, path.canonicalize()?);
+    assert_eq!(file.to_owned().handle().open(&mount, HandleOpen::Directory).err(), Some(Errno::ENOTDIR));
+    Ok(())
+}
+
+#[test]
+fn own_outputs() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let log = root.join("log");
+    let other = root.join("other");
+    fs::write(&log, "")?;
+    fs::write(&other, "")?;
+    let fanotify = get_init().to_fanotify()?;
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::MODIFY | Mask::EVENT_ON_CHILD,
+        path: mark::Path::absolute(&root),
+    }.try_into()?).map_err(|e| e.error)?;
+    let mut outputs = OwnOutputs::new(&fanotify, OwnOutputs::MASK);
+    outputs.register(&log).map_err(|e| e.error)?;
+    fs::write(&log, "mine")?;
+    fs::write(&other, "theirs")?;
+    // rotate the log, which isn't ignored until refreshed
+    fs::rename(&log, root.join("log.1"))?;
+    fs::write(&log, "")?;
+    let report = outputs.refresh();
+    assert!(report.is_complete());
+    assert_eq!(report.applied, 1);
+    fs::write(&log, "mine again")?;
+    drop(outputs);
+    let mut fanotify = fanotify.buffered_default();
+    let mut paths = Vec::new();
+    while fanotify.fanotify.readable(Some(Duration::from_millis(100)))? {
+        for event in fanotify.read()? {
+            paths.extend(event?.file().path().transpose()?);
+        }
+    }
+    assert_eq!(paths, vec![other]);
+    Ok(())
+}
+
+#[test]
+fn suppress_own_changes() -> AnyResult {
+    use fanotify::clock::MockClock;
+    use fanotify::fanotify::suppress::Suppressor;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let mine = root.join("mine");
+    let other = root.join("other");
+    fs::write(&mine, "")?;
+    fs::write(&other, "")?;
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::MODIFY | Mask::EVENT_ON_CHILD,
+        path: mark::Path::absolute(&root),
+    }.try_into()?).map_err(|e| e.error)?;
+    let clock = MockClock::new();
+    let mut suppressor = Suppressor::new().with_clock(clock.shared());
+    let mut read = |suppressor: &mut Suppressor| -> AnyResult<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        while fanotify.fanotify.readable(Some(Duration::from_millis(100)))? {
+            for event in fanotify.read()? {
+                let event = event?;
+                if !suppressor.is_suppressed(&event) {
+                    paths.extend(event.file().path().transpose()?);
+                }
+            }
+        }
+        Ok(paths)
+    };
+    
+    // only the touched paths
+    suppressor.suppress(|scope| {
+        scope.touch(&mine);
+        fs::write(&mine, "mine")
+    })?;
+    fs::write(&other, "other")?;
+    assert_eq!(read(&mut suppressor)?, vec![other.clone()]);
+    assert!(suppressor.is_touched(&mine));
+    
+    // everything from this process
+    suppressor.suppress(|_| fs::write(&other, "mine"))?;
+    assert_eq!(read(&mut suppressor)?, Vec::<PathBuf>::new());
+    assert_eq!(suppressor.active(), 2);
+    clock.advance(Suppressor::DEFAULT_GRACE);
+    assert_eq!(suppressor.active(), 0);
+    fs::write(&other, "other again")?;
+    assert_eq!(read(&mut suppressor)?, vec![other]);
+    assert_eq!(suppressor.suppressed(), 2);
+    Ok(())
+}
+
+#[test]
+fn path_cache() -> AnyResult {
+    use fanotify::event::file::path_cache::PathCache;
+    
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let open = |path: &Path| -> io::Result<FD> {
+        fs::write(path, "")?;
+        Ok(fs::File::open(path)?.apply(|it| unsafe { FD::from_raw_fd(it.into_raw_fd()) }))
+    };
+    let (a, b) = (open(&root.join("a"))?, open(&root.join("b"))?);
+    let mut cache = PathCache::new(1);
+    assert_eq!(cache.path(&a)?, root.join("a"));
+    assert_eq!(cache.len(), 1);
+    // a cached path isn't resolved again, so it's stale after a rename until invalidated
+    fs::rename(root.join("a"), root.join("renamed"))?;
+    assert_eq!(cache.path(&a)?, root.join("a"));
+    assert_eq!(cache.invalidate(a.identity()?), Some(root.join("a")));
+    assert_eq!(cache.path(&a)?, root.join("renamed"));
+    // the least recently used path is evicted
+    assert_eq!(cache.path(&b)?, root.join("b"));
+    assert_eq!(cache.len(), 1);
+    assert_eq!(cache.invalidate(a.identity()?), None);
+    cache.clear();
+    assert!(cache.is_empty());
+    Ok(())
+}
+
+#[test]
+fn proc_root() -> AnyResult {
+    // the proc root is process-wide
+    if !run_alone("proc_root")? {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let path = root.join("file");
+    fs::write(&path, "")?;
+    let fd = fs::File::open(&path)?.apply(|it| unsafe { FD::from_raw_fd(it.into_raw_fd()) });
+    // a proc mounted somewhere else
+    let mounted = root.join("proc");
+    fs::create_dir(&mounted)?;
+    std::os::unix::fs::symlink("/proc/self", mounted.join("self"))?;
+    proc::set_root(&mounted);
+    assert_eq!(proc::root()?, mounted);
+    assert_eq!(proc::self_fd(fd.as_raw_fd())?, mounted.join("self/fd").join(fd.as_raw_fd().to_string()));
+    assert_eq!(fd.path()?, path);
+    // no proc mounted there
+    let empty = root.join("empty");
+    fs::create_dir(&empty)?;
+    proc::set_root(&empty);
+    assert_eq!(fd.path().map_err(|e| e.kind()), Err(io::ErrorKind::NotFound));
+    proc::set_root(proc::DEFAULT_ROOT);
+    assert_eq!(fd.path()?, path);
+    Ok(())
+}
+
+#[test]
+fn proc_free() -> AnyResult {
+    // proc-free mode is process-wide
+    if !run_alone("proc_free")? {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().canonicalize()?.join("file");
+    fs::write(&path, "")?;
+    let fd = fs::File::open(&path)?.apply(|it| unsafe { FD::from_raw_fd(it.into_raw_fd()) });
+    proc::set_unavailable();
+    assert!(!proc::is_available());
+    assert!(proc::is_unavailable(&fd.path().expect_err("proc-free")));
+    if supports(Partial) {
+        let mut fanotify = get_init().to_fanotify()?.buffered_default();
+        fanotify.mark(mark::One {
+            action: Add,
+            what: mark::What::Inode,
+            flags: mark::Flags::empty(),
+            mask: Mask::OPEN,
+            path: mark::Path::absolute(&path),
+        }.try_into()?).map_err(|e| e.error)?;
+        fs::File::open(&path)?;
+        let events = fanotify.read()?.all().collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(events.len(), 1);
+        // self-event detection doesn't need proc
+        assert!(events[0].id().is_generated_by_self());
+        assert!(proc::is_unavailable(&events[0].file().path().expect("fd").expect_err("proc-free")));
+    }
+    proc::set_root(proc::DEFAULT_ROOT);
+    assert!(proc::is_available());
+    assert_eq!(fd.path()?, path);
+    Ok(())
+}
+
+#[test]
+fn fan_out() -> AnyResult {
+    use fanotify::event::sink::FanOut;
+    use fanotify::event::sink::Sink;
+    use fanotify::event::sink::SinkError;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let file = NamedTempFile::new()?;
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(file.path()),
+    }.try_into()?).map_err(|e| e.error)?;
+    fs::File::open(file.path())?;
+    let (mut first, mut last) = (0, 0);
+    {
+        let mut fan_out = FanOut::new()
+            .with(|_: &Event<'_>| -> Result<(), SinkError> {
+                first += 1;
+                Ok(())
+            })
+            .with(|_: &Event<'_>| -> Result<(), SinkError> { Err("failed".into()) });
+        fan_out.add(|_: &Event<'_>| -> Result<(), SinkError> {
+            last += 1;
+            Ok(())
+        });
+        assert_eq!(fan_out.len(), 3);
+        let mut failures = 0;
+        for event in fanotify.read()?.all() {
+            // the failing sink doesn't stop the ones after it
+            let error = fan_out.consume_all(&event?).expect_err("sink failed");
+            assert_eq!(error.errors.iter().map(|(i, _)| *i).collect::<Vec<_>>(), vec![1]);
+            assert_eq!(error.to_string(), "1 sink(s) failed: sink 1: failed");
+            failures += 1;
+        }
+        assert_eq!(failures, 1);
+        // a FanOut is a Sink itself
+        let mut nested = FanOut::new().with(fan_out);
+        fs::File::open(file.path())?;
+        let event = fanotify.read()?.all().next().expect("event")?;
+        assert!(nested.consume(&event).is_err());
+    }
+    assert_eq!((first, last), (2, 2));
+    Ok(())
+}
+
+#[test]
+fn resolve_symlinks() -> AnyResult {
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let real = root.join("real");
+    fs::create_dir(&real)?;
+    std::os::unix::fs::symlink(&real, root.join("link"))?;
+    let path = real.join("file (deleted)");
+    fs::write(&path, "")?;
+    let fd = fs::File::open(root.join("link").join("file (deleted)"))?
+        .apply(|it| unsafe { FD::from_raw_fd(it.into_raw_fd()) });
+    let resolved = fd.resolve(proc::Symlinks::Both)?;
+    // just named like a deleted file
+    assert_eq!(fd.path()?, path);
+    assert_eq!(resolved.state, proc::LinkState::Present);
+    assert_eq!(resolved.literal.as_ref(), Some(&path));
+    assert_eq!(resolved.canonical.as_ref(), Some(&path));
+    fs::remove_file(&path)?;
+    assert_eq!(fd.path()?, path);
+    let resolved = fd.resolve(proc::Symlinks::Canonical)?;
+    assert!(resolved.is_deleted());
+    assert_eq!((resolved.literal, resolved.canonical), (None, None));
+    let resolved = fd.resolve(proc::Symlinks::Literal)?;
+    assert_eq!(resolved.path(), Some(path.as_path()));
+    Ok(())
+}
+
+#[test]
+fn reconcile_deleted() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let doomed = root.join("doomed");
+    fs::write(&doomed, "")?;
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::CLOSE_WRITE | Mask::EVENT_ON_CHILD,
+        path: mark::Path::absolute(&root),
+    }.try_into()?).map_err(|e| e.error)?;
+    let mut changes = Vec::new();
+    let mut reconciler = Reconciler::new(&root, |change: &Change| changes.push(change.clone()))?;
+    {
+        let mut file = fs::OpenOptions::new().write(true).open(&doomed)?;
+        file.write_all(b"last words")?;
+        fs::remove_file(&doomed)?;
+    }
+    let events = fanotify.read()?.all().collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(events.len(), 1);
+    let resolved = events[0].file().resolve(proc::Symlinks::Literal).transpose()?;
+    assert!(resolved.as_ref().is_some_and(|it| it.is_deleted()));
+    assert_eq!(resolved.and_then(|it| it.literal), Some(doomed.clone()));
+    Reconciler::apply(&mut reconciler, &events[0])?;
+    assert!(reconciler.snapshot().is_empty());
+    drop(events);
+    drop(reconciler);
+    assert!(matches!(changes.as_slice(), [Change::Removed { path, .. }] if path == &doomed));
+    Ok(())
+}
+
+#[test]
+fn decision_cache() -> AnyResult {
+    let mut file = NamedTempFile::new()?;
+    let fd = file.reopen()?.apply(|it| unsafe { FD::from_raw_fd(it.into_raw_fd()) });
+    let mut cache = DecisionCache::new(16, None);
+    let mut scans = 0;
+    let mut scan = || {
+        scans += 1;
+        PermissionDecision::Deny
+    };
+    assert_eq!(cache.decide(&fd, &mut scan), PermissionDecision::Deny);
+    assert_eq!(cache.decide(&fd, &mut scan), PermissionDecision::Deny);
+    assert_eq!((cache.hits(), cache.misses()), (1, 1));
+    // a new version of the file needs a new scan
+    file.write_all(b"changed")?;
+    assert_eq!(cache.decide(&fd, &mut scan), PermissionDecision::Deny);
+    assert_eq!((cache.hits(), cache.misses()), (1, 2));
+    assert_eq!(cache.invalidate(fd.identity()?), Some(PermissionDecision::Deny));
+    assert!(cache.is_empty());
+    assert_eq!(scans, 2);
+    // a decision made while the file changed isn't cached
+    let mut writer = file.reopen()?;
+    assert_eq!(cache.decide(&fd, || {
+        writer.write_all(b"changed again").unwrap();
+        PermissionDecision::Allow
+    }), PermissionDecision::Allow);
+    assert!(cache.is_empty());
+    Ok(())
+}
+
+#[test]
+fn decision_cache_prewarm() -> AnyResult {
+    let dir = tempfile::tempdir()?;
+    fs::write(dir.path().join("allowed"), "allowed")?;
+    fs::write(dir.path().join("denied"), "denied")?;
+    fs::create_dir(dir.path().join("sub"))?;
+    fs::write(dir.path().join("sub/nested"), "nested")?;
+    let open = |name: &str| -> io::Result<FD> {
+        let file = fs::File::open(dir.path().join(name))?;
+        Ok(unsafe { FD::from_raw_fd(file.into_raw_fd()) })
+    };
+    
+    let mut cache = DecisionCache::new(16, None);
+    let report = cache.prewarm(dir.path(), false, |path, file| {
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).expect("readable");
+        assert!(path.ends_with(&contents));
+        match contents.as_str() {
+            "denied" => PermissionDecision::Deny,
+            _ => PermissionDecision::Allow,
+        }
+    });
+    assert_eq!((report.decided, report.failed.len(), report.full), (2, 0, false));
+    assert_eq!(cache.get(&open("allowed")?)?, Some(PermissionDecision::Allow));
+    assert_eq!(cache.get(&open("denied")?)?, Some(PermissionDecision::Deny));
+    assert_eq!(cache.get(&open("sub/nested")?)?, None);
+    assert_eq!(cache.decide(&open("allowed")?, || unreachable!()), PermissionDecision::Allow);
+    assert_eq!((cache.hits(), cache.misses()), (1, 0));
+    
+    let mut cache = DecisionCache::new(1, None);
+    let report = cache.prewarm(dir.path(), true, |_, _| PermissionDecision::Allow);
+    assert_eq!((report.decided, report.full), (1, true));
+    
+    // a file changed while deciding isn't cached
+    let mut cache = DecisionCache::new(16, None);
+    let report = cache.prewarm(dir.path(), false, |path, _| {
+        if path.ends_with("allowed") {
+            fs::write(path, "changed").expect("writable");
+        }
+        PermissionDecision::Allow
+    });
+    assert_eq!((report.decided, report.changed, report.failed.len()), (1, 1, 0));
+    assert_eq!(cache.get(&open("allowed")?)?, None);
+    fs::write(dir.path().join("allowed"), "allowed")?;
+    
+    #[cfg(feature = "integrity")]
+    {
+        use fanotify::event::file::exe_allowlist::ExeAllowlist;
+        
+        let mut allowlist = ExeAllowlist::new();
+        allowlist.allow_file(dir.path().join("sub/nested"))?;
+        let report = allowlist.prewarm(dir.path(), true);
+        assert_eq!(report.decided, 3);
+        assert_eq!(allowlist.cache().get(&open("sub/nested")?)?, Some(PermissionDecision::Allow));
+        assert_eq!(allowlist.cache().get(&open("allowed")?)?, Some(PermissionDecision::Deny));
+    }
+    Ok(())
+}
+
+#[test]
+fn file_type() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let path = root.join("file");
+    fs::write(&path, "")?;
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN | Mask::ON_DIR | Mask::EVENT_ON_CHILD,
+        path: mark::Path::absolute(&root),
+    }.try_into()?).map_err(|e| e.error)?;
+    let _ = fs::read(&path)?;
+    let _ = fs::read_dir(&root)?;
+    let events = fanotify.read()?.all().collect::<Result<Vec<_>, _>>()?;
+    let types = events
+        .iter()
+        .map(|it| it.file().file_type().transpose())
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(types, vec![Some(FileType::Regular), Some(FileType::Directory)]);
+    assert!(events[0].file().is_regular_file());
+    assert!(!events[1].file().is_regular_file());
+    Ok(())
+}
+
+#[test]
+fn ring_export() -> AnyResult {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("ring");
+    let mut writer = RingWriter::create(&path, 2)?;
+    let mut reader = RingReader::open(&path)?;
+    assert_eq!(reader.capacity(), 2);
+    let mut record = EventRecord::empty();
+    record.mask = Mask::OPEN.bits();
+    record.set_path(Path::new("/etc/passwd"));
+    assert!(writer.push(&record));
+    assert!(writer.push(&EventRecord::empty()));
+    assert!(!writer.push(&record));
+    assert_eq!((reader.len(), reader.dropped()), (2, 1));
+    let first = reader.pop().expect("record");
+    assert_eq!(first.mask(), Mask::OPEN);
+    assert_eq!(first.path(), Some(Path::new("/etc/passwd")));
+    assert_eq!(reader.pop(), Some(EventRecord::empty()));
+    assert_eq!(reader.pop(), None);
+    // it wraps around
+    assert!(writer.push(&record));
+    assert_eq!(reader.pop(), Some(record));
+    assert!(writer.is_empty());
+    // replacing the file doesn't affect a reader of the old one
+    assert!(writer.push(&record));
+    let new_writer = RingWriter::create(&path, 1)?;
+    assert_eq!((reader.capacity(), reader.pop()), (2, Some(record)));
+    assert_eq!(RingReader::open(&path)?.capacity(), new_writer.capacity());
+    assert_eq!(fs::read_dir(dir.path())?.count(), 1);
+    assert!(RingWriter::create(&path, u64::MAX).is_err());
+    let mut long = EventRecord::empty();
+    long.set_path(&Path::new("/").join("a".repeat(EventRecord::PATH_CAPACITY + 1)));
+    assert_eq!(long.path().map(|it| it.as_os_str().len()), Some(EventRecord::PATH_CAPACITY));
+    assert_ne!(long.flags & EventRecord::IS_PATH_TRUNCATED, 0);
+    Ok(())
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn event_server() -> AnyResult {
+    use fanotify::server::EventClient;
+    use fanotify::server::EventServer;
+    use fanotify::server::Encoding;
+    use fanotify::server::json;
+    
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("socket");
+    let mut server = EventServer::bind(&path)?;
+    // the client blocks until the server accepts it and sends the handshake
+    let client = {
+        let path = path.clone();
+        std::thread::spawn(move || EventClient::connect(path))
+    };
+    while server.accept()? == 0 {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    let mut client = client.join().expect("client panicked")?;
+    assert_eq!(server.clients(), 1);
+    let mut record = EventRecord::empty();
+    record.mask = (Mask::OPEN | Mask::ON_DIR).bits();
+    record.pid = 42;
+    record.set_path(Path::new("/tmp/\"quoted\"\n\u{1}\u{1F980}"));
+    assert_eq!(server.broadcast(&record), 1);
+    assert_eq!(client.recv()?, Some(record));
+    assert_eq!(json::decode(&json::encode(&record))?, record);
+    assert_eq!(
+        json::decode(r#" { "mask" : 1, "future" : "field", "path" : "\ud83e\udd80" } "#)?.path(),
+        Some(Path::new("\u{1F980}")),
+    );
+    assert!(json::decode(r#"{"pid":1}"#).is_err());
+    server.set_encoding(Encoding::Cbor);
+    let cbor_client = {
+        let path = path.clone();
+        std::thread::spawn(move || EventClient::connect(path))
+    };
+    while server.accept()? == 0 {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    let mut cbor_client = cbor_client.join().expect("client panicked")?;
+    assert_eq!((client.encoding(), cbor_client.encoding()), (Encoding::Json, Encoding::Cbor));
+    assert_eq!(server.broadcast(&record), 2);
+    assert_eq!(client.recv()?, Some(record));
+    assert_eq!(cbor_client.recv()?, Some(record));
+    // a consumer that never reads is dropped once its queue is full, without blocking the others
+    server.set_queue_limit(4);
+    let _slow = std::os::unix::net::UnixStream::connect(&path)?;
+    while server.accept()? == 0 {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(server.clients(), 3);
+    let mut sent = 0;
+    while server.broadcast(&record) == 3 {
+        assert_eq!(client.recv()?, Some(record));
+        assert_eq!(cbor_client.recv()?, Some(record));
+        sent += 1;
+    }
+    assert_eq!(server.clients(), 2);
+    assert!(sent > 4);
+    assert_eq!(client.recv()?, Some(record));
+    assert_eq!(server.flush(), 0);
+    drop(server);
+    assert_eq!(client.recv()?, None);
+    Ok(())
+}
+
+#[test]
+fn compact_encoding() -> AnyResult {
+    let mut record = EventRecord::empty();
+    record.mask = (Mask::CLOSE_WRITE | Mask::ON_DIR).bits();
+    record.pid = -1;
+    record.device = 2049;
+    record.inode = u64::MAX;
+    record.flags |= EventRecord::HAS_IDENTITY | EventRecord::IS_PERMISSION;
+    // not UTF-8
+    record.set_path(Path::new(std::ffi::OsStr::from_bytes(b"/tmp/\xff")));
+    let mut bytes = Vec::new();
+    compact::encode(&record, &mut bytes);
+    compact::encode(&EventRecord::empty(), &mut bytes);
+    assert_eq!(compact::decode_all(&bytes)?, vec![record, EventRecord::empty()]);
+    let (_, len) = compact::decode(&bytes)?;
+    assert!(len < 40);
+    assert_eq!(compact::decode(&bytes[..len - 1]), Err(compact::DecodeError::Truncated));
+    bytes[1] = compact::VERSION + 1;
+    assert_eq!(compact::decode(&bytes), Err(compact::DecodeError::UnsupportedVersion(compact::VERSION + 1)));
+    Ok(())
+}
+
+#[test]
+fn event_archive() -> AnyResult {
+    let dir = tempfile::tempdir()?;
+    let records = (0..5)
+        .map(|i| {
+            let mut record = EventRecord::empty();
+            record.mask = Mask::OPEN.bits();
+            record.pid = i;
+            record
+        })
+        .collect::<Vec<_>>();
+    let rotation = Rotation {
+        max_bytes: 1,
+        max_age: None,
+    };
+    let mut writer = ArchiveWriter::open(dir.path(), rotation)?;
+    for record in &records[..3] {
+        writer.append(record)?;
+    }
+    // only finished segments are visible
+    assert_eq!(ArchiveReader::open(dir.path())?.segments().len(), 2);
+    writer.close()?;
+    // a reopened writer continues after the existing segments
+    let mut writer = ArchiveWriter::open(dir.path(), rotation)?;
+    for record in &records[3..] {
+        writer.append(record)?;
+    }
+    drop(writer);
+    let reader = ArchiveReader::open(dir.path())?;
+    let sequences = reader.segments().iter().map(|it| it.sequence).collect::<Vec<_>>();
+    assert_eq!(sequences, vec![0, 1, 2, 3, 4]);
+    assert_eq!(reader.records().collect::<io::Result<Vec<_>>>()?, records);
+    assert_eq!(reader.segments_between(0, u64::MAX).count(), 5);
+    assert_eq!(reader.segments_between(0, 0).count(), 0);
+    #[cfg(feature = "zstd")]
+    {
+        let dir = tempfile::tempdir()?;
+        let mut writer = ArchiveWriter::open(dir.path(), Rotation::default())?.with_compression(0);
+        for record in &records {
+            writer.append(record)?;
+        }
+        writer.close()?;
+        let reader = ArchiveReader::open(dir.path())?;
+        assert!(reader.segments().iter().all(|it| it.compressed));
+        assert_eq!(reader.records().collect::<io::Result<Vec<_>>>()?, records);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "systemd")]
+#[test]
+fn systemd() -> AnyResult {
+    use std::os::unix::net::SocketAddr;
+    use std::os::unix::net::UnixDatagram;
+    
+    use fanotify::event::sink::Sink;
+    use fanotify::systemd::JournalSink;
+    use fanotify::systemd::Notifier;
+    
+    let dir = tempfile::tempdir()?;
+    let notify_path = dir.path().join("notify");
+    let notify_socket = UnixDatagram::bind(&notify_path)?;
+    let mut notifier = Notifier::new(SocketAddr::from_pathname(&notify_path)?, Some(Duration::from_secs(10)))?;
+    let mut buffer = [0; 4096];
+    let mut received = || -> io::Result<String> {
+        let len = notify_socket.recv(&mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer[..len]).into_owned())
+    };
+    notifier.ready()?;
+    assert_eq!(received()?, "READY=1");
+    notifier.status("watching\n/etc")?;
+    assert_eq!(received()?, "STATUS=watching /etc");
+    notifier.keep_alive()?;
+    assert_eq!(received()?, "WATCHDOG=1");
+    // not due again for another 5 seconds
+    notifier.keep_alive()?;
+    assert!(notifier.next_keep_alive().expect("watchdog") > Duration::from_secs(4));
+    assert!(!Notifier::inactive().is_active());
+    Notifier::inactive().ready()?;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let journal_path = dir.path().join("journal");
+    let journal_socket = UnixDatagram::bind(&journal_path)?;
+    let mut journal = JournalSink::connect("fanotify-test", &journal_path)?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    let _ = fs::read(&path)?;
+    for event in fanotify.read()?.all() {
+        journal.consume(&event?).map_err(anyhow::Error::msg)?;
+    }
+    let len = journal_socket.recv(&mut buffer)?;
+    let entry = String::from_utf8_lossy(&buffer[..len]).into_owned();
+    let fields = entry.lines().collect::<Vec<_>>();
+    assert!(fields.contains(&"PRIORITY=6"));
+    assert!(fields.contains(&"SYSLOG_IDENTIFIER=fanotify-test"));
+    assert!(fields.contains(&format!("FANOTIFY_PATH={}", path.display()).as_str()));
+    assert!(fields.contains(&format!("FANOTIFY_PID={}", std::process::id()).as_str()));
+    Ok(())
+}
+
+#[test]
+fn alerts() -> AnyResult {
+    use std::io::BufRead;
+    use std::io::BufReader;
+    use std::os::unix::net::UnixListener;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    
+    use fanotify::alert::AlertSink;
+    use fanotify::alert::Incident;
+    use fanotify::alert::Rule;
+    use fanotify::alert::Severity;
+    use fanotify::alert::UnixSocketAlert;
+    use fanotify::event::sink::Sink;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let sensitive = root.join("shadow");
+    fs::write(&sensitive, "")?;
+    let socket = root.join("alerts.sock");
+    let listener = UnixListener::bind(&socket)?;
+    let incidents = Arc::new(Mutex::new(Vec::<Incident>::new()));
+    let sink = AlertSink::new(Severity::High)
+        .rule(Rule::modified("shadow modified", Severity::Critical, vec![&sensitive]))
+        .rule(Rule::new("anything", Severity::Low, |_: &Event<'_>| true))
+        .alert(UnixSocketAlert::new(&socket))
+        .alert({
+            let incidents = incidents.clone();
+            move |incident: &Incident| {
+                incidents.lock().unwrap().push(incident.clone());
+                Ok(())
+            }
+        });
+    #[cfg(feature = "http")]
+    let (sink, webhook) = {
+        use std::net::TcpListener;
+        
+        use fanotify::alert::WebhookAlert;
+        
+        let server = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/hooks/fanotify", server.local_addr()?);
+        let webhook = std::thread::spawn(move || -> io::Result<String> {
+            let (stream, _) = server.accept()?;
+            let mut reader = BufReader::new(stream.try_clone()?);
+            let mut request = String::new();
+            let mut len = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line)?;
+                if let Some(value) = line.strip_prefix("Content-Length: ") {
+                    len = value.trim().parse().unwrap();
+                }
+                request.push_str(&line);
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body)?;
+            request.push_str(std::str::from_utf8(&body).unwrap());
+            (&stream).write_all(b"HTTP/1.1 204 No Content\r\n\r\n")?;
+            Ok(request)
+        });
+        assert!(WebhookAlert::new("ftp://example.com").is_err());
+        (sink.alert(WebhookAlert::new(url)?), webhook)
+    };
+    let mut sink = sink;
+    let fanotify = get_init().to_fanotify()?;
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN | Mask::MODIFY | Mask::CLOSE_WRITE,
+        path: mark::Path::absolute(&sensitive),
+    }.try_into()?).map_err(|e| e.error)?;
+    let mut fanotify = fanotify.buffered_default();
+    fs::write(&sensitive, "root:x")?;
+    for event in fanotify.read()? {
+        sink.consume(&event?).map_err(anyhow::Error::msg)?;
+    }
+    let incidents = incidents.lock().unwrap().clone();
+    // only the one for the modification, since the rest are below the minimum severity
+    assert_eq!(incidents.len(), 1);
+    let incident = &incidents[0];
+    assert_eq!((incident.rule.as_str(), incident.severity), ("shadow modified", Severity::Critical));
+    assert_eq!(incident.record.path(), Some(sensitive.as_path()));
+    let json = incident.to_json();
+    assert!(json.starts_with(r#"{"rule":"shadow modified","severity":"critical","record":{"#));
+    let (stream, _) = listener.accept()?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    assert_eq!(line, format!("{}\n", json));
+    #[cfg(feature = "http")]
+    {
+        let request = webhook.join().unwrap()?;
+        assert!(request.starts_with("POST /hooks/fanotify HTTP/1.1\r\n"));
+        assert!(request.contains("Content-Type: application/json\r\n"));
+        assert!(request.ends_with(&format!("\r\n\r\n{}", json)));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "trigger")]
+#[test]
+fn trigger() -> AnyResult {
+    use fanotify::event::sink::Sink;
+    use fanotify::trigger::Trigger;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let watched = root.join("watched");
+    let ignored = root.join("ignored");
+    let out = root.join("out");
+    fs::write(&watched, "")?;
+    fs::write(&ignored, "")?;
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    for path in [&watched, &ignored].iter() {
+        fanotify.mark(mark::One {
+            action: Add,
+            what: mark::What::Inode,
+            flags: mark::Flags::empty(),
+            mask: Mask::OPEN,
+            path: mark::Path::absolute(path),
+        }.try_into()?).map_err(|e| e.error)?;
+    }
+    let mut trigger = Trigger::new("sh", vec![
+        "-c".to_owned(),
+        r#"echo "$1 $FANOTIFY_MASK $FANOTIFY_COUNT" >> "$2""#.to_owned(),
+        "sh".to_owned(),
+        "${FANOTIFY_PATH}".to_owned(),
+        out.to_str().unwrap().to_owned(),
+    ])
+        .with_filter({
+            let watched = watched.clone();
+            move |event: &Event<'_>| event.file().path().and_then(Result::ok) == Some(watched.clone())
+        })
+        .with_debounce(Duration::from_millis(100));
+    fs::File::open(&watched)?;
+    fs::File::open(&ignored)?;
+    for event in fanotify.read()? {
+        trigger.consume(&event?).map_err(anyhow::Error::msg)?;
+    }
+    // still debouncing
+    assert!(trigger.is_pending());
+    assert!(!trigger.poll()?);
+    std::thread::sleep(trigger.next_poll().unwrap());
+    assert!(trigger.poll()?);
+    trigger.wait()?;
+    assert_eq!(fs::read_to_string(&out)?, format!("{} OPEN 1\n", watched.display()));
+    let stats = trigger.stats();
+    assert_eq!((stats.matched, stats.runs, stats.failures), (1, 1, 0));
+    Ok(())
+}
+
+#[cfg(feature = "trigger")]
+#[test]
+fn trigger_malicious_path() -> AnyResult {
+    use fanotify::event::sink::Sink;
+    use fanotify::trigger::Trigger;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let pwned = root.join("pwned");
+    let watched = root.join("$(touch pwned); touch pwned; '\"");
+    let out = root.join("out");
+    fs::write(&watched, "")?;
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(&watched),
+    }.try_into()?).map_err(|e| e.error)?;
+    // the path is only ever a whole argument or an environment variable, never part of the script
+    let mut trigger = Trigger::new("sh", vec![
+        "-c".to_owned(),
+        r#"cd "$3" && echo "$FANOTIFY_PATH" > "$2" && echo "$1" >> "$2" && echo $FANOTIFY_PATH >> "$2""#.to_owned(),
+        "sh".to_owned(),
+        "$FANOTIFY_PATH".to_owned(),
+        out.to_str().unwrap().to_owned(),
+        root.to_str().unwrap().to_owned(),
+    ])
+        .with_debounce(Duration::from_millis(0));
+    fs::File::open(&watched)?;
+    for event in fanotify.read()? {
+        trigger.consume(&event?).map_err(anyhow::Error::msg)?;
+    }
+    trigger.flush()?;
+    trigger.wait()?;
+    assert!(!pwned.exists());
+    let path = watched.display();
+    assert_eq!(fs::read_to_string(&out)?, format!("{}\n{}\n{}\n", path, path, path));
+    assert_eq!(trigger.stats().failures, 0);
+    Ok(())
+}
+
+#[cfg(feature = "integrity")]
+#[test]
+fn integrity() -> AnyResult {
+    use fanotify::integrity::IntegrityMonitor;
+    use fanotify::integrity::IntegrityViolation;
+    use fanotify::integrity::Manifest;
+    use fanotify::integrity::sha256;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let config = root.join("config");
+    let binary = root.join("binary");
+    let untracked = root.join("untracked");
+    fs::write(&config, "known")?;
+    fs::write(&binary, "known")?;
+    let manifest = Manifest::generate(vec![&config, &binary])?;
+    assert_eq!(manifest.get(&config), Some(&sha256::hash(b"known")));
+    assert_eq!(Manifest::parse(&manifest.to_string())?, manifest);
+    assert!(Manifest::parse("abc  /etc/passwd").is_err());
+    let monitor = IntegrityMonitor::new(manifest);
+    assert_eq!(monitor.directories().collect::<Vec<_>>(), vec![root.as_path()]);
+    assert!(monitor.verify_all().is_empty());
+    
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    monitor.mark(&fanotify).map_err(|e| e.error)?;
+    fs::write(&config, "tampered")?;
+    fs::write(&untracked, "anything")?;
+    let mut violations = Vec::new();
+    for event in fanotify.read()? {
+        violations.extend(monitor.check(&event?));
+    }
+    assert!(!violations.is_empty());
+    for violation in &violations {
+        match violation {
+            IntegrityViolation::Modified { path, expected, actual } => {
+                assert_eq!(path, &config);
+                assert_eq!(expected, &sha256::hash(b"known"));
+                assert_eq!(actual, &sha256::hash(b"tampered"));
+            }
+            _ => panic!("unexpected violation: {}", violation),
+        }
+    }
+    
+    fs::remove_file(&binary)?;
+    let violations = monitor.verify_all();
+    assert_eq!(violations.len(), 2);
+    assert!(matches!(&violations[0], IntegrityViolation::Missing { path } if path == &binary));
+    assert!(matches!(&violations[1], IntegrityViolation::Modified { path, .. } if path == &config));
+    Ok(())
+}
+
+#[cfg(feature = "integrity")]
+#[test]
+fn integrity_xattr() -> AnyResult {
+    use fanotify::integrity::sha256;
+    use fanotify::integrity::xattr::XattrMismatch;
+    use fanotify::integrity::xattr::XattrVerifier;
+    
+    let digest = sha256::hash(b"contents");
+    assert_eq!(XattrVerifier::parse(&digest), Some(digest));
+    assert_eq!(XattrVerifier::parse(format!("{}\n", sha256::to_hex(&digest)).as_bytes()), Some(digest));
+    assert_eq!(XattrVerifier::parse(b"not a digest"), None);
+    
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "contents")?;
+    let open = || -> io::Result<FD> {
+        let file = fs::File::open(&path)?;
+        Ok(unsafe { FD::from_raw_fd(file.into_raw_fd()) })
+    };
+    let mut verifier = XattrVerifier::new();
+    assert_eq!(verifier.name(), "user.integrity.sha256");
+    assert!(matches!(verifier.verify(&open()?), Err(XattrMismatch::Missing)));
+    assert_eq!(verifier.decide_fd(&open()?), PermissionDecision::Deny);
+    let verifier_allowing_missing = XattrVerifier::new().with_missing(PermissionDecision::Allow);
+    assert_eq!(verifier_allowing_missing.decision(&verifier_allowing_missing.verify(&open()?)), PermissionDecision::Allow);
+    
+    let name = std::ffi::CString::new(XattrVerifier::DEFAULT_NAME)?;
+    let value = sha256::to_hex(&digest);
+    let fd = open()?;
+    let set = unsafe {
+        libc::fsetxattr(fd.as_raw_fd(), name.as_ptr(), value.as_ptr() as *const libc::c_void, value.len(), 0)
+    };
+    if set != 0 {
+        // the filesystem doesn't support user extended attributes
+        return Ok(());
+    }
+    assert_eq!(fd.xattr(&name)?, Some(value.into_bytes()));
+    let mut verifier = XattrVerifier::new();
+    assert!(verifier.verify(&fd).is_ok());
+    assert_eq!(verifier.decide_fd(&fd), PermissionDecision::Allow);
+    assert_eq!(verifier.decide_fd(&fd), PermissionDecision::Allow);
+    assert_eq!((verifier.cache().hits(), verifier.cache().misses()), (1, 1));
+    // a different size, so the cached decision is stale even with coarse timestamps
+    fs::write(&path, "modified contents")?;
+    match verifier.verify(&fd) {
+        Err(XattrMismatch::Modified { expected, actual }) => {
+            assert_eq!(expected, digest);
+            assert_eq!(actual, sha256::hash(b"modified contents"));
+        }
+        result => panic!("expected a modification, got {:?}", result),
+    }
+    assert_eq!(verifier.decide_fd(&fd), PermissionDecision::Deny);
+    Ok(())
+}
+
+#[test]
+fn mark_template() -> AnyResult {
+    use fanotify::mark::MarkTemplate;
+    use fanotify::mark::TemplateError;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    for name in ["Downloads", "Documents", "Music"].iter() {
+        fs::create_dir(root.join(name))?;
+    }
+    for name in ["a.txt", "b.txt", ".hidden.txt", "c.rs"].iter() {
+        fs::write(root.join("Documents").join(name), "")?;
+    }
+    let root_var = root.to_str().unwrap().to_owned();
+    let var = |name: &str| if name == "ROOT" { Some(root_var.clone()) } else { None };
+    
+    let template = MarkTemplate::parse("watch $ROOT/{Downloads,Documents} for modify | CREATE")?;
+    assert_eq!(template.mask(), Mask::MODIFY | Mask::CREATE);
+    assert_eq!(template.expand_with(var)?, vec![root.join("Downloads"), root.join("Documents")]);
+    let template = MarkTemplate::new("${ROOT}/Doc*/[a-b].txt", Mask::OPEN);
+    assert_eq!(template.expand_with(var)?, vec![root.join("Documents/a.txt"), root.join("Documents/b.txt")]);
+    assert_eq!(
+        MarkTemplate::new("$ROOT/*/missing*", Mask::OPEN).expand_with(var),
+        Err(TemplateError::NoMatches("$ROOT/*/missing*".to_owned())),
+    );
+    assert_eq!(
+        MarkTemplate::parse("$ROOT for OPEN | NOPE").map(|_| ()),
+        Err(TemplateError::UnknownFlag("NOPE".to_owned())),
+    );
+    
+    // without variables, since those come from the environment here
+    let template = MarkTemplate::new(format!("{}/{{Music,Doc*}}/*.txt", root.display()), Mask::OPEN);
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    let checked = template.check(&fanotify)?;
+    let marks = template.mark(&fanotify)?;
+    assert_eq!(checked, marks);
+    assert_eq!(marks.iter().map(|it| it.path.clone()).collect::<Vec<_>>(), vec![
+        root.join("Documents/a.txt"),
+        root.join("Documents/b.txt"),
+    ]);
+    fs::File::open(root.join("Documents/b.txt"))?;
+    let paths = fanotify
+        .read()?
+        .all()
+        .map(|it| it.expect("event error").file().path().unwrap().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, vec![root.join("Documents/b.txt")]);
+    let missing = MarkTemplate::new(format!("{}/Music/none.txt", root.display()), Mask::OPEN);
+    assert!(matches!(missing.check(&fanotify), Err(TemplateError::Mark { error: mark::RawError::PathDoesNotExist, .. })));
+    Ok(())
+}
+
+#[test]
+fn mark_batch() -> AnyResult {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    
+    use fanotify::mark::MarkBatch;
+    use fanotify::mark::directory_marks;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    for i in 0..4 {
+        for j in 0..4 {
+            fs::create_dir_all(root.join(i.to_string()).join(j.to_string()))?;
+        }
+    }
+    fs::write(root.join("3/2/file"), "")?;
+    let mut marks = directory_marks(&root, Mask::OPEN)?;
+    assert_eq!(marks.len(), 1 + 4 + 4 * 4);
+    marks.push(fanotify::mark::OwnedMark {
+        path: root.join("missing"),
+        ..marks[0].clone()
+    });
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    let progress = AtomicUsize::new(0);
+    let report = MarkBatch::new()
+        .with_concurrency(4)
+        .with_rate_limit(10_000)
+        .on_progress(|it| {
+            progress.fetch_max(it.done, Ordering::SeqCst);
+        })
+        .apply_all(&fanotify.fanotify, &marks);
+    assert_eq!(report.applied, marks.len() - 1);
+    assert_eq!(report.failed, vec![(marks[marks.len() - 1].clone(), mark::RawError::PathDoesNotExist)]);
+    assert_eq!(progress.load(Ordering::SeqCst), marks.len());
+    fs::File::open(root.join("3/2/file"))?;
+    let paths = fanotify
+        .read()?
+        .all()
+        .map(|it| it.expect("event error").file().path().unwrap().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, vec![root.join("3/2/file")]);
+    Ok(())
+}
+
+#[test]
+fn verify_init() -> AnyResult {
+    use fanotify::fanotify::verify::InitVerifyError;
+    use fanotify::init::InitDifference;
+    use fanotify::init::RawInit;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let init = Init {
+        flags: Flags::CLOSE_ON_EXEC | Flags::NON_BLOCKING,
+        event_flags: init::EventFlags::CLOSE_ON_EXEC,
+        ..Default::default()
+    };
+    let fd = init.to_fanotify()?.into_raw_fd();
+    assert_eq!(RawInit::of_fd(fd)?, Some(init.as_raw()));
+    let wrong = Init::notification();
+    match unsafe { fanotify::raw::fanotify_from_raw_fd_verified(fd, wrong.as_raw()) } {
+        Err(InitVerifyError::Mismatch { actual, diff, .. }) => {
+            assert_eq!(actual, Some(init.as_raw()));
+            assert_eq!(diff.differences, vec![InitDifference::Flags {
+                only_this: Flags::empty(),
+                only_other: Flags::NON_BLOCKING,
+            }]);
+        }
+        result => panic!("expected a mismatch, not {:?}", result.map(|it| it.init())),
+    }
+    // the fd wasn't taken, so it can still be used
+    let fanotify = unsafe { fanotify::raw::fanotify_from_raw_fd_verified(fd, init.as_raw()) }?;
+    fanotify.verify_init()?;
+    
+    // the fd can be made blocking after fanotify_init(), which the group's fdinfo doesn't show
+    assert_ne!(unsafe { libc::fcntl(fd, libc::F_SETFL, 0) }, -1);
+    let blocking = Init {
+        flags: Flags::CLOSE_ON_EXEC,
+        ..init
+    };
+    assert_eq!(RawInit::of_fd(fd)?, Some(blocking.as_raw()));
+    assert!(fanotify.verify_init().is_err());
+    
+    let file = tempfile()?;
+    assert_eq!(RawInit::of_fd(file.as_raw_fd())?, None);
+    assert!(matches!(
+        unsafe { fanotify::raw::fanotify_from_raw_fd_verified(file.as_raw_fd(), init.as_raw()) },
+        Err(InitVerifyError::NotFanotify { .. }),
+    ));
+    Ok(())
+}
+
+#[test]
+fn safe_constructors() -> AnyResult {
+    use std::os::unix::io::AsFd;
+    use std::os::unix::io::OwnedFd;
+    
+    use fanotify::fanotify::Fanotify;
+    use fanotify::fanotify::verify::InitVerifyError;
+    use fanotify::mark::DirFd;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let dir_file = fs::File::open(dir.path())?;
+    assert_eq!(DirFd::try_from_fd(dir_file.as_fd())?, DirFd::directory(&dir_file));
+    let file = tempfile()?;
+    assert_eq!(DirFd::try_from_fd(file.as_fd()), Err(Errno::ENOTDIR));
+    
+    let init = get_init();
+    let fd = unsafe { OwnedFd::from_raw_fd(init.to_fanotify()?.into_raw_fd()) };
+    let fanotify = Fanotify::try_from_owned(fd, init.as_raw())?;
+    fanotify.verify_init()?;
+    assert!(matches!(
+        Fanotify::try_from_owned(OwnedFd::from(file), init.as_raw()),
+        Err(InitVerifyError::NotFanotify { .. }),
+    ));
+    Ok(())
+}
+
+#[test]
+fn fd_passing() -> AnyResult {
+    use fanotify::fanotify::Fanotify;
+    use fanotify::fanotify::fd_passing::ReceiveError;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let watched = root.join("watched");
+    fs::write(&watched, "")?;
+    let (privileged, unprivileged) = UnixStream::pair()?;
+    let sender = get_init().to_fanotify()?;
+    sender.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(&watched),
+    }.try_into()?).map_err(|e| e.error)?;
+    sender.send_to(&privileged)?;
+    // the received group keeps the marks even after the sender's fd is closed
+    drop(sender);
+    let received = Fanotify::receive_from(&unprivileged)?;
+    assert_eq!(received.init(), get_init().as_raw());
+    let mut fanotify = received.buffered_default();
+    fs::File::open(&watched)?;
+    let events = fanotify.read()?.all().collect::<Result<Vec<_>, _>>()?;
+    assert!(events.iter().any(|event| event.file().path().and_then(Result::ok) == Some(watched.clone())));
+    
+    // a message without an fd is rejected
+    (&privileged).write_all(&[0; 8])?;
+    assert!(matches!(
+        Fanotify::receive_from(&unprivileged),
+        Err(ReceiveError::Malformed { bytes: 8, fds: 0 }),
+    ));
+    
+    // and so is one with more fds than fit, instead of silently losing them
+    let file = tempfile()?;
+    let fds = [file.as_raw_fd(); 3];
+    nix::sys::socket::sendmsg(
+        privileged.as_raw_fd(),
+        &[nix::sys::uio::IoVec::from_slice(&[0; 8])],
+        &[nix::sys::socket::ControlMessage::ScmRights(&fds)],
+        nix::sys::socket::MsgFlags::empty(),
+        None,
+    )?;
+    assert!(matches!(Fanotify::receive_from(&unprivileged), Err(ReceiveError::Truncated)));
+    Ok(())
+}
+
+#[test]
+fn broker() -> AnyResult {
+    use fanotify::fanotify::broker;
+    use fanotify::fanotify::broker::Broker;
+    use fanotify::fanotify::broker::Worker;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().canonicalize()?.join("file");
+    fs::write(&path, "contents")?;
+    let fanotify = Init {
+        notification_class: init::NotificationClass::Content,
+        ..get_init()
+    }.to_fanotify()?;
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN_PERMISSION,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    let mut broker = Broker::new(fanotify)?;
+    let (broker_socket, worker_socket) = broker::socket_pair()?;
+    broker.add_worker(broker_socket)?;
+    let worker = Worker::new(worker_socket)?;
+    assert_eq!(worker.init(), broker.fanotify().init());
+    
+    let opener = {
+        let path = path.clone();
+        std::thread::spawn(move || fs::File::open(path).map(|_| ()))
+    };
+    broker.run_once(Some(Duration::from_secs(5)))?;
+    let events = worker.receive()?.expect("broker is connected");
+    let event = events.iter().find(|event| event.is_permission()).expect("permission event");
+    // the worker can read the file through its own fd
+    let mut contents = [0; 8];
+    assert_eq!(event.fd().unwrap().read_at(&mut contents, 0)?, 8);
+    assert_eq!(&contents, b"contents");
+    assert_eq!(event.path().unwrap()?, path);
+    assert_eq!(broker.pending(), 1);
+    worker.respond(event, PermissionDecision::Deny)?;
+    drop(events);
+    broker.run_once(Some(Duration::from_secs(5)))?;
+    assert_eq!(broker.pending(), 0);
+    assert_eq!(opener.join().unwrap().map_err(|e| e.kind()), Err(io::ErrorKind::PermissionDenied));
+    
+    // a worker that disconnects without responding has its events allowed
+    let opener = {
+        let path = path.clone();
+        std::thread::spawn(move || fs::File::open(path).map(|_| ()))
+    };
+    broker.run_once(Some(Duration::from_secs(5)))?;
+    let events = worker.receive()?.expect("broker is connected");
+    assert!(events.iter().any(|event| event.is_permission()));
+    drop(worker);
+    broker.run_once(Some(Duration::from_secs(5)))?;
+    assert_eq!((broker.workers(), broker.pending()), (0, 0));
+    opener.join().unwrap()?;
+    Ok(())
+}
+
+#[test]
+fn drain_pending() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().canonicalize()?.join("file");
+    fs::write(&path, "")?;
+    let mut fanotify = Init {
+        notification_class: init::NotificationClass::Content,
+        ..get_init()
+    }
+        .to_fanotify()?
+        .buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN_PERMISSION,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    let opener = {
+        let path = path.clone();
+        std::thread::spawn(move || fs::File::open(path).map(|_| ()))
+    };
+    assert!(fanotify.fanotify.readable(Some(Duration::from_secs(5)))?);
+    let report = fanotify.drain_pending(Duration::from_secs(5), PermissionDecision::Deny)?;
+    assert!(report.is_complete());
+    assert_eq!(report.decided.len(), 1);
+    let decided = &report.decided[0];
+    assert_eq!((decided.mask, decided.decision), (Mask::OPEN_PERMISSION, PermissionDecision::Deny));
+    assert_eq!(decided.path, Some(path));
+    assert_eq!(report.failed, 0);
+    assert_eq!(opener.join().unwrap().map_err(|e| e.kind()), Err(io::ErrorKind::PermissionDenied));
+    
+    // nothing left
+    let report = fanotify.drain_pending(Duration::from_secs(5), PermissionDecision::Allow)?;
+    // and the responses were already flushed
+    assert_eq!((report.decided.len(), report.discarded, report.flushed, report.timed_out), (0, 0, 0, false));
+    Ok(())
+}
+
+#[test]
+fn pending_responses() -> AnyResult {
+    use fanotify::event::responses::PendingResponse;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let mut fanotify = Init {
+        notification_class: init::NotificationClass::Content,
+        ..get_init()
+    }
+        .to_fanotify()?
+        .buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN_PERMISSION,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    let opener = std::thread::spawn(move || fs::File::open(path).map(|_| ()));
+    let events = fanotify.read()?;
+    assert_eq!(events.pending_responses(), vec![]);
+    let mut events = events.all();
+    let mut permission = events.next().expect("permission event")?.permission().expect("permission").into_file();
+    let fd = permission.fd().as_raw_fd();
+    permission.deny();
+    assert!(permission.write_buffered());
+    assert_eq!(events.pending_responses(), vec![PendingResponse {
+        fd,
+        decision: PermissionDecision::Deny,
+        audit: false,
+        audit_rule: None,
+    }]);
+    // only written once all of the batch's permissions are dropped
+    drop(permission);
+    assert_eq!(events.pending_responses().len(), 1);
+    drop(events);
+    assert_eq!(opener.join().unwrap().map_err(|e| e.kind()), Err(io::ErrorKind::PermissionDenied));
+    Ok(())
+}
+
+#[test]
+fn dual_group() -> AnyResult {
+    use fanotify::fanotify::dual::DualGroup;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let mut dual = DualGroup::new(get_init())?;
+    dual.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN_PERMISSION | Mask::CLOSE_WRITE,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    let writer = std::thread::spawn(move || fs::write(path, "written"));
+    let mut seen = Mask::empty();
+    for _ in 0..10 {
+        let events = dual.read(Some(Duration::from_secs(5)))?;
+        assert!(!events.is_empty());
+        for event in events.content.into_iter().flat_map(|it| it.ok()) {
+            assert!(event.mask().includes_permission());
+            seen |= event.mask();
+        }
+        for event in events.notify.into_iter().flat_map(|it| it.ok()) {
+            assert!(!event.mask().includes_permission());
+            seen |= event.mask();
+        }
+        if seen.contains(Mask::CLOSE_WRITE) {
+            break;
+        }
+    }
+    assert_eq!(seen, Mask::OPEN_PERMISSION | Mask::CLOSE_WRITE);
+    writer.join().unwrap()?;
+    Ok(())
+}
+
+#[test]
+fn wait_idle() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    fs::File::open(&path)?;
+    let mut masks = Vec::new();
+    let count = fanotify.wait_idle_with(Duration::from_millis(50), |event| masks.push(event.map(|it| it.mask())))?;
+    assert!(count >= 1);
+    assert_eq!(masks.len(), count);
+    assert!(masks.iter().all(|mask| matches!(mask, Ok(mask) if *mask == Mask::OPEN)));
+    
+    let start = std::time::Instant::now();
+    assert_eq!(fanotify.wait_idle(Duration::from_millis(50))?, 0);
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    Ok(())
+}
+
+#[test]
+fn sequence_numbers() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let paths = [dir.path().join("a"), dir.path().join("b")];
+    let fanotify = get_init().to_fanotify()?;
+    for path in &paths {
+        fs::write(path, "")?;
+        fanotify.mark(mark::One {
+            action: Add,
+            what: mark::What::Inode,
+            flags: mark::Flags::empty(),
+            mask: Mask::OPEN,
+            path: mark::Path::absolute(path),
+        }.try_into()?).map_err(|e| e.error)?;
+    }
+    let mut buffer = fanotify::event::buffer::EventBuffer::default();
+    let mut detector = GapDetector::new();
+    let mut sequences = Vec::new();
+    for _ in 0..2 {
+        for path in &paths {
+            fs::File::open(path)?;
+        }
+        let events = fanotify.read(&mut buffer)?;
+        let batch = events.batch();
+        for event in events {
+            assert_eq!(detector.observe_result(&event), None);
+            let sequence = event?.sequence();
+            assert_eq!(sequence.batch, batch);
+            sequences.push(sequence);
+        }
+    }
+    assert!(sequences.len() >= 2);
+    assert!(sequences.windows(2).all(|it| it[0] < it[1] && it[0].event + 1 == it[1].event));
+    assert_eq!(sequences[0].event, 0);
+    assert_eq!(sequences[0].batch, 0);
+    assert_eq!(sequences.last().unwrap().batch, 1);
+    assert_eq!(detector.gaps(), 0);
+    Ok(())
+}
+
+#[test]
+fn close_on_parse() -> AnyResult {
+    use fanotify::event::file::File;
+    use fanotify::event::file::resolved::CloseOnParse;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().canonicalize()?.join("file");
+    fs::write(&path, "")?;
+    let metadata = fs::metadata(&path)?;
+    let fanotify = get_init().to_fanotify()?.with_close_on_parse(Some(CloseOnParse {
+        path: Some(proc::Symlinks::Literal),
+        identity: true,
+    }));
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    fs::File::open(&path)?;
+    let mut buffer = fanotify::event::buffer::EventBuffer::default();
+    let events = fanotify.read(&mut buffer)?.all().collect::<Result<Vec<_>, _>>()?;
+    assert!(!events.is_empty());
+    for event in &events {
+        assert!(event.file().get_fd().is_none());
+        assert_eq!(event.file().path().transpose()?, Some(path.clone()));
+        assert_eq!(event.file().file_type(), Some(Ok(FileType::Regular)));
+        match event.file() {
+            File::Resolved(file) => assert_eq!(file.identity(), Some((metadata.dev(), metadata.ino()))),
+            file => panic!("expected a resolved file, not {}", file.variant_name()),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn fd_limit() -> AnyResult {
+    use fanotify::event::file::File;
+    use fanotify::fanotify::fd_limit::Backoff;
+    use fanotify::fanotify::fd_limit::FdAction;
+    use fanotify::fanotify::fd_limit::FdExhausted;
+    use fanotify::fanotify::fd_limit::FdLimitMonitor;
+    use fanotify::fanotify::fd_limit::FdLimitStrategy;
+    use fanotify::fanotify::fd_limit::FdUsage;
+    use fanotify::fanotify::fd_limit::Limit;
+    use nix::libc;
+    
+    let exhausted = |attempt| FdExhausted {
+        limit: Limit::Process,
+        usage: None,
+        attempt,
+    };
+    let mut backoff = Backoff::default();
+    assert_eq!(backoff.on_exhausted(&exhausted(1)), FdAction::Backoff(backoff.initial));
+    assert_eq!(backoff.on_exhausted(&exhausted(2)), FdAction::Backoff(backoff.initial * 2));
+    assert!(matches!(backoff.on_exhausted(&exhausted(4)), FdAction::CloseOnParse(_)));
+    assert_eq!(backoff.on_exhausted(&exhausted(15)), FdAction::Backoff(backoff.max));
+    assert_eq!(backoff.on_exhausted(&exhausted(16)), FdAction::Fail);
+    assert_eq!(Limit::of(Errno::EMFILE), Some(Limit::Process));
+    assert_eq!(Limit::of(Errno::ENFILE), Some(Limit::System));
+    assert_eq!(Limit::of(Errno::EAGAIN), None);
+    
+    // the fd limit is process-wide, so run the rest alone in a child process
+    const CHILD: &str = "FANOTIFY_TEST_FD_LIMIT_CHILD";
+    if std::env::var_os(CHILD).is_none() {
+        let status = std::process::Command::new(std::env::current_exe()?)
+            .args(["--exact", "fd_limit", "--test-threads", "1"])
+            .env(CHILD, "1")
+            .status()?;
+        assert!(status.success());
+        return Ok(());
+    }
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let paths = ["a", "b", "c"].iter().map(|name| dir.path().canonicalize().unwrap().join(name)).collect::<Vec<_>>();
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    for path in &paths {
+        fs::write(path, "")?;
+        fanotify.mark(mark::One {
+            action: Add,
+            what: mark::What::Inode,
+            flags: mark::Flags::empty(),
+            mask: Mask::OPEN,
+            path: mark::Path::absolute(path),
+        }.try_into()?).map_err(|e| e.error)?;
+    }
+    for path in &paths {
+        fs::File::open(path)?;
+    }
+    let usage = FdUsage::current()?;
+    assert!(usage.open > 0);
+    assert!(usage.soft_limit <= usage.hard_limit);
+    assert_eq!(usage.headroom(), usage.soft_limit - usage.open);
+    let set_soft_limit = |soft| {
+        let limit = libc::rlimit {
+            rlim_cur: soft as libc::rlim_t,
+            rlim_max: usage.hard_limit as libc::rlim_t,
+        };
+        assert_eq!(unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) }, 0);
+    };
+    // no room for any event fds, so each read drops an event;
+    // the kernel checks the fd number it would allocate, not how many are open
+    let lowest_free = fs::File::open("/dev/null")?.as_raw_fd();
+    set_soft_limit(lowest_free as u64);
+    let mut attempts = Vec::new();
+    let (exhausted_reads, last) = {
+        let mut monitor = FdLimitMonitor::new(|exhausted: &FdExhausted| {
+            attempts.push((exhausted.limit, exhausted.attempt));
+            if exhausted.attempt == 1 {
+                return FdAction::CloseOnParse(fanotify::event::file::resolved::CloseOnParse::path());
+            }
+            set_soft_limit(usage.soft_limit);
+            FdAction::Backoff(Duration::ZERO)
+        });
+        let events = fanotify.read_monitored(&mut monitor)?.all().collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(events.len(), 1);
+        match events[0].file() {
+            File::Resolved(file) => assert_eq!(file.path(), Some(paths[2].as_path())),
+            file => panic!("expected a resolved file, not {}", file.variant_name()),
+        }
+        (monitor.exhausted_reads(), monitor.last())
+    };
+    assert!(fanotify.fanotify.close_on_parse().is_some());
+    assert_eq!(exhausted_reads, 2);
+    assert_eq!(last.map(|it| it.attempt), Some(2));
+    assert_eq!(attempts, vec![(Limit::Process, 1), (Limit::Process, 2)]);
+    assert_eq!(fanotify.stats().read_errors, 2);
+    Ok(())
+}
+
+#[test]
+fn adaptive_fid() -> AnyResult {
+    use fanotify::event::file::File;
+    use fanotify::fanotify::adaptive::AdaptiveFanotify;
+    
+    if !supports(Full) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let fanotify = get_init().to_fanotify()?.buffered_default();
+    // always over the threshold
+    let mut adaptive = AdaptiveFanotify::new(fanotify).with_threshold(0.0);
+    adaptive.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    assert!(!adaptive.is_fid());
+    let switch = adaptive.check()?.expect("switched");
+    assert_eq!(switch.report.applied, 1);
+    assert!(switch.report.is_complete());
+    assert!(adaptive.is_fid());
+    assert!(adaptive.fanotify().fanotify.init().undo_raw().flags.contains(Flags::REPORT_FID));
+    fs::File::open(&path)?;
+    let events = adaptive.read()?.all().collect::<Result<Vec<_>, _>>()?;
+    assert!(!events.is_empty());
+    for event in &events {
+        match event.file() {
+            File::FID(_) => {}
+            file => panic!("expected a FID file, not {}", file.variant_name()),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn inotify_translation() -> AnyResult {
+    use fanotify::event::inotify::InotifyEvent;
+    use fanotify::event::inotify::InotifyTranslator;
+    
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let watched = root.join("watched");
+    fs::create_dir(&watched)?;
+    let child = watched.join("child");
+    fs::write(&child, "")?;
+    let other = root.join("other");
+    fs::write(&other, "")?;
+    let mark = |mask, path| mark::Mark::one(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask,
+        path: mark::Path::absolute(path),
+    });
+    let mut registry = mark::MarkRegistry::new();
+    registry.record(&mark(Mask::OPEN | Mask::CLOSE_WRITE | Mask::EVENT_ON_CHILD, &watched)?);
+    registry.record(&mark(Mask::OPEN, &other)?);
+    registry.record(&mark(Mask::MODIFY, &other)?);
+    let translator = InotifyTranslator::new(&registry);
+    assert_eq!(translator.watches().len(), 2);
+    assert_eq!(translator.wd(&watched), Some(1));
+    assert_eq!(translator.wd(&other), Some(2));
+    assert_eq!(translator.watch(2).map(|it| it.mask), Some(Mask::OPEN | Mask::MODIFY));
+    assert_eq!(InotifyTranslator::translate_mask(Mask::OPEN_EXEC | Mask::ON_DIR), 0x20 | 0x4000_0000);
+    assert_eq!(InotifyTranslator::translate_mask(Mask::CLOSE_WRITE | Mask::MODIFY), 0x8 | 0x2);
+    assert_eq!(translator.translate_path(Mask::OPEN, &root), None);
+    assert_eq!(translator.translate_path(Mask::OPEN, &watched.join("a/b")), None);
+    assert_eq!(translator.translate_path(Mask::OPEN | Mask::ON_DIR, &watched), Some(InotifyEvent {
+        wd: 1,
+        mask: 0x20 | 0x4000_0000,
+        cookie: 0,
+        name: PathBuf::new(),
+    }));
+    let mut removed = registry.clone();
+    removed.record(&mark::Mark::one(mark::One {
+        action: mark::OneAction::Remove,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN | Mask::MODIFY,
+        path: mark::Path::absolute(&other),
+    })?);
+    assert_eq!(InotifyTranslator::new(&removed).wd(&other), None);
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    assert!(registry.reapply(&fanotify).is_complete());
+    fs::write(&child, "child")?;
+    fs::File::open(&other)?;
+    let mut translated = fanotify
+        .read_all_pending(64 * 1024)?
+        .all()
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .filter_map(|event| translator.translate(event))
+        .collect::<Vec<_>>();
+    translated.sort_by_key(|it| it.wd);
+    assert_eq!(translated.iter().map(|it| (it.wd, it.name.clone())).collect::<Vec<_>>(), vec![
+        (1, PathBuf::from("child")),
+        (2, PathBuf::new()),
+    ]);
+    assert_eq!(translated[0].mask & !0x20, 0x8);
+    assert_eq!(translated[1].mask, 0x20);
+    Ok(())
+}
+
+#[test]
+fn correlate_fd_and_fid() -> AnyResult {
+    use fanotify::clock::MockClock;
+    use fanotify::event::correlate::Correlator;
+    
+    if !supports(Full) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    let other = dir.path().join("other");
+    fs::write(&path, "")?;
+    fs::write(&other, "")?;
+    let fd_group = get_init().to_fanotify()?.buffered_default();
+    let fid_group = Init {
+        flags: Flags::unlimited() | Flags::REPORT_FID,
+        ..Init::const_default()
+    }.to_fanotify()?.buffered_default();
+    let mark = |path| mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(path),
+    }.try_into();
+    fd_group.mark(mark(&path)?).map_err(|e| e.error)?;
+    fid_group.mark(mark(&path)?).map_err(|e| e.error)?;
+    // only the FID group sees this one
+    fid_group.mark(mark(&other)?).map_err(|e| e.error)?;
+    fs::File::open(&path)?;
+    fs::File::open(&other)?;
+    let mut groups = [fd_group, fid_group];
+    let clock = MockClock::new();
+    let mut correlator = Correlator::default().with_clock(clock.shared());
+    let mut ready = Vec::new();
+    for group in &mut groups {
+        let (events, result) = group.read_all_pending(64 * 1024)?.drain_owned();
+        result?;
+        for event in events {
+            correlator.push(event?, &mut ready);
+        }
+    }
+    assert_eq!(ready.len(), 1);
+    assert!(ready[0].is_merged());
+    assert_eq!(ready[0].mask, Mask::OPEN);
+    assert_eq!(correlator.merged(), 1);
+    assert_eq!(correlator.pending(), 1);
+    clock.advance(Correlator::DEFAULT_WINDOW);
+    correlator.expire(&mut ready);
+    assert_eq!(ready.len(), 2);
+    assert!(!ready[1].is_merged());
+    assert!(ready[1].fid.is_some());
+    assert_eq!(correlator.pending(), 0);
+    Ok(())
+}
+
+#[test]
+fn privilege_drop() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let fanotify = get_init().to_fanotify()?;
+    fanotify.after_privilege_drop()?;
+    let mut drop = PrivilegeDrop::new(Uid::from_raw(65534), Gid::from_raw(65534));
+    drop.future_marks = vec![mark::What::Inode, MountPoint];
+    // nothing is dropped if it's going to fail later anyways
+    assert_eq!(drop.apply_to(&fanotify), Err(PrivilegeError::MarksNeededLater(MountPoint)));
+    drop.future_marks.pop();
+    drop.check()?;
+    Ok(())
+}
+
+#[test]
+fn restricted_mode() -> AnyResult {
+    // restricted mode is process-wide
+    if !run_alone("restricted_mode")? {
+        return Ok(());
+    }
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    fanotify::restricted::enable();
+    let _ = fs::read(&path)?;
+    let events = fanotify.read()?.all().collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(events.len(), 1);
+    let file = events[0].file();
+    // the event's own fd still works
+    assert_eq!(file.file_type().transpose()?, Some(FileType::Regular));
+    let error = file.path().expect("fd").expect_err("restricted");
+    assert!(proc::is_unavailable(&error));
+    let error = Snapshot::scan(dir.path()).expect_err("restricted");
+    assert!(fanotify::restricted::is_restricted(&error));
+    fanotify::restricted::disable();
+    assert_eq!(file.path().expect("fd")?, path);
+    assert_eq!(Snapshot::scan(dir.path())?.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn drain_owned() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let fanotify = Init {
+        notification_class: init::NotificationClass::Content,
+        ..get_init()
+    }.to_fanotify()?;
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN_PERMISSION,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    let opener = {
+        let path = path.clone();
+        std::thread::spawn(move || fs::File::open(path).map(|_| ()))
+    };
+    let mut fanotify = fanotify.buffered_default();
+    let (mut events, result) = fanotify.read()?.drain_owned();
+    result?;
+    // the buffer is free to be reused, but the permission event is still pending
+    assert!(!opener.is_finished());
+    assert_eq!(events.len(), 1);
+    let event = events.pop().unwrap()?;
+    assert_eq!(event.mask(), Mask::OPEN_PERMISSION);
+    assert_eq!(event.file().path().transpose()?, Some(path.clone()));
+    let mut ticket = event.into_file().permission().expect("permission ticket");
+    let metadata = fs::metadata(&path)?;
+    assert_eq!(ticket.identity()?, (metadata.dev(), metadata.ino()));
+    // respond from another thread
+    assert!(!ticket.written());
+    let written = std::thread::spawn(move || ticket.respond()).join().unwrap()?;
+    assert!(written);
+    opener.join().unwrap()?;
+    Ok(())
+}
+
+#[test]
+fn event_arena() -> AnyResult {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().canonicalize()?.join("file");
+    fs::write(&path, "")?;
+    let fd = fs::File::open(&path)?.apply(|it| unsafe { FD::from_raw_fd(it.into_raw_fd()) });
+    let mut arena = EventArena::with_capacity(1);
+    for _ in 0..3 {
+        let resolved = arena.path(&fd)?;
+        assert_eq!(resolved, fd.path()?);
+        arena.recycle_path(resolved);
+    }
+    assert_eq!(arena.stats().allocated, 1);
+    assert_eq!(arena.stats().reused, 2);
+    assert_eq!(arena.pooled(), 1);
+    // past the capacity, recycled buffers are dropped
+    let (a, b) = (arena.path(&fd)?, arena.path(&fd)?);
+    arena.recycle_path(a);
+    arena.recycle_path(b);
+    assert_eq!(arena.pooled(), 1);
+    
+    if !supports(Full) {
+        return Ok(());
+    }
+    let mut fanotify = Init::fid_tracking().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::MODIFY,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    let mut arena = EventArena::new();
+    let mut owned = Vec::new();
+    let mut handles = Vec::new();
+    for _ in 0..2 {
+        fs::write(&path, "modified")?;
+        fanotify.read()?.drain_to_in(&mut owned, &mut arena)?;
+        assert_eq!(owned.len(), 1);
+        let event = owned.pop().unwrap()?;
+        assert_eq!(event.mask(), Mask::MODIFY);
+        let handle = event.into_file().fid().expect("fid event").into_handle();
+        handles.push(handle.as_bytes().to_vec());
+        arena.recycle_handle(handle);
+    }
+    // the same file has the same handle, and the second copy reused the first's buffer
+    assert_eq!(handles[0], handles[1]);
+    assert_eq!(arena.stats().allocated, 1);
+    assert_eq!(arena.stats().reused, 1);
+    Ok(())
+}
+
+#[test]
+fn raw_bytes_handoff() -> AnyResult {
+    if !supports(Full) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let mut fanotify = Init::fid_tracking().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::MODIFY,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    let init = fanotify.fanotify.init();
+    fs::write(&path, "modified")?;
+    let events = fanotify.read()?;
+    // ship the raw batch over a socket, like to a separate parser process
+    let (mut reader, mut parser) = UnixStream::pair()?;
+    reader.write_all(events.raw_bytes())?;
+    reader.shutdown(Shutdown::Write)?;
+    let handle = events
+        .into_iter()
+        .next()
+        .expect("one event")?
+        .into_file()
+        .fid()
+        .expect("fid event")
+        .to_owned();
+    let mut bytes = Vec::new();
+    parser.read_to_end(&mut bytes)?;
+    let mut parsed = Events::parse_from_bytes(&bytes, init).into_iter().collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(parsed.len(), 1);
+    let event = parsed.pop().unwrap();
+    assert_eq!(event.mask(), Mask::MODIFY);
+    assert!(!event.id().is_generated_by_self());
+    assert_eq!(event.into_file().fid().expect("fid event").to_owned(), handle);
+    
+    // forged lengths in the fid record are rejected instead of read past the event,
+    // where the record's len is after the 24-byte metadata and the handle's handle_bytes after the fsid
+    let event_len = u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+    let mut forged_record_len = bytes[..event_len].to_vec();
+    forged_record_len[26..28].copy_from_slice(&((event_len - 24 + 4) as u16).to_ne_bytes());
+    let mut forged_handle_bytes = bytes[..event_len].to_vec();
+    forged_handle_bytes[36..40].copy_from_slice(&1000u32.to_ne_bytes());
+    for forged in [forged_record_len, forged_handle_bytes] {
+        let parsed = Events::parse_from_bytes(&forged, init).into_iter().collect::<Vec<_>>();
+        assert!(matches!(parsed.as_slice(), [Err(EventError::TooShort { .. })]));
+    }
+    
+    // fds aren't usable outside of the process that read them
+    let fanotify = get_init().to_fanotify()?;
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    let init = fanotify.init();
+    let mut fanotify = fanotify.buffered_default();
+    fs::File::open(&path)?;
+    let events = fanotify.read()?;
+    let parsed = Events::parse_from_bytes(events.raw_bytes(), init).into_iter().collect::<Vec<_>>();
+    assert_eq!(parsed.len(), 1);
+    assert!(matches!(parsed[0], Err(EventError::FdFromOtherProcess { .. })));
+    drop(parsed);
+    // which still belong to the original events
+    let event = events.into_iter().next().expect("one event")?;
+    assert!(event.into_file().fd().expect("fd event").fd().check());
+    Ok(())
+}
+
+#[test]
+fn truncated_event_carry() -> AnyResult {
+    if !supports(Full) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let mut fanotify = Init::fid_tracking().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::MODIFY,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    let init = fanotify.fanotify.init();
+    fs::write(&path, "modified")?;
+    let bytes = fanotify.read()?.raw_bytes().to_vec();
+    
+    // a partial event is reported as truncated with its bytes, not as too short
+    let (partial, rest) = bytes.split_at(bytes.len() - 3);
+    let parsed = Events::parse_from_bytes(partial, init).into_iter().collect::<Vec<_>>();
+    assert_eq!(parsed.len(), 1);
+    let partial = match &parsed[0] {
+        Err(EventError::Truncated { partial, expected }) => {
+            assert_eq!(*expected, bytes.len());
+            partial.clone()
+        }
+        result => panic!("expected a truncated event, got {:?}", result),
+    };
+    drop(parsed);
+    let completed = [partial.as_slice(), rest].concat();
+    assert_eq!(Events::parse_from_bytes(&completed, init).into_iter().collect::<Result<Vec<_>, _>>()?.len(), 1);
+    
+    // bytes carried over are completed by the next read
+    fanotify.buffer.carry = bytes.clone();
+    fs::write(&path, "modified again")?;
+    let events = fanotify.read()?;
+    assert_eq!(events.carried_in(), bytes.len());
+    assert_eq!(events.carried_over(), 0);
+    assert_eq!(events.into_iter().collect::<Result<Vec<_>, _>>()?.len(), 2);
+    assert!(fanotify.buffer.carry.is_empty());
+    assert_eq!(fanotify.stats().truncations, 0);
+    Ok(())
+}
+
+#[test]
+fn read_session() -> AnyResult {
+    use fanotify::fanotify::session::SessionItem;
+    
+    if !supports(Full) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let mut fanotify = Init::fid_tracking().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::MODIFY,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    fs::write(&path, "modified")?;
+    // a whole event carried over from an earlier read is completed by the session's first one
+    let carry = fanotify.read()?.raw_bytes().to_vec();
+    fanotify.buffer.carry = carry;
+    let mut session = fanotify.session();
+    let mut sequences = Vec::new();
+    for batch in [2, 1] {
+        fs::write(&path, "modified again")?;
+        let items = session.read()?.collect::<Vec<_>>();
+        assert_eq!(items.len(), batch);
+        for item in items {
+            match item {
+                SessionItem::Event(event) => sequences.push(event.sequence().event),
+                item => panic!("expected an event, got {:?}", item),
+            }
+        }
+    }
+    assert!(sequences.windows(2).all(|it| it[1] == it[0] + 1));
+    assert_eq!((session.reads(), session.overflows(), session.carried_over()), (2, 0, 0));
+    assert_eq!(session.gaps().gaps(), 0);
+    assert_eq!(session.gaps().last(), sequences.last().copied());
+    Ok(())
+}
+
+#[test]
+fn subscription_hub() -> AnyResult {
+    use fanotify::fanotify::hub::Interest;
+    use fanotify::fanotify::hub::SharedEvent;
+    use fanotify::fanotify::hub::SubscriptionHub;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let a = root.join("a");
+    let b = root.join("b");
+    let mut hub = SubscriptionHub::new(get_init().to_fanotify()?.buffered_default());
+    let modified_a = hub.subscribe(Interest::new(Mask::MODIFY).path(&a));
+    let closed = hub.subscribe(Interest::new(Mask::CLOSE_WRITE));
+    assert_eq!(hub.mask(), Mask::MODIFY | Mask::CLOSE_WRITE);
+    hub.fanotify().mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: hub.mask() | Mask::EVENT_ON_CHILD,
+        path: mark::Path::absolute(&root),
+    }.try_into()?).map_err(|e| e.error)?;
+    fs::write(&a, "a")?;
+    fs::write(&b, "b")?;
+    assert!(hub.read()? > 0);
+    let paths = |events: Vec<SharedEvent>, mask: Mask| {
+        events
+            .iter()
+            .map(|it| it.as_ref().as_ref().expect("event error"))
+            .inspect(|it| assert!(it.mask().intersects(mask)))
+            .map(|it| it.file().path().unwrap().unwrap())
+            .collect::<Vec<_>>()
+    };
+    let modified_a = paths(modified_a.try_iter().collect(), Mask::MODIFY);
+    assert!(!modified_a.is_empty());
+    assert!(modified_a.iter().all(|it| it == &a));
+    let mut closed = paths(closed.try_iter().collect(), Mask::CLOSE_WRITE);
+    closed.dedup();
+    assert_eq!(closed, vec![a, b]);
+    assert!(hub.delivered() >= 3);
+    Ok(())
+}
+
+#[test]
+fn subscription_hub_permission() -> AnyResult {
+    use fanotify::fanotify::hub::Interest;
+    use fanotify::fanotify::hub::SubscriptionHub;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let fanotify = Init {
+        notification_class: init::NotificationClass::Content,
+        ..get_init()
+    }.to_fanotify()?;
+    let mut hub = SubscriptionHub::new(fanotify.buffered_default());
+    let opened = hub.subscribe(Interest::new(Mask::OPEN_PERMISSION));
+    hub.fanotify().mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: hub.mask(),
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    let opener = std::thread::spawn(move || fs::File::open(path).map(|_| ()));
+    assert_eq!(hub.read()?, 1);
+    // allowed even though the subscriber is still holding the event
+    let event = opened.try_recv()?;
+    opener.join().unwrap()?;
+    assert_eq!(event.as_ref().as_ref().expect("event error").mask(), Mask::OPEN_PERMISSION);
+    Ok(())
+}
+
+#[test]
+fn pause_resume() -> AnyResult {
+    use std::thread;
+    use std::time::Instant;
+    
+    use fanotify::fanotify::pause::Pause;
+    use fanotify::fanotify::pause::PauseMode;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::MODIFY,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    let pause = Pause::new();
+    let paused_for = Duration::from_millis(300);
+    
+    // queued events are read after resuming
+    pause.pause(PauseMode::Queue);
+    let start = Instant::now();
+    thread::scope(|scope| -> AnyResult {
+        let writer = scope.spawn(|| -> io::Result<()> {
+            fs::write(&path, "queued")?;
+            thread::sleep(paused_for);
+            pause.resume();
+            Ok(())
+        });
+        assert_eq!(fanotify.read_or_pause(&pause)?.all().count(), 1);
+        assert!(start.elapsed() >= paused_for);
+        writer.join().unwrap()?;
+        Ok(())
+    })?;
+    
+    // dropped events are discarded and counted
+    pause.pause(PauseMode::Drop);
+    thread::scope(|scope| -> AnyResult {
+        let writer = scope.spawn(|| -> io::Result<()> {
+            fs::write(&path, "dropped")?;
+            thread::sleep(paused_for);
+            pause.resume();
+            fs::write(&path, "read")?;
+            Ok(())
+        });
+        assert_eq!(fanotify.read_or_pause(&pause)?.all().count(), 1);
+        writer.join().unwrap()?;
+        Ok(())
+    })?;
+    assert_eq!(pause.dropped(), 1);
+    assert!(!pause.is_paused());
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn group_by_path() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let a = root.join("a");
+    let b = root.join("b");
+    fs::write(&a, "")?;
+    fs::write(&b, "")?;
+    let mut fanotify = get_init().to_fanotify()?.into_async()?.buffered_default();
+    for path in [&a, &b].iter() {
+        fanotify.mark(mark::One {
+            action: Add,
+            what: mark::What::Inode,
+            flags: mark::Flags::empty(),
+            mask: Mask::MODIFY | Mask::CLOSE_WRITE,
+            path: mark::Path::absolute(path),
+        }.try_into()?).map_err(|e| e.error)?;
+    }
+    fs::write(&a, "1")?;
+    fs::write(&b, "1")?;
+    fs::write(&a, "2")?;
+    let mut grouped = fanotify.group_by_path(Duration::from_millis(100));
+    let groups = block_on(grouped.next())?;
+    assert_eq!(groups.iter().map(|it| &it.path).collect::<Vec<_>>(), vec![&a, &b]);
+    // the kernel may have already merged some of the queued events
+    for group in &groups {
+        assert_eq!(group.mask, Mask::MODIFY | Mask::CLOSE_WRITE);
+        assert!(group.count >= 1);
+    }
+    assert_eq!(grouped.skipped(), 0);
+    Ok(())
+}
+
+#[test]
+fn router() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(&path),
+    }.try_into()?).map_err(|e| e.error)?;
+    let mut router = Router::new();
+    router
+        .route("/proc", |_: Event<'_>| "proc")?
+        .route(dir.path(), |_: Event<'_>| "dir")?
+        .fallback(|_: Event<'_>| "other");
+    assert_eq!(router.paths().count(), 2);
+    let _ = fs::read(&path)?;
+    let routes = fanotify
+        .read()?
+        .all()
+        .map(|it| it.expect("event error"))
+        .filter(|it| it.id().is_generated_by_self())
+        .filter_map(|it| router.dispatch(it))
+        .collect::<Vec<_>>();
+    assert_eq!(routes, vec!["dir"]);
+    Ok(())
+}
+
+#[test]
+fn reconcile() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let existing = root.join("existing");
+    fs::write(&existing, "")?;
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::CLOSE_WRITE | Mask::EVENT_ON_CHILD,
+        path: mark::Path::absolute(&root),
+    }.try_into()?).map_err(|e| e.error)?;
+    let mut changes = Vec::new();
+    {
+        let mut reconciler = Reconciler::new(&root, |change: &Change| changes.push(change.clone()))?;
+        assert_eq!(reconciler.snapshot().len(), 1);
+        fs::write(&existing, "modified")?;
+        fs::write(root.join("created"), "")?;
+        reconciler.apply_all(fanotify.read()?)?;
+        assert_eq!(reconciler.snapshot().len(), 2);
+        assert_eq!(reconciler.rescans(), 0);
+        reconciler.apply_error(&EventError::QueueOverflowed)?;
+        assert_eq!(reconciler.rescans(), 1);
+    }
+    let paths = changes
+        .iter()
+        .map(|it| it.path().to_path_buf())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, vec![existing, root.join("created")]);
+    assert!(matches!(changes[0], Change::Modified { .. }));
+    assert!(matches!(changes[1], Change::Created { .. }));
+    Ok(())
+}
+
+#[test]
+fn scoped() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    fs::write(&path, "")?;
+    let fanotify = get_init().to_fanotify()?;
+    let mark = || mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(&path),
+    }.try_into();
+    let readable = |fanotify: &fanotify::fanotify::Fanotify| -> io::Result<bool> {
+        let _ = fs::read(&path)?;
+        Ok(fanotify.readable(Some(Duration::from_millis(100)))?)
+    };
+    fanotify.scoped(|watcher| -> AnyResult {
+        watcher.mark(mark()?).map_err(|e| e.error)?;
+        assert_eq!(watcher.len(), 1);
+        assert!(readable(watcher.fanotify())?);
+        Ok(())
+    })?;
+    let _ = fanotify.read(&mut Default::default())?;
+    assert!(!readable(&fanotify)?);
+    // marks are removed on panic, too
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        fanotify.scoped(|watcher| {
+            watcher.mark(mark().unwrap()).unwrap();
+            panic!("scoped");
+        })
+    }));
+    assert!(panicked.is_err());
+    assert!(!readable(&fanotify)?);
+    Ok(())
+}
+
+#[test]
+fn origin() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let path = root.join("file");
+    fs::write(&path, "")?;
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    let mut registry = mark::MarkRegistry::new();
+    registry.mark(&fanotify, mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN | Mask::ON_DIR | Mask::EVENT_ON_CHILD,
+        path: mark::Path::absolute(&root),
+    }.try_into()?).map_err(|e| e.error)?;
+    let classifier = OriginClassifier::new(&registry);
+    assert_eq!(classifier.len(), 1);
+    let _ = fs::read(&path)?;
+    let _ = fs::read_dir(&root)?;
+    let origins = fanotify
+        .read()?
+        .all()
+        .map(|it| it.expect("event error"))
+        .filter(|it| it.id().is_generated_by_self())
+        .map(|it| classifier.classify(&it))
+        .collect::<Vec<_>>();
+    assert_eq!(origins, vec![
+        Origin::Child { watched: root.clone() },
+        Origin::Itself { path: root },
+    ]);
+    Ok(())
+}
+
+#[test]
+fn uid_filter() -> AnyResult {
+    use fanotify::event::credentials::Credentials;
+    use fanotify::fanotify::pipeline::Layer;
+    use fanotify::fanotify::pipeline::UidFilter;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let file = NamedTempFile::new()?;
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(file.path()),
+    }.try_into()?).map_err(|e| e.error)?;
+    fs::File::open(file.path())?;
+    let me = Uid::effective();
+    let other = Uid::from_raw(me.as_raw() + 1);
+    let mut only_me = UidFilter::filter_uid(vec![me]);
+    let mut only_other = UidFilter::filter_uid(vec![other]);
+    let mut not_me = UidFilter::exclude_uid(vec![me]);
+    let mut read = 0;
+    for event in fanotify.read()? {
+        let event = event?;
+        let credentials = Credentials::of_event(&event)?;
+        assert_eq!((credentials.effective_uid, credentials.effective_gid), (me, Gid::effective()));
+        assert!(only_me.handle(&event));
+        assert!(!only_other.handle(&event));
+        assert!(!not_me.handle(&event));
+        read += 1;
+    }
+    assert!(read > 0);
+    assert_eq!(only_me.cache().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn cgroup_filter() -> AnyResult {
+    use fanotify::event::cgroup;
+    use fanotify::fanotify::pipeline::CgroupFilter;
+    use fanotify::fanotify::pipeline::Layer;
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let file = NamedTempFile::new()?;
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(file.path()),
+    }.try_into()?).map_err(|e| e.error)?;
+    fs::File::open(file.path())?;
+    let own = cgroup::of(nix::unistd::getpid())?;
+    assert!(own.is_absolute());
+    let mut own_filter = CgroupFilter::new(vec![&own]);
+    let mut root_filter = CgroupFilter::new(vec!["/"]);
+    let mut other_filter = CgroupFilter::new(vec![own.join("not-a-child.scope")]);
+    let mut read = 0;
+    for event in fanotify.read()? {
+        let event = event?;
+        assert_eq!(cgroup::of_event(&event)?, own);
+        assert!(own_filter.handle(&event));
+        assert!(root_filter.handle(&event));
+        assert!(!other_filter.handle(&event));
+        read += 1;
+    }
+    assert!(read > 0);
+    assert_eq!(own_filter.cache().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn mock_clock_and_proc_fs() -> AnyResult {
+    use fanotify::clock::MockClock;
+    use fanotify::event::cgroup::CgroupCache;
+    use fanotify::event::credentials::CredentialsCache;
+    use fanotify::event::id::Id;
+    use fanotify::proc::MockProcFs;
+    
+    let clock = MockClock::new();
+    let file = NamedTempFile::new()?;
+    let fd = file.reopen()?.apply(|it| unsafe { FD::from_raw_fd(it.into_raw_fd()) });
+    let mut decisions = DecisionCache::new(16, Some(Duration::from_secs(10))).with_clock(clock.shared());
+    decisions.insert(&fd, PermissionDecision::Deny)?;
+    clock.advance(Duration::from_secs(9));
+    assert_eq!(decisions.get(&fd)?, Some(PermissionDecision::Deny));
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(decisions.get(&fd)?, None);
+    
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: mark::What::Inode,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(file.path()),
+    }.try_into()?).map_err(|e| e.error)?;
+    fs::File::open(file.path())?;
+    let proc_fs = MockProcFs::new();
+    let mut cgroups = CgroupCache::new(Duration::from_secs(1), 16)
+        .with_clock(clock.shared())
+        .with_proc_fs(proc_fs.shared());
+    let mut credentials = CredentialsCache::new(Duration::from_secs(1), 16)
+        .with_clock(clock.shared())
+        .with_proc_fs(proc_fs.shared());
+    let event = fanotify.read()?.into_iter().next().expect("event")?;
+    let pid = match event.id().id() {
+        Id::Pid(pid) | Id::Tid(pid) => pid.as_raw(),
+    };
+    // a missing process is cached as missing, too
+    assert_eq!(cgroups.get(&event), None);
+    proc_fs.insert(pid, "cgroup", "0::/mock.slice/a.scope\n");
+    proc_fs.insert(pid, "status", "Uid:\t1\t2\t2\t2\nGid:\t3\t4\t4\t4\n");
+    assert_eq!(cgroups.get(&event), None);
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(cgroups.get(&event), Some(PathBuf::from("/mock.slice/a.scope")));
+    let read = credentials.get(&event).expect("credentials");
+    assert_eq!((read.uid, read.effective_uid), (Uid::from_raw(1), Uid::from_raw(2)));
+    assert_eq!((read.gid, read.effective_gid), (Gid::from_raw(3), Gid::from_raw(4)));
+    proc_fs.remove_pid(pid);
+    assert!(credentials.get(&event).is_some());
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(credentials.get(&event), None);
+    Ok(())
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn par_process() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let paths = (0..8)
+        .map(|i| dir.path().join(i.to_string()))
+        .collect::<Vec<_>>();
+    for path in &paths {
+        fs::write(path, "")?;
+    }
+    let mut fanotify = get_init().to_fanotify()?.buffered_default();
+    fanotify.mark(mark::One {
+        action: Add,
+        what: MountPoint,
+        flags: mark::Flags::empty(),
+        mask: Mask::OPEN,
+        path: mark::Path::absolute(dir.path()),
+    }.try_into()?).map_err(|e| e.error)?;
+    for path in &paths {
+        fs::File::open(path)?;
+    }
+    let processed = fanotify
+        .read_all_pending(64 * 1024)?
+        .par_process(|event| event.file().path().and_then(|it| it.ok()));
+    assert!(processed.permissions.is_empty());
+    assert!(processed.errors.is_empty());
+    let results = processed.results
+        .into_iter()
+        .flatten()
+        .filter(|it| it.starts_with(dir.path()))
+        .collect::<Vec<_>>();
+    assert_eq!(results, paths);
+    Ok(())
+}
+
+#[test]
+fn subtree() -> AnyResult {
+    if !supports(Partial) {
+        return Ok(());
+    }
+    let mut monitor = SubtreeMonitor::new(get_init().to_fanotify()?.buffered_default(), "/etc");
+    assert_eq!(monitor.watch(MountPoint, Mask::empty()), Err(WatchError::Static(mark::StaticError::EmptyMask)));
+    monitor.watch(MountPoint, Mask::OPEN)?;
+    let path = Path::new("/etc/passwd");
+    let _ = fs::read(path)?;
+    let paths = monitor
+        .read()?
+        .map(|it| it.expect("event error"))
+        .filter(|it| it.id().is_generated_by_self())
+        .map(|it| it.file().path().unwrap())
+        .collect::<io::Result<Vec<_>>>()?;
+    assert!(paths.iter().all(|it| it.starts_with(monitor.root())));
+    assert!(paths.iter().any(|it| it == path));
+    Ok(())
+}
+
+#[test]
+fn supported_report() {
+    let supported = fanotify::supported();
+    if supports(Partial) {
+        assert_ne!(supported.level(), fanotify::supported::Level::None);
+        assert!(supported.supports_mask(Mask::OPEN | Mask::ACCESS | Mask::close() | Mask::MODIFY));
+    }
+}
+
 fn tmp_file(driver: &mut Driver, text: &str, mut file: impl Read) -> AnyResult {
     let mut buf = String::new();
     file.read_to_string(&mut buf)?;
@@ -1,7 +1,8 @@
+use std::process::Command;
+
 use fanotify::init::Flags;
 use fanotify::init::Init;
 
-pub mod driver;
 pub mod supported;
 
 pub type AnyResult<T = ()> = anyhow::Result<T>;
@@ -12,3 +13,18 @@ pub const fn get_init() -> Init {
         ..Init::const_default()
     }
 }
+
+/// Run the test `name` alone in a child process, for tests that change process-wide state,
+/// returning if this is that child process, which should then run the rest of the test.
+pub fn run_alone(name: &str) -> AnyResult<bool> {
+    const CHILD: &str = "FANOTIFY_TEST_CHILD";
+    if std::env::var_os(CHILD).is_some() {
+        return Ok(true);
+    }
+    let status = Command::new(std::env::current_exe()?)
+        .args(["--exact", name, "--test-threads", "1"])
+        .env(CHILD, "1")
+        .status()?;
+    assert!(status.success(), "{} failed in a child process", name);
+    Ok(false)
+}
@@ -1,19 +1,14 @@
 use apply::Apply;
 use semver::Version;
 
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
 pub enum Supported {
+    #[default]
     None,
     Partial,
     Full,
 }
 
-impl Default for Supported {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
 impl Supported {
     pub fn get() -> Self {
         let uname = nix::sys::utsname::uname();